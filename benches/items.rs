@@ -0,0 +1,106 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use std::fs;
+
+use siege_perilous::item::{Items, GOLD};
+
+// Run with: cargo bench --bench items
+const NUM_OWNERS: i32 = 10000;
+
+const NAMES: [&str; 5] = [
+    GOLD,
+    "Copper Training Axe",
+    "Valleyrun Copper Ore",
+    "Quickforge Iron Ore",
+    "Copper Broad Axe",
+];
+
+// Every owner starts with a stack of each item
+fn populated_items() -> Items {
+    let item_template_file = fs::File::open("item_template.yaml").expect("Could not open file.");
+
+    let mut items = Items::default();
+    items.set_templates(
+        serde_yaml::from_reader(item_template_file).expect("Could not read values."),
+    );
+
+    for owner in 0..NUM_OWNERS {
+        for name in NAMES.iter() {
+            items.create(owner, name.to_string(), 10);
+        }
+    }
+
+    return items;
+}
+
+// Creating more of an item an owner already has merges it into the existing stack
+fn bench_find_stack(c: &mut Criterion) {
+    let mut items = populated_items();
+    let mut owner = 0;
+
+    c.bench_function("create into stack", |b| {
+        b.iter(|| {
+            owner = (owner + 1) % NUM_OWNERS;
+            black_box(items.create(owner, "Valleyrun Copper Ore".to_string(), 1));
+        })
+    });
+}
+
+fn bench_get_by_owner(c: &mut Criterion) {
+    let items = populated_items();
+    let mut owner = 0;
+
+    c.bench_function("get_by_owner", |b| {
+        b.iter(|| {
+            owner = (owner + 1) % NUM_OWNERS;
+            black_box(items.get_by_owner(owner));
+        })
+    });
+
+    c.bench_function("get_total_gold", |b| {
+        b.iter(|| {
+            owner = (owner + 1) % NUM_OWNERS;
+            black_box(items.get_total_gold(owner));
+        })
+    });
+}
+
+// Gold is passed along the owners, so every owner keeps some to give
+fn bench_transfers(c: &mut Criterion) {
+    let mut items = populated_items();
+    let mut owner = 0;
+
+    c.bench_function("transfer_gold", |b| {
+        b.iter(|| {
+            let target_id = (owner + 1) % NUM_OWNERS;
+
+            items.transfer_gold(owner, target_id, 1);
+            owner = target_id;
+        })
+    });
+
+    c.bench_function("transfer", |b| {
+        b.iter(|| {
+            let target_id = (owner + 1) % NUM_OWNERS;
+
+            let Some(item) = items
+                .get_by_owner(owner)
+                .into_iter()
+                .find(|item| item.name == "Copper Broad Axe")
+            else {
+                panic!("Owner {} has no axe to transfer", owner);
+            };
+
+            items.transfer(item.id, target_id);
+            owner = target_id;
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_find_stack,
+    bench_get_by_owner,
+    bench_transfers
+);
+criterion_main!(benches);
@@ -14,6 +14,7 @@ use crate::item::{self, AttrKey, Item, Items};
 use crate::map::Map;
 use crate::obj::Obj;
 use crate::skill::{SkillUpdated};
+use crate::stamina::{self, Stamina};
 use crate::templates::{
     ComboTemplate, ObjTemplate, Templates,
 };
@@ -27,6 +28,16 @@ pub const GOUGE: &str = "Gouge";
 
 pub const TICKS_PER_SEC: i32 = 10;
 
+pub const WEAPON_WEAR: f32 = 1.0;
+pub const ARMOR_WEAR: f32 = 1.0;
+
+pub const FLANKING_DAMAGE_MOD: f32 = 1.25;
+pub const SURROUNDED_DEFENSE_MOD: f32 = 0.75;
+pub const SURROUNDED_COUNT: i32 = 3;
+
+// Ticks after the last attack before a unit is no longer in combat
+pub const COMBAT_TIMEOUT: i32 = 50;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AttackType {
     Quick,
@@ -72,6 +83,50 @@ pub struct ComboTracker {
     pub attacks: Vec<AttackType>,
 }
 
+#[derive(Debug, Component, Clone)]
+pub struct InCombat {
+    pub target_id: i32,
+    pub last_attack: i32,
+}
+
+impl InCombat {
+    pub fn is_active(&self, game_tick: i32) -> bool {
+        return game_tick - self.last_attack <= COMBAT_TIMEOUT;
+    }
+}
+
+// Unit near a fight, used for flanking and surrounded checks
+#[derive(Debug, Clone)]
+pub struct Combatant {
+    pub player_id: i32,
+    pub pos: Position,
+    // Id of the unit it is actively fighting
+    pub fighting: Option<i32>,
+}
+
+impl Combatant {
+    pub fn new(
+        player_id: i32,
+        pos: Position,
+        in_combat: Option<&InCombat>,
+        game_tick: i32,
+    ) -> Self {
+        let fighting = in_combat
+            .filter(|in_combat| in_combat.is_active(game_tick))
+            .map(|in_combat| in_combat.target_id);
+
+        Self {
+            player_id: player_id,
+            pos: pos,
+            fighting: fighting,
+        }
+    }
+
+    pub fn is_fighting(&self, target_id: i32) -> bool {
+        return self.fighting == Some(target_id);
+    }
+}
+
 #[derive(WorldQuery)]
 #[world_query(mutable, derive(Debug))]
 pub struct CombatQuery { 
@@ -87,6 +142,7 @@ pub struct CombatQuery {
     pub stats: &'static mut Stats,
     pub effects: &'static mut Effects,
     pub combo_tracker: Option<&'static mut ComboTracker>,
+    pub in_combat: Option<&'static InCombat>,
 }
 
 #[derive(WorldQuery)]
@@ -113,6 +169,7 @@ impl Combat {
         attack_type: AttackType,
         attacker: &mut CombatQueryItem,
         target: &mut CombatQueryItem,
+        combatants: &Vec<Combatant>,
         commands: &mut Commands,
         items: &mut ResMut<Items>,
         templates: &Res<Templates>,
@@ -150,6 +207,14 @@ impl Combat {
         // 7 Get attack type damage from
         let attack_type_damage_mod = Self::attack_type_damage_mod(attack_type.clone());
 
+        // Get stamina cost and exhaustion damage penalty
+        let stamina_cost = Stamina::attack_cost(attack_type.clone(), &attacker_weapons);
+        let exhaustion_mod = Self::get_exhaustion_mod(attacker);
+
+        // Get flanking damage mod and surrounded defense mod
+        let flanking_mod = Self::get_flanking_mod(attacker, target, combatants);
+        let surrounded_mod = Self::get_surrounded_mod(attacker, target, combatants);
+
         // TODO 8 Get damage reduction from Defensive action
 
         // 9 Get armor from defender items
@@ -176,14 +241,22 @@ impl Combat {
         // TODO 16 Check if target is fortified
 
         // 17 Roll from base damage
-        let roll_damage = rng.gen_range(0.0..damage_range) + base_damage;
+        let mut roll_damage = base_damage;
+
+        if damage_range > 0.0 {
+            roll_damage += rng.gen_range(0.0..damage_range);
+        }
 
         // 18 Calculate total damage
-        let total_damage =
-            (roll_damage + damage_from_items) * damage_effects_mod * attack_type_damage_mod;
+        let total_damage = (roll_damage + damage_from_items)
+            * damage_effects_mod
+            * attack_type_damage_mod
+            * exhaustion_mod
+            * flanking_mod;
 
         // 19 Calculate total defense
-        let total_defense = (base_defense * defense_from_items) * defense_effects_mod;
+        let total_defense =
+            (base_defense * defense_from_items) * defense_effects_mod * surrounded_mod;
 
         // 20 & 21 Calculate damage defense reduction
         let defense_reduction = total_defense / (total_defense + 50.0);
@@ -207,9 +280,11 @@ impl Combat {
         // 26 Update Hp and check if target is dead
         target.stats.hp -= final_damage as i32;
 
-        // 27 Update stamina TODO remove static 100 value
-        let attacker_stamina = attacker.stats.stamina.expect("Missing stamina stat");
-        attacker.stats.stamina = Some(attacker_stamina - 100);
+        // 27 Update stamina
+        Stamina::consume(&mut attacker.stats, stamina_cost);
+
+        // Mark both sides as in combat so escorts can join the fight
+        Self::track_combat(commands, attacker, target, game_tick);
 
         // 28 Apply new effects from this attack
         /*Self::apply_combo_effects(
@@ -225,6 +300,9 @@ impl Combat {
         // 29 Check if any weapons procced
         Self::process_weapon_procs(templates, &attacker_weapons, target);
 
+        // Wear down attacker weapons and defender armor
+        Self::wear_equipment(attacker.id.0, target.id.0, items);
+
         // 30 & 31 Check if target is dead and update skills
         let mut skill_updated = None;
 
@@ -263,6 +341,7 @@ impl Combat {
     pub fn process_combo(
         attacker: &mut CombatQueryItem,
         target: &mut CombatQueryItem,
+        combatants: &Vec<Combatant>,
         commands: &mut Commands,
         items: &mut ResMut<Items>,
         templates: &Res<Templates>,
@@ -297,6 +376,14 @@ impl Combat {
         let damage_from_items =
             Item::get_items_value_by_attr(&item::AttrKey::Damage, attacker_items);
 
+        // Combos cost as much stamina as a fierce attack
+        let stamina_cost = Stamina::attack_cost(AttackType::Fierce, &attacker_weapons);
+        let exhaustion_mod = Self::get_exhaustion_mod(attacker);
+
+        // Get flanking damage mod and surrounded defense mod
+        let flanking_mod = Self::get_flanking_mod(attacker, target, combatants);
+        let surrounded_mod = Self::get_surrounded_mod(attacker, target, combatants);
+
         // TODO 8 Get damage reduction from Defensive action
 
         // 9 Get armor from defender items
@@ -324,14 +411,22 @@ impl Combat {
         // TODO 16 Check if target is fortified
 
         // 17 Roll from base damage
-        let roll_damage = rng.gen_range(0.0..damage_range) + base_damage;
+        let mut roll_damage = base_damage;
+
+        if damage_range > 0.0 {
+            roll_damage += rng.gen_range(0.0..damage_range);
+        }
 
         // 18 Calculate total damage
-        let total_damage =
-            (roll_damage + damage_from_items) * damage_effects_mod * combo_damage_mod;
+        let total_damage = (roll_damage + damage_from_items)
+            * damage_effects_mod
+            * combo_damage_mod
+            * exhaustion_mod
+            * flanking_mod;
 
         // 19 Calculate total defense
-        let total_defense = (base_defense * defense_from_items) * defense_effects_mod;
+        let total_defense =
+            (base_defense * defense_from_items) * defense_effects_mod * surrounded_mod;
 
         // 20 & 21 Calculate damage defense reduction
         let defense_reduction = total_defense / (total_defense + 50.0);
@@ -355,9 +450,11 @@ impl Combat {
         // 26 Update Hp and check if target is dead
         target.stats.hp -= final_damage as i32;
 
-        // 27 Update stamina TODO remove static 100 value
-        let attacker_stamina = attacker.stats.stamina.expect("Missing stamina stat");
-        attacker.stats.stamina = Some(attacker_stamina - 100);
+        // 27 Update stamina
+        Stamina::consume(&mut attacker.stats, stamina_cost);
+
+        // Mark both sides as in combat so escorts can join the fight
+        Self::track_combat(commands, attacker, target, game_tick);
 
         // 28 Apply new effects from this attack
         Self::apply_combo_effects(
@@ -373,6 +470,9 @@ impl Combat {
         // 29 Check if any weapons procced
         Self::process_weapon_procs(templates, &attacker_weapons, target);
 
+        // Wear down attacker weapons and defender armor
+        Self::wear_equipment(attacker.id.0, target.id.0, items);
+
         // 30 & 31 Check if target is dead and update skills
         let mut skill_updated = None;
 
@@ -639,6 +739,91 @@ impl Combat {
         return 1.0;
     }
 
+    fn wear_equipment(attacker_id: i32, target_id: i32, items: &mut ResMut<Items>) {
+        for weapon in items.get_equipped_weapons(attacker_id).iter() {
+            items.wear(weapon.id, WEAPON_WEAR);
+        }
+
+        for item in items.get_equipped(target_id).iter() {
+            if item.class == item::ARMOR {
+                items.wear(item.id, ARMOR_WEAR);
+            }
+        }
+    }
+
+    // Attacking without stamina left reduces damage
+    fn get_exhaustion_mod(attacker: &CombatQueryItem) -> f32 {
+        if Stamina::is_exhausted(&attacker.stats) {
+            return stamina::EXHAUSTED_DAMAGE_MOD;
+        }
+
+        return 1.0;
+    }
+
+    // Attacker and a friendly unit also fighting the target on opposite sides of it
+    fn get_flanking_mod(
+        attacker: &CombatQueryItem,
+        target: &CombatQueryItem,
+        combatants: &Vec<Combatant>,
+    ) -> f32 {
+        for combatant in combatants.iter() {
+            if combatant.player_id != attacker.player_id.0 || !combatant.is_fighting(target.id.0) {
+                continue;
+            }
+
+            if Map::is_opposite(*target.pos, *attacker.pos, combatant.pos) {
+                return FLANKING_DAMAGE_MOD;
+            }
+        }
+
+        return 1.0;
+    }
+
+    // Defender with too many adjacent units fighting it loses defense
+    fn get_surrounded_mod(
+        attacker: &CombatQueryItem,
+        target: &CombatQueryItem,
+        combatants: &Vec<Combatant>,
+    ) -> f32 {
+        let mut num_hostiles = 0;
+
+        if Map::dist(*attacker.pos, *target.pos) <= 1 {
+            num_hostiles += 1;
+        }
+
+        for combatant in combatants.iter() {
+            if combatant.player_id != target.player_id.0
+                && combatant.is_fighting(target.id.0)
+                && Map::dist(*target.pos, combatant.pos) <= 1
+            {
+                num_hostiles += 1;
+            }
+        }
+
+        if num_hostiles >= SURROUNDED_COUNT {
+            return SURROUNDED_DEFENSE_MOD;
+        }
+
+        return 1.0;
+    }
+
+    fn track_combat(
+        commands: &mut Commands,
+        attacker: &CombatQueryItem,
+        target: &CombatQueryItem,
+        game_tick: &Res<GameTick>,
+    ) {
+        commands.entity(attacker.entity).insert(InCombat {
+            target_id: target.id.0,
+            last_attack: game_tick.0,
+        });
+
+        commands.entity(target.entity).insert(InCombat {
+            target_id: attacker.id.0,
+            last_attack: game_tick.0,
+        });
+    }
+
     fn get_terrain_defense(position: Position, map: &Res<Map>) -> f32 {
         return 1.0 + Map::def_bonus(Map::tile_type(position.x, position.y, &map));
     }
@@ -679,6 +864,17 @@ impl Combat {
         }
     }
 
+    // Rough measure of how dangerous a unit is, used to weigh up fights
+    pub fn combat_power(stats: &Stats, equipped: Vec<Item>) -> f32 {
+        let base_damage = stats.base_damage.unwrap_or(0) as f32;
+        let damage_range = stats.damage_range.unwrap_or(0) as f32;
+        let damage_from_items = Item::get_items_value_by_attr(&AttrKey::Damage, equipped);
+
+        let damage = base_damage + damage_range / 2.0 + damage_from_items;
+
+        return stats.hp.max(0) as f32 * damage.max(1.0);
+    }
+
     pub fn attack_type_to_enum(attack_type: String) -> AttackType {
         match attack_type.as_str() {
             QUICK => AttackType::Quick,
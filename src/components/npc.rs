@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use big_brain::prelude::*;
 
+use std::collections::HashMap;
+
 use crate::game::Position;
 
 #[derive(Debug, Clone, Component, ActionBuilder)]
@@ -20,6 +22,42 @@ impl VisibleTarget {
     }
 }
 
+// Threat accumulated per target obj id
+#[derive(Debug, Component, Default)]
+pub struct ThreatTable {
+    pub threat: HashMap<i32, f32>,
+}
+
+impl ThreatTable {
+    pub fn add(&mut self, target: i32, amount: f32) {
+        *self.threat.entry(target).or_insert(0.0) += amount;
+    }
+
+    // Puts the target at the top of the table
+    pub fn taunt(&mut self, target: i32, amount: f32) {
+        let top_threat = self.threat.values().cloned().fold(0.0, f32::max);
+        self.threat.insert(target, top_threat + amount);
+    }
+
+    pub fn get(&self, target: i32) -> f32 {
+        return *self.threat.get(&target).unwrap_or(&0.0);
+    }
+
+    pub fn highest(&self) -> Option<i32> {
+        let mut highest = None;
+        let mut highest_threat = 0.0;
+
+        for (target, threat) in self.threat.iter() {
+            if *threat > highest_threat {
+                highest_threat = *threat;
+                highest = Some(*target);
+            }
+        }
+
+        return highest;
+    }
+}
+
 // Necromancer
 #[derive(Debug, Clone, Component, ActionBuilder)]
 pub struct ChaseAndCast {
@@ -56,6 +94,41 @@ impl VisibleCorpse {
 #[derive(Debug, Clone, Component, ActionBuilder)]
 pub struct Hide;
 
+// Area a monster roams, centered on its lair if it came from one
+#[derive(Debug, Component, Clone)]
+pub struct Territory {
+    pub center: Position,
+    pub range: u32,
+    pub lair: Option<i32>,
+}
+
+// Sent by the empire after a debtor, only fights the debtor's objs
+#[derive(Debug, Component, Clone)]
+pub struct Soldier {
+    pub debtor: i32,
+    pub recall_at: i32,
+}
+
+// Rests by day and only roams and picks fights at night
+#[derive(Debug, Component, Clone)]
+pub struct Nocturnal;
+
+#[derive(Debug, Clone, Component, ScorerBuilder)]
+pub struct WanderScorer;
+
+#[derive(Debug, Clone, Component, ActionBuilder)]
+pub struct Wander {
+    pub dest: Option<Position>,
+}
+
+#[derive(Debug, Clone, Component, ScorerBuilder)]
+pub struct PatrolScorer;
+
+#[derive(Debug, Clone, Component, ActionBuilder)]
+pub struct Patrol {
+    pub dest: Option<Position>,
+}
+
 #[derive(Debug, Clone, Component, ScorerBuilder)]
 pub struct MerchantScorer;
 
@@ -70,6 +143,13 @@ pub struct Transport {
     pub hauling: Vec<i32>,
 }
 
+// Merchant ship tied up at a port, open for trade until the window closes
+#[derive(Debug, Clone, Component)]
+pub struct Docked {
+    pub port: String,
+    pub until: i32,
+}
+
 #[derive(Debug, Reflect, Component, Default)]
 #[reflect(Component)]
 pub struct TaxCollector {
@@ -1,7 +1,17 @@
 use bevy::prelude::*;
 use big_brain::prelude::*;
 
-use crate::game::Position;
+use std::collections::VecDeque;
+
+use crate::constants::{
+    DAWN, DEFEND_MAX_THREAT, EVENING, FLEE_HP_RATIO, GAME_HOUR, GAME_TICKS_PER_DAY,
+    GUARD_MAX_THREAT, GUARD_RANGE, MEAL_HOURS, MILITIA_MAX_THREAT, MILITIA_RANGE,
+    MORALE_CHANGE_RATE, MORALE_DESERTION, MORALE_MAX, MORALE_REFUSAL, MORNING,
+    NEED_MORALE_PENALTY, NIGHT, SLEEP_HOURS,
+};
+use crate::game::{Order, Position};
+use crate::item::Items;
+use crate::map::Map;
 
 
 #[derive(Debug, Clone, Component, ScorerBuilder)]
@@ -41,9 +51,21 @@ pub struct TransferFoodScorer;
 #[derive(Debug, Clone, Component, ScorerBuilder)]
 pub struct HasFoodScorer;
 
+#[derive(Debug, Clone, Component, ScorerBuilder)]
+pub struct EscortScorer;
+
+#[derive(Debug, Clone, Component, ScorerBuilder)]
+pub struct FightScorer;
+
 #[derive(Debug, Clone, Component, ActionBuilder)]
 pub struct Flee;
 
+#[derive(Debug, Clone, Component, ActionBuilder)]
+pub struct Escort;
+
+#[derive(Debug, Clone, Component, ActionBuilder)]
+pub struct Fight;
+
 #[derive(Debug, Clone, Component, ActionBuilder)]
 pub struct MoveToDrink {
     pub dest: Position
@@ -244,14 +266,454 @@ pub struct ProcessOrder;
 #[derive(Debug, Clone, Component, ScorerBuilder)]
 pub struct   GoodMorale;
 
+pub const MORALE_DEATH: &str = "death";
+pub const MORALE_WAGES: &str = "wages";
+pub const MORALE_FRIENDS: &str = "friends";
+pub const MORALE_RIVALS: &str = "rivals";
+
+// Villager refused to work and will not take up its order again until then
+#[derive(Debug, Clone, Component)]
+pub struct RefusedWork {
+    pub until: i32,
+}
+
+// Temporary morale change from an event, e.g. a nearby death
+#[derive(Debug, Clone)]
+pub struct MoraleModifier {
+    pub source: String,
+    pub amount: f32,
+    pub expires_at: i32,
+}
+
 #[derive(Component, Debug)]
 pub struct Morale {
     pub morale: f32,
+    pub low_since: Option<i32>,
+    pub modifiers: Vec<MoraleModifier>,
 }
 
 impl Morale {
     pub fn new(morale: f32) -> Self {
-        Self { morale }
+        Self {
+            morale,
+            low_since: None,
+            modifiers: Vec::new(),
+        }
+    }
+
+    // Only one modifier per source, a new one replaces the old
+    pub fn add_modifier(&mut self, source: &str, amount: f32, expires_at: i32) {
+        self.modifiers.retain(|m| m.source != source);
+
+        self.modifiers.push(MoraleModifier {
+            source: source.to_string(),
+            amount: amount,
+            expires_at: expires_at,
+        });
+    }
+
+    pub fn modifiers_total(&mut self, game_tick: i32) -> f32 {
+        self.modifiers.retain(|m| m.expires_at > game_tick);
+
+        return self.modifiers.iter().map(|m| m.amount).sum();
+    }
+
+    pub fn need_penalty(need: f32) -> f32 {
+        if need >= 80.0 {
+            return NEED_MORALE_PENALTY;
+        } else if need >= 50.0 {
+            return NEED_MORALE_PENALTY / 2.0;
+        }
+
+        return 0.0;
+    }
+
+    pub fn update(&mut self, target: f32) {
+        let change = (target - self.morale).clamp(-MORALE_CHANGE_RATE, MORALE_CHANGE_RATE);
+
+        self.morale = (self.morale + change).clamp(0.0, MORALE_MAX);
+    }
+
+    // 0.75 when miserable up to 1.25 when elated
+    pub fn work_speed_mod(&self) -> f32 {
+        return 0.75 + self.morale / (2.0 * MORALE_MAX);
+    }
+
+    // 0.9 when miserable up to 1.1 when elated
+    pub fn quality_mod(&self) -> f32 {
+        return 0.9 + self.morale / (5.0 * MORALE_MAX);
+    }
+
+    pub fn refusal_chance(&self) -> f32 {
+        if self.morale >= MORALE_REFUSAL {
+            return 0.0;
+        }
+
+        return (MORALE_REFUSAL - self.morale) / MORALE_REFUSAL;
+    }
+
+    pub fn work_duration(base_duration: i32, morale: Option<&Morale>) -> i32 {
+        match morale {
+            Some(morale) => (base_duration as f32 / morale.work_speed_mod()) as i32,
+            None => base_duration,
+        }
+    }
+
+    pub fn to_str(&self) -> String {
+        let mood = if self.morale < MORALE_DESERTION {
+            "Mutinous"
+        } else if self.morale < MORALE_REFUSAL {
+            "Miserable"
+        } else if self.morale < 45.0 {
+            "Unhappy"
+        } else if self.morale < 65.0 {
+            "Content"
+        } else if self.morale < 85.0 {
+            "Happy"
+        } else {
+            "Elated"
+        };
+
+        return format!("{} ({})", mood, self.morale as i32);
+    }
+}
+
+pub const SCHEDULE_WORK: &str = "work";
+pub const SCHEDULE_MEAL: &str = "meal";
+pub const SCHEDULE_SLEEP: &str = "sleep";
+pub const SCHEDULE_FREE: &str = "free";
+
+#[derive(Debug, Clone, Component, ScorerBuilder)]
+pub struct ScheduleScorer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleActivity {
+    Work,
+    Meal,
+    Sleep,
+    Free,
+}
+
+impl ScheduleActivity {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ScheduleActivity::Work => SCHEDULE_WORK,
+            ScheduleActivity::Meal => SCHEDULE_MEAL,
+            ScheduleActivity::Sleep => SCHEDULE_SLEEP,
+            ScheduleActivity::Free => SCHEDULE_FREE,
+        }
+    }
+
+    pub fn from_str(activity: &str) -> Option<Self> {
+        match activity {
+            SCHEDULE_WORK => Some(ScheduleActivity::Work),
+            SCHEDULE_MEAL => Some(ScheduleActivity::Meal),
+            SCHEDULE_SLEEP => Some(ScheduleActivity::Sleep),
+            SCHEDULE_FREE => Some(ScheduleActivity::Free),
+            _ => None,
+        }
+    }
+}
+
+// Block of hours in the day, wraps around midnight if end is before start
+#[derive(Debug, Clone)]
+pub struct ScheduleBlock {
+    pub start: i32,
+    pub end: i32,
+    pub activity: ScheduleActivity,
+}
+
+impl ScheduleBlock {
+    pub fn new(start: i32, end: i32, activity: ScheduleActivity) -> Self {
+        Self {
+            start,
+            end,
+            activity,
+        }
+    }
+
+    pub fn contains(&self, hour: i32) -> bool {
+        if self.start <= self.end {
+            return hour >= self.start && hour < self.end;
+        } else {
+            return hour >= self.start || hour < self.end;
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Schedule {
+    pub blocks: Vec<ScheduleBlock>,
+}
+
+impl Schedule {
+    pub const HOURS_PER_DAY: i32 = GAME_TICKS_PER_DAY / GAME_HOUR;
+
+    pub fn new(blocks: Vec<ScheduleBlock>) -> Self {
+        Self { blocks }
+    }
+
+    pub fn hour_of_day(game_tick: i32) -> i32 {
+        return game_tick.rem_euclid(GAME_TICKS_PER_DAY) / GAME_HOUR;
+    }
+
+    // Moves every block later in the day, e.g. for night owls
+    pub fn shifted(mut self, hours: i32) -> Self {
+        for block in self.blocks.iter_mut() {
+            block.start = (block.start + hours).rem_euclid(Self::HOURS_PER_DAY);
+            block.end = (block.end + hours).rem_euclid(Self::HOURS_PER_DAY);
+        }
+
+        return self;
+    }
+
+    // Hours not covered by a block are free time
+    pub fn current_activity(&self, game_tick: i32) -> ScheduleActivity {
+        let hour = Self::hour_of_day(game_tick);
+
+        for block in self.blocks.iter() {
+            if block.contains(hour) {
+                return block.activity;
+            }
+        }
+
+        return ScheduleActivity::Free;
+    }
+}
+
+impl Default for Schedule {
+    // Two work shifts between dawn and night with meals in between, sleeping until dawn
+    fn default() -> Self {
+        let wake = Self::hour_of_day(DAWN - MEAL_HOURS * GAME_HOUR);
+        let bedtime = Self::hour_of_day(DAWN - (MEAL_HOURS + SLEEP_HOURS) * GAME_HOUR);
+        let supper_end = Self::hour_of_day(NIGHT + MEAL_HOURS * GAME_HOUR);
+
+        Self::new(vec![
+            ScheduleBlock::new(bedtime, wake, ScheduleActivity::Sleep),
+            ScheduleBlock::new(wake, Self::hour_of_day(DAWN), ScheduleActivity::Meal),
+            ScheduleBlock::new(
+                Self::hour_of_day(DAWN),
+                Self::hour_of_day(MORNING),
+                ScheduleActivity::Work,
+            ),
+            ScheduleBlock::new(
+                Self::hour_of_day(MORNING),
+                Self::hour_of_day(EVENING),
+                ScheduleActivity::Meal,
+            ),
+            ScheduleBlock::new(
+                Self::hour_of_day(EVENING),
+                Self::hour_of_day(NIGHT),
+                ScheduleActivity::Work,
+            ),
+            ScheduleBlock::new(Self::hour_of_day(NIGHT), supper_end, ScheduleActivity::Meal),
+            ScheduleBlock::new(supper_end, bedtime, ScheduleActivity::Free),
+        ])
+    }
+}
+
+// When a queued order is finished and the next one can start
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderStop {
+    Count(i32),
+    Holds {
+        owner: i32,
+        item: String,
+        quantity: i32,
+    },
+    Repeat,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueuedOrder {
+    pub id: i32,
+    pub order: Order,
+    pub structure: i32,
+    pub stop: OrderStop,
+    pub completed: i32,
+}
+
+impl QueuedOrder {
+    pub fn is_complete(&self, items: &Items) -> bool {
+        match &self.stop {
+            OrderStop::Count(count) => self.completed >= *count,
+            OrderStop::Holds {
+                owner,
+                item,
+                quantity,
+            } => items.get_total_quantity(*owner, item) >= *quantity,
+            OrderStop::Repeat => false,
+        }
+    }
+
+    pub fn progress(&self, items: &Items) -> String {
+        match &self.stop {
+            OrderStop::Count(count) => format!("{}/{}", self.completed, count),
+            OrderStop::Holds {
+                owner,
+                item,
+                quantity,
+            } => format!(
+                "{}/{} {}",
+                items.get_total_quantity(*owner, item),
+                quantity,
+                item
+            ),
+            OrderStop::Repeat => format!("{} done", self.completed),
+        }
+    }
+}
+
+// Orders are worked front to back, the front order is mirrored into the Order component
+#[derive(Component, Debug, Clone, Default)]
+pub struct OrderQueue {
+    pub next_id: i32,
+    pub orders: VecDeque<QueuedOrder>,
+}
+
+impl OrderQueue {
+    pub const MAX_ORDERS: usize = 10;
+
+    pub fn push(&mut self, order: Order, structure: i32, stop: OrderStop) -> Option<i32> {
+        if self.orders.len() >= Self::MAX_ORDERS {
+            return None;
+        }
+
+        self.next_id += 1;
+
+        self.orders.push_back(QueuedOrder {
+            id: self.next_id,
+            order: order,
+            structure: structure,
+            stop: stop,
+            completed: 0,
+        });
+
+        return Some(self.next_id);
+    }
+
+    pub fn current(&self) -> Option<&QueuedOrder> {
+        return self.orders.front();
+    }
+
+    pub fn cancel(&mut self, order_id: i32) -> bool {
+        let Some(index) = self.orders.iter().position(|o| o.id == order_id) else {
+            return false;
+        };
+
+        self.orders.remove(index);
+        return true;
+    }
+
+    pub fn move_order(&mut self, order_id: i32, new_index: usize) -> bool {
+        let Some(index) = self.orders.iter().position(|o| o.id == order_id) else {
+            return false;
+        };
+
+        if new_index >= self.orders.len() {
+            return false;
+        }
+
+        if let Some(queued_order) = self.orders.remove(index) {
+            self.orders.insert(new_index, queued_order);
+        }
+
+        return true;
+    }
+
+    // Called when the villager finishes one unit of work for the current order
+    pub fn record_progress(&mut self) {
+        if let Some(queued_order) = self.orders.front_mut() {
+            queued_order.completed += 1;
+        }
+    }
+}
+
+// Generated once per villager from the villager templates
+#[derive(Component, Debug, Clone)]
+pub struct Personality {
+    pub culture: String,
+    pub traits: Vec<String>,
+    pub likes: Vec<String>,
+    pub dislikes: Vec<String>,
+    pub backstory: String,
+    pub thirst_rate: f32,
+    pub hunger_rate: f32,
+    pub tired_rate: f32,
+    pub work_weight: f32,
+    pub flee_weight: f32,
+    pub schedule_shift: i32,
+}
+
+impl Personality {
+    pub fn likes(&self, work_type: &String) -> bool {
+        return self.likes.contains(work_type);
+    }
+
+    pub fn dislikes(&self, work_type: &String) -> bool {
+        return self.dislikes.contains(work_type);
     }
 }
 
+pub const STANCE_FLEE: &str = "flee";
+pub const STANCE_DEFEND_SELF: &str = "defend_self";
+pub const STANCE_GUARD_AREA: &str = "guard_area";
+pub const STANCE_MILITIA: &str = "militia";
+
+// How a villager reacts to enemies, villagers without one flee
+#[derive(Component, Debug, Clone, PartialEq)]
+pub enum CombatStance {
+    Flee,
+    DefendSelf,
+    GuardArea { center: Position },
+    Militia,
+}
+
+impl CombatStance {
+    pub fn to_str(&self) -> String {
+        let stance = match self {
+            CombatStance::Flee => STANCE_FLEE,
+            CombatStance::DefendSelf => STANCE_DEFEND_SELF,
+            CombatStance::GuardArea { .. } => STANCE_GUARD_AREA,
+            CombatStance::Militia => STANCE_MILITIA,
+        };
+
+        return stance.to_string();
+    }
+
+    // Guards keep watch over the position they were given the stance at
+    pub fn from_str(stance: &str, pos: Position) -> Option<CombatStance> {
+        match stance {
+            STANCE_FLEE => Some(CombatStance::Flee),
+            STANCE_DEFEND_SELF => Some(CombatStance::DefendSelf),
+            STANCE_GUARD_AREA => Some(CombatStance::GuardArea { center: pos }),
+            STANCE_MILITIA => Some(CombatStance::Militia),
+            _ => None,
+        }
+    }
+
+    // Whether the villager will go after an enemy at this position
+    pub fn in_engage_range(&self, pos: Position, enemy_pos: Position) -> bool {
+        match self {
+            CombatStance::Flee => false,
+            CombatStance::DefendSelf => Map::dist(pos, enemy_pos) <= 1,
+            CombatStance::GuardArea { center } => Map::dist(*center, enemy_pos) <= GUARD_RANGE,
+            CombatStance::Militia => Map::dist(pos, enemy_pos) <= MILITIA_RANGE,
+        }
+    }
+
+    // Threat is the enemy's combat power relative to the villager's
+    pub fn should_fight(&self, hp_ratio: f32, morale: f32, threat: f32) -> bool {
+        // Badly hurt or demoralised villagers run whatever their stance
+        if hp_ratio < FLEE_HP_RATIO || morale < MORALE_REFUSAL {
+            return false;
+        }
+
+        match self {
+            CombatStance::Flee => false,
+            CombatStance::DefendSelf => threat <= DEFEND_MAX_THREAT,
+            CombatStance::GuardArea { .. } => threat <= GUARD_MAX_THREAT,
+            CombatStance::Militia => threat <= MILITIA_MAX_THREAT,
+        }
+    }
+}
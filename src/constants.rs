@@ -4,6 +4,10 @@
 pub const EMERGENCY_SCORE: f32 = 99.0;
 pub const URGENT_SCORE: f32 = 98.0;
 pub const MAX_ROUTINE_SCORE: f32 = 97.0;
+pub const SCHEDULED_SCORE: f32 = 70.0;
+
+// Minimum need before a scheduled meal or sleep is taken
+pub const SCHEDULED_NEED_MIN: f32 = 10.0;
 
 pub const HYDRATED: f32 = 0.0;
 pub const REFRESHED: f32 = 15.0;
@@ -19,12 +23,52 @@ pub const EXHAUSTED: f32 = 99.0;
 pub const GAME_HOUR: i32 = 100;
 pub const GAME_TICKS_PER_DAY: i32 = 2400;
 
+// Monsters and other unowned NPCs belong to this player
+pub const NPC_PLAYER_ID: i32 = 1000;
+
 pub const DAWN: i32 = 200;
 pub const MORNING: i32 = 500;
 pub const EVENING: i32 = 600;
 pub const DUSK: i32 = 700;
 pub const NIGHT: i32 = 800;
 
+// Villagers work the daylight hours and sleep this long before dawn
+pub const SLEEP_HOURS: i32 = 8;
+pub const MEAL_HOURS: i32 = 1;
+
 pub const COMFORT_TEMPERATURE: f32 = 20.0;
 
 
+
+pub const MORALE_MAX: f32 = 100.0;
+pub const MORALE_BASE: f32 = 50.0;
+
+// Morale moves towards its target by this much every game hour
+pub const MORALE_CHANGE_RATE: f32 = 5.0;
+
+// Below these villagers start refusing orders and eventually desert
+pub const MORALE_REFUSAL: f32 = 25.0;
+pub const MORALE_DESERTION: f32 = 5.0;
+pub const DESERTION_TIME: i32 = GAME_TICKS_PER_DAY;
+// A villager that refused to work sits idle this long before it can be asked again
+pub const REFUSAL_COOLDOWN: i32 = GAME_HOUR * 2;
+
+pub const NEED_MORALE_PENALTY: f32 = 10.0;
+pub const DISCOMFORT_HEAT: f32 = 25.0;
+pub const SHELTER_MORALE_BONUS: f32 = 5.0;
+pub const NO_SHELTER_MORALE_PENALTY: f32 = 10.0;
+pub const COMBAT_MORALE_PENALTY: f32 = 10.0;
+pub const DEATH_MORALE_PENALTY: f32 = 20.0;
+pub const DEATH_MORALE_RANGE: u32 = 5;
+pub const TAX_DEBT_MORALE_PENALTY: f32 = 10.0;
+pub const WORK_MORALE_MODIFIER: f32 = 5.0;
+
+// Villagers below this fraction of their hp always flee
+pub const FLEE_HP_RATIO: f32 = 0.3;
+pub const GUARD_RANGE: u32 = 4;
+pub const MILITIA_RANGE: u32 = 8;
+
+// Strongest enemy, relative to the villager, each stance will stand against
+pub const DEFEND_MAX_THREAT: f32 = 1.0;
+pub const GUARD_MAX_THREAT: f32 = 1.5;
+pub const MILITIA_MAX_THREAT: f32 = 3.0;
@@ -0,0 +1,415 @@
+use bevy::prelude::*;
+use big_brain::prelude::*;
+use big_brain::thinker::HasThinker;
+
+use std::collections::HashMap;
+
+use crate::components::npc::ThreatTable;
+use crate::components::villager::{
+    CombatStance, Hunger, Morale, OrderQueue, Schedule, Thirst, Tired, MORALE_WAGES,
+};
+use crate::constants::GAME_TICKS_PER_DAY;
+use crate::event::{MapEvents, VisibleEvent};
+use crate::game::{GameTick, Id, PlayerId};
+use crate::ids::Ids;
+use crate::item::Items;
+use crate::ledger;
+use crate::merchant::MERCHANT_PLAYER_ID;
+use crate::network;
+use crate::obj::Obj;
+use crate::plugins::ai::thinker::{AiThinker, ThinkerContext};
+use crate::social::Relationships;
+use crate::templates::Templates;
+
+// Daily wage before skills are taken into account
+pub const BASE_WAGE: i32 = 5;
+// Every this many total skill levels adds a gold to the wage
+pub const SKILL_LEVELS_PER_WAGE: i32 = 5;
+
+// Contracts run for this many days and renew if the wages are paid up
+pub const CONTRACT_TERM: i32 = 7;
+
+pub const START_LOYALTY: i32 = 50;
+pub const MAX_LOYALTY: i32 = 100;
+pub const PAID_LOYALTY: i32 = 5;
+pub const UNPAID_LOYALTY: i32 = -20;
+
+// Morale penalty lasting a day for each missed payday
+pub const UNPAID_MORALE_PENALTY: f32 = 15.0;
+
+// Villagers that quit go back to wandering the land
+pub const QUIT_THINKER: &str = "Drifter";
+
+// Quit villager waiting for its hired thinker to be torn down
+#[derive(Debug, Clone, Component)]
+pub struct Quitting;
+
+// Employment terms of a hired villager, wages come out of the hero's gold
+#[derive(Debug, Clone, Component)]
+pub struct Contract {
+    pub wage: i32,
+    // Length of each term in days
+    pub term: i32,
+    pub ends_at: i32,
+    pub next_payday: i32,
+    // Wages missed so far this term
+    pub owed: i32,
+    pub loyalty: i32,
+}
+
+impl Contract {
+    pub fn new(wage: i32, game_tick: i32) -> Self {
+        Self {
+            wage: wage,
+            term: CONTRACT_TERM,
+            ends_at: game_tick + CONTRACT_TERM * GAME_TICKS_PER_DAY,
+            next_payday: game_tick + GAME_TICKS_PER_DAY,
+            owed: 0,
+            loyalty: START_LOYALTY,
+        }
+    }
+
+    // Skill levels of the villager being hired
+    pub fn wage(skills: &HashMap<String, i32>) -> i32 {
+        let total_levels: i32 = skills.values().sum();
+
+        return BASE_WAGE + total_levels / SKILL_LEVELS_PER_WAGE;
+    }
+
+    // Only villagers the merchants still hold and nobody else has under contract can be hired
+    pub fn can_hire(target_player_id: i32, contract: Option<&Contract>) -> Result<(), String> {
+        if target_player_id != MERCHANT_PLAYER_ID || contract.is_some() {
+            return Err("Villager is not for hire".to_string());
+        }
+
+        return Ok(());
+    }
+
+    pub fn add_loyalty(&mut self, amount: i32) {
+        self.loyalty = (self.loyalty + amount).clamp(0, MAX_LOYALTY);
+    }
+
+    // Returns the amount paid, whatever could not be paid is added to what is owed.
+    // Wages leave the economy rather than sitting in the villager's inventory
+    pub fn pay(&mut self, hero_id: i32, items: &mut Items) -> i32 {
+        let due = self.wage + self.owed;
+        let paid = i32::min(items.get_total_gold(hero_id), due);
+
+        if paid > 0 {
+            items.set_reason(ledger::REASON_WAGES);
+            items.remove_gold(hero_id, paid);
+            items.clear_reason();
+        }
+
+        self.owed = due - paid;
+
+        if self.owed > 0 {
+            self.add_loyalty(UNPAID_LOYALTY);
+        } else {
+            self.add_loyalty(PAID_LOYALTY);
+        }
+
+        return paid;
+    }
+
+    // Lapses at the end of a term with wages still owed or once all loyalty is gone
+    pub fn is_lapsed(&self, game_tick: i32) -> bool {
+        return (game_tick >= self.ends_at && self.owed > 0) || self.loyalty <= 0;
+    }
+
+    pub fn renew(&mut self) {
+        self.ends_at += self.term * GAME_TICKS_PER_DAY;
+    }
+
+    pub fn days_remaining(&self, game_tick: i32) -> i32 {
+        return i32::max(self.ends_at - game_tick, 0) / GAME_TICKS_PER_DAY;
+    }
+
+    pub fn to_data(&self, game_tick: i32) -> network::ContractData {
+        return network::ContractData {
+            wage: self.wage,
+            term: self.term,
+            remaining: self.days_remaining(game_tick),
+            owed: self.owed,
+            loyalty: self.loyalty,
+        };
+    }
+}
+
+fn contract_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut ids: ResMut<Ids>,
+    mut items: ResMut<Items>,
+    mut map_events: ResMut<MapEvents>,
+    mut query: Query<(
+        Entity,
+        &Id,
+        &mut PlayerId,
+        &mut Contract,
+        Option<&mut Morale>,
+    )>,
+) {
+    for (entity, id, mut player_id, mut contract, morale) in query.iter_mut() {
+        if game_tick.0 < contract.next_payday {
+            continue;
+        }
+
+        contract.next_payday += GAME_TICKS_PER_DAY;
+
+        let Some(hero_id) = ids.get_hero(player_id.0) else {
+            error!("Cannot find hero for player {:?}", player_id.0);
+            continue;
+        };
+
+        contract.pay(hero_id, &mut items);
+
+        if contract.owed > 0 {
+            if let Some(mut morale) = morale {
+                morale.add_modifier(
+                    MORALE_WAGES,
+                    -UNPAID_MORALE_PENALTY,
+                    game_tick.0 + GAME_TICKS_PER_DAY,
+                );
+            }
+        }
+
+        if contract.is_lapsed(game_tick.0) {
+            info!("Villager {:?} quit working for player {:?}", id.0, player_id.0);
+
+            Obj::add_sound_obj_event(
+                game_tick.0,
+                "No pay, no work. I'm off!".to_string(),
+                id,
+                &mut map_events,
+            );
+
+            // Whatever the villager was carrying is handed back to the hero
+            items.hand_over_all_items(id.0, hero_id);
+
+            *player_id = PlayerId(MERCHANT_PLAYER_ID);
+            ids.set_player(id.0, MERCHANT_PLAYER_ID);
+
+            map_events.new(
+                id.0,
+                game_tick.0 + 1,
+                VisibleEvent::NewObjEvent { new_player: false },
+            );
+
+            commands
+                .entity(entity)
+                .remove::<(
+                    Contract,
+                    Morale,
+                    Thirst,
+                    Hunger,
+                    Tired,
+                    Schedule,
+                    OrderQueue,
+                    Relationships,
+                    CombatStance,
+                    ThreatTable,
+                    ThinkerBuilder,
+                )>()
+                .insert(Quitting);
+        } else if game_tick.0 >= contract.ends_at {
+            contract.renew();
+        } else if contract.owed > 0 {
+            Obj::add_sound_obj_event(
+                game_tick.0,
+                "Where are my wages?".to_string(),
+                id,
+                &mut map_events,
+            );
+        }
+    }
+}
+
+// A new thinker can only be attached once the hired one is gone
+fn quit_system(
+    mut commands: Commands,
+    templates: Res<Templates>,
+    query: Query<Entity, (With<Quitting>, Without<HasThinker>)>,
+) {
+    for entity in query.iter() {
+        commands
+            .entity(entity)
+            .remove::<Quitting>()
+            .insert(AiThinker::build(
+                QUIT_THINKER,
+                &ThinkerContext::default(),
+                &templates,
+            ));
+    }
+}
+
+pub struct ContractPlugin;
+
+impl Plugin for ContractPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (contract_system, quit_system));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::ledger::LedgerOp;
+    use crate::templates::ItemTemplate;
+
+    fn load_items() -> Items {
+        let item_template_file =
+            fs::File::open("item_template.yaml").expect("Could not open file.");
+        let item_templates: Vec<ItemTemplate> =
+            serde_yaml::from_reader(item_template_file).expect("Could not read values.");
+
+        let mut items = Items::default();
+        items.set_templates(item_templates);
+
+        return items;
+    }
+
+    fn new_ids() -> Ids {
+        return Ids {
+            map_event: 0,
+            player_event: 0,
+            obj: 0,
+            item: 0,
+            player_hero_map: HashMap::new(),
+            obj_entity_map: HashMap::new(),
+            obj_player_map: HashMap::new(),
+        };
+    }
+
+    #[test]
+    fn test_pay_wages() {
+        let mut items = load_items();
+        let gold = items.new(1, "Gold Coins".to_string(), 12);
+
+        let mut contract = Contract::new(5, 0);
+
+        assert_eq!(contract.pay(1, &mut items), 5);
+        assert_eq!(items.get_total_gold(1), 7);
+        assert_eq!(contract.loyalty, START_LOYALTY + PAID_LOYALTY);
+
+        // Wages leave the economy rather than going to the villager
+        let wages: Vec<_> = items
+            .ledger()
+            .by_item(gold.id)
+            .into_iter()
+            .filter(|entry| entry.op == LedgerOp::Destroy)
+            .collect();
+
+        assert_eq!(wages.len(), 1);
+        assert_eq!(wages[0].reason, ledger::REASON_WAGES);
+        assert!(items.ledger().check(&items.recount()).is_empty());
+
+        // Whatever cannot be paid is owed on the next payday
+        assert_eq!(contract.pay(1, &mut items), 5);
+        assert_eq!(contract.pay(1, &mut items), 2);
+        assert_eq!(contract.owed, 3);
+        assert_eq!(
+            contract.loyalty,
+            START_LOYALTY + PAID_LOYALTY * 2 + UNPAID_LOYALTY
+        );
+
+        items.new(1, "Gold Coins".to_string(), 20);
+
+        assert_eq!(contract.pay(1, &mut items), 8);
+        assert_eq!(contract.owed, 0);
+        assert_eq!(items.get_total_gold(1), 12);
+    }
+
+    #[test]
+    fn test_lapse() {
+        let mut items = load_items();
+
+        // Paid up contracts renew at the end of the term
+        let mut contract = Contract::new(5, 0);
+        assert!(!contract.is_lapsed(contract.ends_at));
+
+        contract.renew();
+        assert_eq!(contract.ends_at, CONTRACT_TERM * 2 * GAME_TICKS_PER_DAY);
+
+        // Wages still owed at the end of a term
+        contract.pay(1, &mut items);
+
+        assert!(!contract.is_lapsed(contract.ends_at - 1));
+        assert!(contract.is_lapsed(contract.ends_at));
+
+        // Loyalty runs out before the term is up
+        let mut contract = Contract::new(5, 0);
+        let mut paydays = 0;
+
+        while !contract.is_lapsed(0) {
+            contract.pay(1, &mut items);
+            paydays += 1;
+        }
+
+        // 50, 30, 10 and then nothing
+        assert_eq!(paydays, 3);
+        assert_eq!(contract.loyalty, 0);
+    }
+
+    #[test]
+    fn test_quit_and_rehire() {
+        let mut items = load_items();
+        let mut ids = new_ids();
+
+        let mut app = App::new();
+
+        let hero_entity = app.world.spawn_empty().id();
+        ids.new_hero(1, 100, hero_entity);
+
+        let mut contract = Contract::new(5, 0);
+        contract.loyalty = -UNPAID_LOYALTY;
+
+        let villager_entity = app
+            .world
+            .spawn((Id(10), PlayerId(100), contract, Morale::new(50.0)))
+            .id();
+        ids.new_obj(10, 100, villager_entity);
+
+        let axe = items.new(10, "Copper Training Axe".to_string(), 1);
+        items.equip(axe.id, true);
+
+        app.insert_resource(GameTick(GAME_TICKS_PER_DAY))
+            .insert_resource(ids)
+            .insert_resource(items)
+            .init_resource::<MapEvents>()
+            .add_systems(Update, contract_system);
+
+        // Not hired away while under contract
+        let contract = app.world.get::<Contract>(villager_entity);
+
+        assert!(Contract::can_hire(MERCHANT_PLAYER_ID, contract).is_err());
+        assert!(Contract::can_hire(100, None).is_err());
+
+        // Hero has no gold for the wages, the last of the villager's loyalty is gone
+        app.update();
+
+        let villager = app.world.entity(villager_entity);
+
+        assert!(villager.get::<Contract>().is_none());
+        assert!(villager.get::<Morale>().is_none());
+        assert!(villager.get::<Quitting>().is_some());
+        assert_eq!(villager.get::<PlayerId>().unwrap().0, MERCHANT_PLAYER_ID);
+        assert_eq!(
+            app.world.resource::<Ids>().get_player(10),
+            Some(MERCHANT_PLAYER_ID)
+        );
+
+        // Villager is still around and its items went back to the hero
+        let items = app.world.resource::<Items>();
+
+        assert!(items.get_by_owner(10).is_empty());
+        assert_eq!(items.get_by_owner(1).len(), 1);
+        assert!(!items.get_by_owner(1)[0].equipped);
+
+        // Back with the merchants and free to be hired again
+        assert!(Contract::can_hire(MERCHANT_PLAYER_ID, villager.get::<Contract>()).is_ok());
+    }
+}
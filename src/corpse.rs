@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::encounter::Encounter;
+use crate::item::{self, Items};
+use crate::ledger;
+use crate::templates::LootTemplate;
+
+pub const BONES: &str = "Bones";
+pub const BONES_ITEM: &str = "bones";
+
+// Ticks after death before a corpse rots into bones
+pub const ROT_TIME: i32 = 500;
+// Ticks after death before bones are removed
+pub const DECAY_TIME: i32 = 1000;
+// Ticks after death before an empty corpse is removed
+pub const EMPTY_DECAY_TIME: i32 = 100;
+
+pub const SKIN_XP: i32 = 10;
+pub const BUTCHER_XP: i32 = 10;
+
+// Each skill level adds 25% to skinning and butchering yields
+pub const YIELD_PER_LEVEL: f32 = 0.25;
+
+#[derive(Debug, Component, Clone, Default)]
+pub struct Harvested {
+    pub skinned: bool,
+    pub butchered: bool,
+}
+
+// Tag for corpses that have already rotted into bones
+#[derive(Debug, Component, Clone)]
+pub struct Bones;
+
+#[derive(Debug, Clone)]
+pub struct Corpse;
+
+impl Corpse {
+    pub fn harvest_yield(harvest_table: &Vec<LootTemplate>, skill_level: i32) -> Vec<(String, i32)> {
+        let yield_mod = 1.0 + skill_level as f32 * YIELD_PER_LEVEL;
+        let mut harvest = Vec::new();
+
+        for (item_name, quantity) in Encounter::roll_loot(harvest_table).into_iter() {
+            harvest.push((item_name, (quantity as f32 * yield_mod) as i32));
+        }
+
+        return harvest;
+    }
+
+    // Moves everything that does not spoil to the bones and adds the bones themselves
+    pub fn rot(corpse_id: i32, bones_id: i32, items: &mut ResMut<Items>) {
+        items.set_reason(ledger::REASON_DECAY);
+
+        for corpse_item in items.get_by_owner(corpse_id).iter() {
+            if corpse_item.class == item::FOOD {
+                items.remove_item(corpse_item.id);
+            } else {
+                items.transfer(corpse_item.id, bones_id);
+            }
+        }
+
+        items.clear_reason();
+        items.create(bones_id, BONES_ITEM.to_string(), 1);
+    }
+
+    // Whatever is left on the remains is lost once they are removed
+    pub fn decay(obj_id: i32, items: &mut ResMut<Items>) {
+        items.set_reason(ledger::REASON_DECAY);
+
+        for remaining_item in items.get_by_owner(obj_id).iter() {
+            items.remove_item(remaining_item.id);
+        }
+
+        items.clear_reason();
+    }
+}
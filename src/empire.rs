@@ -0,0 +1,475 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use std::collections::HashMap;
+
+use crate::components::npc::{Soldier, TaxCollector, Territory, ThreatTable};
+use crate::encounter::Encounter;
+use crate::event::{MapEvents, VisibleEvent};
+use crate::game::{GameTick, Id, Position, State};
+use crate::ids::Ids;
+use crate::item::{self, Items};
+use crate::ledger;
+use crate::map::Map;
+use crate::merchant::MERCHANT_PLAYER_ID;
+use crate::network::ResponsePacket;
+use crate::obj::Obj;
+use crate::pricing::Pricing;
+use crate::templates::{EmpireTemplate, Templates};
+
+// Tax collectors, soldiers and merchant ships all serve the empire
+pub const EMPIRE_PLAYER_ID: i32 = MERCHANT_PLAYER_ID;
+
+// Soldiers go straight for the debtor's hero
+pub const SOLDIER_THREAT: f32 = 1000.0;
+pub const SOLDIER_SPAWN_RANGE: u32 = 2;
+pub const SOLDIER_PATROL_RANGE: u32 = 3;
+
+// Standing of a player's settlement with the empire
+#[derive(Debug, Reflect, Clone, Default)]
+pub struct Vassal {
+    pub loyalty: i32,
+    // Forfeitures in a row that left debt behind
+    pub strikes: i32,
+    // Only one attempt at negotiating each assessment
+    pub negotiated: bool,
+    pub tier: String,
+    // Ids of the squad sent after the vassal's debt
+    pub soldiers: Vec<i32>,
+}
+
+impl Vassal {
+    pub fn add_loyalty(&mut self, amount: i32, empire_template: &EmpireTemplate) {
+        self.loyalty = (self.loyalty + amount).clamp(0, empire_template.loyalty.max);
+    }
+
+    // Returns true if soldiers should be sent, never while a squad is already out
+    pub fn add_strike(&mut self, empire_template: &EmpireTemplate) -> bool {
+        self.strikes += 1;
+
+        if self.strikes < empire_template.soldiers.strikes || !self.soldiers.is_empty() {
+            return false;
+        }
+
+        self.strikes = 0;
+
+        return true;
+    }
+
+    // Debt is settled, any soldiers out are recalled
+    pub fn settle(&mut self) {
+        self.strikes = 0;
+        self.soldiers.clear();
+    }
+}
+
+#[derive(Resource, Reflect, Default, Deref, DerefMut, Debug)]
+#[reflect(Resource)]
+pub struct Vassals(pub HashMap<i32, Vassal>);
+
+impl Vassals {
+    pub fn get_or_new(&mut self, player_id: i32, empire_template: &EmpireTemplate) -> &mut Vassal {
+        return self.0.entry(player_id).or_insert(Vassal {
+            loyalty: empire_template.loyalty.start,
+            ..Default::default()
+        });
+    }
+
+    // Loyal vassals are kept safe from monsters
+    pub fn is_protected(&self, player_id: i32, empire_template: &EmpireTemplate) -> bool {
+        return self
+            .0
+            .get(&player_id)
+            .is_some_and(|vassal| vassal.loyalty >= empire_template.loyalty.protection);
+    }
+}
+
+pub struct Empire;
+
+impl Empire {
+    pub fn capital(empire_template: &EmpireTemplate) -> Position {
+        return Position {
+            x: empire_template.capital[0],
+            y: empire_template.capital[1],
+        };
+    }
+
+    // Disloyal vassals pay more, loyal ones less
+    pub fn loyalty_mod(loyalty: i32, empire_template: &EmpireTemplate) -> f32 {
+        let loyalty = loyalty as f32 / empire_template.loyalty.max as f32;
+
+        return 1.0 + empire_template.loyalty.rate_mod * (0.5 - loyalty);
+    }
+
+    // Structure levels are of the player's completed structures
+    pub fn assess(
+        num_villagers: i32,
+        structure_levels: &Vec<i32>,
+        loyalty: i32,
+        empire_template: &EmpireTemplate,
+    ) -> (i32, String) {
+        let tax = &empire_template.tax;
+
+        let mut amount = tax.base + num_villagers * tax.per_villager;
+
+        for level in structure_levels.iter() {
+            amount += tax.per_structure * (level + 1);
+        }
+
+        let (rate, tier) = match tax.get_tier(structure_levels.len() as i32) {
+            Some(tier) => (tier.rate, tier.name.clone()),
+            None => (1.0, String::new()),
+        };
+
+        let amount = amount as f32 * rate * Self::loyalty_mod(loyalty, empire_template);
+
+        return (amount.ceil() as i32, tier);
+    }
+
+    // Gold counts in full, goods at a share of their value
+    pub fn collected_value(collector_id: i32, items: &Items, templates: &Templates) -> i32 {
+        let mut value = 0.0;
+
+        for item in items.get_by_owner(collector_id).iter() {
+            if item.class == item::GOLD {
+                value += item.quantity as f32;
+            } else {
+                let base_value = Pricing::base_value(&item.name, &templates.item_templates);
+                let goods_rate = templates.empire_template.tax.goods_rate;
+
+                value += (base_value * item.quantity) as f32 * goods_rate;
+            }
+        }
+
+        return value.floor() as i32;
+    }
+
+    // Payments leave with the collector for the capital
+    pub fn remit(collector_id: i32, items: &mut ResMut<Items>) {
+        for item in items.get_by_owner(collector_id).iter() {
+            items.remove_item(item.id);
+        }
+    }
+
+    pub fn amount_owed(collector: &TaxCollector) -> i32 {
+        return collector.collection_amount + collector.debt_amount;
+    }
+
+    // Returns true if the collector agreed to lower the taxes
+    pub fn negotiate(
+        collector: &mut TaxCollector,
+        vassal: &mut Vassal,
+        empire_template: &EmpireTemplate,
+    ) -> Result<bool, String> {
+        if collector.collection_amount <= 0 {
+            return Err("There are no taxes to negotiate.".to_string());
+        }
+
+        if vassal.negotiated {
+            return Err("The tax collector will not hear any more of it.".to_string());
+        }
+
+        vassal.negotiated = true;
+
+        let chance = empire_template.tax.negotiate_chance
+            + vassal.loyalty as f32 / empire_template.loyalty.max as f32;
+
+        if rand::thread_rng().gen::<f32>() < chance {
+            let discount = (collector.collection_amount as f32
+                * empire_template.tax.negotiate_discount) as i32;
+
+            collector.collection_amount -= discount;
+
+            return Ok(true);
+        }
+
+        vassal.add_loyalty(empire_template.loyalty.negotiate_failed, empire_template);
+
+        return Ok(false);
+    }
+
+    // The collector stashes the bribe aboard the tax ship and forgives what is owed, unless caught
+    pub fn bribe(
+        hero_id: i32,
+        amount: i32,
+        collector: &mut TaxCollector,
+        vassal: &mut Vassal,
+        items: &mut ResMut<Items>,
+        empire_template: &EmpireTemplate,
+    ) -> Result<bool, String> {
+        let owed = Self::amount_owed(collector);
+
+        if owed <= 0 {
+            return Err("There are no taxes to avoid.".to_string());
+        }
+
+        let min_bribe = (owed as f32 * empire_template.tax.bribe_share).ceil() as i32;
+
+        if amount < min_bribe {
+            return Err("The tax collector scoffs at your offer.".to_string());
+        }
+
+        if items.get_total_gold(hero_id) < amount {
+            return Err("Insufficient gold".to_string());
+        }
+
+        items.set_reason(ledger::REASON_TAX_BRIBE);
+        items.transfer_gold(hero_id, collector.transport_id, amount);
+        items.clear_reason();
+
+        if rand::thread_rng().gen::<f32>() < empire_template.tax.bribe_caught_chance {
+            collector.debt_amount += collector.collection_amount;
+            collector.collection_amount = 0;
+
+            vassal.add_loyalty(empire_template.loyalty.bribe_caught, empire_template);
+
+            return Ok(false);
+        }
+
+        collector.collection_amount = 0;
+        collector.debt_amount = 0;
+        vassal.settle();
+
+        return Ok(true);
+    }
+
+    // One soldier for each share of the debt, up to the template's max, returns the soldier ids
+    pub fn send_soldiers(
+        debtor: i32,
+        hero_id: i32,
+        hero_pos: Position,
+        debt: i32,
+        commands: &mut Commands,
+        ids: &mut ResMut<Ids>,
+        items: &mut ResMut<Items>,
+        map: &Map,
+        templates: &Res<Templates>,
+        game_tick: &Res<GameTick>,
+        map_events: &mut ResMut<MapEvents>,
+    ) -> Vec<i32> {
+        let soldiers = &templates.empire_template.soldiers;
+        let num_soldiers = i32::min(1 + debt / soldiers.debt_per_soldier.max(1), soldiers.max);
+
+        let spawn_pos: Vec<Position> = Map::range((hero_pos.x, hero_pos.y), SOLDIER_SPAWN_RANGE)
+            .into_iter()
+            .filter(|(x, y)| Map::is_passable(*x, *y, map))
+            .map(|(x, y)| Position { x: x, y: y })
+            .filter(|pos| *pos != hero_pos)
+            .take(num_soldiers as usize)
+            .collect();
+
+        let mut soldier_ids = Vec::new();
+
+        for pos in spawn_pos.iter() {
+            let (entity, soldier_id, _player_id, _pos) = Encounter::spawn_npc(
+                EMPIRE_PLAYER_ID,
+                *pos,
+                soldiers.template.clone(),
+                commands,
+                ids,
+                items,
+                templates,
+            );
+
+            let mut threat_table = ThreatTable::default();
+            threat_table.add(hero_id, SOLDIER_THREAT);
+
+            commands.entity(entity).insert((
+                threat_table,
+                Territory {
+                    center: hero_pos,
+                    range: SOLDIER_PATROL_RANGE,
+                    lair: None,
+                },
+                Soldier {
+                    debtor: debtor,
+                    recall_at: game_tick.0 + soldiers.recall,
+                },
+            ));
+
+            map_events.new(
+                soldier_id.0,
+                game_tick.0 + 1,
+                VisibleEvent::NewObjEvent { new_player: false },
+            );
+
+            soldier_ids.push(soldier_id.0);
+        }
+
+        return soldier_ids;
+    }
+
+    pub fn status_packet(
+        collector_id: Option<i32>,
+        collector: Option<&TaxCollector>,
+        vassal: &Vassal,
+        empire_template: &EmpireTemplate,
+    ) -> ResponsePacket {
+        return ResponsePacket::TaxStatus {
+            empire: empire_template.name.clone(),
+            collectorid: collector_id,
+            tier: vassal.tier.clone(),
+            due: collector.map(|collector| collector.collection_amount).unwrap_or(0),
+            debt: collector.map(|collector| collector.debt_amount).unwrap_or(0),
+            loyalty: vassal.loyalty,
+            protected: vassal.loyalty >= empire_template.loyalty.protection,
+        };
+    }
+}
+
+// Soldiers return to the capital once recalled or their time is up, fallen ones leave the squad
+fn soldier_recall_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut vassals: ResMut<Vassals>,
+    mut map_events: ResMut<MapEvents>,
+    query: Query<(Entity, &Id, &Position, &State, &Soldier)>,
+) {
+    for (entity, id, pos, state, soldier) in query.iter() {
+        let vassal = vassals.get_mut(&soldier.debtor);
+
+        // Recalled soldiers have already been dropped from the squad
+        let recalled = vassal
+            .as_ref()
+            .map_or(true, |vassal| !vassal.soldiers.contains(&id.0));
+
+        if Obj::is_dead(state) {
+            debug!("Soldier {:?} has fallen", id.0);
+        } else if recalled || game_tick.0 >= soldier.recall_at {
+            debug!("Soldier {:?} returns to the capital", id.0);
+
+            map_events.new(
+                id.0,
+                game_tick.0 + 1,
+                VisibleEvent::RemoveObjEvent { pos: *pos },
+            );
+        } else {
+            continue;
+        }
+
+        if let Some(vassal) = vassal {
+            vassal.soldiers.retain(|soldier_id| *soldier_id != id.0);
+        }
+
+        // Leaves the squad only once
+        commands.entity(entity).remove::<Soldier>();
+    }
+}
+
+pub struct EmpirePlugin;
+
+impl Plugin for EmpirePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Vassals>()
+            .add_systems(Update, soldier_recall_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    fn load_empire_template() -> EmpireTemplate {
+        let empire_template_file =
+            fs::File::open("empire_template.yaml").expect("Could not open file.");
+
+        return serde_yaml::from_reader(empire_template_file).expect("Could not read values.");
+    }
+
+    #[test]
+    fn test_strike_escalation() {
+        let empire_template = load_empire_template();
+        let strikes = empire_template.soldiers.strikes;
+
+        let mut vassal = Vassal::default();
+
+        for _ in 1..strikes {
+            assert!(!vassal.add_strike(&empire_template));
+        }
+
+        // Strikes start over once soldiers are sent
+        assert!(vassal.add_strike(&empire_template));
+        assert_eq!(vassal.strikes, 0);
+
+        // No second squad while the first is out
+        vassal.soldiers = vec![10, 11];
+
+        for _ in 0..strikes * 2 {
+            assert!(!vassal.add_strike(&empire_template));
+        }
+
+        // Once the squad is gone the next failed forfeiture sends soldiers again
+        vassal.soldiers.clear();
+        assert!(vassal.add_strike(&empire_template));
+    }
+
+    #[test]
+    fn test_recall() {
+        let mut vassal = Vassal {
+            strikes: 1,
+            soldiers: vec![10, 11],
+            ..Default::default()
+        };
+
+        vassal.settle();
+
+        assert_eq!(vassal.strikes, 0);
+        assert!(vassal.soldiers.is_empty());
+
+        // First vassal paid up and recalled its squad
+        let mut vassals = Vassals::default();
+        vassals.insert(1, vassal);
+        vassals.insert(
+            2,
+            Vassal {
+                soldiers: vec![11, 12],
+                ..Default::default()
+            },
+        );
+
+        let mut app = App::new();
+
+        app.insert_resource(GameTick(100))
+            .insert_resource(vassals)
+            .init_resource::<MapEvents>()
+            .add_systems(Update, soldier_recall_system);
+
+        let soldier = |id: i32, debtor: i32, recall_at: i32| {
+            (
+                Id(id),
+                Position { x: 1, y: 1 },
+                State::None,
+                Soldier {
+                    debtor: debtor,
+                    recall_at: recall_at,
+                },
+            )
+        };
+
+        let recalled = app.world.spawn(soldier(10, 1, 1000)).id();
+        let expired = app.world.spawn(soldier(11, 2, 50)).id();
+        let on_duty = app.world.spawn(soldier(12, 2, 1000)).id();
+
+        app.update();
+
+        assert!(app.world.get::<Soldier>(recalled).is_none());
+        assert!(app.world.get::<Soldier>(expired).is_none());
+        assert!(app.world.get::<Soldier>(on_duty).is_some());
+
+        let removed: Vec<i32> = app
+            .world
+            .resource::<MapEvents>()
+            .values()
+            .filter(|map_event| matches!(map_event.event_type, VisibleEvent::RemoveObjEvent { .. }))
+            .map(|map_event| map_event.obj_id)
+            .collect();
+
+        assert_eq!(removed.len(), 2);
+        assert!(removed.contains(&10) && removed.contains(&11));
+
+        let vassals = app.world.resource::<Vassals>();
+        assert_eq!(vassals.get(&2).unwrap().soldiers, vec![12]);
+    }
+}
@@ -1,20 +1,12 @@
 use std::collections::HashMap;
-use std::i32::MAX;
 
 use bevy::prelude::*;
-use big_brain::actions::{Steps};
-use big_brain::prelude::{Highest, Thinker};
 
 use rand::{Rng};
 
 use crate::components::npc::{
-    AtLanding, Destination, Forfeiture, Hide, Idle, IsAboard, IsTaxCollected, MoveToEmpire,
-    MoveToPos, MoveToTarget, NoTaxesToCollect, OverdueTaxScorer, Talk, TaxCollector,
-    TaxCollectorTransport, TaxesToCollect, Transport, VisibleTarget,
-};
-use crate::components::npc::{
-    ChaseAndAttack, ChaseAndCast, FleeScorer, FleeToHome, RaiseDead, VisibleCorpse,
-    VisibleCorpseScorer, VisibleTargetScorer,
+    Destination, Nocturnal, TaxCollector, TaxCollectorTransport, ThreatTable, Transport,
+    VisibleCorpse, VisibleTarget,
 };
 use crate::effect::Effects;
 use crate::event::{MapEvents, VisibleEvent};
@@ -27,20 +19,13 @@ use crate::item::{Items};
 use crate::map::TileType;
 use crate::obj::Obj;
 use crate::plugins::ai::npc::NO_TARGET;
+use crate::plugins::ai::thinker::{AiThinker, ThinkerContext};
 
-use crate::templates::{ObjTemplate, Templates};
+use crate::templates::{LootTemplate, ObjTemplate, Templates};
 
 #[derive(Debug, Clone)]
 pub struct Encounter;
 
-#[derive(Debug, Clone)]
-struct Loot {
-    item_name: String,
-    drop_rate: f32,
-    min: i32,
-    max: i32,
-}
-
 impl Encounter {
     pub fn spawn_npc(
         player_id: i32,
@@ -89,7 +74,7 @@ impl Encounter {
             misc: Misc {
                 image: image,
                 hsl: Vec::new().into(),
-                groups: Vec::new().into(),
+                groups: npc_template.groups.clone().unwrap_or(Vec::new()),
             },
             stats: Stats {
                 hp: npc_template.base_hp.unwrap(),
@@ -110,14 +95,20 @@ impl Encounter {
                 npc,
                 SubclassNPC,
                 VisibleTarget::new(NO_TARGET),
-                Thinker::build()
-                    .label("NPC Chase")
-                    .picker(Highest)
-                    .when(VisibleTargetScorer, ChaseAndAttack),
+                ThreatTable::default(),
+                AiThinker::build_for_template(
+                    &npc_template.template,
+                    &ThinkerContext::default(),
+                    templates,
+                ),
             ))
             .id();
 
-        Encounter::generate_loot(npc_id, ids, items, templates);
+        if npc_template.nocturnal.unwrap_or(false) {
+            commands.entity(entity).insert(Nocturnal);
+        }
+
+        Encounter::generate_loot(npc_id, npc_template.template.clone(), items, templates);
 
         ids.new_obj(npc_id, player_id, entity);
 
@@ -143,15 +134,6 @@ impl Encounter {
             templates,
         );
 
-        let flee_and_hide = Steps::build()
-            .label("Flee and Hide")
-            .step(FleeToHome)
-            .step(Hide)
-            .step(Idle {
-                start_time: 0,
-                duration: MAX,
-            });
-
         // Spawn Necromancer
         let necro_entity = commands
             .spawn((
@@ -160,19 +142,19 @@ impl Encounter {
                 Minions { ids: Vec::new() },
                 Home { pos: home_pos },
                 VisibleTarget::new(NO_TARGET),
+                ThreatTable::default(),
                 VisibleCorpse::new(NO_TARGET),
-                Thinker::build()
-                    .label("Necromancer")
-                    .picker(Highest)
-                    .when(VisibleTargetScorer, ChaseAndCast { start_time: MAX })
-                    .when(VisibleCorpseScorer, RaiseDead { start_time: MAX })
-                    .when(FleeScorer, flee_and_hide),
+                AiThinker::build_for_template(
+                    &necro_obj.template.0,
+                    &ThinkerContext::default(),
+                    templates,
+                ),
             ))
             .id();
 
         ids.new_obj(necro_obj.id.0, player_id, necro_entity);
 
-        Encounter::generate_loot(necro_obj.id.0, ids, items, templates);
+        Encounter::generate_loot(necro_obj.id.0, necro_obj.template.0.clone(), items, templates);
 
         return (necro_entity, necro_obj.id, PlayerId(player_id), pos);
     }
@@ -207,22 +189,6 @@ impl Encounter {
             templates,
         );
 
-        let move_to_empire_and_idle = Steps::build()
-            .label("MoveToEmpire and Idle")
-            .step(MoveToEmpire)
-            .step(Idle {
-                start_time: 0,
-                duration: 100,
-            });
-
-        let move_to_landing_and_idle = Steps::build()
-            .label("MoveToPos and Idle")
-            .step(MoveToPos)
-            .step(Idle {
-                start_time: 0,
-                duration: 100,
-            });
-
         // Spawn Tax Collector Ship
         let tax_collector_ship_entity = commands
             .spawn((
@@ -237,11 +203,11 @@ impl Encounter {
                 TaxCollectorTransport {
                     tax_collector_id: tax_collector_obj.id.0,
                 },
-                Thinker::build()
-                    .label("Tax Collector Ship")
-                    .picker(Highest)
-                    .when(NoTaxesToCollect, move_to_empire_and_idle)
-                    .when(TaxesToCollect, move_to_landing_and_idle),
+                AiThinker::build_for_template(
+                    &tax_collector_ship_obj.template.0,
+                    &ThinkerContext::default(),
+                    templates,
+                ),
             ))
             .id();
 
@@ -261,35 +227,10 @@ impl Encounter {
             .get_hero(target_player)
             .expect("Cannot find hero for player");
 
-        let move_to_hero_and_idle = Steps::build()
-            .label("MoveToTarget and Idle")
-            .step(MoveToTarget {
-                target: target_hero_id,
-            })
-            .step(Idle {
-                start_time: 0,
-                duration: 100,
-            });
-
-        let move_to_ship_and_idle = Steps::build()
-            .label("MoveToTarget and Idle")
-            .step(Talk {
-                speech: "The poor rabble actually paid, shocking!".to_string(),
-            })
-            .step(MoveToTarget {
-                target: tax_collector_ship_obj.id.0,
-            })
-            .step(Idle {
-                start_time: 0,
-                duration: 100,
-            });
-
-        let forfeiture = Steps::build()
-            .label("Forfeiture")
-            .step(MoveToTarget {
-                target: target_hero_id,
-            })
-            .step(Forfeiture);
+        // Named targets for the tax collector's MoveToTarget actions
+        let context = ThinkerContext::default()
+            .with_target("target_hero", target_hero_id)
+            .with_target("transport", tax_collector_ship_obj.id.0);
 
         // Spawn Tax Collector
         let tax_collector_entity = commands
@@ -308,25 +249,18 @@ impl Encounter {
                 StateAboard {
                     transport_id: tax_collector_ship_obj.id.0,
                 },
-                Thinker::build()
-                    .label("Tax Collector")
-                    .picker(Highest)
-                    .when(
-                        IsAboard,
-                        Idle {
-                            start_time: 0,
-                            duration: 100,
-                        },
-                    )
-                    .when(AtLanding, move_to_hero_and_idle)
-                    .when(IsTaxCollected, move_to_ship_and_idle)
-                    .when(OverdueTaxScorer, forfeiture),
+                AiThinker::build_for_template(&tax_collector_obj.template.0, &context, templates),
             ))
             .id();
 
         ids.new_obj(tax_collector_obj.id.0, player_id, tax_collector_entity);
 
-        Encounter::generate_loot(tax_collector_obj.id.0, ids, items, templates);
+        Encounter::generate_loot(
+            tax_collector_obj.id.0,
+            tax_collector_obj.template.0.clone(),
+            items,
+            templates,
+        );
 
         map_events.new(
             tax_collector_obj.id.0,
@@ -337,27 +271,38 @@ impl Encounter {
 
     pub fn generate_loot(
         npc_id: i32,
-        _ids: &mut ResMut<Ids>,
+        template_name: String,
         items: &mut ResMut<Items>,
-        _templates: &Res<Templates>,
+        templates: &Res<Templates>,
     ) {
-        let mut rng = rand::thread_rng();
+        let npc_template = ObjTemplate::get_template(template_name, templates);
 
-        let loot_list = Self::loot_list();
+        let Some(loot_table) = npc_template.loot else {
+            return;
+        };
+
+        for (item_name, quantity) in Encounter::roll_loot(&loot_table).into_iter() {
+            items.create(npc_id, item_name, quantity);
+        }
+    }
 
-        for loot in loot_list.iter() {
+    pub fn roll_loot(loot_table: &Vec<LootTemplate>) -> Vec<(String, i32)> {
+        let mut rng = rand::thread_rng();
+        let mut loot = Vec::new();
+
+        for loot_template in loot_table.iter() {
             let random_num = rng.gen::<f32>();
 
-            if loot.drop_rate > random_num {
-                let item_quantity = rng.gen_range(loot.min..loot.max);
+            if loot_template.drop_rate > random_num {
+                let quantity = rng.gen_range(loot_template.min..=loot_template.max);
 
-                items.create(
-                    npc_id,
-                    loot.item_name.clone(),
-                    item_quantity, //TODO should this be only 1 ?
-                );
+                if quantity > 0 {
+                    loot.push((loot_template.item.clone(), quantity));
+                }
             }
         }
+
+        return loot;
     }
 
     pub fn npc_list(tile_type: TileType) -> Vec<&'static str> {
@@ -368,54 +313,14 @@ impl Encounter {
             TileType::FrozenForest => return vec!["Wose", "Yeti", "Spider"],
             TileType::Desert => return vec!["Scorpion", "Giant Rat", "Skeleton"],
             TileType::HillsDesert => return vec!["Scorpion", "Giant Rat", "Skeleton"],
+            TileType::PineForest => return vec!["Wolf", "Spider", "Wose"],
+            TileType::Swamp => return vec!["Giant Rat", "Spider", "Zombie"],
+            TileType::Grasslands => return vec!["Wolf", "Giant Rat"],
+            TileType::HillsGrasslands => return vec!["Wolf", "Skeleton"],
+            TileType::Plains => return vec!["Wolf", "Giant Rat"],
+            TileType::HillsPlains => return vec!["Wolf", "Skeleton"],
             //_ => return vec!["Giant Rat", "Wolf", "Skeleton"],
             _ => return vec!["Wolf"],
         }
     }
-
-    fn loot_list() -> Vec<Loot> {
-        let copper_dust = Loot {
-            item_name: "Valleyrun Copper Dust".to_string(),
-            drop_rate: 0.2,
-            min: 1,
-            max: 5,
-        };
-
-        let grape = Loot {
-            item_name: "Amitanian Grape".to_string(),
-            drop_rate: 0.5,
-            min: 1,
-            max: 3,
-        };
-
-        let training_axe = Loot {
-            item_name: "Copper Training Axe".to_string(),
-            drop_rate: 0.02,
-            min: 1,
-            max: 2,
-        };
-
-        let berries = Loot {
-            item_name: "Honeybell Berries".to_string(),
-            drop_rate: 0.99,
-            min: 5,
-            max: 10,
-        };
-
-        let mana = Loot {
-            item_name: "Mana".to_string(),
-            drop_rate: 0.75,
-            min: 1,
-            max: 3,
-        };
-
-        let coins = Loot {
-            item_name: "Gold Coins".to_string(),
-            drop_rate: 0.99,
-            min: 1,
-            max: 10,
-        };
-
-        return vec![copper_dust, grape, training_axe, berries, mana, coins];
-    }
 }
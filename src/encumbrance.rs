@@ -0,0 +1,222 @@
+use bevy::prelude::*;
+
+use crate::game::{BaseAttrs, GameTick, Id, SubclassVillager, Template};
+use crate::item::Items;
+use crate::network::ResponsePacket;
+use crate::obj::Obj;
+use crate::templates::{ObjTemplates, Templates};
+
+// Extra capacity per point of strength
+pub const CAPACITY_PER_STRENGTH: i32 = 10;
+
+// Share of capacity that can be carried at full speed
+pub const LIGHT_LOAD: f32 = 0.5;
+// Moving takes this much longer at full capacity
+pub const FULL_LOAD_MOVE_MOD: f32 = 0.5;
+// and this many times longer again once over capacity
+pub const OVERLOAD_MOVE_MOD: f32 = 2.0;
+// Units cannot take on more than this share of their capacity
+pub const HARD_LIMIT_MOD: f32 = 1.5;
+
+// Ticks for a hero to move one tile when unburdened
+pub const HERO_MOVE_TICKS: i32 = 12;
+
+// Stamina lost each regen interval while overloaded
+pub const OVERLOAD_STAMINA_DRAIN: i32 = 75;
+pub const OVERLOAD_TIRED_MOD: f32 = 1.5;
+
+pub const ENCUMBRANCE_INTERVAL: i32 = 10;
+
+pub const UNBURDENED: &str = "unburdened";
+pub const BURDENED: &str = "burdened";
+pub const OVERLOADED: &str = "overloaded";
+
+// Carrying more than capacity
+#[derive(Debug, Clone, Component)]
+pub struct Overloaded;
+
+// Share of capacity carried as of the last encumbrance update
+#[derive(Debug, Clone, Component)]
+pub struct Load(pub f32);
+
+pub struct Encumbrance;
+
+impl Encumbrance {
+    // Structures and other objs without attributes only have their template capacity
+    pub fn capacity(
+        template: &String,
+        attrs: Option<&BaseAttrs>,
+        obj_templates: &ObjTemplates,
+    ) -> i32 {
+        let strength = attrs.map_or(0, |attrs| attrs.strength);
+
+        return Obj::get_capacity(template, obj_templates) + strength * CAPACITY_PER_STRENGTH;
+    }
+
+    // Units can be overloaded up to the hard limit, structures cannot go over capacity
+    pub fn hard_limit(capacity: i32, attrs: Option<&BaseAttrs>) -> i32 {
+        if attrs.is_none() {
+            return capacity;
+        }
+
+        return (capacity as f32 * HARD_LIMIT_MOD) as i32;
+    }
+
+    pub fn load(total_weight: i32, capacity: i32) -> f32 {
+        if capacity <= 0 {
+            return 0.0;
+        }
+
+        return total_weight as f32 / capacity as f32;
+    }
+
+    pub fn is_overloaded(load: f32) -> bool {
+        return load > 1.0;
+    }
+
+    pub fn move_ticks(base_ticks: i32, load: f32) -> i32 {
+        let mut move_mod = 1.0;
+
+        if load > LIGHT_LOAD {
+            let burden = (f32::min(load, 1.0) - LIGHT_LOAD) / (1.0 - LIGHT_LOAD);
+            move_mod += FULL_LOAD_MOVE_MOD * burden;
+        }
+
+        if Self::is_overloaded(load) {
+            move_mod *= OVERLOAD_MOVE_MOD;
+        }
+
+        return (base_ticks as f32 * move_mod).round() as i32;
+    }
+
+    pub fn status(load: f32) -> &'static str {
+        if Self::is_overloaded(load) {
+            return OVERLOADED;
+        } else if load > LIGHT_LOAD {
+            return BURDENED;
+        }
+
+        return UNBURDENED;
+    }
+
+    pub fn inventory_packet(
+        id: i32,
+        template: &String,
+        attrs: Option<&BaseAttrs>,
+        items: &Items,
+        templates: &Templates,
+    ) -> ResponsePacket {
+        let capacity = Self::capacity(template, attrs, &templates.obj_templates);
+        let total_weight = items.get_total_weight(id);
+        let load = Self::load(total_weight, capacity);
+
+        return ResponsePacket::InfoInventory {
+            id: id,
+            cap: capacity,
+            tw: total_weight,
+            load: (load * 100.0).round() as i32,
+            enc: Self::status(load).to_string(),
+            items: items.get_by_owner_packet(id),
+        };
+    }
+}
+
+// Villagers have no attributes and carry their template capacity
+fn encumbrance_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    items: Res<Items>,
+    templates: Res<Templates>,
+    mut query: Query<
+        (
+            Entity,
+            &Id,
+            &Template,
+            Option<&BaseAttrs>,
+            Option<&Overloaded>,
+            Option<&mut Load>,
+        ),
+        Or<(With<BaseAttrs>, With<SubclassVillager>)>,
+    >,
+) {
+    if game_tick.0 % ENCUMBRANCE_INTERVAL != 0 {
+        return;
+    }
+
+    for (entity, id, template, attrs, overloaded, current_load) in query.iter_mut() {
+        let capacity = Encumbrance::capacity(&template.0, attrs, &templates.obj_templates);
+        let load = Encumbrance::load(items.get_total_weight(id.0), capacity);
+
+        if let Some(mut current_load) = current_load {
+            current_load.0 = load;
+        } else {
+            commands.entity(entity).insert(Load(load));
+        }
+
+        if Encumbrance::is_overloaded(load) && overloaded.is_none() {
+            commands.entity(entity).insert(Overloaded);
+        } else if !Encumbrance::is_overloaded(load) && overloaded.is_some() {
+            commands.entity(entity).remove::<Overloaded>();
+        }
+    }
+}
+
+pub struct EncumbrancePlugin;
+
+impl Plugin for EncumbrancePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, encumbrance_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_attrs() -> BaseAttrs {
+        return BaseAttrs {
+            creativity: 0,
+            dexterity: 0,
+            endurance: 0,
+            focus: 0,
+            intellect: 0,
+            spirit: 0,
+            strength: 0,
+            toughness: 0,
+        };
+    }
+
+    #[test]
+    fn test_move_ticks() {
+        // Light loads move at full speed
+        assert_eq!(Encumbrance::move_ticks(HERO_MOVE_TICKS, 0.0), HERO_MOVE_TICKS);
+        assert_eq!(Encumbrance::move_ticks(HERO_MOVE_TICKS, LIGHT_LOAD), HERO_MOVE_TICKS);
+
+        // Slows down linearly up to full capacity
+        assert_eq!(Encumbrance::move_ticks(HERO_MOVE_TICKS, 0.75), 15);
+        assert_eq!(Encumbrance::move_ticks(HERO_MOVE_TICKS, 1.0), 18);
+        assert_eq!(Encumbrance::move_ticks(36, 1.0), 54);
+
+        // Overloaded units take twice as long again
+        assert_eq!(Encumbrance::move_ticks(HERO_MOVE_TICKS, 1.2), 36);
+        assert_eq!(Encumbrance::move_ticks(HERO_MOVE_TICKS, 2.0), 36);
+    }
+
+    #[test]
+    fn test_hard_limit() {
+        assert_eq!(Encumbrance::hard_limit(100, None), 100);
+        assert_eq!(Encumbrance::hard_limit(100, Some(&base_attrs())), 150);
+        assert_eq!(Encumbrance::hard_limit(0, Some(&base_attrs())), 0);
+    }
+
+    #[test]
+    fn test_status() {
+        assert_eq!(Encumbrance::status(0.0), UNBURDENED);
+        assert_eq!(Encumbrance::status(LIGHT_LOAD), UNBURDENED);
+        assert_eq!(Encumbrance::status(0.75), BURDENED);
+        assert_eq!(Encumbrance::status(1.0), BURDENED);
+        assert_eq!(Encumbrance::status(1.01), OVERLOADED);
+        assert_eq!(Encumbrance::load(150, 100), 1.5);
+        assert_eq!(Encumbrance::load(10, 0), 0.0);
+    }
+}
@@ -25,10 +25,20 @@ use tokio::sync::mpsc::Sender;
 use async_compat::Compat;
 
 use crate::account::Accounts;
-use crate::combat::{Combat, CombatSpellQuery};
-use crate::components::npc::Transport;
-use crate::components::villager::{Dehydrated, Exhausted, Hunger, Starving, Thirst, Tired, Heat};
-use crate::constants::{COMFORT_TEMPERATURE, DAWN, DUSK, EVENING, GAME_HOUR, GAME_TICKS_PER_DAY, MORNING, NIGHT};
+use crate::combat::{Combat, CombatSpellQuery, InCombat};
+use crate::components::npc::{TaxCollector, ThreatTable, Transport};
+use crate::components::villager::{
+    Dehydrated, Exhausted, Heat, Hunger, Morale, OrderQueue, Personality, Starving, Thirst, Tired,
+    MORALE_DEATH,
+};
+use crate::constants::{
+    COMBAT_MORALE_PENALTY, COMFORT_TEMPERATURE, DAWN, DEATH_MORALE_PENALTY, DEATH_MORALE_RANGE,
+    DESERTION_TIME, DISCOMFORT_HEAT, DUSK, EVENING, GAME_HOUR, GAME_TICKS_PER_DAY, MORALE_BASE,
+    MORALE_DESERTION, MORNING, NEED_MORALE_PENALTY, NIGHT, NO_SHELTER_MORALE_PENALTY,
+    SHELTER_MORALE_BONUS, TAX_DEBT_MORALE_PENALTY, WORK_MORALE_MODIFIER,
+};
+use crate::contract::ContractPlugin;
+use crate::corpse::{self, Bones, Corpse};
 use crate::effect::Effects;
 use crate::encounter::Encounter;
 use crate::event::{
@@ -43,10 +53,20 @@ use crate::network::{self, network_obj, send_to_client, BroadcastEvents};
 use crate::network::{ResponsePacket, StatsData};
 use crate::obj::{self, Obj};
 use crate::player::{self, ActiveInfos, PlayerEvent, PlayerPlugin};
-use crate::plugins::ai::AIPlugin;
+use crate::plugins::ai::{npc, AIPlugin};
 use crate::recipe::{RecipePlugin, Recipes};
 use crate::resource::{Resource, ResourcePlugin, Resources};
-use crate::skill::{Skill, SkillPlugin, Skills};
+use crate::skill::{self, Skill, SkillPlugin, Skills};
+use crate::social::SocialPlugin;
+use crate::merchant::MerchantPlugin;
+use crate::population::PopulationPlugin;
+use crate::quality::Quality;
+use crate::trade::TradePlugin;
+use crate::market::{MarketPlugin, Markets};
+use crate::empire::{EmpirePlugin, Vassals};
+use crate::ledger::LedgerPlugin;
+use crate::encumbrance::{self, Encumbrance, EncumbrancePlugin, Overloaded};
+use crate::stamina::{self, Stamina, StaminaPlugin};
 use crate::structure::{Plans, Structure, StructurePlugin};
 use crate::templates::{ObjTemplate, Templates, TemplatesPlugin};
 use crate::terrain_feature::{TerrainFeature, TerrainFeaturePlugin, TerrainFeatures};
@@ -242,7 +262,7 @@ pub struct NPCAttrs {
     pub target: i32,
 }
 
-#[derive(Debug, Component, Eq, PartialEq)]
+#[derive(Debug, Component, Clone, Eq, PartialEq)]
 pub enum Order {
     Follow { target: Entity },
     Gather { res_type: String },
@@ -379,6 +399,16 @@ impl Plugin for GamePlugin {
             .add_plugins(StructurePlugin)
             .add_plugins(FarmPlugin)
             .add_plugins(WorldPlugin)
+            .add_plugins(StaminaPlugin)
+            .add_plugins(SocialPlugin)
+            .add_plugins(PopulationPlugin)
+            .add_plugins(MerchantPlugin)
+            .add_plugins(TradePlugin)
+            .add_plugins(MarketPlugin)
+            .add_plugins(EmpirePlugin)
+            .add_plugins(LedgerPlugin)
+            .add_plugins(EncumbrancePlugin)
+            .add_plugins(ContractPlugin)
             .init_resource::<GameTick>()
             .add_systems(Startup, Game::setup)
             .add_systems(PreUpdate, update_game_tick)
@@ -407,6 +437,8 @@ impl Plugin for GamePlugin {
             .add_systems(Update, game_event_system)
             .add_systems(Update, resurrect_system)
             .add_systems(Update, remove_dead_system)
+            .add_systems(Update, update_morale_system)
+            .add_systems(Update, personality_system)
             .add_systems(Update, perception_system);
 
         // .add_system(task_move_to_target_system);
@@ -460,12 +492,19 @@ impl Game {
         //Spawn the tokio runtime setup using a Compat with the clients and client to game channel
         thread_pool
             .spawn(Compat::new(network::tokio_setup(
-                client_to_game_sender,
+                client_to_game_sender.clone(),
                 clients.clone(),
                 accounts,
             )))
             .detach();
 
+        thread_pool
+            .spawn(Compat::new(network::admin_setup(
+                client_to_game_sender,
+                clients.clone(),
+            )))
+            .detach();
+
         let network_receiver = NetworkReceiver(client_to_game_receiver);
 
         // Initialize indexes
@@ -543,7 +582,7 @@ fn remove_obj_event_system(
     game_tick: Res<GameTick>,
     mut map_events: ResMut<MapEvents>,
     mut visible_events: ResMut<VisibleEvents>,
-    ids: Res<Ids>,
+    mut ids: ResMut<Ids>,
 ) {
     let mut events_to_remove = Vec::new();
 
@@ -561,8 +600,7 @@ fn remove_obj_event_system(
 
                     // Remove entity
                     commands.entity(entity).despawn();
-
-                    // TODO Remove Ids entry for obj
+                    ids.remove_obj(map_event.obj_id);
 
                     visible_events.push(map_event.clone());
                     events_to_remove.push(*map_event_id);
@@ -996,6 +1034,7 @@ fn build_event_system(
     mut map_events: ResMut<MapEvents>,
     mut visible_events: ResMut<VisibleEvents>,
     ids: ResMut<Ids>,
+    mut skills: ResMut<Skills>,
     templates: Res<Templates>,
     mut query: Query<ObjWithStatsQuery>,
 ) {
@@ -1027,6 +1066,17 @@ fn build_event_system(
 
                     *builder.state = State::None;
 
+                    // Consume stamina for building
+                    Stamina::consume(&mut builder.stats, stamina::BUILD_COST);
+
+                    Skill::award(
+                        *builder_id,
+                        skill::BUILDING,
+                        skill::BUILD_XP,
+                        &mut skills,
+                        &templates.skill_templates,
+                    );
+
                     visible_events.new(
                         builder.id.0,
                         game_tick.0 + 1,
@@ -1175,10 +1225,13 @@ fn gather_event_system(
     mut ids: ResMut<Ids>,
     resources: ResMut<Resources>,
     mut items: ResMut<Items>,
-    skills: ResMut<Skills>,
+    mut skills: ResMut<Skills>,
     templates: Res<Templates>,
     mut map_events: ResMut<MapEvents>,
     query: Query<ObjQuery>,
+    mut stats_query: Query<&mut Stats>,
+    mut queue_query: Query<&mut OrderQueue>,
+    attrs_query: Query<&BaseAttrs>,
 ) {
     let mut events_to_remove = Vec::new();
 
@@ -1200,8 +1253,23 @@ fn gather_event_system(
                         continue;
                     };
 
-                    let capacity =
-                        Obj::get_capacity(&gatherer.template.0, &templates.obj_templates);
+                    // Consume stamina for gathering
+                    if let Ok(mut stats) = stats_query.get_mut(gatherer_entity) {
+                        Stamina::consume(&mut stats, stamina::GATHER_COST);
+                    }
+
+                    // Wear down equipped gathering tools
+                    for tool in items.get_equipped(gatherer.id.0).iter() {
+                        if tool.class == item::GATHERING {
+                            items.wear(tool.id, item::TOOL_WEAR);
+                        }
+                    }
+
+                    let capacity = Encumbrance::capacity(
+                        &gatherer.template.0,
+                        attrs_query.get(gatherer_entity).ok(),
+                        &templates.obj_templates,
+                    );
 
                     let new_items = Resource::gather_by_type(
                         map_event.obj_id,
@@ -1217,9 +1285,23 @@ fn gather_event_system(
                         &templates.item_templates,
                         &resources,
                         &templates.res_templates,
+                        &templates.res_property_templates,
                         &mut ids,
                     );
 
+                    Skill::award(
+                        map_event.obj_id,
+                        &Resource::type_to_skill(res_type.to_string()),
+                        skill::GATHER_XP,
+                        &mut skills,
+                        &templates.skill_templates,
+                    );
+
+                    // Count towards the gatherer's queued order
+                    if let Ok(mut order_queue) = queue_query.get_mut(gatherer_entity) {
+                        order_queue.record_progress();
+                    }
+
                     if new_items.len() > 0 {
                         let notification_packet: ResponsePacket = ResponsePacket::NewItems {
                             action: obj::STATE_GATHERING.to_string(),
@@ -1247,11 +1329,13 @@ fn operate_refine_event_system(
     mut ids: ResMut<Ids>,
     resources: ResMut<Resources>,
     mut items: ResMut<Items>,
-    skills: ResMut<Skills>,
+    mut skills: ResMut<Skills>,
     templates: Res<Templates>,
     //mut villager_query: Query<VillagerQuery, With<SubclassVillager>>,
     //mut state_query: Query<&mut State>,
     mut query: Query<ObjQuery>,
+    mut stats_query: Query<&mut Stats>,
+    mut queue_query: Query<&mut OrderQueue>,
     mut map_events: ResMut<MapEvents>,
     active_infos: Res<ActiveInfos>,
 ) {
@@ -1291,6 +1375,26 @@ fn operate_refine_event_system(
                     // Reset villager state to None
                     *villager.state = State::None;
 
+                    // Consume stamina for refining
+                    if let Ok(mut stats) = stats_query.get_mut(entity) {
+                        Stamina::consume(&mut stats, stamina::REFINE_COST);
+                    }
+
+                    // Count towards the villager's queued order
+                    if let Ok(mut order_queue) = queue_query.get_mut(entity) {
+                        order_queue.record_progress();
+                    }
+
+                    let refine_level = Skill::get_level(map_event.obj_id, skill::REFINING, &skills);
+
+                    Skill::award(
+                        map_event.obj_id,
+                        skill::REFINING,
+                        skill::REFINE_XP,
+                        &mut skills,
+                        &templates.skill_templates,
+                    );
+
                     let Some(structure_template) = Structure::get_template(
                         structure.template.0.clone(),
                         &templates.obj_templates,
@@ -1349,12 +1453,29 @@ fn operate_refine_event_system(
                             items_to_remove.push(item_to_refine.id);
                         }
 
+                        // Skilled refiners produce better items
+                        let mut refined_attrs = item_to_refine.attrs.clone();
+
+                        for attr_val in refined_attrs.values_mut() {
+                            if let item::AttrVal::Num(value) = attr_val {
+                                *value *= Skill::quality_mod(refine_level);
+                            }
+                        }
+
                         // Create new items
                         for produce_item in produces_list.iter() {
+                            // Skilled refiners sometimes get an extra item
+                            let quantity =
+                                if rand::thread_rng().gen::<f32>() < Skill::yield_chance(refine_level) {
+                                    2
+                                } else {
+                                    1
+                                };
+
                             let current_total_weight = items.get_total_weight(*structure_id);
                             let item_weight = Item::get_weight_from_template(
                                 produce_item.to_string(),
-                                1,
+                                quantity,
                                 &templates.item_templates,
                             );
 
@@ -1363,11 +1484,15 @@ fn operate_refine_event_system(
                                 continue;
                             }
 
-                            let (new_item, _merged) = items.new_with_attrs(
+                            let quality =
+                                Quality::upgrade(item_to_refine.quality, refine_level, 1.0);
+
+                            let (new_item, _merged) = items.new_with_quality(
                                 *structure_id,
                                 produce_item.to_string(),
-                                1,
-                                item_to_refine.attrs.clone(),
+                                quantity,
+                                refined_attrs.clone(),
+                                quality,
                             );
 
                             // Convert items to be updated to packets
@@ -1423,6 +1548,11 @@ fn operate_refine_event_system(
                     // Reset villager state to None
                     *villager.state = State::None;
 
+                    // Count towards the villager's queued order
+                    if let Ok(mut order_queue) = queue_query.get_mut(entity) {
+                        order_queue.record_progress();
+                    }
+
                     let Some(structure_entity) = ids.get_entity(*structure_id) else {
                         error!("Cannot find entity from structure_id: {:?}", structure_id);
                         continue;
@@ -1452,9 +1582,18 @@ fn operate_refine_event_system(
                         &templates.item_templates,
                         &resources,
                         &templates.res_templates,
+                        &templates.res_property_templates,
                         &mut ids,
                     );
 
+                    Skill::award(
+                        map_event.obj_id,
+                        &Resource::type_to_skill(res_type.to_string()),
+                        skill::OPERATE_XP,
+                        &mut skills,
+                        &templates.skill_templates,
+                    );
+
                     let active_info_key = (
                         structure.player_id.0,
                         structure.id.0,
@@ -1493,6 +1632,9 @@ fn craft_event_system(
     recipes: Res<Recipes>,
     //mut villager_query: Query<VillagerQuery, With<SubclassVillager>>,
     mut query: Query<ObjQuery>,
+    mut stats_query: Query<&mut Stats>,
+    mut queue_query: Query<&mut OrderQueue>,
+    morale_query: Query<&Morale>,
     mut map_events: ResMut<MapEvents>,
     active_infos: Res<ActiveInfos>,
 ) {
@@ -1532,33 +1674,70 @@ fn craft_event_system(
                             // Remove Event In Progress
                             commands.entity(entity).remove::<EventInProgress>();
 
+                            // Consume stamina for crafting
+                            if let Ok(mut stats) = stats_query.get_mut(entity) {
+                                Stamina::consume(
+                                    &mut stats,
+                                    Stamina::craft_cost(recipe.stamina_req),
+                                );
+                            }
+
                             let mut item_attrs = HashMap::new();
 
                             for consumed_item in consumed_items.iter() {
                                 item_attrs.extend(consumed_item.attrs.clone());
                             }
 
+                            let craft_skill = Skill::item_class_to_skill(recipe.class.clone());
+
+                            let craft_level = craft_skill.as_ref().map_or(0, |skill_name| {
+                                Skill::get_level(map_event.obj_id, skill_name, &skills)
+                            });
+
+                            // Happier and more skilled crafters take more care over their work
+                            let morale_mod = morale_query
+                                .get(entity)
+                                .map_or(1.0, |morale| morale.quality_mod());
+
+                            for attr_val in item_attrs.values_mut() {
+                                if let item::AttrVal::Num(value) = attr_val {
+                                    *value *= morale_mod * Skill::quality_mod(craft_level);
+                                }
+                            }
+
+                            let quality = Quality::upgrade(
+                                Quality::combine(&consumed_items),
+                                craft_level,
+                                morale_mod,
+                            );
+
                             // Create new item
                             let new_item = items.craft(
                                 *structure_id,
                                 recipe_name.to_string(),
                                 1,
                                 item_attrs,
+                                quality,
                                 &templates.recipe_templates,
                                 None,
                                 None,
                             );
 
-                            debug!("recipe: {:?}", recipe.class);
-                            let skill_name = Skill::item_class_to_skill(recipe.class);
+                            // Count towards the crafter's queued order
+                            if let Ok(mut order_queue) = queue_query.get_mut(entity) {
+                                order_queue.record_progress();
+                            }
 
-                            Skill::update(
-                                map_event.obj_id,
-                                skill_name,
-                                100,
-                                &mut skills,
-                                &templates.skill_templates,
-                            );
+                            debug!("recipe: {:?}", recipe.class);
+                            if let Some(skill_name) = craft_skill {
+                                Skill::update(
+                                    map_event.obj_id,
+                                    skill_name,
+                                    skill::CRAFT_XP,
+                                    &mut skills,
+                                    &templates.skill_templates,
+                                );
+                            }
 
                             let notification_packet: ResponsePacket = ResponsePacket::NewItems {
                                 action: obj::STATE_CRAFTING.to_string(),
@@ -1572,11 +1751,23 @@ fn craft_event_system(
                                 (crafter.player_id.0, *structure_id, "inventory".to_string());
 
                             if let Some(_active_info) = active_infos.get(&active_info_key) {
+                                let mut items_updated = vec![Item::to_packet(new_item)];
+                                let mut items_removed = Vec::new();
+
+                                // Requirements can be taken from several stacks
+                                for consumed_item in consumed_items.iter() {
+                                    if let Some(item) = items.find_by_id(consumed_item.id) {
+                                        items_updated.push(Item::to_packet(item));
+                                    } else {
+                                        items_removed.push(consumed_item.id);
+                                    }
+                                }
+
                                 let item_update_packet: ResponsePacket =
                                     ResponsePacket::InfoItemsUpdate {
                                         id: *structure_id,
-                                        items_updated: vec![Item::to_packet(new_item)],
-                                        items_removed: Vec::new(),
+                                        items_updated: items_updated,
+                                        items_removed: items_removed,
                                     };
 
                                 send_to_client(crafter.player_id.0, item_update_packet, &clients);
@@ -1601,7 +1792,7 @@ fn experiment_event_system(
     ids: ResMut<Ids>,
     _resources: ResMut<Resources>,
     mut items: ResMut<Items>,
-    _skills: ResMut<Skills>,
+    mut skills: ResMut<Skills>,
     templates: Res<Templates>,
     mut recipes: ResMut<Recipes>,
     mut experiments: ResMut<Experiments>,
@@ -1681,6 +1872,14 @@ fn experiment_event_system(
                         // Check res reqs
                         debug!("Checking experiment reagents");
                         if Experiment::check_reqs(*structure_id, experiment, &items) {
+                            Skill::award(
+                                map_event.obj_id,
+                                skill::EXPERIMENTING,
+                                skill::EXPERIMENT_XP,
+                                &mut skills,
+                                &templates.skill_templates,
+                            );
+
                             // Check discovery and create new recipe
                             let exp_state = Experiment::check_discovery(
                                 structure.player_id.0,
@@ -1726,6 +1925,7 @@ fn explore_event_system(
     game_tick: Res<GameTick>,
     ids: ResMut<Ids>,
     mut resources: ResMut<Resources>,
+    mut skills: ResMut<Skills>,
     templates: Res<Templates>,
     mut query: Query<(&PlayerId, &Position, &mut State)>,
     mut map_events: ResMut<MapEvents>,
@@ -1764,6 +1964,14 @@ fn explore_event_system(
                         &templates.res_templates,
                     );
 
+                    Skill::award(
+                        map_event.obj_id,
+                        skill::EXPLORING,
+                        skill::EXPLORE_XP,
+                        &mut skills,
+                        &templates.skill_templates,
+                    );
+
                     if revealed_resources.len() > 0 {
                         // Set explorer state to none
                         *explorer_state = State::None;
@@ -1810,7 +2018,7 @@ fn farm_event_system(
     mut crops: ResMut<Crops>,
     resources: ResMut<Resources>,
     mut items: ResMut<Items>,
-    skills: ResMut<Skills>,
+    mut skills: ResMut<Skills>,
     templates: Res<Templates>,
     //mut villager_query: Query<VillagerQuery, With<SubclassVillager>>,
     //mut state_query: Query<&mut State>,
@@ -1872,6 +2080,14 @@ fn farm_event_system(
                     info!("Planting Wheat crops: {:?}", seeds_to_plant);
                     crops.plant(game_tick.0, *structure_id, "Wheat".to_string(), seeds_to_plant);
 
+                    Skill::award(
+                        map_event.obj_id,
+                        skill::FARMING,
+                        skill::FARM_XP,
+                        &mut skills,
+                        &templates.skill_templates,
+                    );
+
                     // Consume item to refine
                     let new_seeds = items.remove_quantity(seeds.id, seeds_to_plant);
 
@@ -1937,6 +2153,15 @@ fn farm_event_system(
 
                     if let Some(crop) = crops.harvest(*structure_id, 1) {
                         info!("Harvesting crop: {:?}", crop);
+
+                        Skill::award(
+                            map_event.obj_id,
+                            skill::FARMING,
+                            skill::FARM_XP,
+                            &mut skills,
+                            &templates.skill_templates,
+                        );
+
                         let item_template = Item::get_template(crop.crop_type.clone(), &templates.item_templates);
 
                         let capacity = Obj::get_capacity(&structure.template.0, &templates.obj_templates);
@@ -2311,6 +2536,8 @@ fn use_item_system(
     _visible_events: ResMut<VisibleEvents>,
     mut map_events: ResMut<MapEvents>,
     mut query: Query<ObjWithStatsQuery>,
+    mut threat_query: Query<&mut ThreatTable>,
+    attrs_query: Query<&BaseAttrs>,
 ) {
     let mut events_to_remove = Vec::new();
 
@@ -2364,13 +2591,23 @@ fn use_item_system(
 
                                 debug!("Entity: {:?} Hp: {:?}", item_owner_id, item_owner.stats.hp);
 
+                                // Healing draws threat from NPCs already fighting the healed
+                                for mut threat_table in threat_query.iter_mut() {
+                                    if threat_table.threat.contains_key(item_owner_id) {
+                                        threat_table.add(
+                                            *item_owner_id,
+                                            healing_value as f32 * npc::HEALING_THREAT,
+                                        );
+                                    }
+                                }
+
                                 let packet = ResponsePacket::Stats {
                                     data: StatsData {
                                         id: *item_owner_id,
                                         hp: item_owner.stats.hp,
                                         base_hp: item_owner.stats.base_hp,
-                                        stamina: 10000, // TODO missing stamina
-                                        base_stamina: 10000,
+                                        stamina: item_owner.stats.stamina.unwrap_or(0),
+                                        base_stamina: item_owner.stats.base_stamina.unwrap_or(0),
                                         effects: Vec::new(),
                                     },
                                 };
@@ -2383,18 +2620,13 @@ fn use_item_system(
 
                             items.remove_item(item.id);
 
-                            let inventory_items = items.get_by_owner_packet(item.owner);
-
-                            let info_inventory_packet: ResponsePacket =
-                                ResponsePacket::InfoInventory {
-                                    id: item.owner,
-                                    cap: Obj::get_capacity(
-                                        &item_owner.template.0,
-                                        &templates.obj_templates,
-                                    ),
-                                    tw: items.get_total_weight(item.owner),
-                                    items: inventory_items,
-                                };
+                            let info_inventory_packet = Encumbrance::inventory_packet(
+                                item.owner,
+                                &item_owner.template.0,
+                                attrs_query.get(entity).ok(),
+                                &items,
+                                &templates,
+                            );
 
                             send_to_client(item.owner, info_inventory_packet, &clients);
 
@@ -2627,12 +2859,12 @@ fn visible_event_system(
     for map_event in visible_events.iter() {
         debug!("Checking if map_event is visible: {:?}", map_event);
 
-        let Some(entity) = ids.get_entity(map_event.obj_id) else {
-            error!("Cannot entity from id: {:?}", map_event.obj_id);
-            continue;
-        };
+        // Removed objs no longer have an entity
+        let event_obj = ids
+            .get_entity(map_event.obj_id)
+            .and_then(|entity| map_obj_query.get(entity).ok());
 
-        if let Ok(event_obj) = map_obj_query.get(entity) {
+        if let Some(event_obj) = event_obj {
             let network_obj = network::create_network_obj(&event_obj);
 
             for observer in map_obj_query.iter() {
@@ -2865,7 +3097,7 @@ fn visible_event_system(
         } else {
             debug!(
                 "VisibleEventSystem no entity found, assuming removed: {:?}",
-                entity
+                map_event.obj_id
             );
             for observer in map_obj_query.iter() {
                 match &map_event.event_type {
@@ -3487,15 +3719,22 @@ fn resurrect_system(
 }
 
 fn remove_dead_system(
-    game_tick: ResMut<GameTick>,
-    dead_state_query: Query<(&Id, &Position, &StateDead)>,
-    items: ResMut<Items>,
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut ids: ResMut<Ids>,
+    templates: Res<Templates>,
+    dead_state_query: Query<(&Id, &PlayerId, &Position, &StateDead, Option<&Bones>)>,
+    mut items: ResMut<Items>,
     mut map_events: ResMut<MapEvents>,
 ) {
     // Every 10 ticks
     if (game_tick.0 % 10) == 0 {
-        for (id, pos, dead_state) in dead_state_query.iter() {
-            if (game_tick.0 - dead_state.dead_at) > 500 {
+        for (id, player_id, pos, dead_state, bones) in dead_state_query.iter() {
+            let time_dead = game_tick.0 - dead_state.dead_at;
+
+            if time_dead > corpse::DECAY_TIME {
+                Corpse::decay(id.0, &mut items);
+
                 map_events.new(
                     id.0,
                     game_tick.0 + 1,
@@ -3503,9 +3742,41 @@ fn remove_dead_system(
                         pos: pos.to_owned(),
                     },
                 );
-            } else if (game_tick.0 - dead_state.dead_at) > 100 {
+            } else if time_dead > corpse::ROT_TIME && bones.is_none() {
+                // Corpse rots away and is replaced by its bones
+                let (bones_id, bones_entity) = Obj::create(
+                    player_id.0,
+                    corpse::BONES.to_string(),
+                    *pos,
+                    State::Dead,
+                    &mut commands,
+                    &mut ids,
+                    &mut map_events,
+                    &game_tick,
+                    &templates,
+                );
+
+                commands.entity(bones_entity).insert((
+                    StateDead {
+                        dead_at: dead_state.dead_at,
+                    },
+                    Bones,
+                ));
+
+                Corpse::rot(id.0, bones_id, &mut items);
+
+                map_events.new(
+                    id.0,
+                    game_tick.0 + 1,
+                    VisibleEvent::RemoveObjEvent {
+                        pos: pos.to_owned(),
+                    },
+                );
+            } else if time_dead > corpse::EMPTY_DECAY_TIME {
                 // Remove dead object faster if it contains no items
                 if items.get_by_owner(id.0).is_empty() {
+                    Corpse::decay(id.0, &mut items);
+
                     map_events.new(
                         id.0,
                         game_tick.0 + 1,
@@ -3540,6 +3811,8 @@ fn snapshot_system(world: &mut World) {
             .extract_resource::<Items>()
             .extract_resource::<MapEvents>()
             .extract_resource::<GameEvents>()
+            .extract_resource::<Markets>()
+            .extract_resource::<Vassals>()
             /* .extract_entities_matching(|e| {
                 e.contains::<Merchant>()
             }) */
@@ -3550,6 +3823,12 @@ fn snapshot_system(world: &mut World) {
         let _output = serialize(&snapshot, registry);
 
         //debug!("snapshot: {:?}", output);
+
+        // Listings and their escrow are loaded back at startup
+        let markets = world.resource::<Markets>();
+        let items = world.resource::<Items>();
+
+        markets.save(items);
     }
 }
 
@@ -3563,6 +3842,8 @@ fn update_game_tick(
     starving: Query<&Starving>,
     exhausted: Query<&Exhausted>,
     state_query: Query<&State>,
+    stats_query: Query<&Stats>,
+    overloaded: Query<&Overloaded>,
 ) {
     game_tick.0 = game_tick.0 + 1;
 
@@ -3584,7 +3865,19 @@ fn update_game_tick(
 
         if let Ok(state) = state_query.get(entity) {
             if *state != State::Sleeping {
-                tired.update_by_tick_amount(2.0);
+                // Working without stamina tires twice as fast
+                let exhaustion_mod = match stats_query.get(entity) {
+                    Ok(stats) if Stamina::is_exhausted(stats) => 2.0,
+                    _ => 1.0,
+                };
+
+                let overload_mod = if overloaded.contains(entity) {
+                    encumbrance::OVERLOAD_TIRED_MOD
+                } else {
+                    1.0
+                };
+
+                tired.update_by_tick_amount(2.0 * exhaustion_mod * overload_mod);
             }
         }
 
@@ -3642,22 +3935,180 @@ fn update_game_tick(
             "Thirst: {:?} Hunger: {:?} Tired: {:?}",
             thirst.thirst, hunger.hunger, tired.tired
         );*/
-        // Is thirsty
-        /*if thirst.thirst >= 80.0 {
-            morale.morale -= morale.per_tick;
-        } else if thirst.thirst >= 90.0 {
-            morale.morale -= 2.0 * morale.per_tick;
-        } else if thirst.thirst >= 95.0 {
-            morale.morale -= 5.0 * morale.per_tick;
-        } else {
-            morale.morale += morale.per_tick;
+    }
+}
+
+fn update_morale_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    ids: Res<Ids>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    mut map_events: ResMut<MapEvents>,
+    mut query: Query<(
+        Entity,
+        &Id,
+        &PlayerId,
+        &Position,
+        &State,
+        &VillagerAttrs,
+        &Thirst,
+        &Hunger,
+        &Tired,
+        &Heat,
+        &mut Morale,
+        Option<&Personality>,
+        Option<&Order>,
+    )>,
+    combat_query: Query<&InCombat>,
+    dead_query: Query<(&PlayerId, &Position, &StateDead), With<SubclassVillager>>,
+    collector_query: Query<&TaxCollector>,
+) {
+    // Every game hour
+    if game_tick.0 % GAME_HOUR != 0 {
+        return;
+    }
+
+    let mut recent_deaths = Vec::new();
+
+    for (dead_player_id, dead_pos, dead_state) in dead_query.iter() {
+        if game_tick.0 - dead_state.dead_at <= GAME_HOUR {
+            recent_deaths.push((dead_player_id.0, *dead_pos));
+        }
+    }
+
+    for (
+        entity,
+        id,
+        player_id,
+        pos,
+        state,
+        attrs,
+        thirst,
+        hunger,
+        tired,
+        heat,
+        mut morale,
+        personality,
+        order,
+    ) in query.iter_mut()
+    {
+        if Obj::is_dead(state) {
+            continue;
+        }
 
-            if morale.morale >= 100.0 {
-                morale.morale = 100.0;
+        // Deaths of nearby villagers weigh on morale for a day
+        for (dead_player_id, dead_pos) in recent_deaths.iter() {
+            if *dead_player_id == player_id.0 && Map::dist(*pos, *dead_pos) <= DEATH_MORALE_RANGE
+            {
+                morale.add_modifier(
+                    MORALE_DEATH,
+                    -DEATH_MORALE_PENALTY,
+                    game_tick.0 + GAME_TICKS_PER_DAY,
+                );
             }
-        }*/
+        }
+
+        let mut target_morale = MORALE_BASE;
+
+        // Unmet needs
+        target_morale -= Morale::need_penalty(thirst.thirst);
+        target_morale -= Morale::need_penalty(hunger.hunger);
+        target_morale -= Morale::need_penalty(tired.tired);
+
+        if (heat.heat - 50.0).abs() > DISCOMFORT_HEAT {
+            target_morale -= NEED_MORALE_PENALTY;
+        }
+
+        // Better shelters have a higher template level
+        match Structure::get_template_by_name(attrs.shelter.clone(), &templates.obj_templates) {
+            Some(shelter_template) => {
+                let shelter_level = shelter_template.level.unwrap_or(0);
+                target_morale += SHELTER_MORALE_BONUS * (shelter_level + 1) as f32;
+            }
+            None => target_morale -= NO_SHELTER_MORALE_PENALTY,
+        }
+
+        if let Ok(in_combat) = combat_query.get(entity) {
+            if in_combat.is_active(game_tick.0) {
+                target_morale -= COMBAT_MORALE_PENALTY;
+            }
+        }
+
+        // Overdue taxes owed by the villager's player
+        if collector_query
+            .iter()
+            .any(|collector| collector.target_player == player_id.0 && collector.debt_amount > 0)
+        {
+            target_morale -= TAX_DEBT_MORALE_PENALTY;
+        }
+
+        // Doing work they like or dislike
+        if let (Some(personality), Some(order)) = (personality, order) {
+            let (work_type, _target) = villager::Villager::order_to_str(order);
+
+            if personality.likes(&work_type) {
+                target_morale += WORK_MORALE_MODIFIER;
+            } else if personality.dislikes(&work_type) {
+                target_morale -= WORK_MORALE_MODIFIER;
+            }
+        }
+
+        target_morale += morale.modifiers_total(game_tick.0);
+
+        morale.update(target_morale);
+
+        // Desert after a full day at rock bottom
+        if morale.morale <= MORALE_DESERTION {
+            let low_since = *morale.low_since.get_or_insert(game_tick.0);
+
+            if game_tick.0 - low_since >= DESERTION_TIME {
+                info!("Villager {:?} deserted player {:?}", id.0, player_id.0);
+
+                // Leaves whatever it carried behind with the hero
+                match ids.get_hero(player_id.0) {
+                    Some(hero_id) => items.hand_over_all_items(id.0, hero_id),
+                    None => {
+                        for item in items.get_by_owner(id.0).iter() {
+                            items.remove_item(item.id);
+                        }
+                    }
+                }
+
+                Obj::add_sound_obj_event(
+                    game_tick.0,
+                    "I've had enough of this, I'm leaving!".to_string(),
+                    id,
+                    &mut map_events,
+                );
+
+                map_events.new(
+                    id.0,
+                    game_tick.0 + 1,
+                    VisibleEvent::RemoveObjEvent { pos: *pos },
+                );
+
+                commands.entity(entity).remove::<Morale>();
+            }
+        } else {
+            morale.low_since = None;
+        }
+    }
+}
+
+// Villagers spawned without a personality, such as merchant stock, are given one
+fn personality_system(
+    mut commands: Commands,
+    templates: Res<Templates>,
+    mut query: Query<(Entity, &mut Name), (With<SubclassVillager>, Without<Personality>)>,
+) {
+    for (entity, mut name) in query.iter_mut() {
+        let (villager_name, personality) =
+            villager::Villager::generate(&templates.villager_templates);
+
+        name.0 = villager_name;
 
-        //debug!("thirst: {:?} morale: {:?}", thirst.thirst, morale.morale);
+        commands.entity(entity).insert(personality);
     }
 }
 
@@ -68,4 +68,13 @@ impl Ids {
         self.player_hero_map.insert(player_id, hero_id);
         self.new_obj(hero_id, player_id, entity);
     }
+
+    pub fn remove_obj(&mut self, obj_id: i32) {
+        self.obj_player_map.remove(&obj_id);
+        self.obj_entity_map.remove(&obj_id);
+    }
+
+    pub fn set_player(&mut self, obj_id: i32, player_id: i32) {
+        self.obj_player_map.insert(obj_id, player_id);
+    }
 }
\ No newline at end of file
@@ -5,7 +5,11 @@ use std::collections::HashMap;
 use std::slice::Iter;
 
 use crate::effect::Effect;
-use crate::network;
+use crate::ledger::{self, Ledger, LedgerOp};
+use crate::game::Clients;
+use crate::ids::Ids;
+use crate::network::{self, send_to_client, ResponsePacket};
+use crate::quality;
 use crate::resource::{self};
 use crate::templates::{ItemTemplate, RecipeTemplates, ResReq};
 
@@ -44,7 +48,9 @@ pub enum AttrKey {
     MeidumArmorDefense,
     MeidumArmorDurabilility,
     StructureHp,
-    StructureDefense
+    StructureDefense,
+    Durability,
+    MaxDurability,
 }
 
 impl AttrKey {
@@ -58,6 +64,35 @@ impl AttrKey {
         PROC_ATTR_KEYS.iter()
     }
 
+    // Attributes that come from resource properties
+    pub fn property_iter() -> Iter<'static, AttrKey> {
+        static PROPERTY_ATTR_KEYS: [AttrKey; 22] = [
+            AttrKey::AllAttributes,
+            AttrKey::Creativity,
+            AttrKey::Dexterity,
+            AttrKey::Endurance,
+            AttrKey::Focus,
+            AttrKey::Intellect,
+            AttrKey::Spirit,
+            AttrKey::Strength,
+            AttrKey::Toughness,
+            AttrKey::AxeDamage,
+            AttrKey::SwordDamage,
+            AttrKey::HammerDamage,
+            AttrKey::DaggerDamage,
+            AttrKey::SpearDamage,
+            AttrKey::AxeSpeed,
+            AttrKey::BowDamage,
+            AttrKey::HeavyArmorDefense,
+            AttrKey::HeavyArmorDurability,
+            AttrKey::MeidumArmorDefense,
+            AttrKey::MeidumArmorDurabilility,
+            AttrKey::StructureHp,
+            AttrKey::StructureDefense,
+        ];
+        PROPERTY_ATTR_KEYS.iter()
+    }
+
     pub fn proc_to_effect(self) -> Effect {
         match self {
             AttrKey::DeepWoundChance => Effect::DeepWound,
@@ -127,6 +162,14 @@ pub const HEALING: &str = "Healing";
 
 pub const VISIBLE: &str = "Visble";
 
+pub const DEFAULT_DURABILITY: f32 = 100.0;
+pub const MIN_DURABILITY_MOD: f32 = 0.5; // Stats kept at zero durability
+pub const TOOL_WEAR: f32 = 1.0;
+
+pub const REPAIR_BASE: f32 = 25.0;
+pub const REPAIR_PER_LEVEL: f32 = 5.0;
+pub const REPAIR_XP: i32 = 25;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ItemLocation {
     Own,
@@ -205,16 +248,105 @@ pub struct Item {
     pub image: String,
     pub weight: f32,
     pub equipped: bool,
+    pub quality: i32,
     pub experiment: Option<ExperimentItemType>,
     pub attrs: HashMap<AttrKey, AttrVal>,
 }
 
+// Lookups into the item list, rebuilt from it after a load
+#[derive(Default, Debug)]
+struct ItemIndex {
+    // Item id to its position in the item list
+    by_id: HashMap<i32, usize>,
+    // Item ids of each owner in the order they were received
+    by_owner: HashMap<i32, Vec<i32>>,
+    by_owner_class: HashMap<i32, HashMap<String, Vec<i32>>>,
+}
+
+impl ItemIndex {
+    fn add(&mut self, item: &Item, index: usize) {
+        self.by_id.insert(item.id, index);
+        self.add_owner(item);
+    }
+
+    fn remove(&mut self, item: &Item) {
+        self.by_id.remove(&item.id);
+        self.remove_owner(item);
+    }
+
+    fn add_owner(&mut self, item: &Item) {
+        self.by_owner.entry(item.owner).or_default().push(item.id);
+
+        self.by_owner_class
+            .entry(item.owner)
+            .or_default()
+            .entry(item.class.clone())
+            .or_default()
+            .push(item.id);
+    }
+
+    fn remove_owner(&mut self, item: &Item) {
+        if let Some(owned) = self.by_owner.get_mut(&item.owner) {
+            owned.retain(|id| *id != item.id);
+
+            if owned.is_empty() {
+                self.by_owner.remove(&item.owner);
+            }
+        }
+
+        if let Some(classes) = self.by_owner_class.get_mut(&item.owner) {
+            if let Some(owned) = classes.get_mut(&item.class) {
+                owned.retain(|id| *id != item.id);
+
+                if owned.is_empty() {
+                    classes.remove(&item.class);
+                }
+            }
+
+            if classes.is_empty() {
+                self.by_owner_class.remove(&item.owner);
+            }
+        }
+    }
+
+    fn owned(&self, owner: i32) -> &[i32] {
+        return self
+            .by_owner
+            .get(&owner)
+            .map(|owned| owned.as_slice())
+            .unwrap_or(&[]);
+    }
+
+    fn owned_by_class(&self, owner: i32, class: &str) -> &[i32] {
+        return self
+            .by_owner_class
+            .get(&owner)
+            .and_then(|classes| classes.get(class))
+            .map(|owned| owned.as_slice())
+            .unwrap_or(&[]);
+    }
+
+    fn rebuild(&mut self, items: &[Item]) {
+        *self = ItemIndex::default();
+
+        for (index, item) in items.iter().enumerate() {
+            self.add(item, index);
+        }
+    }
+}
+
 #[derive(Resource, Reflect, Default, Debug)]
 #[reflect(Resource)]
 pub struct Items {
     items: Vec<Item>,
     next_id: i32,
     item_templates: Vec<ItemTemplate>,
+    ledger: Ledger,
+    #[reflect(ignore)]
+    index: ItemIndex,
+    // Items broken by wear whose owners have not been told yet
+    #[reflect(ignore)]
+    broken: Vec<Item>,
 }
 
 impl Items {
@@ -222,6 +354,130 @@ impl Items {
         self.item_templates = item_templates;
     }
 
+    pub fn ledger(&self) -> &Ledger {
+        return &self.ledger;
+    }
+
+    pub fn ledger_mut(&mut self) -> &mut Ledger {
+        return &mut self.ledger;
+    }
+
+    // Tags the ledger entries of the following operations
+    pub fn set_reason(&mut self, reason: &str) {
+        self.ledger.set_reason(reason);
+    }
+
+    pub fn clear_reason(&mut self) {
+        self.ledger.clear_reason();
+    }
+
+    // Total quantity of each item name
+    // Counted straight from the items, without the index or the ledger
+    pub fn recount(&self) -> HashMap<(i32, String), i32> {
+        let mut holdings = HashMap::new();
+
+        for item in self.items.iter() {
+            *holdings.entry((item.owner, item.name.clone())).or_insert(0) += item.quantity;
+        }
+
+        return holdings;
+    }
+
+    // The index is not saved and is empty after the items are loaded
+    pub fn is_indexed(&self) -> bool {
+        return self.index.by_id.len() == self.items.len();
+    }
+
+    pub fn reindex(&mut self) {
+        self.index.rebuild(&self.items);
+    }
+
+    // Items loaded from a save get a new id, the saved one may already be taken
+    pub fn restore(&mut self, mut item: Item, owner: i32) -> Item {
+        item.id = self.get_next_id();
+        item.owner = owner;
+
+        self.insert(item.clone());
+        self.record_create(&item, item.quantity);
+
+        return item;
+    }
+
+    fn insert(&mut self, item: Item) {
+        self.index.add(&item, self.items.len());
+        self.items.push(item);
+    }
+
+    fn remove_at(&mut self, index: usize) -> Item {
+        let item = self.items.swap_remove(index);
+        self.index.remove(&item);
+
+        // The last item was moved into the place of the removed one
+        if let Some(moved_item) = self.items.get(index) {
+            self.index.by_id.insert(moved_item.id, index);
+        }
+
+        return item;
+    }
+
+    fn set_owner(&mut self, index: usize, owner: i32) {
+        let item = &mut self.items[index];
+
+        self.index.remove_owner(item);
+        item.owner = owner;
+        self.index.add_owner(item);
+    }
+
+    fn owned(&self, owner: i32) -> impl Iterator<Item = &Item> {
+        return self
+            .index
+            .owned(owner)
+            .iter()
+            .map(|id| &self.items[self.index.by_id[id]]);
+    }
+
+    fn owned_by_class(&self, owner: i32, class: &str) -> impl Iterator<Item = &Item> {
+        return self
+            .index
+            .owned_by_class(owner, class)
+            .iter()
+            .map(|id| &self.items[self.index.by_id[id]]);
+    }
+
+    // Existing stack the item would merge into, only items of the same quality stack
+    fn find_stack(&self, owner: i32, name: &String, class: &str, quality: i32) -> Option<usize> {
+        return self
+            .owned_by_class(owner, class)
+            .find(|item| item.name == *name && item.quality == quality)
+            .map(|item| self.index.by_id[&item.id]);
+    }
+
+    fn record_create(&mut self, item: &Item, quantity: i32) {
+        self.ledger.record(
+            LedgerOp::Create,
+            item.id,
+            None,
+            &item.name,
+            quantity,
+            None,
+            Some(item.owner),
+            ledger::REASON_CREATE,
+        );
+    }
+
+    fn record_destroy(&mut self, item: &Item, quantity: i32, default_reason: &str) {
+        self.ledger.record(
+            LedgerOp::Destroy,
+            item.id,
+            None,
+            &item.name,
+            quantity,
+            Some(item.owner),
+            None,
+            default_reason,
+        );
+    }
+
     pub fn new(&mut self, owner: i32, name: String, quantity: i32) -> Item {
         let mut class = "Invalid".to_string();
         let mut subclass = "Invalid".to_string();
@@ -242,7 +498,8 @@ impl Items {
             }
         }
 
-        let attrs = HashMap::new();
+        let mut attrs = HashMap::new();
+        Item::init_durability(&class, &mut attrs);
 
         let new_item = Item {
             id: self.get_next_id(),
@@ -255,11 +512,13 @@ impl Items {
             image: image,
             weight: weight,
             equipped: false,
+            quality: quality::DEFAULT_QUALITY,
             experiment: None,
             attrs: attrs,
         };
 
-        self.items.push(new_item.clone());
+        self.insert(new_item.clone());
+        self.record_create(&new_item, new_item.quantity);
         debug!("New Item by new(): {:?}", new_item);
 
         new_item
@@ -271,6 +530,17 @@ impl Items {
         name: String,
         quantity: i32,
         attrs: HashMap<AttrKey, AttrVal>,
+    ) -> (Item, bool) {
+        return self.new_with_quality(owner, name, quantity, attrs, quality::DEFAULT_QUALITY);
+    }
+
+    pub fn new_with_quality(
+        &mut self,
+        owner: i32,
+        name: String,
+        quantity: i32,
+        mut attrs: HashMap<AttrKey, AttrVal>,
+        quality: i32,
     ) -> (Item, bool) {
         let mut class = "Invalid".to_string();
         let mut subclass = "Invalid".to_string();
@@ -291,17 +561,18 @@ impl Items {
             }
         }
 
+        Item::init_durability(&class, &mut attrs);
+
         // Can new item be merged into existing
         if Item::can_merge_by_class(class.clone()) {
-            if let Some(merged_index) = self
-                .items
-                .iter()
-                .position(|item| item.owner == owner && item.name == name)
-            {
+            if let Some(merged_index) = self.find_stack(owner, &name, &class, quality) {
                 let merged_item = &mut self.items[merged_index];
                 merged_item.quantity += quantity;
 
-                return (merged_item.clone(), true);
+                let merged_item = merged_item.clone();
+                self.record_create(&merged_item, quantity);
+
+                return (merged_item, true);
             } else {
                 // Create the new item
                 let new_item = Item {
@@ -315,11 +586,13 @@ impl Items {
                     image: image,
                     weight: weight,
                     equipped: false,
+                    quality: quality,
                     experiment: None,
                     attrs: attrs,
                 };
 
-                self.items.push(new_item.clone());
+                self.insert(new_item.clone());
+                self.record_create(&new_item, new_item.quantity);
 
                 // Return new item to send to client
                 return (new_item, false);
@@ -338,11 +611,13 @@ impl Items {
                     image: image,
                     weight: weight,
                     equipped: false,
+                    quality: quality,
                     experiment: None,
                     attrs: attrs,
                 };
 
-                self.items.push(new_item.clone());
+                self.insert(new_item.clone());
+                self.record_create(&new_item, new_item.quantity);
 
             // Return new item to send to client
             return (new_item, false);
@@ -350,6 +625,16 @@ impl Items {
     }
 
     pub fn create(&mut self, owner: i32, name: String, quantity: i32) -> (Item, bool) {
+        return self.create_with_quality(owner, name, quantity, quality::DEFAULT_QUALITY);
+    }
+
+    pub fn create_with_quality(
+        &mut self,
+        owner: i32,
+        name: String,
+        quantity: i32,
+        quality: i32,
+    ) -> (Item, bool) {
         let mut class = "Invalid".to_string();
         let mut subclass = "Invalid".to_string();
         let mut image = "Invalid".to_string();
@@ -365,26 +650,27 @@ impl Items {
         }
 
         // Can new item be merged into existing
-        if Item::can_merge_by_class(class) {
-            if let Some(merged_index) = self
-                .items
-                .iter()
-                .position(|item| item.owner == owner && item.name == name)
-            {
+        if Item::can_merge_by_class(class.clone()) {
+            if let Some(merged_index) = self.find_stack(owner, &name, &class, quality) {
                 let merged_item = &mut self.items[merged_index];
                 merged_item.quantity += quantity;
 
-                return (merged_item.clone(), true);
+                let merged_item = merged_item.clone();
+                self.record_create(&merged_item, quantity);
+
+                return (merged_item, true);
             } else {
                 // Create the new item
-                let new_item = self.new(owner, name, quantity);
+                let (new_item, _merged) =
+                    self.new_with_quality(owner, name, quantity, HashMap::new(), quality);
 
                 // Return new item to send to client
                 return (new_item, false);
             }
         } else {
             // Create the new item
-            let new_item = self.new(owner, name, quantity);
+            let (new_item, _merged) =
+                self.new_with_quality(owner, name, quantity, HashMap::new(), quality);
 
             // Return new item to send to client
             return (new_item, false);
@@ -392,40 +678,70 @@ impl Items {
     }
 
     pub fn transfer(&mut self, item_id: i32, target_id: i32) {
-        if let Some(transfer_index) = self.items.iter().position(|item| item.id == item_id) {
+        if let Some(transfer_index) = self.find_index_by_id(item_id) {
             // Immutable item to transfer
             let item_to_transfer = self.items[transfer_index].clone();
 
             if Item::can_merge_by_class(item_to_transfer.class.clone()) {
-                if let Some(merged_index) = self
-                    .items
-                    .iter()
-                    .position(|item| item.owner == target_id && item.name == item_to_transfer.name)
-                {
+                // An item never merges into itself
+                let merged_index = self
+                    .find_stack(
+                        target_id,
+                        &item_to_transfer.name,
+                        &item_to_transfer.class,
+                        item_to_transfer.quality,
+                    )
+                    .filter(|merged_index| *merged_index != transfer_index);
+
+                if let Some(merged_index) = merged_index {
                     let merged_item = &mut self.items[merged_index];
                     merged_item.quantity += item_to_transfer.quantity;
 
-                    self.items.swap_remove(transfer_index);
+                    let merged_id = merged_item.id;
+                    self.remove_at(transfer_index);
+
+                    self.record_transfer(&item_to_transfer, Some(merged_id), target_id);
                 } else {
-                    // Have to retrieve the item to transfer again as it was immutable above
-                    let transfer_item = &mut self.items[transfer_index];
-                    transfer_item.owner = target_id;
+                    self.set_owner(transfer_index, target_id);
+
+                    self.record_transfer(&item_to_transfer, None, target_id);
                 }
             } else {
-                let transfer_item = &mut self.items[transfer_index];
-                transfer_item.owner = target_id;
+                self.set_owner(transfer_index, target_id);
+
+                self.record_transfer(&item_to_transfer, None, target_id);
             }
         }
     }
 
+    fn record_transfer(&mut self, item: &Item, merged_into: Option<i32>, target_id: i32) {
+        // Moving an item to where it already is changes nothing
+        if item.owner == target_id {
+            return;
+        }
+
+        self.ledger.record(
+            LedgerOp::Transfer,
+            item.id,
+            merged_into,
+            &item.name,
+            item.quantity,
+            Some(item.owner),
+            Some(target_id),
+            ledger::REASON_TRANSFER,
+        );
+    }
+
     pub fn split(&mut self, item_id: i32, quantity: i32) -> Option<Item> {
-        if let Some(index) = self.items.iter().position(|item| item.id == item_id) {
+        if let Some(index) = self.find_index_by_id(item_id) {
             let new_item_id = self.get_next_id();
             let item = &mut self.items[index];
 
             if (item.quantity - quantity) > 0 {
                 item.quantity -= quantity;
 
+                let item = item.clone();
+
                 /*let new_item = self.new_with_attrs(
                     item.owner,
                     item.name.clone(),
@@ -463,13 +779,25 @@ impl Items {
                     image: image,
                     weight: weight,
                     equipped: false,
+                    quality: item.quality,
                     experiment: None,
                     attrs: item.attrs.clone(),
                 };
 
-                self.items.push(new_item.clone());
+                self.insert(new_item.clone());
                 debug!("New Item: {:?}", new_item);
 
+                self.ledger.record(
+                    LedgerOp::Split,
+                    new_item.id,
+                    Some(item_id),
+                    &new_item.name,
+                    quantity,
+                    Some(new_item.owner),
+                    Some(new_item.owner),
+                    ledger::REASON_SPLIT,
+                );
+
                 return Some(new_item);
             } else {
                 return None;
@@ -493,19 +821,31 @@ impl Items {
     }
 
     pub fn transfer_all_items(&mut self, source_id: i32, target_id: i32) {
-        let source_items = self.get_by_owner(source_id);
+        let source_item_ids = self.index.owned(source_id).to_vec();
 
-        for source_item in source_items.iter() {
-            self.transfer(source_item.id, target_id);
+        for source_item_id in source_item_ids.iter() {
+            self.transfer(*source_item_id, target_id);
         }
     }
 
+    // Equipment is taken off before it changes hands
+    pub fn hand_over_all_items(&mut self, source_id: i32, target_id: i32) {
+        for item in self.get_by_owner(source_id).iter() {
+            if item.equipped {
+                self.equip(item.id, false);
+            }
+        }
+
+        self.transfer_all_items(source_id, target_id);
+    }
+
     pub fn craft(
         &mut self,
         owner: i32,
         recipe_name: String,
         quantity: i32,
-        attrs: HashMap<AttrKey, AttrVal>,
+        mut attrs: HashMap<AttrKey, AttrVal>,
+        quality: i32,
         recipe_templates: &RecipeTemplates,
         custom_name: Option<String>,  //override
         custom_image: Option<String>, //override
@@ -540,6 +880,7 @@ impl Items {
             image = custom_image;
         }
 
+        Item::init_durability(&class, &mut attrs);
 
         let new_item = Item {
             id: self.get_next_id(),
@@ -552,25 +893,19 @@ impl Items {
             image: image,
             weight: weight,
             equipped: false,
+            quality: quality,
             experiment: None,
             attrs: attrs,
         };
 
-        self.items.push(new_item.clone());
+        self.insert(new_item.clone());
+        self.record_create(&new_item, new_item.quantity);
 
         return new_item;
     }
 
     pub fn get_by_owner(&self, owner: i32) -> Vec<Item> {
-        let mut owner_items: Vec<Item> = Vec::new();
-
-        for item in self.items.iter() {
-            if item.owner == owner {
-                owner_items.push(item.clone());
-            }
-        }
-
-        return owner_items;
+        return self.owned(owner).cloned().collect();
     }
 
     pub fn get_by_class(&self, owner: i32, class: String) -> Option<Item> {
@@ -585,25 +920,23 @@ impl Items {
     pub fn get_by_owner_packet(&self, owner: i32) -> Vec<network::Item> {
         let mut owner_items: Vec<network::Item> = Vec::new();
 
-        for item in self.items.iter() {
-            if item.owner == owner {
-
-                let item_packet = network::Item {
-                    id: item.id,
-                    owner: item.owner,
-                    name: item.name.clone(),
-                    quantity: item.quantity,
-                    class: item.class.clone(),
-                    subclass: item.subclass.clone(),
-                    slot: Slot::to_str(item.slot.clone()),
-                    image: item.image.clone(),
-                    weight: item.weight,
-                    equipped: item.equipped,
-                    attrs: None,
-                };
+        for item in self.owned(owner) {
+            let item_packet = network::Item {
+                id: item.id,
+                owner: item.owner,
+                name: item.name.clone(),
+                quantity: item.quantity,
+                class: item.class.clone(),
+                subclass: item.subclass.clone(),
+                slot: Slot::to_str(item.slot.clone()),
+                image: item.image.clone(),
+                weight: item.weight,
+                equipped: item.equipped,
+                quality: item.quality,
+                attrs: None,
+            };
 
-                owner_items.push(item_packet);
-            }
+            owner_items.push(item_packet);
         }
 
         return owner_items;
@@ -620,36 +953,9 @@ impl Items {
             return vec![];
         }
 
-        for item in self.items.iter() {
-            if item.owner == owner {
-                if !filter.contains(&item.name) {
-                    let item_packet = network::Item {
-                        id: item.id,
-                        owner: item.owner,
-                        name: item.name.clone(),
-                        quantity: item.quantity,
-                        class: item.class.clone(),
-                        subclass: item.subclass.clone(),
-                        slot: Slot::to_str(item.slot.clone()),
-                        image: item.image.clone(),
-                        weight: item.weight,
-                        equipped: item.equipped,
-                        attrs: None,
-                    };
-
-                    owner_items.push(item_packet);
-                }
-            }
-        }
-
-        return owner_items;
-    }
-
-    pub fn get_packet(&self, item_id: i32) -> Option<network::Item> {
-        for item in self.items.iter() {
-
-            if item.id == item_id {
-                return Some(network::Item {
+        for item in self.owned(owner) {
+            if !filter.contains(&item.name) {
+                let item_packet = network::Item {
                     id: item.id,
                     owner: item.owner,
                     name: item.name.clone(),
@@ -660,12 +966,35 @@ impl Items {
                     image: item.image.clone(),
                     weight: item.weight,
                     equipped: item.equipped,
-                    attrs: Some(item.attrs.clone()),
-                });
+                    quality: item.quality,
+                    attrs: None,
+                };
+
+                owner_items.push(item_packet);
             }
         }
 
-        return None;
+        return owner_items;
+    }
+
+    pub fn get_packet(&self, item_id: i32) -> Option<network::Item> {
+        let index = self.find_index_by_id(item_id)?;
+        let item = &self.items[index];
+
+        return Some(network::Item {
+            id: item.id,
+            owner: item.owner,
+            name: item.name.clone(),
+            quantity: item.quantity,
+            class: item.class.clone(),
+            subclass: item.subclass.clone(),
+            slot: Slot::to_str(item.slot.clone()),
+            image: item.image.clone(),
+            weight: item.weight,
+            equipped: item.equipped,
+            quality: item.quality,
+            attrs: Some(item.attrs.clone()),
+        });
     }
 
     pub fn get_by_name_packet(&self, item_name: String) -> Option<network::Item> {
@@ -682,6 +1011,7 @@ impl Items {
                     image: item.image.clone(),
                     weight: item.weight,
                     equipped: item.equipped,
+                    quality: item.quality,
                     attrs: None, //TODO actually get the attrs
                 });
             }
@@ -691,71 +1021,57 @@ impl Items {
     }
 
     pub fn get_equipped(&self, owner: i32) -> Vec<Item> {
-        let mut equipped = Vec::new();
-
-        for item in self.items.iter() {
-            if item.owner == owner && item.equipped {
-                equipped.push(item.clone());
-            }
-        }
-
-        return equipped;
+        return self.owned(owner).filter(|item| item.equipped).cloned().collect();
     }
 
     pub fn get_equipped_weapons(&self, owner: i32) -> Vec<Item> {
-        let mut equipped_weapons = Vec::new();
-
-        for item in self.items.iter() {
-            if item.owner == owner && item.class == WEAPON && item.equipped {
-                equipped_weapons.push(item.clone());
-            }
-        }
-
-        return equipped_weapons;
+        return self
+            .owned_by_class(owner, WEAPON)
+            .filter(|item| item.equipped)
+            .cloned()
+            .collect();
     }
 
     pub fn get_total_weight(&self, owner: i32) -> i32 {
         let mut total_weight = 0.0;
 
-        for item in self.items.iter() {
-            if item.owner == owner {
-                total_weight += item.weight * item.quantity as f32;
-            }
+        for item in self.owned(owner) {
+            total_weight += item.weight * item.quantity as f32;
         }
 
         return total_weight as i32;
     }
 
     pub fn equip(&mut self, item_id: i32, status: bool) {
-        for item in &mut self.items.iter_mut() {
-            if item_id == item.id {
-                item.equipped = status;
-            }
+        if let Some(index) = self.find_index_by_id(item_id) {
+            self.items[index].equipped = status;
         }
     }
 
     pub fn remove_quantity(&mut self, item_id: i32, quantity: i32) -> Option<Item> {
-        let index = self
-            .items
-            .iter()
-            .position(|item| item.id == item_id)
-            .unwrap(); // Should panic if item is not found
+        let index = self.find_index_by_id(item_id).unwrap(); // Should panic if item is not found
         let item = &mut self.items[index];
         if item.quantity >= quantity {
             item.quantity -= quantity;
 
+            let item = item.clone();
+            self.record_destroy(&item, quantity, ledger::REASON_DESTROY);
+
             if item.quantity == 0 {
-                self.items.swap_remove(index);
+                self.remove_at(index);
                 return None;
             }
+
+            return Some(item);
         }
 
         return Some(item.clone());
     }
 
     pub fn remove_item(&mut self, item_id: i32) {
-        if let Some(index) = self.items.iter().position(|item| item.id == item_id) {
-            self.items.remove(index);
+        if let Some(index) = self.find_index_by_id(item_id) {
+            let item = self.remove_at(index);
+            self.record_destroy(&item, item.quantity, ledger::REASON_DESTROY);
         } else {
             error!("Item does not exist");
         }
@@ -775,11 +1091,20 @@ impl Items {
             );
             if (item.quantity + mod_quantity) > 0 {
                 item.quantity += mod_quantity;
-                return Some(item.clone());
+
+                let item = item.clone();
+
+                if mod_quantity > 0 {
+                    self.record_create(&item, mod_quantity);
+                } else {
+                    self.record_destroy(&item, -mod_quantity, ledger::REASON_DESTROY);
+                }
+
+                return Some(item);
             } else if (item.quantity + mod_quantity) == 0 {
                 debug!("Removing item {:?}", index);
-                self.items.swap_remove(index);
-                debug!("items: {:?}", self.items);
+                let item = self.remove_at(index);
+                self.record_destroy(&item, item.quantity, ledger::REASON_DESTROY);
                 return None;
             } else {
                 return None;
@@ -790,7 +1115,7 @@ impl Items {
     }
 
     pub fn set_experiment_source(&mut self, item_id: i32) -> Item {
-        if let Some(index) = self.items.iter().position(|item| item.id == item_id) {
+        if let Some(index) = self.find_index_by_id(item_id) {
             let item = &mut self.items[index];
 
             item.experiment = Some(ExperimentItemType::Source);
@@ -801,7 +1126,7 @@ impl Items {
     }
 
     pub fn remove_experiment_source(&mut self, item_id: i32) -> Item {
-        if let Some(index) = self.items.iter().position(|item| item.id == item_id) {
+        if let Some(index) = self.find_index_by_id(item_id) {
             let item = &mut self.items[index];
 
             item.experiment = None;
@@ -812,7 +1137,7 @@ impl Items {
     }
 
     pub fn set_experiment_reagent(&mut self, item_id: i32) {
-        if let Some(index) = self.items.iter().position(|item| item.id == item_id) {
+        if let Some(index) = self.find_index_by_id(item_id) {
             let item = &mut self.items[index];
 
             item.experiment = Some(ExperimentItemType::Reagent);
@@ -822,7 +1147,7 @@ impl Items {
     }
 
     pub fn remove_experiment_reagent(&mut self, item_id: i32) {
-        if let Some(index) = self.items.iter().position(|item| item.id == item_id) {
+        if let Some(index) = self.find_index_by_id(item_id) {
             let item = &mut self.items[index];
 
             item.experiment = None;
@@ -839,17 +1164,15 @@ impl Items {
         let mut experiment_reagents: Vec<network::Item> = Vec::new();
         let mut other_resources: Vec<network::Item> = Vec::new();
 
-        for item in self.items.iter() {
-            if item.owner == structure_id {
-                if let Some(item_experiment_type) = &item.experiment {
-                    if *item_experiment_type == ExperimentItemType::Reagent {
-                        experiment_reagents.push(Item::to_packet(item.clone()));
-                    } else if *item_experiment_type == ExperimentItemType::Source {
-                        experiment_source.push(Item::to_packet(item.clone()));
-                    }
-                } else {
-                    other_resources.push(Item::to_packet(item.clone()));
+        for item in self.owned(structure_id) {
+            if let Some(item_experiment_type) = &item.experiment {
+                if *item_experiment_type == ExperimentItemType::Reagent {
+                    experiment_reagents.push(Item::to_packet(item.clone()));
+                } else if *item_experiment_type == ExperimentItemType::Source {
+                    experiment_source.push(Item::to_packet(item.clone()));
                 }
+            } else {
+                other_resources.push(Item::to_packet(item.clone()));
             }
         }
 
@@ -860,14 +1183,12 @@ impl Items {
         let mut experiment_source = None;
         let mut experiment_reagents = Vec::new();
 
-        for item in self.items.iter() {
-            if item.owner == structure_id {
-                if let Some(item_experiment_type) = &item.experiment {
-                    if *item_experiment_type == ExperimentItemType::Reagent {
-                        experiment_reagents.push(item.clone());
-                    } else if *item_experiment_type == ExperimentItemType::Source {
-                        experiment_source = Some(item.clone());
-                    }
+        for item in self.owned(structure_id) {
+            if let Some(item_experiment_type) = &item.experiment {
+                if *item_experiment_type == ExperimentItemType::Reagent {
+                    experiment_reagents.push(item.clone());
+                } else if *item_experiment_type == ExperimentItemType::Source {
+                    experiment_source = Some(item.clone());
                 }
             }
         }
@@ -876,42 +1197,38 @@ impl Items {
     }
 
     pub fn get_experiment_reagent(&self, structure_id: i32, subclass: String) -> Option<i32> {
-        for item in self.items.iter() {
-            if item.owner == structure_id
-                && item.subclass == subclass
-                && item.experiment == Some(ExperimentItemType::Reagent)
-            {
-                return Some(item.id);
-            }
-        }
-        return None;
+        return self
+            .owned(structure_id)
+            .find(|item| {
+                item.subclass == subclass && item.experiment == Some(ExperimentItemType::Reagent)
+            })
+            .map(|item| item.id);
     }
 
     pub fn get_total_gold(&self, owner: i32) -> i32 {
-        let mut total_gold = 0;
-
-        for item in self.items.iter() {
-            if item.owner == owner && item.class == GOLD.to_string() {
-                total_gold += item.quantity;
-            }
-        }
+        return self.owned_by_class(owner, GOLD).map(|item| item.quantity).sum();
+    }
 
-        return total_gold;
+    pub fn get_total_quantity(&self, owner: i32, name: &String) -> i32 {
+        return self
+            .owned(owner)
+            .filter(|item| item.name == *name)
+            .map(|item| item.quantity)
+            .sum();
     }
 
     pub fn transfer_gold(&mut self, owner: i32, target_id: i32, quantity: i32) {
         let mut remainder = quantity;
         let mut transfer_items = Vec::new();
 
-        for item in &mut self.items.iter() {
-            if item.owner == owner && item.class == GOLD.to_string() {
-                if item.quantity >= remainder {
-                    transfer_items.push((item.id, remainder));
-                } else {
-                    transfer_items.push((item.id, item.quantity));
+        for item in self.owned_by_class(owner, GOLD) {
+            if item.quantity >= remainder {
+                transfer_items.push((item.id, remainder));
+                break;
+            } else {
+                transfer_items.push((item.id, item.quantity));
 
-                    remainder = remainder - item.quantity;
-                }
+                remainder = remainder - item.quantity;
             }
         }
 
@@ -920,9 +1237,81 @@ impl Items {
         }
     }
 
+    // Gold taken out of the economy
+    pub fn remove_gold(&mut self, owner: i32, quantity: i32) {
+        let mut remainder = quantity;
+        let mut remove_items = Vec::new();
+
+        for item in self.owned_by_class(owner, GOLD) {
+            if item.quantity >= remainder {
+                remove_items.push((item.id, remainder));
+                break;
+            } else {
+                remove_items.push((item.id, item.quantity));
+
+                remainder = remainder - item.quantity;
+            }
+        }
+
+        for (remove_item_id, remove_quantity) in remove_items.iter() {
+            self.remove_quantity(*remove_item_id, *remove_quantity);
+        }
+    }
+
+    // Returns None if the item broke and was removed
+    pub fn wear(&mut self, item_id: i32, amount: f32) -> Option<Item> {
+        let Some(index) = self.find_index_by_id(item_id) else {
+            error!("Cannot find item: {:?}", item_id);
+            return None;
+        };
+
+        let item = &mut self.items[index];
+
+        let Some((durability, _max_durability)) = item.get_durability() else {
+            return Some(item.clone());
+        };
+
+        let new_durability = f32::max(durability - amount, 0.0);
+
+        if new_durability <= 0.0 {
+            info!("Item {:?} has broken", item);
+            let item = self.remove_at(index);
+            self.record_destroy(&item, item.quantity, ledger::REASON_BROKEN);
+            self.broken.push(item);
+            return None;
+        }
+
+        item.attrs
+            .insert(AttrKey::Durability, AttrVal::Num(new_durability));
+
+        return Some(item.clone());
+    }
+
+    pub fn take_broken(&mut self) -> Vec<Item> {
+        return std::mem::take(&mut self.broken);
+    }
+
+    pub fn repair(&mut self, item_id: i32, amount: f32) -> Option<Item> {
+        let Some(index) = self.find_index_by_id(item_id) else {
+            error!("Cannot find item: {:?}", item_id);
+            return None;
+        };
+
+        let item = &mut self.items[index];
+
+        if let Some((durability, max_durability)) = item.get_durability() {
+            let new_durability = f32::min(durability + amount, max_durability);
+
+            item.attrs
+                .insert(AttrKey::Durability, AttrVal::Num(new_durability));
+        }
+
+        return Some(item.clone());
+    }
+
     // TODO reconsider returning the cloned item...
     pub fn find_by_id(&self, item_id: i32) -> Option<Item> {
-        if let Some(index) = self.items.iter().position(|item| item.id == item_id) {
+        if let Some(index) = self.find_index_by_id(item_id) {
             return Some(self.items[index].clone());
         }
 
@@ -930,14 +1319,15 @@ impl Items {
     }
 
     pub fn find_index_by_id(&self, item_id: i32) -> Option<usize> {
-        self.items.iter().position(|item| item.id == item_id)
+        return self.index.by_id.get(&item_id).copied();
     }
 
     fn find_by_class(&self, owner: i32, class: String) -> Option<usize> {
         let index = self
-            .items
-            .iter()
-            .position(|item| item.owner == owner && item.class == class);
+            .index
+            .owned_by_class(owner, &class)
+            .first()
+            .map(|id| self.index.by_id[id]);
         return index;
     }
 
@@ -961,17 +1351,102 @@ impl Item {
             image: item.image.clone(),
             weight: item.weight,
             equipped: item.equipped,
+            quality: item.quality,
             attrs: None,
         };
     }
 
     pub fn is_equipable(item: Item) -> bool {
-        if item.class == WEAPON || item.class == ARMOR {
+        if item.class == WEAPON || item.class == ARMOR || item.class == GATHERING {
             return true;
         }
         return false;
     }
 
+    pub fn has_durability(class: &String) -> bool {
+        match class.as_str() {
+            WEAPON => true,
+            ARMOR => true,
+            GATHERING => true,
+            _ => false,
+        }
+    }
+
+    pub fn init_durability(class: &String, attrs: &mut HashMap<AttrKey, AttrVal>) {
+        if !Item::has_durability(class) || attrs.contains_key(&AttrKey::Durability) {
+            return;
+        }
+
+        let mut max_durability = DEFAULT_DURABILITY;
+
+        // Durability bonuses from crafting materials
+        for bonus_key in [
+            AttrKey::HeavyArmorDurability,
+            AttrKey::MeidumArmorDurabilility,
+        ] {
+            if let Some(AttrVal::Num(bonus)) = attrs.get(&bonus_key) {
+                max_durability += *bonus;
+            }
+        }
+
+        attrs.insert(AttrKey::Durability, AttrVal::Num(max_durability));
+        attrs.insert(AttrKey::MaxDurability, AttrVal::Num(max_durability));
+    }
+
+    pub fn get_durability(&self) -> Option<(f32, f32)> {
+        let Some(AttrVal::Num(durability)) = self.attrs.get(&AttrKey::Durability) else {
+            return None;
+        };
+
+        let Some(AttrVal::Num(max_durability)) = self.attrs.get(&AttrKey::MaxDurability) else {
+            return None;
+        };
+
+        return Some((*durability, *max_durability));
+    }
+
+    // Repairs cost the share of the recipe matching the durability actually restored
+    pub fn get_repair_reqs(
+        item: &Item,
+        recipe_reqs: &Vec<ResReq>,
+        repair_amount: f32,
+    ) -> Vec<ResReq> {
+        let mut repair_reqs = Vec::new();
+
+        let repair_ratio = match item.get_durability() {
+            Some((durability, max_durability)) if max_durability > 0.0 => {
+                let missing = max_durability - durability;
+                f32::min(repair_amount, missing) / max_durability
+            }
+            _ => 1.0,
+        };
+
+        for recipe_req in recipe_reqs.iter() {
+            let quantity = (recipe_req.quantity as f32 * repair_ratio).ceil() as i32;
+
+            repair_reqs.push(ResReq {
+                req_type: recipe_req.req_type.clone(),
+                quantity: i32::max(quantity, 1),
+                cquantity: None,
+            });
+        }
+
+        return repair_reqs;
+    }
+
+    // Item stats degrade linearly down to MIN_DURABILITY_MOD as durability drops
+    pub fn durability_mod(&self) -> f32 {
+        let Some((durability, max_durability)) = self.get_durability() else {
+            return 1.0;
+        };
+
+        if max_durability <= 0.0 {
+            return 1.0;
+        }
+
+        return MIN_DURABILITY_MOD + (1.0 - MIN_DURABILITY_MOD) * (durability / max_durability);
+    }
+
     pub fn use_item(_item_id: i32, _status: bool, _items: &mut ResMut<Items>) {}
 
     pub fn get_items_value_by_attr(attr: &AttrKey, items: Vec<Item>) -> f32 {
@@ -986,7 +1461,7 @@ impl Item {
                         AttrVal::Num(attr_val) => val = *attr_val,
                         _ => val = 0.0,
                     }
-                    item_values += val
+                    item_values += val * item.durability_mod()
                 }
                 None => item_values += 0.0,
             }
@@ -1050,16 +1525,204 @@ impl Item {
 
 }
 
+fn reindex_system(mut items: ResMut<Items>) {
+    if !items.is_indexed() {
+        info!("Rebuilding item index");
+        items.reindex();
+    }
+}
+
+// Removes broken items from their owners' inventories on the client
+fn broken_items_system(clients: Res<Clients>, ids: Res<Ids>, mut items: ResMut<Items>) {
+    for item in items.take_broken() {
+        let Some(player_id) = ids.get_player(item.owner) else {
+            continue;
+        };
+
+        let item_update_packet = ResponsePacket::InfoItemsUpdate {
+            id: item.owner,
+            items_updated: Vec::new(),
+            items_removed: vec![item.id],
+        };
+
+        send_to_client(player_id, item_update_packet, &clients);
+    }
+}
+
 pub struct ItemPlugin;
 
 impl Plugin for ItemPlugin {
     fn build(&self, app: &mut App) {
-        let items = Items {
-            items: Vec::new(),
-            next_id: 0,
-            item_templates: Vec::new(),
+        app.init_resource::<Items>()
+            .add_systems(First, reindex_system)
+            .add_systems(Last, broken_items_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ledger::LedgerEntry;
+    use crate::quality::Quality;
+    use crate::structure::Structure;
+
+    use std::fs;
+
+    fn load_items() -> Items {
+        let item_template_file =
+            fs::File::open("item_template.yaml").expect("Could not open file.");
+        let item_templates: Vec<ItemTemplate> =
+            serde_yaml::from_reader(item_template_file).expect("Could not read values.");
+
+        let mut items = Items::default();
+        items.set_templates(item_templates);
+
+        return items;
+    }
+
+    // Every lookup through the index must agree with a scan of the item list
+    fn assert_index(items: &Items) {
+        assert!(items.is_indexed());
+
+        for (index, item) in items.items.iter().enumerate() {
+            assert_eq!(items.find_index_by_id(item.id), Some(index));
+
+            let mut owner_ids: Vec<i32> = items
+                .items
+                .iter()
+                .filter(|other| other.owner == item.owner)
+                .map(|other| other.id)
+                .collect();
+            owner_ids.sort();
+
+            let mut indexed_ids: Vec<i32> = items
+                .get_by_owner(item.owner)
+                .iter()
+                .map(|other| other.id)
+                .collect();
+            indexed_ids.sort();
+
+            assert_eq!(indexed_ids, owner_ids);
+        }
+    }
+
+    #[test]
+    fn test_index_kept_in_sync() {
+        let mut items = load_items();
+
+        let (gold, _merged) = items.create(1, GOLD.to_string(), 100);
+        let axe = items.new(1, "Copper Training Axe".to_string(), 1);
+        let ore = items.new(1, "Valleyrun Copper Ore".to_string(), 10);
+        items.create(2, GOLD.to_string(), 5);
+
+        items.equip(axe.id, true);
+        assert_eq!(items.get_equipped_weapons(1).len(), 1);
+
+        // Gold merges into the target's stack
+        items.transfer_gold(1, 2, 40);
+        assert_eq!(items.get_total_gold(1), 60);
+        assert_eq!(items.get_total_gold(2), 45);
+        assert_eq!(items.get_by_class(2, GOLD.to_string()).unwrap().quantity, 45);
+        assert_index(&items);
+
+        items.transfer(axe.id, 2);
+        assert!(items.get_equipped(1).is_empty());
+        assert_eq!(items.find_by_id(axe.id).unwrap().owner, 2);
+        assert_index(&items);
+
+        items.remove_item(gold.id);
+        items.remove_quantity(ore.id, 10);
+        assert!(items.get_by_owner(1).is_empty());
+        assert_index(&items);
+
+        items.transfer_all_items(2, 3);
+        assert_eq!(items.get_by_owner(3).len(), 2);
+        assert_index(&items);
+
+        // A loaded store starts without an index
+        items.index = ItemIndex::default();
+        assert!(!items.is_indexed());
+
+        items.reindex();
+        assert_index(&items);
+    }
+
+    #[test]
+    fn test_ledger_matches_recount() {
+        let mut items = load_items();
+
+        let ore = items.new(1, "Valleyrun Copper Ore".to_string(), 10);
+        items.create(1, GOLD.to_string(), 100);
+
+        items.transfer_quantity(ore.id, 2, 4);
+        items.transfer_gold(1, 2, 30);
+        items.remove_quantity(ore.id, 2);
+
+        let split_entries: Vec<&LedgerEntry> = items
+            .ledger()
+            .by_item(ore.id)
+            .into_iter()
+            .filter(|entry| entry.op == LedgerOp::Split)
+            .collect();
+
+        assert_eq!(split_entries.len(), 1);
+        assert_eq!(split_entries[0].quantity, 4);
+        assert!(items.ledger().check(&items.recount()).is_empty());
+
+        // An owner change without an entry shows up for both owners
+        let index = items.find_index_by_id(ore.id).unwrap();
+        items.items[index].owner = 3;
+
+        assert_eq!(items.ledger().check(&items.recount()).len(), 2);
+    }
+
+    #[test]
+    fn test_craft_from_mixed_quality() {
+        let mut items = load_items();
+        let ore = "Valleyrun Copper Ore".to_string();
+
+        items.new_with_quality(10, ore.clone(), 3, HashMap::new(), 1);
+        items.new_with_quality(10, ore.clone(), 6, HashMap::new(), 4);
+        items.new(10, "Copper Training Axe".to_string(), 1);
+
+        // Stacks of different quality are kept apart
+        assert_eq!(items.get_by_owner(10).len(), 3);
+
+        let reqs = |quantity: i32| {
+            vec![ResReq {
+                req_type: ore.clone(),
+                quantity: quantity,
+                cquantity: None,
+            }]
         };
 
-        app.insert_resource(items);
+        assert!(!Structure::has_req(10, &reqs(10), &items));
+        assert!(Structure::has_req(10, &reqs(9), &items));
+
+        let consumed_items = Structure::consume_reqs(10, reqs(9), &mut items);
+
+        assert_eq!(consumed_items.len(), 2);
+        assert_eq!(
+            consumed_items.iter().map(|item| item.quantity).sum::<i32>(),
+            9
+        );
+        assert_eq!(items.get_by_owner(10).len(), 1);
+        assert_index(&items);
+
+        // Three crude and six superior ores make a fine item
+        let quality = Quality::combine(&consumed_items);
+        assert_eq!(quality, 3);
+
+        let (crafted_item, _merged) = items.new_with_quality(
+            10,
+            "Copper Training Axe".to_string(),
+            1,
+            HashMap::new(),
+            quality,
+        );
+
+        assert_eq!(crafted_item.quality, 3);
+        assert!(items.ledger().check(&items.recount()).is_empty());
     }
 }
@@ -0,0 +1,374 @@
+use bevy::prelude::*;
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use crate::game::{Clients, GameTick};
+use crate::ids::Ids;
+use crate::item::Items;
+use crate::network::{self, send_to_client, ResponsePacket};
+use crate::player::{PlayerEvent, PlayerEvents};
+
+pub const LEDGER_CHECK_INTERVAL: i32 = 100;
+
+// Entries kept in memory, older ones are appended to the ledger file
+// and only the ones in memory can be queried
+pub const MAX_LEDGER_ENTRIES: usize = 10000;
+pub const LEDGER_FILE: &str = "ledger.log";
+
+// Reasons for entries made outside of a tagged operation
+pub const REASON_CREATE: &str = "create";
+pub const REASON_DESTROY: &str = "destroy";
+pub const REASON_TRANSFER: &str = "transfer";
+pub const REASON_SPLIT: &str = "split";
+pub const REASON_BROKEN: &str = "broken";
+
+pub const REASON_BUY: &str = "buy";
+pub const REASON_SELL: &str = "sell";
+pub const REASON_TRADE: &str = "trade";
+pub const REASON_MARKET: &str = "market";
+pub const REASON_MARKET_ORPHANED: &str = "market orphaned";
+pub const REASON_MERCHANT: &str = "merchant";
+pub const REASON_TAX: &str = "tax";
+pub const REASON_TAX_FORFEITURE: &str = "tax forfeiture";
+pub const REASON_TAX_BRIBE: &str = "tax bribe";
+pub const REASON_WAGES: &str = "wages";
+pub const REASON_DECAY: &str = "decay";
+
+#[derive(Debug, Reflect, Clone, Copy, PartialEq, Default, Serialize)]
+pub enum LedgerOp {
+    #[default]
+    Create,
+    Destroy,
+    Transfer,
+    Split,
+}
+
+// Source is None for created items and destination is None for destroyed ones
+#[derive(Debug, Reflect, Clone, Default, Serialize)]
+pub struct LedgerEntry {
+    pub tick: i32,
+    pub op: LedgerOp,
+    pub item_id: i32,
+    // Stack the transferred item was merged into or the split item came from
+    pub related_id: Option<i32>,
+    pub name: String,
+    pub quantity: i32,
+    pub source: Option<i32>,
+    pub destination: Option<i32>,
+    pub reason: String,
+}
+
+// Admin queries only cover the entries still in memory, older ones are in the ledger file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LedgerQuery {
+    Item(i32),
+    Player(i32),
+    Since(i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discrepancy {
+    pub owner: i32,
+    pub name: String,
+    pub expected: i32,
+    pub actual: i32,
+}
+
+impl LedgerEntry {
+    pub fn to_packet(&self) -> network::LedgerEntry {
+        return network::LedgerEntry {
+            tick: self.tick,
+            op: format!("{:?}", self.op),
+            item_id: self.item_id,
+            related_id: self.related_id,
+            name: self.name.clone(),
+            quantity: self.quantity,
+            source: self.source,
+            destination: self.destination,
+            reason: self.reason.clone(),
+        };
+    }
+}
+
+impl Discrepancy {
+    pub fn is_duplication(&self) -> bool {
+        return self.actual > self.expected;
+    }
+}
+
+// Append only record of everything created, destroyed, transferred and split by Items
+#[derive(Debug, Reflect, Clone, Default)]
+pub struct Ledger {
+    entries: Vec<LedgerEntry>,
+    // Quantity each owner held of each item name before the kept entries
+    #[reflect(ignore)]
+    opening: HashMap<(i32, String), i32>,
+    tick: i32,
+    reason: Option<String>,
+}
+
+impl Ledger {
+    pub fn set_tick(&mut self, tick: i32) {
+        self.tick = tick;
+    }
+
+    // Entries are tagged with the reason until it is cleared
+    pub fn set_reason(&mut self, reason: &str) {
+        self.reason = Some(reason.to_string());
+    }
+
+    pub fn clear_reason(&mut self) {
+        self.reason = None;
+    }
+
+    pub fn record(
+        &mut self,
+        op: LedgerOp,
+        item_id: i32,
+        related_id: Option<i32>,
+        name: &String,
+        quantity: i32,
+        source: Option<i32>,
+        destination: Option<i32>,
+        default_reason: &str,
+    ) {
+        if quantity == 0 {
+            return;
+        }
+
+        let reason = self.reason.clone().unwrap_or(default_reason.to_string());
+
+        self.entries.push(LedgerEntry {
+            tick: self.tick,
+            op: op,
+            item_id: item_id,
+            related_id: related_id,
+            name: name.clone(),
+            quantity: quantity,
+            source: source,
+            destination: destination,
+            reason: reason,
+        });
+    }
+
+    pub fn entries(&self) -> &Vec<LedgerEntry> {
+        return &self.entries;
+    }
+
+    pub fn by_item(&self, item_id: i32) -> Vec<&LedgerEntry> {
+        return self
+            .entries
+            .iter()
+            .filter(|entry| entry.item_id == item_id || entry.related_id == Some(item_id))
+            .collect();
+    }
+
+    pub fn by_owner(&self, owner: i32) -> Vec<&LedgerEntry> {
+        return self
+            .entries
+            .iter()
+            .filter(|entry| entry.source == Some(owner) || entry.destination == Some(owner))
+            .collect();
+    }
+
+    // Entries touching any obj of the player, escrows have no player and are left out
+    pub fn by_player(&self, player_id: i32, ids: &Ids) -> Vec<&LedgerEntry> {
+        let is_player = |owner: Option<i32>| {
+            owner.is_some_and(|owner| ids.get_player(owner) == Some(player_id))
+        };
+
+        return self
+            .entries
+            .iter()
+            .filter(|entry| is_player(entry.source) || is_player(entry.destination))
+            .collect();
+    }
+
+    // Tick of the oldest entry still in memory
+    pub fn oldest(&self) -> Option<i32> {
+        return self.entries.first().map(|entry| entry.tick);
+    }
+
+    pub fn since(&self, tick: i32) -> Vec<&LedgerEntry> {
+        return self
+            .entries
+            .iter()
+            .filter(|entry| entry.tick >= tick)
+            .collect();
+    }
+
+    fn apply(holdings: &mut HashMap<(i32, String), i32>, entry: &LedgerEntry) {
+        if let (LedgerOp::Destroy | LedgerOp::Transfer, Some(source)) = (entry.op, entry.source) {
+            *holdings.entry((source, entry.name.clone())).or_insert(0) -= entry.quantity;
+        }
+
+        if let (LedgerOp::Create | LedgerOp::Transfer, Some(destination)) =
+            (entry.op, entry.destination)
+        {
+            *holdings.entry((destination, entry.name.clone())).or_insert(0) += entry.quantity;
+        }
+    }
+
+    // Quantity of each item name every owner should hold by replaying the entries
+    pub fn holdings(&self) -> HashMap<(i32, String), i32> {
+        let mut holdings = self.opening.clone();
+
+        for entry in self.entries.iter() {
+            Ledger::apply(&mut holdings, entry);
+        }
+
+        return holdings;
+    }
+
+    // Removes the oldest entries once over the limit, their effect is kept in the opening holdings
+    pub fn flush(&mut self) -> Vec<LedgerEntry> {
+        if self.entries.len() <= MAX_LEDGER_ENTRIES {
+            return Vec::new();
+        }
+
+        let num_flushed = self.entries.len() - MAX_LEDGER_ENTRIES / 2;
+        let flushed: Vec<LedgerEntry> = self.entries.drain(..num_flushed).collect();
+
+        for entry in flushed.iter() {
+            Ledger::apply(&mut self.opening, entry);
+        }
+
+        return flushed;
+    }
+
+    // Holdings recounted from the items that do not match the replayed entries
+    // point at items made, lost or moved without an entry
+    pub fn check(&self, recount: &HashMap<(i32, String), i32>) -> Vec<Discrepancy> {
+        let mut discrepancies = Vec::new();
+        let holdings = self.holdings();
+
+        for ((owner, name), expected) in holdings.iter() {
+            let actual = *recount.get(&(*owner, name.clone())).unwrap_or(&0);
+
+            if actual != *expected {
+                discrepancies.push(Discrepancy {
+                    owner: *owner,
+                    name: name.clone(),
+                    expected: *expected,
+                    actual: actual,
+                });
+            }
+        }
+
+        for ((owner, name), actual) in recount.iter() {
+            if !holdings.contains_key(&(*owner, name.clone())) && *actual != 0 {
+                discrepancies.push(Discrepancy {
+                    owner: *owner,
+                    name: name.clone(),
+                    expected: 0,
+                    actual: *actual,
+                });
+            }
+        }
+
+        return discrepancies;
+    }
+}
+
+fn ledger_tick_system(game_tick: Res<GameTick>, mut items: ResMut<Items>) {
+    let ledger = items.ledger_mut();
+
+    ledger.set_tick(game_tick.0);
+    ledger.clear_reason();
+}
+
+fn conservation_check_system(game_tick: Res<GameTick>, items: Res<Items>) {
+    if game_tick.0 % LEDGER_CHECK_INTERVAL != 0 {
+        return;
+    }
+
+    for discrepancy in items.ledger().check(&items.recount()).iter() {
+        if discrepancy.is_duplication() {
+            error!("Ledger: {:?} duplicated {:?}", discrepancy.name, discrepancy);
+        } else {
+            error!("Ledger: {:?} disappeared {:?}", discrepancy.name, discrepancy);
+        }
+    }
+}
+
+fn ledger_flush_system(mut items: ResMut<Items>) {
+    let flushed = items.ledger_mut().flush();
+
+    if flushed.is_empty() {
+        return;
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(LEDGER_FILE);
+
+    let Ok(mut file) = file else {
+        error!("Cannot open {}, dropping {} ledger entries", LEDGER_FILE, flushed.len());
+        return;
+    };
+
+    for entry in flushed.iter() {
+        let Ok(line) = serde_json::to_string(entry) else {
+            error!("Cannot serialize ledger entry {:?}", entry);
+            continue;
+        };
+
+        if let Err(err) = writeln!(file, "{}", line) {
+            error!("Cannot write {}: {:?}", LEDGER_FILE, err);
+            return;
+        }
+    }
+}
+
+fn ledger_query_system(
+    mut events: ResMut<PlayerEvents>,
+    ids: Res<Ids>,
+    clients: Res<Clients>,
+    items: Res<Items>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::LedgerQuery { player_id, query } => {
+                events_to_remove.push(*event_id);
+
+                let ledger = items.ledger();
+
+                let entries = match query {
+                    LedgerQuery::Item(item_id) => ledger.by_item(*item_id),
+                    LedgerQuery::Player(query_player_id) => {
+                        ledger.by_player(*query_player_id, &ids)
+                    }
+                    LedgerQuery::Since(tick) => ledger.since(*tick),
+                };
+
+                let packet = ResponsePacket::Ledger {
+                    entries: entries.iter().map(|entry| entry.to_packet()).collect(),
+                    oldest: ledger.oldest(),
+                };
+
+                send_to_client(*player_id, packet, &clients);
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+pub struct LedgerPlugin;
+
+impl Plugin for LedgerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(First, ledger_tick_system)
+            .add_systems(Update, ledger_query_system)
+            .add_systems(
+                Last,
+                (conservation_check_system, ledger_flush_system).chain(),
+            );
+    }
+}
@@ -18,6 +18,8 @@ use bevy_save::SavePlugins;
 use event::{MapEvents, GameEvents};
 use game::{GamePlugin, Position, Merchant};
 use item::Items;
+use market::Markets;
+use empire::Vassals;
 
 mod account;
 mod combat;
@@ -27,7 +29,7 @@ mod event;
 mod encounter;
 mod experiment;
 mod game;
-mod item;
+pub mod item;
 mod ids;
 mod map;
 mod network;
@@ -44,6 +46,19 @@ mod terrain_feature;
 mod constants;
 mod world;
 mod farm;
+mod stamina;
+mod corpse;
+mod social;
+mod population;
+mod pricing;
+mod merchant;
+mod trade;
+mod market;
+mod empire;
+mod ledger;
+mod encumbrance;
+mod quality;
+mod contract;
 
 const TIMESTEP_10_PER_SECOND: f64 = 1.0 / 10.0;
 
@@ -66,5 +81,7 @@ pub fn setup() {
         .register_type::<Items>()
         .register_type::<MapEvents>()
         .register_type::<GameEvents>()
+        .register_type::<Markets>()
+        .register_type::<Vassals>()
         .run();
 }
@@ -13,7 +13,9 @@ use tiled::Loader;
 
 use pathfinding::prelude::astar;
 
+use crate::empire::Empire;
 use crate::game::Position;
+use crate::templates::EmpireTemplate;
 use crate::world::Weather;
 
 pub struct MapPlugin;
@@ -883,6 +885,19 @@ impl Map {
         result
     }
 
+    // Checks if a and b are adjacent to center on opposite sides
+    pub fn is_opposite(center: Position, a: Position, b: Position) -> bool {
+        let (cx, cy, cz) = Map::odd_q_to_cube((center.x, center.y));
+        let (ax, ay, az) = Map::odd_q_to_cube((a.x, a.y));
+        let (bx, by, bz) = Map::odd_q_to_cube((b.x, b.y));
+
+        if Map::dist(center, a) != 1 || Map::dist(center, b) != 1 {
+            return false;
+        }
+
+        return (ax - cx) == -(bx - cx) && (ay - cy) == -(by - cy) && (az - cz) == -(bz - cz);
+    }
+
     pub fn is_adjacent(source_pos: Position, target_pos: Position) -> bool {
         let neighbours = Self::neighbours((source_pos.x, source_pos.y));
 
@@ -904,11 +919,8 @@ impl Map {
         q >= 0 && r >= 0 && q < (WIDTH as i32) && r < (HEIGHT as i32)
     }
 
-    pub fn in_empire(pos: Position) -> bool {
-        match pos {
-            Position { x: 0, y: 15 } => true,
-            _ => false,
-        }
+    pub fn in_empire(pos: Position, empire_template: &EmpireTemplate) -> bool {
+        return Map::dist(pos, Empire::capital(empire_template)) <= empire_template.territory;
     }
 
     // Climate functions
@@ -1166,4 +1178,14 @@ mod tests {
 
         assert_eq!(Map::neighbours((0, 0)), result);
     }
+
+    #[test]
+    fn test_is_opposite() {
+        let center = Position { x: 1, y: 1 };
+
+        assert!(Map::is_opposite(center, Position { x: 1, y: 0 }, Position { x: 1, y: 2 }));
+        assert!(Map::is_opposite(center, Position { x: 0, y: 1 }, Position { x: 2, y: 2 }));
+        assert!(!Map::is_opposite(center, Position { x: 1, y: 0 }, Position { x: 2, y: 1 }));
+        assert!(!Map::is_opposite(center, Position { x: 1, y: 3 }, Position { x: 1, y: -1 }));
+    }
 }
@@ -0,0 +1,794 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+
+use crate::constants::GAME_HOUR;
+use crate::game::{ClassStructure, GameTick, State, Subclass};
+use crate::ids::Ids;
+use crate::item::{self, Item, Items};
+use crate::ledger;
+use crate::network;
+
+pub const SUBCLASS_MARKET: &str = "market";
+
+// Share of every sale kept by the market structure
+pub const MARKET_FEE: f32 = 0.05;
+
+// Listing durations are given in game hours
+pub const MIN_LISTING_HOURS: i32 = 1;
+pub const MAX_LISTING_HOURS: i32 = 72;
+
+pub const MIN_BID_INCREMENT: i32 = 1;
+pub const MARKET_UPDATE_INTERVAL: i32 = 10;
+
+pub const MARKETS_FILE: &str = "markets.json";
+
+#[derive(Debug, Reflect, Clone, Default, Serialize, Deserialize)]
+pub struct Bid {
+    pub player_id: i32,
+    pub hero_id: i32,
+    pub amount: i32,
+}
+
+// The escrow id holds the listed item and the gold of the highest bid
+#[derive(Debug, Reflect, Clone, Default, Serialize, Deserialize)]
+pub struct Listing {
+    pub id: i32,
+    pub market_id: i32,
+    pub seller: i32,
+    pub return_id: i32,
+    pub escrow_id: i32,
+    pub item_id: i32,
+    pub price: i32,
+    pub expires: i32,
+    pub bid: Option<Bid>,
+}
+
+#[derive(Resource, Reflect, Default, Debug)]
+#[reflect(Resource)]
+pub struct Markets {
+    pub listings: Vec<Listing>,
+    next_id: i32,
+}
+
+// Listings with the items and gold held in their escrow
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarketsSave {
+    pub listings: Vec<Listing>,
+    pub next_id: i32,
+    pub escrow: Vec<Item>,
+}
+
+impl Markets {
+    pub fn fee(amount: i32) -> i32 {
+        return (amount as f32 * MARKET_FEE).ceil() as i32;
+    }
+
+    // Moves the item from the seller's hero into escrow
+    pub fn list(
+        &mut self,
+        market_id: i32,
+        seller: i32,
+        hero_id: i32,
+        return_id: i32,
+        item_id: i32,
+        quantity: i32,
+        price: i32,
+        hours: i32,
+        ids: &mut ResMut<Ids>,
+        items: &mut ResMut<Items>,
+        game_tick: &Res<GameTick>,
+    ) -> Result<i32, String> {
+        let Some(item) = items.find_by_id(item_id) else {
+            return Err("Item does not exist.".to_string());
+        };
+
+        if item.owner != hero_id {
+            return Err("Item is not owned by you.".to_string());
+        }
+
+        if item.equipped {
+            return Err("Item is equipped.".to_string());
+        }
+
+        if item.class == item::GOLD {
+            return Err("Gold cannot be listed.".to_string());
+        }
+
+        if quantity <= 0 || item.quantity < quantity {
+            return Err("Invalid quantity.".to_string());
+        }
+
+        if price <= 0 {
+            return Err("Invalid price.".to_string());
+        }
+
+        if hours < MIN_LISTING_HOURS || hours > MAX_LISTING_HOURS {
+            return Err(format!(
+                "Listings last between {} and {} hours.",
+                MIN_LISTING_HOURS, MAX_LISTING_HOURS
+            ));
+        }
+
+        let escrow_id = ids.new_obj_id();
+
+        items.set_reason(ledger::REASON_MARKET);
+        items.transfer_quantity(item_id, escrow_id, quantity);
+        items.clear_reason();
+
+        // The listed part may have been split off into a new item
+        let Some(listed_item) = items.get_by_owner(escrow_id).into_iter().next() else {
+            return Err("Item could not be listed.".to_string());
+        };
+
+        self.next_id += 1;
+
+        let listing = Listing {
+            id: self.next_id,
+            market_id: market_id,
+            seller: seller,
+            return_id: return_id,
+            escrow_id: escrow_id,
+            item_id: listed_item.id,
+            price: price,
+            expires: game_tick.0 + hours * GAME_HOUR,
+            bid: None,
+        };
+
+        info!("Market listing: {:?}", listing);
+
+        self.listings.push(listing);
+
+        return Ok(self.next_id);
+    }
+
+    pub fn buy(
+        &mut self,
+        listing_id: i32,
+        player_id: i32,
+        hero_id: i32,
+        hard_limit: i32,
+        ids: &Ids,
+        is_standing: impl Fn(i32) -> bool,
+        items: &mut ResMut<Items>,
+    ) -> Result<Listing, String> {
+        let Some(index) = self.listings.iter().position(|listing| listing.id == listing_id) else {
+            return Err("Listing does not exist.".to_string());
+        };
+
+        let listing = &self.listings[index];
+
+        if listing.seller == player_id {
+            return Err("Cannot buy your own listing.".to_string());
+        }
+
+        if items.get_total_gold(hero_id) < listing.price {
+            return Err("Insufficient gold".to_string());
+        }
+
+        if items.get_total_weight(hero_id) + items.get_total_weight(listing.escrow_id) > hard_limit {
+            return Err("You cannot carry that.".to_string());
+        }
+
+        let listing = self.listings.remove(index);
+
+        items.set_reason(ledger::REASON_MARKET);
+        items.transfer_gold(hero_id, listing.escrow_id, listing.price);
+
+        let proceeds_id = Markets::proceeds_owner(&listing, ids, &is_standing);
+
+        Markets::refund_bid(&listing, items);
+        Markets::settle(&listing, hero_id, listing.price, proceeds_id, items);
+        items.clear_reason();
+
+        return Ok(listing);
+    }
+
+    // Bids hold their gold in escrow, the previous high bid is refunded
+    pub fn bid(
+        &mut self,
+        listing_id: i32,
+        player_id: i32,
+        hero_id: i32,
+        amount: i32,
+        items: &mut ResMut<Items>,
+    ) -> Result<(), String> {
+        let Some(listing) = self
+            .listings
+            .iter_mut()
+            .find(|listing| listing.id == listing_id)
+        else {
+            return Err("Listing does not exist.".to_string());
+        };
+
+        if listing.seller == player_id {
+            return Err("Cannot bid on your own listing.".to_string());
+        }
+
+        let min_bid = match &listing.bid {
+            Some(bid) => bid.amount + MIN_BID_INCREMENT,
+            None => MIN_BID_INCREMENT,
+        };
+
+        if amount < min_bid {
+            return Err(format!("Bid has to be at least {} gold.", min_bid));
+        }
+
+        if amount >= listing.price {
+            return Err("Bid meets the asking price, buy the item instead.".to_string());
+        }
+
+        if items.get_total_gold(hero_id) < amount {
+            return Err("Insufficient gold".to_string());
+        }
+
+        items.set_reason(ledger::REASON_MARKET);
+        Markets::refund_bid(listing, items);
+
+        items.transfer_gold(hero_id, listing.escrow_id, amount);
+        items.clear_reason();
+
+        listing.bid = Some(Bid {
+            player_id: player_id,
+            hero_id: hero_id,
+            amount: amount,
+        });
+
+        return Ok(());
+    }
+
+    // Expired listings go to the highest bidder or back to the seller,
+    // listings at a market that is no longer standing are called off
+    pub fn expire(
+        &mut self,
+        game_tick: &Res<GameTick>,
+        ids: &Ids,
+        is_standing: impl Fn(i32) -> bool,
+        items: &mut ResMut<Items>,
+    ) -> Vec<Listing> {
+        let (expired, active): (Vec<Listing>, Vec<Listing>) =
+            self.listings.drain(..).partition(|listing| {
+                listing.expires <= game_tick.0 || !is_standing(listing.market_id)
+            });
+
+        self.listings = active;
+
+        items.set_reason(ledger::REASON_MARKET);
+
+        for listing in expired.iter() {
+            let proceeds_id = Markets::proceeds_owner(listing, ids, &is_standing);
+
+            if !is_standing(listing.market_id) {
+                info!("Market listing {:?} called off, market is gone", listing.id);
+                Markets::refund_bid(listing, items);
+                Markets::return_item(listing, proceeds_id, items);
+            } else if let Some(bid) = &listing.bid {
+                info!("Market listing {:?} sold to bidder {:?}", listing.id, bid.player_id);
+                Markets::settle(listing, bid.hero_id, bid.amount, proceeds_id, items);
+            } else {
+                info!("Market listing {:?} expired unsold", listing.id);
+                Markets::return_item(listing, proceeds_id, items);
+            }
+        }
+
+        items.clear_reason();
+
+        return expired;
+    }
+
+    pub fn search(
+        &self,
+        market_id: i32,
+        name: &String,
+        items: &Items,
+    ) -> Vec<network::MarketListing> {
+        let name = name.to_lowercase();
+        let mut results = Vec::new();
+
+        for listing in self.listings.iter() {
+            if listing.market_id != market_id {
+                continue;
+            }
+
+            let Some(item) = items.find_by_id(listing.item_id) else {
+                continue;
+            };
+
+            if !item.name.to_lowercase().contains(&name) {
+                continue;
+            }
+
+            results.push(network::MarketListing {
+                id: listing.id,
+                seller: listing.seller,
+                item: Items::to_packet(item),
+                price: listing.price,
+                bid: listing.bid.as_ref().map(|bid| bid.amount),
+                expires: listing.expires,
+            });
+        }
+
+        return results;
+    }
+
+    // Item goes to the buyer, the fee to the market and the rest to the seller
+    fn settle(
+        listing: &Listing,
+        buyer_hero_id: i32,
+        amount: i32,
+        proceeds_id: Option<i32>,
+        items: &mut ResMut<Items>,
+    ) {
+        let fee = Markets::fee(amount);
+
+        items.transfer(listing.item_id, buyer_hero_id);
+        items.transfer_gold(listing.escrow_id, listing.market_id, fee);
+
+        match proceeds_id {
+            Some(proceeds_id) => items.transfer_gold(listing.escrow_id, proceeds_id, amount - fee),
+            None => {
+                items.set_reason(ledger::REASON_MARKET_ORPHANED);
+                items.remove_gold(listing.escrow_id, amount - fee);
+                items.set_reason(ledger::REASON_MARKET);
+            }
+        }
+    }
+
+    // The seller's structure, or the seller's hero once that structure is gone
+    fn proceeds_owner(
+        listing: &Listing,
+        ids: &Ids,
+        is_standing: impl Fn(i32) -> bool,
+    ) -> Option<i32> {
+        if is_standing(listing.return_id) {
+            return Some(listing.return_id);
+        }
+
+        let Some(hero_id) = ids.get_hero(listing.seller) else {
+            error!("Cannot find hero for seller {:?}", listing.seller);
+            return None;
+        };
+
+        return Some(hero_id);
+    }
+
+    pub fn to_save(&self, items: &Items) -> MarketsSave {
+        let mut escrow = Vec::new();
+
+        for listing in self.listings.iter() {
+            escrow.extend(items.get_by_owner(listing.escrow_id));
+        }
+
+        return MarketsSave {
+            listings: self.listings.clone(),
+            next_id: self.next_id,
+            escrow: escrow,
+        };
+    }
+
+    // The world is not saved, so markets, structures and heroes from the save
+    // are checked against the live ones and only escrow items are trusted.
+    // Listings that lost their market or seller are called off.
+    pub fn from_save(
+        save: MarketsSave,
+        ids: &mut Ids,
+        items: &mut Items,
+        is_standing: impl Fn(i32) -> bool,
+        is_market: impl Fn(i32) -> bool,
+    ) -> Markets {
+        let mut markets = Markets {
+            listings: Vec::new(),
+            next_id: save.next_id,
+        };
+
+        items.set_reason(ledger::REASON_MARKET);
+
+        for mut listing in save.listings {
+            let escrow_id = ids.new_obj_id();
+
+            for item in save.escrow.iter() {
+                if item.owner != listing.escrow_id {
+                    continue;
+                }
+
+                let restored_item = items.restore(item.clone(), escrow_id);
+
+                if item.id == listing.item_id {
+                    listing.item_id = restored_item.id;
+                }
+            }
+
+            listing.escrow_id = escrow_id;
+
+            // Only bids whose hero is still the bidder's hero are refunded
+            if let Some(bid) = &listing.bid {
+                if ids.get_hero(bid.player_id) != Some(bid.hero_id) {
+                    info!("Market listing {:?} dropped bid {:?}", listing.id, bid);
+                    listing.bid = None;
+                }
+            }
+
+            let seller_hero_id = ids.get_hero(listing.seller);
+
+            if !is_standing(listing.return_id) {
+                if let Some(hero_id) = seller_hero_id {
+                    listing.return_id = hero_id;
+                }
+            }
+
+            if is_market(listing.market_id) && seller_hero_id.is_some() {
+                markets.listings.push(listing);
+                continue;
+            }
+
+            info!("Market listing {:?} called off on load", listing.id);
+
+            Markets::refund_bid(&listing, items);
+            Markets::return_item(&listing, seller_hero_id, items);
+
+            // Gold of dropped bids has nobody to go back to
+            items.set_reason(ledger::REASON_MARKET_ORPHANED);
+
+            for item in items.get_by_owner(escrow_id) {
+                items.remove_item(item.id);
+            }
+
+            items.set_reason(ledger::REASON_MARKET);
+        }
+
+        // Kept listings may still hold the gold of a dropped bid
+        items.set_reason(ledger::REASON_MARKET_ORPHANED);
+
+        for listing in markets.listings.iter() {
+            let bid_amount = listing.bid.as_ref().map_or(0, |bid| bid.amount);
+            let excess = items.get_total_gold(listing.escrow_id) - bid_amount;
+
+            if excess > 0 {
+                items.remove_gold(listing.escrow_id, excess);
+            }
+        }
+
+        items.clear_reason();
+
+        return markets;
+    }
+
+    pub fn save(&self, items: &Items) {
+        let save = self.to_save(items);
+
+        let json = match serde_json::to_string_pretty(&save) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("Cannot serialize markets: {:?}", err);
+                return;
+            }
+        };
+
+        if let Err(err) = fs::write(MARKETS_FILE, json) {
+            error!("Cannot write {}: {:?}", MARKETS_FILE, err);
+        }
+    }
+
+    pub fn load(
+        ids: &mut Ids,
+        items: &mut Items,
+        is_standing: impl Fn(i32) -> bool,
+        is_market: impl Fn(i32) -> bool,
+    ) -> Option<Markets> {
+        let Ok(json) = fs::read_to_string(MARKETS_FILE) else {
+            info!("No saved markets found");
+            return None;
+        };
+
+        let save: MarketsSave = match serde_json::from_str(&json) {
+            Ok(save) => save,
+            Err(err) => {
+                error!("Cannot read {}: {:?}", MARKETS_FILE, err);
+                return None;
+            }
+        };
+
+        return Some(Markets::from_save(save, ids, items, is_standing, is_market));
+    }
+
+    fn refund_bid(listing: &Listing, items: &mut Items) {
+        if let Some(bid) = &listing.bid {
+            items.transfer_gold(listing.escrow_id, bid.hero_id, bid.amount);
+        }
+    }
+
+    // Items without anyone left to return them to are destroyed
+    fn return_item(listing: &Listing, owner: Option<i32>, items: &mut Items) {
+        match owner {
+            Some(owner) => items.transfer(listing.item_id, owner),
+            None => {
+                items.set_reason(ledger::REASON_MARKET_ORPHANED);
+                items.remove_item(listing.item_id);
+                items.set_reason(ledger::REASON_MARKET);
+            }
+        }
+    }
+}
+
+// Structures that have been despawned or destroyed no longer hold items
+pub fn is_standing(
+    structure_id: i32,
+    ids: &Ids,
+    structure_query: &Query<&State, With<ClassStructure>>,
+) -> bool {
+    return ids
+        .get_entity(structure_id)
+        .and_then(|entity| structure_query.get(entity).ok())
+        .is_some_and(|state| *state != State::Dead);
+}
+
+fn load_markets_system(
+    mut ids: ResMut<Ids>,
+    mut items: ResMut<Items>,
+    mut markets: ResMut<Markets>,
+    structure_query: Query<(&State, &Subclass), With<ClassStructure>>,
+) {
+    let standing_subclass = |ids: &Ids, structure_id: i32| {
+        return ids
+            .get_entity(structure_id)
+            .and_then(|entity| structure_query.get(entity).ok())
+            .filter(|(state, _subclass)| **state != State::Dead)
+            .map(|(_state, subclass)| subclass.0.clone());
+    };
+
+    let live_ids = ids.clone();
+
+    let loaded = Markets::load(
+        &mut ids,
+        &mut items,
+        |structure_id| standing_subclass(&live_ids, structure_id).is_some(),
+        |structure_id| {
+            standing_subclass(&live_ids, structure_id)
+                .is_some_and(|subclass| subclass == SUBCLASS_MARKET)
+        },
+    );
+
+    if let Some(loaded_markets) = loaded {
+        info!("Loaded {} market listings", loaded_markets.listings.len());
+        *markets = loaded_markets;
+    }
+}
+
+fn market_expiry_system(
+    game_tick: Res<GameTick>,
+    ids: Res<Ids>,
+    mut items: ResMut<Items>,
+    mut markets: ResMut<Markets>,
+    structure_query: Query<&State, With<ClassStructure>>,
+) {
+    if game_tick.0 % MARKET_UPDATE_INTERVAL != 0 {
+        return;
+    }
+
+    markets.expire(
+        &game_tick,
+        &ids,
+        |structure_id| is_standing(structure_id, &ids, &structure_query),
+        &mut items,
+    );
+}
+
+pub struct MarketPlugin;
+
+impl Plugin for MarketPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Markets>()
+            .add_systems(PostStartup, load_markets_system)
+            .add_systems(Update, market_expiry_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::templates::ItemTemplate;
+
+    fn load_items() -> Items {
+        let item_template_file =
+            fs::File::open("item_template.yaml").expect("Could not open file.");
+        let item_templates: Vec<ItemTemplate> =
+            serde_yaml::from_reader(item_template_file).expect("Could not read values.");
+
+        let mut items = Items::default();
+        items.set_templates(item_templates);
+
+        return items;
+    }
+
+    fn new_ids(obj: i32) -> Ids {
+        return Ids {
+            map_event: 0,
+            player_event: 0,
+            obj: obj,
+            item: 0,
+            player_hero_map: HashMap::new(),
+            obj_entity_map: HashMap::new(),
+            obj_player_map: HashMap::new(),
+        };
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let escrow_id = 50;
+
+        let mut items = load_items();
+        let listed_item = items.new(escrow_id, "Copper Ore".to_string(), 5);
+        items.new(escrow_id, "Gold Coins".to_string(), 20);
+
+        let markets = Markets {
+            listings: vec![Listing {
+                id: 1,
+                market_id: 10,
+                seller: 1,
+                return_id: 11,
+                escrow_id: escrow_id,
+                item_id: listed_item.id,
+                price: 30,
+                expires: 500,
+                bid: Some(Bid {
+                    player_id: 2,
+                    hero_id: 12,
+                    amount: 20,
+                }),
+            }],
+            next_id: 1,
+        };
+
+        let json = serde_json::to_string(&markets.to_save(&items)).unwrap();
+        let save: MarketsSave = serde_json::from_str(&json).unwrap();
+
+        // Ids and items have moved on since the save was written
+        let mut loaded_ids = new_ids(escrow_id);
+        loaded_ids.player_hero_map.insert(1, 21);
+        loaded_ids.player_hero_map.insert(2, 12);
+
+        let mut loaded_items = Items::default();
+        let other_item = loaded_items.new(1, "Copper Ore".to_string(), 1);
+
+        let loaded = Markets::from_save(
+            save,
+            &mut loaded_ids,
+            &mut loaded_items,
+            |structure_id| structure_id == 10,
+            |structure_id| structure_id == 10,
+        );
+
+        assert_eq!(loaded.next_id, 1);
+        assert_eq!(loaded.listings.len(), 1);
+
+        let listing = &loaded.listings[0];
+
+        assert_eq!(listing.price, 30);
+        assert_eq!(listing.return_id, 21);
+        assert_eq!(listing.bid.as_ref().map(|bid| bid.amount), Some(20));
+        assert_ne!(listing.escrow_id, escrow_id);
+        assert_eq!(loaded_items.get_by_owner(listing.escrow_id).len(), 2);
+
+        let restored_item = loaded_items.find_by_id(listing.item_id).unwrap();
+
+        assert_ne!(restored_item.id, other_item.id);
+        assert_eq!(restored_item.owner, listing.escrow_id);
+        assert_eq!(restored_item.name, "Copper Ore");
+        assert_eq!(restored_item.quantity, 5);
+        assert_eq!(loaded_items.get_by_owner(1).len(), 1);
+    }
+
+    fn orphaned_save() -> MarketsSave {
+        let escrow_id = 50;
+
+        let mut items = load_items();
+        let listed_item = items.new(escrow_id, "Copper Ore".to_string(), 5);
+        items.new(escrow_id, "Gold Coins".to_string(), 20);
+
+        let markets = Markets {
+            listings: vec![Listing {
+                id: 1,
+                market_id: 10,
+                seller: 1,
+                return_id: 11,
+                escrow_id: escrow_id,
+                item_id: listed_item.id,
+                price: 30,
+                expires: 500,
+                bid: Some(Bid {
+                    player_id: 2,
+                    hero_id: 12,
+                    amount: 20,
+                }),
+            }],
+            next_id: 1,
+        };
+
+        return markets.to_save(&items);
+    }
+
+    #[test]
+    fn test_load_without_market() {
+        let mut ids = new_ids(100);
+        ids.player_hero_map.insert(1, 21);
+        ids.player_hero_map.insert(2, 12);
+
+        let mut items = Items::default();
+
+        // The market and the seller's structure did not survive the restart
+        let loaded =
+            Markets::from_save(orphaned_save(), &mut ids, &mut items, |_| false, |_| false);
+
+        assert!(loaded.listings.is_empty());
+        assert_eq!(items.get_total_gold(12), 20);
+
+        let returned_items = items.get_by_owner(21);
+
+        assert_eq!(returned_items.len(), 1);
+        assert_eq!(returned_items[0].name, "Copper Ore");
+        assert_eq!(returned_items[0].quantity, 5);
+        assert!(items.ledger().check(&items.recount()).is_empty());
+    }
+
+    #[test]
+    fn test_load_without_heroes() {
+        let mut ids = new_ids(100);
+
+        // Bidder 2 now has a different hero than the one that placed the bid
+        ids.player_hero_map.insert(2, 13);
+
+        let mut items = Items::default();
+
+        let loaded = Markets::from_save(
+            orphaned_save(),
+            &mut ids,
+            &mut items,
+            |structure_id| structure_id == 10,
+            |structure_id| structure_id == 10,
+        );
+
+        assert!(loaded.listings.is_empty());
+        assert!(items.get_by_owner(12).is_empty());
+        assert!(items.get_by_owner(13).is_empty());
+        assert!(items.get_by_owner(101).is_empty());
+
+        let destroyed = items
+            .ledger()
+            .entries()
+            .iter()
+            .filter(|entry| entry.reason == ledger::REASON_MARKET_ORPHANED)
+            .count();
+
+        assert_eq!(destroyed, 2);
+        assert!(items.ledger().check(&items.recount()).is_empty());
+    }
+
+    #[test]
+    fn test_load_drops_stale_bid() {
+        let mut ids = new_ids(100);
+        ids.player_hero_map.insert(1, 21);
+
+        let mut items = Items::default();
+
+        let loaded = Markets::from_save(
+            orphaned_save(),
+            &mut ids,
+            &mut items,
+            |structure_id| structure_id == 10,
+            |structure_id| structure_id == 10,
+        );
+
+        assert_eq!(loaded.listings.len(), 1);
+
+        let listing = &loaded.listings[0];
+
+        assert!(listing.bid.is_none());
+        assert_eq!(listing.return_id, 21);
+        assert_eq!(items.get_total_gold(listing.escrow_id), 0);
+        assert_eq!(items.get_by_owner(listing.escrow_id).len(), 1);
+    }
+}
@@ -0,0 +1,233 @@
+use bevy::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::components::npc::{Docked, Transport};
+use crate::constants::GAME_HOUR;
+use crate::event::{MapEvents, VisibleEvent};
+use crate::game::{GameTick, Id, Merchant, Position, State, SubclassNPC};
+use crate::ids::Ids;
+use crate::item::{self, Items};
+use crate::ledger;
+use crate::obj::Obj;
+use crate::plugins::ai::thinker::{AiThinker, ThinkerContext};
+use crate::pricing::Pricing;
+use crate::templates::{PortTemplate, Templates};
+
+pub const MERCHANT_PLAYER_ID: i32 = 2000;
+
+// Ships stay in port this long to trade before sailing on
+pub const DOCK_DURATION: i32 = GAME_HOUR * 4;
+
+// Ships always carry enough gold to buy from players
+pub const MIN_MERCHANT_GOLD: i32 = 200;
+
+// Ship obj id sailing each route
+#[derive(Resource, Debug, Default)]
+pub struct MerchantShips {
+    pub routes: HashMap<String, i32>,
+}
+
+impl MerchantShips {
+    pub fn port_pos(port: &PortTemplate) -> Position {
+        return Position {
+            x: port.pos[0],
+            y: port.pos[1],
+        };
+    }
+
+    // Merchants on land always trade, ships only while docked
+    pub fn is_open(transport: Option<&Transport>, docked: Option<&Docked>) -> bool {
+        return transport.is_none() || docked.is_some();
+    }
+
+    // Sells the goods the port wants, restocks from the port's supplies and opens the trading window
+    pub fn dock(
+        ship_entity: Entity,
+        ship_id: &Id,
+        port: &PortTemplate,
+        commands: &mut Commands,
+        items: &mut ResMut<Items>,
+        templates: &Res<Templates>,
+        game_tick: &Res<GameTick>,
+        map_events: &mut ResMut<MapEvents>,
+    ) {
+        let mut earnings = 0;
+
+        items.set_reason(ledger::REASON_MERCHANT);
+
+        for demand in port.demands.clone().unwrap_or_default().iter() {
+            let unloaded: Vec<item::Item> = items
+                .get_by_owner(ship_id.0)
+                .into_iter()
+                .filter(|item| item.name == *demand)
+                .collect();
+
+            for item in unloaded.iter() {
+                earnings +=
+                    Pricing::base_value(&item.name, &templates.item_templates) * item.quantity;
+                items.remove_item(item.id);
+            }
+        }
+
+        if earnings > 0 {
+            debug!("Ship {:?} unloaded goods at {:?} for {:?} gold", ship_id, port.name, earnings);
+            items.create(ship_id.0, item::GOLD.to_string(), earnings);
+        }
+
+        for supply in port.supplies.iter() {
+            let stock = items.get_total_quantity(ship_id.0, &supply.item);
+
+            if stock < supply.quantity {
+                items.create(ship_id.0, supply.item.clone(), supply.quantity - stock);
+            }
+        }
+
+        let gold = items.get_total_gold(ship_id.0);
+
+        if gold < MIN_MERCHANT_GOLD {
+            items.create(ship_id.0, item::GOLD.to_string(), MIN_MERCHANT_GOLD - gold);
+        }
+
+        items.clear_reason();
+
+        commands.entity(ship_entity).insert(Docked {
+            port: port.name.clone(),
+            until: game_tick.0 + DOCK_DURATION,
+        });
+
+        Obj::add_sound_obj_event(
+            game_tick.0 + 1,
+            format!("A merchant ship has docked at {} and is open for trade.", port.name),
+            ship_id,
+            map_events,
+        );
+    }
+
+    pub fn depart(
+        ship_entity: Entity,
+        ship_id: &Id,
+        docked: &Docked,
+        next_port: Option<&PortTemplate>,
+        commands: &mut Commands,
+        game_tick: &Res<GameTick>,
+        map_events: &mut ResMut<MapEvents>,
+    ) {
+        commands.entity(ship_entity).remove::<Docked>();
+
+        let sound = match next_port {
+            Some(next_port) => format!(
+                "The merchant ship casts off from {}, bound for {}.",
+                docked.port, next_port.name
+            ),
+            None => format!("The merchant ship casts off from {}.", docked.port),
+        };
+
+        Obj::add_sound_obj_event(game_tick.0 + 1, sound, ship_id, map_events);
+    }
+}
+
+fn spawn_ship_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut ids: ResMut<Ids>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    mut map_events: ResMut<MapEvents>,
+    mut merchant_ships: ResMut<MerchantShips>,
+    ship_query: Query<(&Id, &State), With<Merchant>>,
+) {
+    // Every game hour
+    if game_tick.0 % GAME_HOUR != 0 {
+        return;
+    }
+
+    for route in templates.trade_templates.routes.iter() {
+        // Lost ships are replaced
+        if let Some(ship_id) = merchant_ships.routes.get(&route.name) {
+            if ship_query
+                .iter()
+                .any(|(id, state)| id.0 == *ship_id && !Obj::is_dead(state))
+            {
+                continue;
+            }
+        }
+
+        let ports: Vec<&PortTemplate> = route
+            .ports
+            .iter()
+            .filter_map(|port_name| templates.trade_templates.get_port(port_name))
+            .collect();
+
+        if ports.len() < 2 || ports.len() != route.ports.len() {
+            error!("Route {:?} needs at least two known ports", route.name);
+            continue;
+        }
+
+        let route_pos: Vec<Position> = ports
+            .iter()
+            .map(|port| MerchantShips::port_pos(port))
+            .collect();
+
+        let ship = Obj::create_nospawn(
+            &mut ids,
+            MERCHANT_PLAYER_ID,
+            route.ship.clone(),
+            route_pos[0],
+            State::None,
+            &templates,
+        );
+
+        let ship_id = ship.id.clone();
+
+        let ship_entity = commands
+            .spawn((
+                ship.clone(),
+                SubclassNPC,
+                Merchant,
+                Transport {
+                    route: route_pos,
+                    next_stop: 1,
+                    hauling: Vec::new(),
+                },
+                AiThinker::build_for_template(
+                    &ship.template.0,
+                    &ThinkerContext::default(),
+                    &templates,
+                ),
+            ))
+            .id();
+
+        ids.new_obj(ship_id.0, MERCHANT_PLAYER_ID, ship_entity);
+
+        map_events.new(
+            ship_id.0,
+            game_tick.0 + 1,
+            VisibleEvent::NewObjEvent { new_player: false },
+        );
+
+        info!("Merchant ship {:?} starts the {:?} route", ship_id, route.name);
+
+        MerchantShips::dock(
+            ship_entity,
+            &ship_id,
+            ports[0],
+            &mut commands,
+            &mut items,
+            &templates,
+            &game_tick,
+            &mut map_events,
+        );
+
+        merchant_ships.routes.insert(route.name.clone(), ship_id.0);
+    }
+}
+
+pub struct MerchantPlugin;
+
+impl Plugin for MerchantPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MerchantShips>()
+            .add_systems(Update, spawn_ship_system);
+    }
+}
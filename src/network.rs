@@ -4,6 +4,7 @@ use serde_with::skip_serializing_none;
 
 use std::{
     collections::HashMap,
+    sync::atomic::{AtomicI32, Ordering},
 };
 
 use lazy_static::lazy_static;
@@ -21,6 +22,7 @@ use crate::{
 };
 use crate::{
     game::{Client, Clients},
+    ledger::LedgerQuery,
     obj::HeroClassList,
     player::PlayerEvent,
 };
@@ -32,6 +34,13 @@ use glob::glob;
 
 //pub struct Network; // Is this needed?
 
+// Admin tools only listen locally
+pub const ADMIN_ADDR: &str = "127.0.0.1:9003";
+
+// Admin connections answer to their own client id in place of a player id, these count
+// down from -2 as -1 is the player id of clients that have not logged in, and are never reused
+static NEXT_ADMIN_CLIENT_ID: AtomicI32 = AtomicI32::new(-2);
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "cmd")]
 enum NetworkPacket {
@@ -127,6 +136,30 @@ enum NetworkPacket {
     Assign { sourceid: i32, targetid: i32 },
     #[serde(rename = "equip")]
     Equip { item: i32, status: bool },
+    #[serde(rename = "repair")]
+    Repair { item: i32 },
+    #[serde(rename = "loot")]
+    Loot { sourceid: i32, targetid: i32 },
+    #[serde(rename = "skin")]
+    Skin { sourceid: i32, targetid: i32 },
+    #[serde(rename = "butcher")]
+    Butcher { sourceid: i32, targetid: i32 },
+    #[serde(rename = "info_schedule")]
+    InfoSchedule { sourceid: i32 },
+    #[serde(rename = "set_schedule")]
+    SetSchedule { sourceid: i32, schedule: Vec<ScheduleBlock> },
+    #[serde(rename = "info_order_queue")]
+    InfoOrderQueue { sourceid: i32 },
+    #[serde(rename = "queue_order")]
+    QueueOrder { sourceid: i32, order: QueuedOrder },
+    #[serde(rename = "move_queued_order")]
+    MoveQueuedOrder { sourceid: i32, orderid: i32, index: i32 },
+    #[serde(rename = "cancel_queued_order")]
+    CancelQueuedOrder { sourceid: i32, orderid: i32 },
+    #[serde(rename = "train")]
+    Train { sourceid: i32, targetid: i32, structureid: i32, skill: String },
+    #[serde(rename = "set_stance")]
+    SetStance { sourceid: i32, stance: String },
     #[serde(rename = "recipe_list")]
     RecipeList { structureid: i32 },
     #[serde(rename = "use")]
@@ -148,7 +181,35 @@ enum NetworkPacket {
     #[serde(rename = "buy_item")]
     BuyItem {itemid: i32, quantity: i32},
     #[serde(rename = "sell_item")]
-    SellItem {itemid: i32, targetid: i32, quantity: i32}
+    SellItem {itemid: i32, targetid: i32, quantity: i32},
+    #[serde(rename = "info_price")]
+    InfoPrice {itemid: i32, merchantid: i32, quantity: i32},
+    #[serde(rename = "trade_request")]
+    TradeRequest {targetid: i32},
+    #[serde(rename = "trade_offer")]
+    TradeOffer {tradeid: i32, itemid: i32, quantity: i32},
+    #[serde(rename = "trade_withdraw")]
+    TradeWithdraw {tradeid: i32, itemid: i32},
+    #[serde(rename = "trade_accept")]
+    TradeAccept {tradeid: i32},
+    #[serde(rename = "trade_confirm")]
+    TradeConfirm {tradeid: i32},
+    #[serde(rename = "trade_cancel")]
+    TradeCancel {tradeid: i32},
+    #[serde(rename = "market_list")]
+    MarketList {marketid: i32, itemid: i32, quantity: i32, price: i32, duration: i32, returnid: i32},
+    #[serde(rename = "market_search")]
+    MarketSearch {marketid: i32, name: String},
+    #[serde(rename = "market_buy")]
+    MarketBuy {listingid: i32},
+    #[serde(rename = "market_bid")]
+    MarketBid {listingid: i32, amount: i32},
+    #[serde(rename = "info_tax")]
+    InfoTax {},
+    #[serde(rename = "tax_negotiate")]
+    TaxNegotiate {collectorid: i32},
+    #[serde(rename = "tax_bribe")]
+    TaxBribe {collectorid: i32, amount: i32}
 }
 
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
@@ -156,6 +217,19 @@ pub struct StructureList {
     pub result: Vec<Structure>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "cmd")]
+enum AdminPacket {
+    #[serde(rename = "inspect_ai")]
+    InspectAi { id: i32 },
+    #[serde(rename = "ledger_item")]
+    LedgerItem { id: i32 },
+    #[serde(rename = "ledger_player")]
+    LedgerPlayer { id: i32 },
+    #[serde(rename = "ledger_since")]
+    LedgerSince { tick: i32 },
+}
+
 #[skip_serializing_none]
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 #[serde(tag = "packet")]
@@ -264,9 +338,19 @@ pub enum ResponsePacket {
         activity: Option<String>,
         shelter: Option<String>,
         morale: Option<String>,
+        stance: Option<String>,
+        traits: Option<Vec<String>>,
+        likes: Option<Vec<String>>,
+        dislikes: Option<Vec<String>>,
+        backstory: Option<String>,
+        friends: Option<Vec<i32>>,
+        rivals: Option<Vec<i32>>,
+        partner: Option<i32>,
+        child: bool,
         order: Option<String>,
         capacity: Option<i32>,
         total_weight: Option<i32>,
+        contract: Option<ContractData>,
     },
     #[serde(rename = "info_structure")]
     InfoStructure {
@@ -352,6 +436,8 @@ pub enum ResponsePacket {
         id: i32,
         cap: i32,
         tw: i32,
+        load: i32,
+        enc: String,
         items: Vec<Item>,
     },
     #[serde(rename = "info_item")]
@@ -365,6 +451,8 @@ pub enum ResponsePacket {
         image: String,
         weight: f32,
         equipped: bool,
+        quality: i32,
+        grade: String,
         price: Option<i32>,
         attrs: Option<HashMap<item::AttrKey, item::AttrVal>>
     },
@@ -391,11 +479,22 @@ pub enum ResponsePacket {
     InfoActivityUpdate {
         id: i32,
         activity: String,
+        progress: Option<String>,
     },
     #[serde(rename = "info_hire")]
     InfoHire {
         data: Vec<HireData>
     },
+    #[serde(rename = "info_schedule")]
+    InfoSchedule {
+        id: i32,
+        schedule: Vec<ScheduleBlock>,
+    },
+    #[serde(rename = "info_order_queue")]
+    InfoOrderQueue {
+        id: i32,
+        orders: Vec<QueuedOrder>,
+    },
     #[serde(rename = "item_transfer")]
     ItemTransfer {
         result: String,
@@ -497,6 +596,28 @@ pub enum ResponsePacket {
         xp_type: String,
         xp: i32,
     },
+    #[serde(rename = "inspect_ai")]
+    InspectAi {
+        id: i32,
+        thinker: String,
+        scores: Vec<AiScore>,
+        action: Option<String>,
+        action_state: Option<String>,
+        history: Vec<AiDecision>,
+    },
+    #[serde(rename = "ledger")]
+    Ledger {
+        entries: Vec<LedgerEntry>,
+        oldest: Option<i32>,
+    },
+    #[serde(rename = "skill_milestone")]
+    SkillMilestone {
+        id: i32,
+        name: String,
+        skill: String,
+        level: i32,
+        title: String,
+    },
     #[serde(rename = "new_items")]
     NewItems {
         action: String,
@@ -517,6 +638,48 @@ pub enum ResponsePacket {
         targetid: i32,
         targetitems: Inventory,
     },
+    #[serde(rename = "trade_update")]
+    TradeUpdate {
+        tradeid: i32,
+        phase: String,
+        sides: Vec<TradeSide>,
+    },
+    #[serde(rename = "trade_closed")]
+    TradeClosed {
+        tradeid: i32,
+        completed: bool,
+        reason: String,
+    },
+    #[serde(rename = "market_listings")]
+    MarketListings {
+        marketid: i32,
+        listings: Vec<MarketListing>,
+    },
+    #[serde(rename = "tax_status")]
+    TaxStatus {
+        empire: String,
+        collectorid: Option<i32>,
+        tier: String,
+        due: i32,
+        debt: i32,
+        loyalty: i32,
+        protected: bool,
+    },
+    #[serde(rename = "info_price")]
+    InfoPrice {
+        itemid: i32,
+        merchantid: i32,
+        action: String,
+        quantity: i32,
+        base_value: i32,
+        quality_mod: f32,
+        property_mod: f32,
+        supply_mod: f32,
+        margin: f32,
+        discount: f32,
+        unit_price: i32,
+        total: i32,
+    },
     Ok,
     None,
     Pong,
@@ -609,6 +772,25 @@ pub struct MapWeather {
     pub weather: String
 }
 
+#[skip_serializing_none]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct MarketListing {
+    pub id: i32,
+    pub seller: i32,
+    pub item: Item,
+    pub price: i32,
+    pub bid: Option<i32>,
+    pub expires: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct TradeSide {
+    pub heroid: i32,
+    pub accepted: bool,
+    pub confirmed: bool,
+    pub items: Vec<Item>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Inventory {
     pub id: i32,
@@ -629,6 +811,7 @@ pub struct Item {
     pub image: String,
     pub weight: f32,
     pub equipped: bool,
+    pub quality: i32,
     pub attrs: Option<HashMap<item::AttrKey, item::AttrVal>>,
 }
 
@@ -711,6 +894,7 @@ pub struct HireData {
     pub name: String,
     pub image: String,
     pub wage: i32,
+    pub term: i32,
     pub creativity: i32,
     pub dexterity: i32,
     pub endurance: i32,
@@ -719,7 +903,69 @@ pub struct HireData {
     pub spirit: i32,
     pub strength: i32,
     pub toughness: i32,
-    pub skills: HashMap<String, i32>
+    pub skills: HashMap<String, i32>,
+    pub traits: Vec<String>,
+    pub likes: Vec<String>,
+    pub dislikes: Vec<String>,
+    pub backstory: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ContractData {
+    pub wage: i32,
+    pub term: i32,
+    pub remaining: i32,
+    pub owed: i32,
+    pub loyalty: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ScheduleBlock {
+    pub start: i32,
+    pub end: i32,
+    pub activity: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct LedgerEntry {
+    pub tick: i32,
+    pub op: String,
+    pub item_id: i32,
+    pub related_id: Option<i32>,
+    pub name: String,
+    pub quantity: i32,
+    pub source: Option<i32>,
+    pub destination: Option<i32>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct AiScore {
+    pub name: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct AiDecision {
+    pub tick: i32,
+    pub action: String,
+    pub scores: Vec<AiScore>,
+}
+
+// Id and completed are only set by the server
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct QueuedOrder {
+    #[serde(default)]
+    pub id: i32,
+    pub order: String,
+    pub structureid: Option<i32>,
+    pub target: Option<String>,
+    pub stop: String,
+    pub item: Option<String>,
+    pub quantity: Option<i32>,
+    pub ownerid: Option<i32>,
+    #[serde(default)]
+    pub completed: i32,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -1094,6 +1340,42 @@ async fn handle_connection(
                                             NetworkPacket::Equip{item, status} => {
                                                 handle_equip(player_id, item, status, client_to_game_sender.clone())
                                             }
+                                            NetworkPacket::Repair{item} => {
+                                                handle_repair(player_id, item, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::Loot{sourceid, targetid} => {
+                                                handle_loot(player_id, sourceid, targetid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::Skin{sourceid, targetid} => {
+                                                handle_skin(player_id, sourceid, targetid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::Butcher{sourceid, targetid} => {
+                                                handle_butcher(player_id, sourceid, targetid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::InfoSchedule{sourceid} => {
+                                                handle_info_schedule(player_id, sourceid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::SetSchedule{sourceid, schedule} => {
+                                                handle_set_schedule(player_id, sourceid, schedule, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::InfoOrderQueue{sourceid} => {
+                                                handle_info_order_queue(player_id, sourceid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::QueueOrder{sourceid, order} => {
+                                                handle_queue_order(player_id, sourceid, order, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::MoveQueuedOrder{sourceid, orderid, index} => {
+                                                handle_move_queued_order(player_id, sourceid, orderid, index, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::CancelQueuedOrder{sourceid, orderid} => {
+                                                handle_cancel_queued_order(player_id, sourceid, orderid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::Train{sourceid, targetid, structureid, skill} => {
+                                                handle_train(player_id, sourceid, targetid, structureid, skill, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::SetStance{sourceid, stance} => {
+                                                handle_set_stance(player_id, sourceid, stance, client_to_game_sender.clone())
+                                            }
                                             NetworkPacket::RecipeList{structureid} => {
                                                 handle_recipe_list(player_id, structureid, client_to_game_sender.clone())
                                             }
@@ -1149,6 +1431,48 @@ async fn handle_connection(
                                             }                                                 
                                             NetworkPacket::SellItem{itemid, targetid, quantity} => {
                                                 handle_sell_item(player_id, itemid, targetid, quantity, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::InfoPrice{itemid, merchantid, quantity} => {
+                                                handle_info_price(player_id, itemid, merchantid, quantity, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::TradeRequest{targetid} => {
+                                                handle_trade(PlayerEvent::TradeRequest{player_id: player_id, target_id: targetid}, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::TradeOffer{tradeid, itemid, quantity} => {
+                                                handle_trade(PlayerEvent::TradeOffer{player_id: player_id, trade_id: tradeid, item_id: itemid, quantity: quantity}, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::TradeWithdraw{tradeid, itemid} => {
+                                                handle_trade(PlayerEvent::TradeWithdraw{player_id: player_id, trade_id: tradeid, item_id: itemid}, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::TradeAccept{tradeid} => {
+                                                handle_trade(PlayerEvent::TradeAccept{player_id: player_id, trade_id: tradeid}, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::TradeConfirm{tradeid} => {
+                                                handle_trade(PlayerEvent::TradeConfirm{player_id: player_id, trade_id: tradeid}, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::TradeCancel{tradeid} => {
+                                                handle_trade(PlayerEvent::TradeCancel{player_id: player_id, trade_id: tradeid}, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::MarketList{marketid, itemid, quantity, price, duration, returnid} => {
+                                                handle_market_list(player_id, marketid, itemid, quantity, price, duration, returnid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::MarketSearch{marketid, name} => {
+                                                handle_market_search(player_id, marketid, name, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::MarketBuy{listingid} => {
+                                                handle_market_buy(player_id, listingid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::MarketBid{listingid, amount} => {
+                                                handle_market_bid(player_id, listingid, amount, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::InfoTax{} => {
+                                                handle_info_tax(player_id, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::TaxNegotiate{collectorid} => {
+                                                handle_tax_negotiate(player_id, collectorid, client_to_game_sender.clone())
+                                            }
+                                            NetworkPacket::TaxBribe{collectorid, amount} => {
+                                                handle_tax_bribe(player_id, collectorid, amount, client_to_game_sender.clone())
                                             }                                            
                                             _ => ResponsePacket::Ok
                                         }
@@ -1191,6 +1515,142 @@ async fn handle_connection(
     Ok(())
 }
 
+pub async fn admin_setup(client_to_game_sender: CBSender<PlayerEvent>, clients: Clients) {
+    let listener = TcpListener::bind(ADMIN_ADDR).await.expect("Can't listen");
+    println!("Admin listening on: {}", ADMIN_ADDR);
+
+    while let Ok((stream, peer)) = listener.accept().await {
+        println!("Admin peer address: {}", peer);
+
+        tokio::spawn(accept_admin_connection(
+            peer,
+            stream,
+            client_to_game_sender.clone(),
+            clients.clone(),
+        ));
+    }
+}
+
+async fn accept_admin_connection(
+    peer: SocketAddr,
+    stream: TcpStream,
+    client_to_game_sender: CBSender<PlayerEvent>,
+    clients: Clients,
+) {
+    if let Err(e) = handle_admin_connection(peer, stream, client_to_game_sender, clients).await {
+        match e {
+            Error::ConnectionClosed | Error::Protocol(_) | Error::Utf8 => {
+                println!("Admin connection closed")
+            }
+            err => println!("Error processing admin connection: {}", err),
+        }
+    }
+}
+
+async fn handle_admin_connection(
+    peer: SocketAddr,
+    stream: TcpStream,
+    client_to_game_sender: CBSender<PlayerEvent>,
+    clients: Clients,
+) -> Result<()> {
+    println!("New admin connection: {}", peer);
+    let ws_stream = accept_async(stream).await.expect("Failed to accept");
+
+    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+
+    let (game_to_client_sender, mut game_to_client_receiver) = tokio::sync::mpsc::channel(100);
+
+    // Negative client ids keep admin connections apart from players
+    let client_id = NEXT_ADMIN_CLIENT_ID.fetch_sub(1, Ordering::Relaxed);
+
+    clients.lock().unwrap().insert(
+        client_id,
+        Client {
+            id: client_id,
+            player_id: client_id,
+            sender: game_to_client_sender,
+        },
+    );
+
+    loop {
+        tokio::select! {
+            msg = ws_receiver.next() => {
+                match msg {
+                    Some(msg) => {
+                        let msg = msg?;
+                        if msg.is_text() || msg.is_binary() {
+                            let res_packet: ResponsePacket = match serde_json::from_str(msg.to_text().unwrap()) {
+                                Ok(AdminPacket::InspectAi{id}) => {
+                                    handle_inspect_ai(id, client_id, client_to_game_sender.clone())
+                                }
+                                Ok(AdminPacket::LedgerItem{id}) => {
+                                    handle_ledger_query(LedgerQuery::Item(id), client_id, client_to_game_sender.clone())
+                                }
+                                Ok(AdminPacket::LedgerPlayer{id}) => {
+                                    handle_ledger_query(LedgerQuery::Player(id), client_id, client_to_game_sender.clone())
+                                }
+                                Ok(AdminPacket::LedgerSince{tick}) => {
+                                    handle_ledger_query(LedgerQuery::Since(tick), client_id, client_to_game_sender.clone())
+                                }
+                                Err(_) => ResponsePacket::Error{errmsg: "Unknown packet".to_owned()}
+                            };
+
+                            if res_packet != ResponsePacket::None {
+                                let res = serde_json::to_string(&res_packet).unwrap();
+                                ws_sender.send(Message::Text(res)).await?;
+                            }
+                        } else if msg.is_close() {
+                            handle_disconnect(client_id, clients.clone());
+                            break;
+                        }
+                    }
+                    None => {
+                        handle_disconnect(client_id, clients.clone());
+                        break;
+                    }
+                }
+            }
+            game_msg = game_to_client_receiver.recv() => {
+                let game_msg = game_msg.unwrap();
+                ws_sender.send(Message::Text(game_msg)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_inspect_ai(
+    id: i32,
+    admin_id: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::InspectAi {
+            player_id: admin_id,
+            obj_id: id,
+        })
+        .expect("Could not send message");
+
+    // Response will come from the ai inspector
+    ResponsePacket::None
+}
+
+fn handle_ledger_query(
+    query: LedgerQuery,
+    admin_id: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::LedgerQuery {
+            player_id: admin_id,
+            query: query,
+        })
+        .expect("Could not send message");
+
+    // Response will come from the ledger
+    ResponsePacket::None
+}
+
 fn handle_login(
     username: String,
     password: String,
@@ -1892,6 +2352,222 @@ fn handle_equip(
     ResponsePacket::Ok
 }
 
+fn handle_repair(
+    player_id: i32,
+    item: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::Repair {
+            player_id: player_id,
+            item_id: item,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_loot(
+    player_id: i32,
+    sourceid: i32,
+    targetid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::Loot {
+            player_id: player_id,
+            source_id: sourceid,
+            corpse_id: targetid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_skin(
+    player_id: i32,
+    sourceid: i32,
+    targetid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::Skin {
+            player_id: player_id,
+            source_id: sourceid,
+            corpse_id: targetid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_butcher(
+    player_id: i32,
+    sourceid: i32,
+    targetid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::Butcher {
+            player_id: player_id,
+            source_id: sourceid,
+            corpse_id: targetid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_info_schedule(
+    player_id: i32,
+    sourceid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::InfoSchedule {
+            player_id: player_id,
+            villager_id: sourceid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_set_schedule(
+    player_id: i32,
+    sourceid: i32,
+    schedule: Vec<ScheduleBlock>,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::SetSchedule {
+            player_id: player_id,
+            villager_id: sourceid,
+            schedule: schedule,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_info_order_queue(
+    player_id: i32,
+    sourceid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::InfoOrderQueue {
+            player_id: player_id,
+            villager_id: sourceid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_queue_order(
+    player_id: i32,
+    sourceid: i32,
+    order: QueuedOrder,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::QueueOrder {
+            player_id: player_id,
+            villager_id: sourceid,
+            order: order,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_move_queued_order(
+    player_id: i32,
+    sourceid: i32,
+    orderid: i32,
+    index: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::MoveQueuedOrder {
+            player_id: player_id,
+            villager_id: sourceid,
+            order_id: orderid,
+            index: index,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_cancel_queued_order(
+    player_id: i32,
+    sourceid: i32,
+    orderid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::CancelQueuedOrder {
+            player_id: player_id,
+            villager_id: sourceid,
+            order_id: orderid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_train(
+    player_id: i32,
+    sourceid: i32,
+    targetid: i32,
+    structureid: i32,
+    skill: String,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::Train {
+            player_id: player_id,
+            teacher_id: sourceid,
+            student_id: targetid,
+            structure_id: structureid,
+            skill_name: skill,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
+fn handle_set_stance(
+    player_id: i32,
+    sourceid: i32,
+    stance: String,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::SetStance {
+            player_id: player_id,
+            villager_id: sourceid,
+            stance: stance,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::Ok
+}
+
 fn handle_recipe_list(
     player_id: i32,
     structureid: i32,
@@ -2175,3 +2851,156 @@ fn handle_sell_item(
     // Response will come from game.rs
     ResponsePacket::Ok
 }
+
+fn handle_info_price(
+    player_id: i32,
+    itemid: i32,
+    merchantid: i32,
+    quantity: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::InfoPrice {
+            player_id: player_id,
+            item_id: itemid,
+            merchant_id: merchantid,
+            quantity: quantity,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
+
+// Trade events all carry their own player id, responses come from the trade system
+fn handle_trade(event: PlayerEvent, client_to_game_sender: CBSender<PlayerEvent>) -> ResponsePacket {
+    client_to_game_sender
+        .send(event)
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
+
+fn handle_market_list(
+    player_id: i32,
+    marketid: i32,
+    itemid: i32,
+    quantity: i32,
+    price: i32,
+    duration: i32,
+    returnid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::MarketList {
+            player_id: player_id,
+            market_id: marketid,
+            item_id: itemid,
+            quantity: quantity,
+            price: price,
+            duration: duration,
+            return_id: returnid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
+
+fn handle_market_search(
+    player_id: i32,
+    marketid: i32,
+    name: String,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::MarketSearch {
+            player_id: player_id,
+            market_id: marketid,
+            name: name,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
+
+fn handle_market_buy(
+    player_id: i32,
+    listingid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::MarketBuy {
+            player_id: player_id,
+            listing_id: listingid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
+
+fn handle_market_bid(
+    player_id: i32,
+    listingid: i32,
+    amount: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::MarketBid {
+            player_id: player_id,
+            listing_id: listingid,
+            amount: amount,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
+
+fn handle_info_tax(player_id: i32, client_to_game_sender: CBSender<PlayerEvent>) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::InfoTax {
+            player_id: player_id,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
+
+fn handle_tax_negotiate(
+    player_id: i32,
+    collectorid: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::TaxNegotiate {
+            player_id: player_id,
+            collector_id: collectorid,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
+
+fn handle_tax_bribe(
+    player_id: i32,
+    collectorid: i32,
+    amount: i32,
+    client_to_game_sender: CBSender<PlayerEvent>,
+) -> ResponsePacket {
+    client_to_game_sender
+        .send(PlayerEvent::TaxBribe {
+            player_id: player_id,
+            collector_id: collectorid,
+            amount: amount,
+        })
+        .expect("Could not send message");
+
+    // Response will come from game.rs
+    ResponsePacket::None
+}
@@ -8,30 +8,46 @@ use std::fs;
 use std::collections::HashMap;
 
 use crate::components::npc::{
-    Destination, Idle, MerchantScorer, MoveToPos, SetDestination, Transport,
+    Destination, Docked, Idle, MerchantScorer, MoveToPos, SetDestination, TaxCollector,
+    ThreatTable, Transport, VisibleTarget,
 };
 use crate::components::villager::{
-    Drink, DrinkDistanceScorer, DrowsyScorer, Eat, EnemyDistanceScorer, FindDrink, FindDrinkScorer, FindFood, FindFoodScorer, FindShelter, FindShelterScorer, Flee, FoodDistanceScorer, GoodMorale, HasDrinkScorer, HasFoodScorer, Heat, Hunger, HungryScorer, IdleScorer, Morale, MoveToFoodSource, MoveToSleepPos, MoveToWaterSource, NearShelterScorer, ProcessOrder, ShelterDistanceScorer, Sleep, Thirst, ThirstyScorer, Tired, TransferDrink, TransferDrinkScorer, TransferFood, TransferFoodScorer
+    CombatStance, Heat, Hunger, Morale, OrderQueue, OrderStop, Personality, Schedule,
+    ScheduleActivity, ScheduleBlock, Thirst, Tired,
 };
 use crate::event::{GameEvent, GameEventType, GameEvents, MapEvents, VisibleEvent};
 use crate::ids::Ids;
 
-use crate::combat::{Combat, CombatQuery};
+use crate::combat::{AttackType, Combat, CombatQuery, Combatant};
+use crate::contract::{self, Contract};
+use crate::corpse::{self, Corpse, Harvested};
 use crate::effect::Effects;
 use crate::experiment::{self, Experiment, ExperimentState, Experiments};
 use crate::game::{
     is_pos_empty, BaseAttrs, Class, ClassStructure, Clients, GameTick, Id, MapObjQuery, Merchant, Misc, Name, NetworkReceiver, Order, PlayerId, Position, State, Stats, StructureAttrs, Subclass, SubclassHero, SubclassVillager, Template, Viewshed, VillagerAttrs
 };
 use crate::item::{self, Item, Items};
+use crate::empire::{Empire, Vassals};
+use crate::encumbrance::{self, Encumbrance};
+use crate::ledger::{self, LedgerQuery};
 use crate::map::Map;
+use crate::market::{self, Markets};
 use crate::network::{self, send_to_client, ResponsePacket, StatsData, StructureList};
 use crate::obj::{self, Obj};
+use crate::plugins::ai::npc;
+use crate::plugins::ai::thinker::{AiThinker, ThinkerContext};
+use crate::merchant::MerchantShips;
+use crate::pricing::{Pricing, Reputations, Trade};
+use crate::quality::Quality;
 use crate::recipe::Recipes;
 use crate::resource::{Resource, Resources};
-use crate::skill::{Skill, Skills};
+use crate::skill::{self, Skill, Skills};
+use crate::social::{Child, Household, Relationships};
+use crate::stamina::{self, Stamina};
 use crate::structure::{self, Plans, Structure};
 use crate::templates::{ObjTemplate, ResReq, Templates};
 use crate::terrain_feature::{TerrainFeature, TerrainFeatures};
+use crate::trade::{self, TradeParty, TradeSession, Trades};
 use crate::villager::{self, Villager};
 
 #[derive(Resource, Deref, DerefMut)]
@@ -231,6 +247,74 @@ pub enum PlayerEvent {
         item_id: i32,
         status: bool,
     },
+    Repair {
+        player_id: i32,
+        item_id: i32,
+    },
+    Loot {
+        player_id: i32,
+        source_id: i32,
+        corpse_id: i32,
+    },
+    Skin {
+        player_id: i32,
+        source_id: i32,
+        corpse_id: i32,
+    },
+    Butcher {
+        player_id: i32,
+        source_id: i32,
+        corpse_id: i32,
+    },
+    InfoSchedule {
+        player_id: i32,
+        villager_id: i32,
+    },
+    SetSchedule {
+        player_id: i32,
+        villager_id: i32,
+        schedule: Vec<network::ScheduleBlock>,
+    },
+    InfoOrderQueue {
+        player_id: i32,
+        villager_id: i32,
+    },
+    QueueOrder {
+        player_id: i32,
+        villager_id: i32,
+        order: network::QueuedOrder,
+    },
+    MoveQueuedOrder {
+        player_id: i32,
+        villager_id: i32,
+        order_id: i32,
+        index: i32,
+    },
+    CancelQueuedOrder {
+        player_id: i32,
+        villager_id: i32,
+        order_id: i32,
+    },
+    Train {
+        player_id: i32,
+        teacher_id: i32,
+        student_id: i32,
+        structure_id: i32,
+        skill_name: String,
+    },
+    SetStance {
+        player_id: i32,
+        villager_id: i32,
+        stance: String,
+    },
+    InspectAi {
+        player_id: i32,
+        obj_id: i32,
+    },
+    LedgerQuery {
+        player_id: i32,
+        query: LedgerQuery,
+    },
     RecipeList {
         player_id: i32,
         structure_id: i32,
@@ -276,6 +360,74 @@ pub enum PlayerEvent {
         target_id: i32,
         quantity: i32,
     },
+    InfoPrice {
+        player_id: i32,
+        item_id: i32,
+        merchant_id: i32,
+        quantity: i32,
+    },
+    TradeRequest {
+        player_id: i32,
+        target_id: i32,
+    },
+    TradeOffer {
+        player_id: i32,
+        trade_id: i32,
+        item_id: i32,
+        quantity: i32,
+    },
+    TradeWithdraw {
+        player_id: i32,
+        trade_id: i32,
+        item_id: i32,
+    },
+    TradeAccept {
+        player_id: i32,
+        trade_id: i32,
+    },
+    TradeConfirm {
+        player_id: i32,
+        trade_id: i32,
+    },
+    TradeCancel {
+        player_id: i32,
+        trade_id: i32,
+    },
+    MarketList {
+        player_id: i32,
+        market_id: i32,
+        item_id: i32,
+        quantity: i32,
+        price: i32,
+        duration: i32,
+        return_id: i32,
+    },
+    MarketSearch {
+        player_id: i32,
+        market_id: i32,
+        name: String,
+    },
+    MarketBuy {
+        player_id: i32,
+        listing_id: i32,
+    },
+    MarketBid {
+        player_id: i32,
+        listing_id: i32,
+        amount: i32,
+    },
+    InfoTax {
+        player_id: i32,
+    },
+    TaxNegotiate {
+        player_id: i32,
+        collector_id: i32,
+    },
+    TaxBribe {
+        player_id: i32,
+        collector_id: i32,
+        amount: i32,
+    },
 }
 
 #[derive(Debug, Resource, Deref, DerefMut)]
@@ -437,6 +589,23 @@ impl Plugin for PlayerPlugin {
                 buy_sell_system,
             ),
         )
+        .add_systems(
+            Update,
+            (
+                repair_system,
+                loot_system,
+                harvest_corpse_system,
+                schedule_system,
+                order_queue_system,
+                train_system,
+                stance_system,
+                info_price_system,
+                trade_system,
+                market_system,
+                tax_system,
+            ),
+        )
+        .init_resource::<Reputations>()
         .insert_resource(player_events)
         .insert_resource(active_infos)
         .insert_resource(start_locations);
@@ -551,8 +720,11 @@ fn move_system(
     mut map_events: ResMut<MapEvents>,
     mut game_events: ResMut<GameEvents>,
     map: Res<Map>,
+    items: Res<Items>,
+    templates: Res<Templates>,
     hero_query: Query<CoreQuery, With<SubclassHero>>,
     query: Query<MapObjQuery>,
+    attrs_query: Query<&BaseAttrs>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
@@ -601,6 +773,22 @@ fn move_system(
                     continue;
                 }
 
+                let hero_attrs = attrs_query.get(hero_entity).ok();
+                let capacity =
+                    Encumbrance::capacity(&hero.template.0, hero_attrs, &templates.obj_templates);
+                let total_weight = items.get_total_weight(hero_id);
+
+                if total_weight > Encumbrance::hard_limit(capacity, hero_attrs) {
+                    let error = ResponsePacket::Error {
+                        errmsg: "You are carrying too much to move.".to_owned(),
+                    };
+                    send_to_client(*player_id, error, &clients);
+                    continue;
+                }
+
+                let load = Encumbrance::load(total_weight, capacity);
+                let move_ticks = Encumbrance::move_ticks(encumbrance::HERO_MOVE_TICKS, load);
+
                 // Remove events that are cancellable
                 let mut events_to_remove = Vec::new();
 
@@ -650,7 +838,7 @@ fn move_system(
 
                 map_events.new(
                     hero.id.0,
-                    game_tick.0 + 12, // in the future
+                    game_tick.0 + move_ticks, // in the future
                     move_event,
                 );
             }
@@ -675,6 +863,7 @@ fn attack_system(
     templates: Res<Templates>,
     map: Res<Map>,
     mut query: Query<CombatQuery>,
+    mut threat_query: Query<(&mut ThreatTable, &mut VisibleTarget)>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
@@ -698,6 +887,23 @@ fn attack_system(
                     continue;
                 };
 
+                // Other living units used for flanking and surrounded checks
+                let mut combatants = Vec::new();
+
+                for unit in query.iter() {
+                    if unit.entity != attacker_entity
+                        && unit.entity != target_entity
+                        && !Obj::is_dead(&unit.state)
+                    {
+                        combatants.push(Combatant::new(
+                            unit.player_id.0,
+                            *unit.pos,
+                            unit.in_combat,
+                            game_tick.0,
+                        ));
+                    }
+                }
+
                 let entities = [attacker_entity, target_entity];
 
                 let Ok([mut attacker, mut target]) = query.get_many_mut(entities) else {
@@ -743,11 +949,18 @@ fn attack_system(
                     continue;
                 }
 
+                let attack_type_enum = Combat::attack_type_to_enum(attack_type.to_string());
+                let stamina_cost = Stamina::attack_cost(
+                    attack_type_enum.clone(),
+                    &items.get_equipped_weapons(attacker.id.0),
+                );
+
                 // Calculate and process damage
                 let (damage, combo, skill_updated) = Combat::process_attack(
-                    Combat::attack_type_to_enum(attack_type.to_string()),
+                    attack_type_enum,
                     &mut attacker,
                     &mut target,
+                    &combatants,
                     &mut commands,
                     &mut items,
                     &templates,
@@ -768,16 +981,33 @@ fn attack_system(
                     &mut map_events,
                 );
 
+                // Add threat to the target's threat table
+                if let Ok((mut threat_table, mut visible_target)) =
+                    threat_query.get_mut(target_entity)
+                {
+                    threat_table.add(attacker.id.0, damage as f32 * npc::DAMAGE_THREAT);
+
+                    if visible_target.target == npc::NO_TARGET {
+                        visible_target.target = attacker.id.0;
+                    }
+                }
+
                 // Response to client with attack response packet
                 let packet = ResponsePacket::Attack {
                     sourceid: *source_id,
                     attacktype: attack_type.clone(),
                     cooldown: 5,
-                    stamina_cost: 5,
+                    stamina_cost: stamina_cost,
                 };
 
                 send_to_client(*player_id, packet, &clients);
 
+                let stats_packet = ResponsePacket::Stats {
+                    data: Stamina::to_stats_data(attacker.id.0, &attacker.stats),
+                };
+
+                send_to_client(*player_id, stats_packet, &clients);
+
                 debug!("Skill gain: {:?}", skill_updated);
 
                 if let Some(skill_updated) = skill_updated {
@@ -816,6 +1046,23 @@ fn attack_system(
                     continue;
                 };
 
+                // Other living units used for flanking and surrounded checks
+                let mut combatants = Vec::new();
+
+                for unit in query.iter() {
+                    if unit.entity != attacker_entity
+                        && unit.entity != target_entity
+                        && !Obj::is_dead(&unit.state)
+                    {
+                        combatants.push(Combatant::new(
+                            unit.player_id.0,
+                            *unit.pos,
+                            unit.in_combat,
+                            game_tick.0,
+                        ));
+                    }
+                }
+
                 let entities = [attacker_entity, target_entity];
 
                 let Ok([mut attacker, mut target]) = query.get_many_mut(entities) else {
@@ -861,10 +1108,16 @@ fn attack_system(
                     continue;
                 }
 
+                let stamina_cost = Stamina::attack_cost(
+                    AttackType::Fierce,
+                    &items.get_equipped_weapons(attacker.id.0),
+                );
+
                 // Calculate and process damage
                 let (damage, combo, skill_updated) = Combat::process_combo(
                     &mut attacker,
                     &mut target,
+                    &combatants,
                     &mut commands,
                     &mut items,
                     &templates,
@@ -876,6 +1129,20 @@ fn attack_system(
 
                 debug!("Found combo: {:?}", combo);
 
+                // Completed combos taunt the target
+                if let Ok((mut threat_table, mut visible_target)) =
+                    threat_query.get_mut(target_entity)
+                {
+                    threat_table.add(attacker.id.0, damage as f32 * npc::DAMAGE_THREAT);
+
+                    if combo.is_some() {
+                        threat_table.taunt(attacker.id.0, npc::TAUNT_THREAT);
+                        visible_target.target = attacker.id.0;
+                    } else if visible_target.target == npc::NO_TARGET {
+                        visible_target.target = attacker.id.0;
+                    }
+                }
+
                 // Add visible damage event to broadcast to everyone nearby
                 Combat::add_damage_event(
                     game_tick.0,
@@ -892,11 +1159,17 @@ fn attack_system(
                     sourceid: *source_id,
                     attacktype: "combo".to_string(),
                     cooldown: 5,
-                    stamina_cost: 5,
+                    stamina_cost: stamina_cost,
                 };
 
                 send_to_client(*player_id, packet, &clients);
 
+                let stats_packet = ResponsePacket::Stats {
+                    data: Stamina::to_stats_data(attacker.id.0, &attacker.stats),
+                };
+
+                send_to_client(*player_id, stats_packet, &clients);
+
                 debug!("Skill gain: {:?}", skill_updated);
 
                 if let Some(skill_updated) = skill_updated {
@@ -962,6 +1235,7 @@ fn gather_refine_system(
     recipes: Res<Recipes>,
     hero_query: Query<CoreQuery, With<SubclassHero>>,
     structure_query: Query<StructureQuery, With<ClassStructure>>,
+    stats_query: Query<&Stats>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
@@ -998,6 +1272,19 @@ fn gather_refine_system(
                     continue;
                 }
 
+                let Ok(stats) = stats_query.get(hero_entity) else {
+                    error!("Cannot find stats for {:?}", hero_entity);
+                    continue;
+                };
+
+                if !Stamina::has_stamina(stats, stamina::GATHER_COST) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Not enough stamina to gather.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
                 let gather_event = VisibleEvent::GatherEvent {
                     res_type: res_type.clone(),
                 };
@@ -1073,6 +1360,19 @@ fn gather_refine_system(
                     continue;
                 }
 
+                let Ok(stats) = stats_query.get(hero_entity) else {
+                    error!("Cannot find stats for {:?}", hero_entity);
+                    continue;
+                };
+
+                if !Stamina::has_stamina(stats, stamina::REFINE_COST) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Not enough stamina to refine.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
                 let mut refining_structure = None;
 
                 // Get structure from hero position
@@ -1148,6 +1448,11 @@ fn gather_refine_system(
                     continue;
                 }
 
+                let Ok(stats) = stats_query.get(hero_entity) else {
+                    error!("Cannot find stats for {:?}", hero_entity);
+                    continue;
+                };
+
                 let Some(mut recipe) = recipes.get_by_name(recipe_name.clone()) else {
                     error!("Invalid recipe name {:?}", *recipe_name);
                     let packet = ResponsePacket::Error {
@@ -1157,6 +1462,14 @@ fn gather_refine_system(
                     continue;
                 };
 
+                if !Stamina::has_stamina(stats, Stamina::craft_cost(recipe.stamina_req)) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Not enough stamina to craft.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
                 let mut crafting_structure = None;
 
                 // Get structure from hero position
@@ -1237,7 +1550,10 @@ fn info_obj_system(
     attrs_query: Query<&BaseAttrs>,
     stats_query: Query<&Stats>,
     structure_query: Query<&StructureAttrs>,
-    villager_query: Query<&VillagerAttrs>,
+    villager_query: Query<(&VillagerAttrs, Option<&CombatStance>, Option<&Contract>)>,
+    morale_query: Query<&Morale>,
+    personality_query: Query<&Personality>,
+    social_query: Query<(Option<&Relationships>, Option<&Household>, Option<&Child>)>,
 ) {
 
 
@@ -1313,19 +1629,22 @@ fn info_obj_system(
                         let mut base_speed = None;
                         let mut base_vision = None;
 
-                        let stamina = None;
-                        let base_stamina = None;
+                        let mut stamina = None;
+                        let mut base_stamina = None;
 
                         let mut structure = None;
                         let mut activity = None;
                         let mut shelter = None;
 
-                        let morale = None;
+                        let mut morale = None;
+                        let mut stance = None;
+                        let mut contract = None;
                         let order = None;
 
                         let total_weight = Some(items.get_total_weight(obj.id.0));
-                        let capacity = Some(Obj::get_capacity(
-                            &obj.template.0.to_string(),
+                        let capacity = Some(Encumbrance::capacity(
+                            &obj.template.0,
+                            attrs_query.get(obj.entity).ok(),
                             &templates.obj_templates,
                         ));
 
@@ -1345,6 +1664,9 @@ fn info_obj_system(
                             base_hp = Some(stats.base_hp);
                             base_def = Some(stats.base_def);
 
+                            stamina = stats.stamina;
+                            base_stamina = stats.base_stamina;
+
                             damage_range = stats.damage_range;
                             base_damage = stats.base_damage;
                             base_speed = stats.base_speed;
@@ -1377,12 +1699,26 @@ fn info_obj_system(
                             };
                         } else if obj.subclass.0 == obj::SUBCLASS_VILLAGER {
 
-                            if let Ok(villager_attrs) = villager_query.get(obj.entity) {
+                            if let Ok((villager_attrs, combat_stance, villager_contract)) =
+                                villager_query.get(obj.entity)
+                            {
                                 activity = Some(villager_attrs.activity.to_string());
                                 shelter = Some(villager_attrs.shelter.clone());
                                 structure = Some(villager_attrs.structure);
+                                stance = combat_stance.map(|s| s.to_str());
+                                contract = villager_contract.map(|c| c.to_data(game_tick.0));
+                            }
+
+                            if let Ok(villager_morale) = morale_query.get(obj.entity) {
+                                morale = Some(villager_morale.to_str());
                             }
 
+                            let personality = personality_query.get(obj.entity).ok();
+
+                            let (relationships, household, child) = social_query
+                                .get(obj.entity)
+                                .unwrap_or((None, None, None));
+
                             response_packet = ResponsePacket::InfoVillager {
                                 id: obj.id.0,
                                 name: obj.name.0.to_string(),
@@ -1409,9 +1745,19 @@ fn info_obj_system(
                                 activity,
                                 shelter: shelter,
                                 morale: morale,
+                                stance: stance,
+                                traits: personality.map(|p| p.traits.clone()),
+                                likes: personality.map(|p| p.likes.clone()),
+                                dislikes: personality.map(|p| p.dislikes.clone()),
+                                backstory: personality.map(|p| p.backstory.clone()),
+                                friends: relationships.map(|r| r.friends()),
+                                rivals: relationships.map(|r| r.rivals()),
+                                partner: household.map(|h| h.partner),
+                                child: child.is_some(),
                                 order: order,
                                 capacity: capacity,
                                 total_weight: total_weight,
+                                contract: contract,
                             };
 
                             let active_info_key = (*player_id, obj.id.0, "obj".to_string());
@@ -1891,7 +2237,9 @@ fn info_item_system(
     items: ResMut<Items>,
     query: Query<CoreQuery>,
     templates: Res<Templates>,
+    reputations: Res<Reputations>,
     mut active_infos: ResMut<ActiveInfos>,
+    attrs_query: Query<&BaseAttrs>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
@@ -1911,17 +2259,13 @@ fn info_item_system(
                     break;
                 };
 
-                let capacity = Obj::get_capacity(&obj.template.0, &templates.obj_templates);
-                let total_weight = items.get_total_weight(*id);
-
-                let inventory_items = items.get_by_owner_packet(*id);
-
-                let info_inventory_packet: ResponsePacket = ResponsePacket::InfoInventory {
-                    id: *id,
-                    cap: capacity as i32,
-                    tw: total_weight as i32,
-                    items: inventory_items,
-                };
+                let info_inventory_packet = Encumbrance::inventory_packet(
+                    *id,
+                    &obj.template.0,
+                    attrs_query.get(entity).ok(),
+                    &items,
+                    &templates,
+                );
 
                 let active_info_key = (*player_id, *id, "inventory".to_string());
                 active_infos.insert(active_info_key, true);
@@ -1931,12 +2275,27 @@ fn info_item_system(
             PlayerEvent::InfoItem {
                 player_id,
                 id,
-                merchant_id: _,
+                merchant_id,
                 merchant_action,
             } => {
                 events_to_remove.push(*event_id);
 
+                let reputation = reputations.get(*player_id);
+
                 if merchant_action == "merchantsell" {
+                    let price = items.find_by_id(*id).map(|item| {
+                        Pricing::quote_with_items(
+                            Trade::Buy,
+                            &item,
+                            1,
+                            item.owner,
+                            reputation,
+                            &items,
+                            &templates.item_templates,
+                        )
+                        .unit_price
+                    });
+
                     let item = items.get_packet(*id);
 
                     if let Some(item) = item {
@@ -1950,13 +2309,28 @@ fn info_item_system(
                             image: item.image,
                             weight: item.weight,
                             equipped: item.equipped,
-                            price: Some(10),
+                            quality: item.quality,
+                            grade: Quality::name(item.quality),
+                            price: price,
                             attrs: None,
                         };
 
                         send_to_client(*player_id, info_item_packet, &clients);
                     }
                 } else if merchant_action == "merchantbuy" {
+                    let price = items.find_by_id(*id).map(|item| {
+                        Pricing::quote_with_items(
+                            Trade::Sell,
+                            &item,
+                            1,
+                            *merchant_id,
+                            reputation,
+                            &items,
+                            &templates.item_templates,
+                        )
+                        .unit_price
+                    });
+
                     let item = items.get_packet(*id);
 
                     if let Some(item) = item {
@@ -1970,7 +2344,9 @@ fn info_item_system(
                             image: item.image,
                             weight: item.weight,
                             equipped: item.equipped,
-                            price: Some(10),
+                            quality: item.quality,
+                            grade: Quality::name(item.quality),
+                            price: price,
                             attrs: None,
                         };
 
@@ -1993,6 +2369,8 @@ fn info_item_system(
                             image: item.image,
                             weight: item.weight,
                             equipped: item.equipped,
+                            quality: item.quality,
+                            grade: Quality::name(item.quality),
                             price: None,
                             attrs: item.attrs,
                         };
@@ -2018,6 +2396,8 @@ fn info_item_system(
                         image: item.image,
                         weight: item.weight,
                         equipped: item.equipped,
+                        quality: item.quality,
+                        grade: Quality::name(item.quality),
                         price: None,
                         attrs: None,
                     };
@@ -2055,6 +2435,7 @@ fn item_transfer_system(
     mut items: ResMut<Items>,
     templates: Res<Templates>,
     query: Query<ItemTransferQuery>,
+    attrs_query: Query<&BaseAttrs>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
@@ -2136,8 +2517,11 @@ fn item_transfer_system(
                     // Transfer target does not have enough capacity
                     let target_total_weight = items.get_total_weight(target.id.0);
                     let transfer_item_weight = (item.quantity as f32 * item.weight) as i32;
-                    let target_capacity =
-                        Obj::get_capacity(&target.template.0, &templates.obj_templates);
+                    let target_capacity = Encumbrance::capacity(
+                        &target.template.0,
+                        attrs_query.get(target.entity).ok(),
+                        &templates.obj_templates,
+                    );
 
                     // Structure founded and under construction use case
                     if target.class.0 == "structure" && *target.state == State::Founded {
@@ -2177,8 +2561,11 @@ fn item_transfer_system(
                             continue;
                         }
 
-                        let source_capacity =
-                            Obj::get_capacity(&owner.template.0, &templates.obj_templates);
+                        let source_capacity = Encumbrance::capacity(
+                            &owner.template.0,
+                            attrs_query.get(owner.entity).ok(),
+                            &templates.obj_templates,
+                        );
                         let source_total_weight = items.get_total_weight(owner.id.0);
 
                         let source_items = items.get_by_owner_packet(item.owner);
@@ -2240,8 +2627,11 @@ fn item_transfer_system(
                             let req_items =
                                 Structure::process_req_items(structure_items, structure_req);
 
-                            let source_capacity =
-                                Obj::get_capacity(&owner.template.0, &templates.obj_templates);
+                            let source_capacity = Encumbrance::capacity(
+                                &owner.template.0,
+                                attrs_query.get(owner.entity).ok(),
+                                &templates.obj_templates,
+                            );
                             let source_total_weight = items.get_total_weight(owner.id.0);
 
                             let source_items = items.get_by_owner_packet(item.owner);
@@ -2276,7 +2666,13 @@ fn item_transfer_system(
                             error!("Obj is missing expected structure attributes");
                         }
                     } else {
-                        if target_total_weight + transfer_item_weight > target_capacity {
+                        // Units can be loaded past their capacity up to the hard limit
+                        let target_limit = Encumbrance::hard_limit(
+                            target_capacity,
+                            attrs_query.get(target.entity).ok(),
+                        );
+
+                        if target_total_weight + transfer_item_weight > target_limit {
                             let packet = ResponsePacket::Error {
                                 errmsg: "Transfer target does not have enough capacity".to_string(),
                             };
@@ -2287,8 +2683,11 @@ fn item_transfer_system(
                         info!("Other item transfer");
                         items.transfer(item.id, target.id.0);
 
-                        let source_capacity =
-                            Obj::get_capacity(&owner.template.0, &templates.obj_templates);
+                        let source_capacity = Encumbrance::capacity(
+                            &owner.template.0,
+                            attrs_query.get(owner.entity).ok(),
+                            &templates.obj_templates,
+                        );
                         let source_total_weight = items.get_total_weight(owner.id.0);
 
                         let source_items = items.get_by_owner_packet(item.owner);
@@ -2382,16 +2781,22 @@ fn item_transfer_system(
                     continue;
                 }
 
-                let source_capacity =
-                    Obj::get_capacity(&source.template.0, &templates.obj_templates);
+                let source_capacity = Encumbrance::capacity(
+                    &source.template.0,
+                    attrs_query.get(source.entity).ok(),
+                    &templates.obj_templates,
+                );
                 let source_total_weight = items.get_total_weight(source.id.0);
 
                 let mut target_capacity = -1; // -1 representing unknown
                 let mut target_total_weight = -1; // -1 representing unknown
 
                 if target.player_id.0 == *player_id {
-                    target_capacity =
-                        Obj::get_capacity(&target.template.0, &templates.obj_templates);
+                    target_capacity = Encumbrance::capacity(
+                        &target.template.0,
+                        attrs_query.get(target.entity).ok(),
+                        &templates.obj_templates,
+                    );
                     target_total_weight = items.get_total_weight(target.id.0);
                 }
 
@@ -2568,6 +2973,8 @@ fn info_hire_system(
     merchant_query: Query<&Transport, With<Merchant>>,
     query: Query<CoreQuery>,
     attrs_query: Query<&BaseAttrs>,
+    personality_query: Query<&Personality>,
+    contract_query: Query<&Contract>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
@@ -2602,6 +3009,13 @@ fn info_hire_system(
                         break;
                     };
 
+                    // Villagers already hired are not listed
+                    let contract = contract_query.get(entity).ok();
+
+                    if Contract::can_hire(obj.player_id.0, contract).is_err() {
+                        continue;
+                    }
+
                     let Ok(attrs) = attrs_query.get(entity) else {
                         error!("Cannot find attrs for {:?}", entity);
                         break;
@@ -2609,11 +3023,14 @@ fn info_hire_system(
 
                     let skills = Skill::get_levels_by_owner(*obj_id, &skills);
 
+                    let personality = personality_query.get(entity).ok();
+
                     let villager_data = network::HireData {
                         id: obj.id.0,
                         name: obj.name.0.clone(),
                         image: obj.misc.image.clone(),
-                        wage: 25,
+                        wage: Contract::wage(&skills),
+                        term: contract::CONTRACT_TERM,
                         creativity: attrs.creativity,
                         dexterity: attrs.dexterity,
                         endurance: attrs.endurance,
@@ -2623,6 +3040,10 @@ fn info_hire_system(
                         strength: attrs.strength,
                         toughness: attrs.toughness,
                         skills: skills,
+                        traits: personality.map(|p| p.traits.clone()).unwrap_or_default(),
+                        likes: personality.map(|p| p.likes.clone()).unwrap_or_default(),
+                        dislikes: personality.map(|p| p.dislikes.clone()).unwrap_or_default(),
+                        backstory: personality.map(|p| p.backstory.clone()),
                     };
 
                     hire_data.push(villager_data);
@@ -2954,6 +3375,7 @@ fn build_system(
     templates: Res<Templates>,
     builder_query: Query<CoreQuery, Or<(With<SubclassHero>, With<SubclassVillager>)>>,
     mut structure_query: Query<StructureQuery, With<ClassStructure>>,
+    stats_query: Query<&Stats>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
@@ -3015,6 +3437,17 @@ fn build_system(
                     break;
                 }
 
+                // Check if builder has enough stamina
+                if let Ok(stats) = stats_query.get(builder_entity) {
+                    if !Stamina::has_stamina(stats, stamina::BUILD_COST) {
+                        let packet = ResponsePacket::Error {
+                            errmsg: "Not enough stamina to build.".to_string(),
+                        };
+                        send_to_client(*player_id, packet, &clients);
+                        break;
+                    }
+                }
+
                 let structure_template =
                     ObjTemplate::get_template_by_name(structure.name.0.clone(), &templates);
 
@@ -3541,8 +3974,8 @@ fn equip_system(
                     continue;
                 }
 
-                // Check if object is busy
-                if *owner.state != State::None {
+                // Check if object is busy, villagers can be armed while they work
+                if *owner.state != State::None && owner.subclass.0 != obj::SUBCLASS_VILLAGER {
                     let packet = ResponsePacket::Error {
                         errmsg: "Item owner is busy".to_string(),
                     };
@@ -3583,106 +4016,158 @@ fn equip_system(
     }
 }
 
-fn recipe_list_system(
+fn repair_system(
     mut events: ResMut<PlayerEvents>,
-    clients: Res<Clients>,
     ids: ResMut<Ids>,
-    recipes: Res<Recipes>,
+    clients: Res<Clients>,
+    mut items: ResMut<Items>,
+    mut skills: ResMut<Skills>,
+    templates: Res<Templates>,
+    query: Query<CoreQuery>,
     structure_query: Query<StructureQuery, With<ClassStructure>>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
     for (event_id, event) in events.iter() {
         match event {
-            PlayerEvent::RecipeList {
-                player_id,
-                structure_id,
-            } => {
+            PlayerEvent::Repair { player_id, item_id } => {
                 events_to_remove.push(*event_id);
 
-                let Some(structure_entity) = ids.get_entity(*structure_id) else {
-                    error!("Cannot find structure entity for {:?}", structure_id);
+                let Some(item) = items.find_by_id(*item_id) else {
+                    debug!("Failed to find item: {:?}", item_id);
                     continue;
                 };
 
-                let Ok(structure) = structure_query.get(structure_entity) else {
-                    error!("Query failed to find entity {:?}", structure_entity);
+                let Some(owner_entity) = ids.get_entity(item.owner) else {
+                    error!("Cannot find owner entity for {:?}", item.owner);
                     continue;
                 };
 
-                let structure_recipes =
-                    recipes.get_by_structure_packet(*player_id, structure.template.0.clone());
-
-                let packet = ResponsePacket::RecipeList {
-                    result: structure_recipes,
+                let Ok(owner) = query.get(owner_entity) else {
+                    error!("Query failed to find entity {:?}", owner_entity);
+                    continue;
                 };
 
-                send_to_client(*player_id, packet, &clients);
-            }
-            _ => {}
-        }
-    }
+                if Obj::is_dead(&owner.state) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "The dead cannot repair items.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
 
-    for event_id in events_to_remove.iter() {
-        events.remove(event_id);
-    }
-}
+                // Check if entity is owned by player
+                if owner.player_id.0 != *player_id {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Item not owned by player.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
 
-fn order_refine_system(
-    mut commands: Commands,
-    game_tick: Res<GameTick>,
-    _ids: ResMut<Ids>,
-    mut events: ResMut<PlayerEvents>,
-    mut map_events: ResMut<MapEvents>,
-    clients: Res<Clients>,
-    villager_query: Query<VillagerQuery, With<SubclassVillager>>,
-) {
-    let mut events_to_remove: Vec<i32> = Vec::new();
+                let Some((durability, max_durability)) = item.get_durability() else {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Item cannot be repaired.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                };
 
-    for (event_id, event) in events.iter() {
-        match event {
-            PlayerEvent::OrderRefine {
-                player_id,
-                structure_id,
-            } => {
-                events_to_remove.push(*event_id);
+                if durability >= max_durability {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Item does not need repair.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
 
-                let mut villager = None;
+                let Some(recipe_template) = templates
+                    .recipe_templates
+                    .iter()
+                    .find(|recipe_template| recipe_template.name == item.name)
+                else {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Item has no recipe to repair from.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                };
 
-                //Find villager assigned to structure
-                for villager_item in villager_query.iter() {
-                    if villager_item.attrs.structure == *structure_id
-                        && villager_item.player_id.0 == *player_id
+                // Get the matching crafting structure from owner position
+                let mut repair_structure = None;
+
+                for structure in structure_query.iter() {
+                    if *structure.pos == *owner.pos
+                        && structure.player_id.0 == *player_id
+                        && structure.template.0 == recipe_template.structure
                     {
-                        villager = Some(villager_item);
+                        repair_structure = Some(structure);
                     }
                 }
 
-                if villager.is_none() {
-                    error!(
-                        "Cannot find a villager assigned to structure {:?}",
-                        *structure_id
-                    );
+                let Some(repair_structure) = repair_structure else {
                     let packet = ResponsePacket::Error {
-                        errmsg: "No villager assigned to structure to refine.".to_string(),
+                        errmsg: format!(
+                            "Item must be repaired at a {}.",
+                            recipe_template.structure
+                        ),
                     };
                     send_to_client(*player_id, packet, &clients);
-                    break;
-                }
+                    continue;
+                };
 
-                if let Some(villager) = villager {
-                    info!("Adding Order Refine to {:?}", villager.id);
+                // Repair amount scales with the repair skill
+                let repair_level =
+                    Skill::get_by_name(owner.id.0, skill::REPAIRING.to_string(), &skills)
+                        .map_or(0, |skill| skill.level);
 
-                    //Add speech
-                    Obj::add_sound_obj_event(
-                        game_tick.0,
-                        Villager::order_to_speech(&Order::Refine),
-                        villager.id,
-                        &mut map_events,
-                    );
+                let repair_amount =
+                    item::REPAIR_BASE + repair_level as f32 * item::REPAIR_PER_LEVEL;
 
-                    commands.entity(villager.entity).insert(Order::Refine);
+                let repair_reqs =
+                    Item::get_repair_reqs(&item, &recipe_template.req, repair_amount);
+
+                if !Structure::has_req(repair_structure.id.0, &repair_reqs, &items) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Insufficient resources to repair".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
                 }
+
+                Structure::consume_reqs(repair_structure.id.0, repair_reqs, &mut items);
+
+                let Some(repaired_item) = items.repair(item.id, repair_amount) else {
+                    continue;
+                };
+
+                Skill::update(
+                    owner.id.0,
+                    skill::REPAIRING.to_string(),
+                    item::REPAIR_XP,
+                    &mut skills,
+                    &templates.skill_templates,
+                );
+
+                let Some(item_packet) = items.get_packet(repaired_item.id) else {
+                    continue;
+                };
+
+                let item_update_packet: ResponsePacket = ResponsePacket::InfoItemsUpdate {
+                    id: repaired_item.owner,
+                    items_updated: vec![item_packet],
+                    items_removed: Vec::new(),
+                };
+
+                send_to_client(*player_id, item_update_packet, &clients);
+
+                let skill_updated_packet = ResponsePacket::Xp {
+                    id: owner.id.0,
+                    xp_type: skill::REPAIRING.to_string(),
+                    xp: item::REPAIR_XP,
+                };
+
+                send_to_client(*player_id, skill_updated_packet, &clients);
             }
             _ => {}
         }
@@ -3693,159 +4178,141 @@ fn order_refine_system(
     }
 }
 
-fn order_craft_system(
-    mut commands: Commands,
-    game_tick: Res<GameTick>,
-    _ids: ResMut<Ids>,
+fn loot_system(
     mut events: ResMut<PlayerEvents>,
-    mut map_events: ResMut<MapEvents>,
+    ids: ResMut<Ids>,
     clients: Res<Clients>,
     mut items: ResMut<Items>,
-    recipes: Res<Recipes>,
-    villager_query: Query<VillagerQuery, With<SubclassVillager>>,
+    templates: Res<Templates>,
+    query: Query<CoreQuery>,
+    attrs_query: Query<&BaseAttrs>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
     for (event_id, event) in events.iter() {
         match event {
-            PlayerEvent::OrderCraft {
+            PlayerEvent::Loot {
                 player_id,
-                structure_id,
-                recipe_name,
+                source_id,
+                corpse_id,
             } => {
                 events_to_remove.push(*event_id);
 
-                let mut villager = None;
+                let Some(source_entity) = ids.get_entity(*source_id) else {
+                    error!("Cannot find source entity from id: {:?}", source_id);
+                    continue;
+                };
 
-                //Find villager assigned to structure
-                for villager_item in villager_query.iter() {
-                    if villager_item.attrs.structure == *structure_id
-                        && villager_item.player_id.0 == *player_id
-                    {
-                        villager = Some(villager_item);
-                    }
+                let Some(corpse_entity) = ids.get_entity(*corpse_id) else {
+                    error!("Cannot find corpse entity from id: {:?}", corpse_id);
+                    continue;
+                };
+
+                let entities = [source_entity, corpse_entity];
+
+                let Ok([source, corpse]) = query.get_many(entities) else {
+                    error!("Cannot find source or corpse from entities {:?}", entities);
+                    continue;
+                };
+
+                if source.player_id.0 != *player_id {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Source not owned by player.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
                 }
 
-                if villager.is_none() {
-                    error!(
-                        "Cannot find a villager assigned to structure {:?}",
-                        *structure_id
-                    );
+                if Obj::is_dead(&source.state) {
                     let packet = ResponsePacket::Error {
-                        errmsg: "No villager assigned to structure to refine.".to_string(),
+                        errmsg: "The dead cannot loot.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
-                    break;
+                    continue;
                 }
 
-                let recipe = recipes.get_by_name(recipe_name.clone());
+                if !Obj::is_dead(&corpse.state) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Only the dead can be looted.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
 
-                if recipe.is_none() {
-                    error!("Invalid recipe name {:?}", *recipe_name);
+                if !Map::is_adjacent(*source.pos, *corpse.pos) {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Invalid recipe".to_string(),
+                        errmsg: "Corpse is not nearby.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
-                    break;
+                    continue;
                 }
 
-                if let Some(mut recipe) = recipe {
-                    //TODO consider if checking reqs is required here
-                    if Structure::has_req(*structure_id, &mut recipe.req, &mut items) {
-                        if let Some(villager) = villager {
-                            info!("Adding Order Craft to {:?}", villager.id);
-                            commands.entity(villager.entity).insert(Order::Craft {
-                                recipe_name: recipe_name.clone(),
-                            });
+                let corpse_items = items.get_by_owner(corpse.id.0);
 
-                            //Add speech
-                            Obj::add_sound_obj_event(
-                                game_tick.0,
-                                Villager::order_to_speech(&Order::Craft {
-                                    recipe_name: recipe_name.to_string(),
-                                }),
-                                villager.id,
-                                &mut map_events,
-                            );
-                        }
-                    } else {
-                        error!("Insufficient resources to craft {:?}", *recipe_name);
-                        let packet = ResponsePacket::Error {
-                            errmsg: "Insufficient resources to craft".to_string(),
-                        };
-                        send_to_client(*player_id, packet, &clients);
-                        break;
-                    }
-                } else {
-                    error!("Cannot find recipe: {:?}", *recipe_name);
+                if corpse_items.is_empty() {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Cannot find recipe".to_string(),
+                        errmsg: "Nothing to loot.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
-                    break;
+                    continue;
                 }
-            }
-            _ => {}
-        }
-    }
 
-    for event_id in events_to_remove.iter() {
-        events.remove(event_id);
-    }
-}
+                let source_attrs = attrs_query.get(source_entity).ok();
+                let source_capacity = Encumbrance::capacity(
+                    &source.template.0,
+                    source_attrs,
+                    &templates.obj_templates,
+                );
+                let source_limit = Encumbrance::hard_limit(source_capacity, source_attrs);
+                let mut source_total_weight = items.get_total_weight(source.id.0);
+                let mut num_looted = 0;
 
-fn order_explore_system(
-    mut events: ResMut<PlayerEvents>,
-    game_tick: Res<GameTick>,
-    ids: ResMut<Ids>,
-    mut commands: Commands,
-    mut map_events: ResMut<MapEvents>,
-    clients: Res<Clients>,
-    query: Query<MapObjQuery>,
-) {
-    let mut events_to_remove: Vec<i32> = Vec::new();
+                // Take as much as the source can carry
+                for corpse_item in corpse_items.iter() {
+                    let item_weight = (corpse_item.quantity as f32 * corpse_item.weight) as i32;
 
-    for (event_id, event) in events.iter() {
-        match event {
-            PlayerEvent::OrderExplore {
-                player_id,
-                villager_id,
-            } => {
-                events_to_remove.push(*event_id);
+                    if source_total_weight + item_weight > source_limit {
+                        continue;
+                    }
 
-                let Some(entity) = ids.get_entity(*villager_id) else {
-                    error!("Cannot find entity for {:?}", villager_id);
-                    break;
-                };
+                    items.transfer(corpse_item.id, source.id.0);
 
-                let Ok(villager) = query.get(entity) else {
-                    error!("Cannot find villager for {:?}", entity);
-                    break;
-                };
+                    source_total_weight += item_weight;
+                    num_looted += 1;
+                }
 
-                if villager.player_id.0 != *player_id {
-                    error!("Villager not owned by player {:?}", *player_id);
+                if num_looted == 0 {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Cannot order another player's villager".to_string(),
+                        errmsg: "Not enough capacity to loot.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
-                    break;
+                    continue;
                 }
 
-                // Add OrderFollow component to source and set hero_entity as target
-                for q in &query {
-                    if q.id.0 == *villager_id {
-                        //Add speech
-                        Obj::add_sound_obj_event(
-                            game_tick.0,
-                            Villager::order_to_speech(&Order::Explore),
-                            villager.id,
-                            &mut map_events,
-                        );
+                let corpse_inventory = network::Inventory {
+                    id: corpse.id.0,
+                    cap: Obj::get_capacity(&corpse.template.0, &templates.obj_templates),
+                    tw: items.get_total_weight(corpse.id.0),
+                    items: items.get_by_owner_packet(corpse.id.0),
+                };
 
-                        commands.entity(q.entity).insert(Order::Explore);
-                    }
-                }
+                let source_inventory = network::Inventory {
+                    id: source.id.0,
+                    cap: source_capacity,
+                    tw: source_total_weight,
+                    items: items.get_by_owner_packet(source.id.0),
+                };
+
+                let item_transfer_packet: ResponsePacket = ResponsePacket::ItemTransfer {
+                    result: "success".to_string(),
+                    sourceid: corpse.id.0,
+                    sourceitems: corpse_inventory,
+                    targetid: source.id.0,
+                    targetitems: source_inventory,
+                    reqitems: Vec::new(),
+                };
+
+                send_to_client(*player_id, item_transfer_packet, &clients);
             }
             _ => {}
         }
@@ -3856,155 +4323,232 @@ fn order_explore_system(
     }
 }
 
-fn order_experiment_system(
+fn harvest_corpse_system(
     mut commands: Commands,
-    game_tick: Res<GameTick>,
-    _ids: ResMut<Ids>,
     mut events: ResMut<PlayerEvents>,
-    mut map_events: ResMut<MapEvents>,
-    items: ResMut<Items>,
-    mut experiments: ResMut<Experiments>,
-    _templates: Res<Templates>,
-    active_infos: Res<ActiveInfos>,
+    ids: ResMut<Ids>,
     clients: Res<Clients>,
-    villager_query: Query<VillagerQuery, With<SubclassVillager>>,
+    mut items: ResMut<Items>,
+    mut skills: ResMut<Skills>,
+    templates: Res<Templates>,
+    query: Query<CoreQuery>,
+    mut stats_query: Query<&mut Stats>,
+    mut harvested_query: Query<&mut Harvested>,
+    attrs_query: Query<&BaseAttrs>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
     for (event_id, event) in events.iter() {
-        match event {
-            PlayerEvent::OrderExperiment {
+        let (player_id, source_id, corpse_id, is_skinning) = match event {
+            PlayerEvent::Skin {
                 player_id,
-                structure_id,
-            } => {
-                events_to_remove.push(*event_id);
+                source_id,
+                corpse_id,
+            } => (*player_id, *source_id, *corpse_id, true),
+            PlayerEvent::Butcher {
+                player_id,
+                source_id,
+                corpse_id,
+            } => (*player_id, *source_id, *corpse_id, false),
+            _ => continue,
+        };
 
-                let mut villager = None;
+        events_to_remove.push(*event_id);
 
-                //Find villager assigned to structure
-                for villager_item in villager_query.iter() {
-                    if villager_item.attrs.structure == *structure_id
-                        && villager_item.player_id.0 == *player_id
-                    {
-                        villager = Some(villager_item);
-                    }
-                }
+        let (action, participle, skill_name, skill_xp) = if is_skinning {
+            ("skin", "skinned", skill::SKINNING, corpse::SKIN_XP)
+        } else {
+            ("butcher", "butchered", skill::BUTCHERING, corpse::BUTCHER_XP)
+        };
+
+        let Some(source_entity) = ids.get_entity(source_id) else {
+            error!("Cannot find source entity from id: {:?}", source_id);
+            continue;
+        };
+
+        let Some(corpse_entity) = ids.get_entity(corpse_id) else {
+            error!("Cannot find corpse entity from id: {:?}", corpse_id);
+            continue;
+        };
+
+        let entities = [source_entity, corpse_entity];
+
+        let Ok([source, corpse]) = query.get_many(entities) else {
+            error!("Cannot find source or corpse from entities {:?}", entities);
+            continue;
+        };
+
+        if source.player_id.0 != player_id {
+            let packet = ResponsePacket::Error {
+                errmsg: "Source not owned by player.".to_string(),
+            };
+            send_to_client(player_id, packet, &clients);
+            continue;
+        }
 
-                if villager.is_none() {
-                    error!(
-                        "Cannot find a villager assigned to structure {:?}",
-                        *structure_id
-                    );
-                    let packet = ResponsePacket::Error {
-                        errmsg: "No villager assigned to structure to refine.".to_string(),
-                    };
-                    send_to_client(*player_id, packet, &clients);
-                    break;
+        if Obj::is_dead(&source.state) {
+            let packet = ResponsePacket::Error {
+                errmsg: format!("The dead cannot {}.", action),
+            };
+            send_to_client(player_id, packet, &clients);
+            continue;
+        }
+
+        if !Obj::is_dead(&corpse.state) {
+            let packet = ResponsePacket::Error {
+                errmsg: format!("Only the dead can be {}.", participle),
+            };
+            send_to_client(player_id, packet, &clients);
+            continue;
+        }
+
+        if !Map::is_adjacent(*source.pos, *corpse.pos) {
+            let packet = ResponsePacket::Error {
+                errmsg: "Corpse is not nearby.".to_string(),
+            };
+            send_to_client(player_id, packet, &clients);
+            continue;
+        }
+
+        let corpse_template = ObjTemplate::get_template(corpse.template.0.clone(), &templates);
+
+        let harvest_table = if is_skinning {
+            corpse_template.skin
+        } else {
+            corpse_template.butcher
+        };
+
+        let Some(harvest_table) = harvest_table else {
+            let packet = ResponsePacket::Error {
+                errmsg: format!("Nothing to {}.", action),
+            };
+            send_to_client(player_id, packet, &clients);
+            continue;
+        };
+
+        let already_harvested = harvested_query
+            .get(corpse_entity)
+            .map_or(false, |harvested| {
+                if is_skinning {
+                    harvested.skinned
+                } else {
+                    harvested.butchered
                 }
+            });
+
+        if already_harvested {
+            let packet = ResponsePacket::Error {
+                errmsg: format!("Corpse has already been {}.", participle),
+            };
+            send_to_client(player_id, packet, &clients);
+            continue;
+        }
 
-                if let Some(villager) = villager {
-                    info!("Adding Order Experiment to {:?}", villager.id);
+        let Ok(mut source_stats) = stats_query.get_mut(source_entity) else {
+            error!("Query failed to find stats for entity {:?}", source_entity);
+            continue;
+        };
+
+        if !Stamina::has_stamina(&source_stats, stamina::GATHER_COST) {
+            let packet = ResponsePacket::Error {
+                errmsg: format!("Not enough stamina to {}.", action),
+            };
+            send_to_client(player_id, packet, &clients);
+            continue;
+        }
 
-                    // Update experiment state to progressing
-                    let updated_experiment = Experiment::update_state(
-                        villager.attrs.structure,
-                        experiment::ExperimentState::Waiting,
-                        &mut experiments,
-                    );
+        // Yield scales with the skinning or butchering skill
+        let skill_level = Skill::get_by_name(source.id.0, skill_name.to_string(), &skills)
+            .map_or(0, |skill| skill.level);
 
-                    if let Some(updated_experiment) = updated_experiment {
-                        active_info_experiment(
-                            villager.player_id.0,
-                            villager.attrs.structure,
-                            updated_experiment,
-                            &items,
-                            &active_infos,
-                            &clients,
-                        );
-                    }
+        let harvest = Corpse::harvest_yield(&harvest_table, skill_level);
 
-                    commands.entity(villager.entity).insert(Order::Experiment);
+        let mut harvest_weight = 0;
 
-                    Obj::add_sound_obj_event(
-                        game_tick.0,
-                        Villager::order_to_speech(&Order::Experiment),
-                        villager.id,
-                        &mut map_events,
-                    );
-                }
-            }
-            _ => {}
+        for (item_name, quantity) in harvest.iter() {
+            harvest_weight += Item::get_weight_from_template(
+                item_name.clone(),
+                *quantity,
+                &templates.item_templates,
+            );
         }
-    }
 
-    for event_id in events_to_remove.iter() {
-        events.remove(event_id);
-    }
-}
+        let source_attrs = attrs_query.get(source_entity).ok();
+        let source_capacity =
+            Encumbrance::capacity(&source.template.0, source_attrs, &templates.obj_templates);
+
+        if items.get_total_weight(source.id.0) + harvest_weight
+            > Encumbrance::hard_limit(source_capacity, source_attrs)
+        {
+            let packet = ResponsePacket::Error {
+                errmsg: "Not enough capacity.".to_string(),
+            };
+            send_to_client(player_id, packet, &clients);
+            continue;
+        }
 
-fn order_farm_system(
-    mut commands: Commands,
-    game_tick: Res<GameTick>,
-    _ids: ResMut<Ids>,
-    mut events: ResMut<PlayerEvents>,
-    mut map_events: ResMut<MapEvents>,
-    items: ResMut<Items>,
-    mut experiments: ResMut<Experiments>,
-    _templates: Res<Templates>,
-    active_infos: Res<ActiveInfos>,
-    clients: Res<Clients>,
-    villager_query: Query<VillagerQuery, With<SubclassVillager>>,
-) {
-    let mut events_to_remove: Vec<i32> = Vec::new();
+        if let Ok(mut harvested) = harvested_query.get_mut(corpse_entity) {
+            if is_skinning {
+                harvested.skinned = true;
+            } else {
+                harvested.butchered = true;
+            }
+        } else {
+            commands.entity(corpse_entity).insert(Harvested {
+                skinned: is_skinning,
+                butchered: !is_skinning,
+            });
+        }
 
-    for (event_id, event) in events.iter() {
-        match event {
-            PlayerEvent::OrderPlant {
-                player_id,
-                structure_id,
-            } => {
-                events_to_remove.push(*event_id);
+        Stamina::consume(&mut source_stats, stamina::GATHER_COST);
 
-                // Find all villagers assigned to farm and order them to plant
-                for villager_item in villager_query.iter() {
-                    if villager_item.attrs.structure == *structure_id
-                        && villager_item.player_id.0 == *player_id
-                    {
-                        commands.entity(villager_item.entity).insert(Order::Plant);
+        let mut items_updated = Vec::new();
 
-                        Obj::add_sound_obj_event(
-                            game_tick.0,
-                            Villager::order_to_speech(&Order::Plant),
-                            villager_item.id,
-                            &mut map_events,
-                        );
-                    }
-                }                
+        for (item_name, quantity) in harvest.into_iter() {
+            if quantity <= 0 {
+                continue;
             }
-            PlayerEvent::OrderHarvest {
-                player_id,
-                structure_id,
-            } => {
-                events_to_remove.push(*event_id);
 
-                // Find all villagers assigned to farm and order them to plant
-                for villager_item in villager_query.iter() {
-                    if villager_item.attrs.structure == *structure_id
-                        && villager_item.player_id.0 == *player_id
-                    {
-                        commands.entity(villager_item.entity).insert(Order::Harvest);
+            let (new_item, _merged) = items.create(source.id.0, item_name, quantity);
+            items_updated.push(Item::to_packet(new_item));
+        }
 
-                        Obj::add_sound_obj_event(
-                            game_tick.0,
-                            Villager::order_to_speech(&Order::Harvest),
-                            villager_item.id,
-                            &mut map_events,
-                        );
-                    }
-                }                
-            }            
-            _ => {}
+        if items_updated.is_empty() {
+            let packet = ResponsePacket::Error {
+                errmsg: format!("Failed to {} anything useful.", action),
+            };
+            send_to_client(player_id, packet, &clients);
+        } else {
+            let item_update_packet: ResponsePacket = ResponsePacket::InfoItemsUpdate {
+                id: source.id.0,
+                items_updated: items_updated,
+                items_removed: Vec::new(),
+            };
+
+            send_to_client(player_id, item_update_packet, &clients);
         }
+
+        let stats_packet = ResponsePacket::Stats {
+            data: Stamina::to_stats_data(source.id.0, &source_stats),
+        };
+
+        send_to_client(player_id, stats_packet, &clients);
+
+        Skill::update(
+            source.id.0,
+            skill_name.to_string(),
+            skill_xp,
+            &mut skills,
+            &templates.skill_templates,
+        );
+
+        let skill_updated_packet = ResponsePacket::Xp {
+            id: source.id.0,
+            xp_type: skill_name.to_string(),
+            xp: skill_xp,
+        };
+
+        send_to_client(player_id, skill_updated_packet, &clients);
     }
 
     for event_id in events_to_remove.iter() {
@@ -4012,119 +4556,133 @@ fn order_farm_system(
     }
 }
 
-
-fn use_item_system(
+fn schedule_system(
     mut events: ResMut<PlayerEvents>,
-    game_tick: Res<GameTick>,
     ids: ResMut<Ids>,
-    _commands: Commands,
     clients: Res<Clients>,
-    items: ResMut<Items>,
-    mut map_events: ResMut<MapEvents>,
-    query: Query<MapObjQuery>,
+    villager_query: Query<(&PlayerId, &State), With<SubclassVillager>>,
+    mut schedule_query: Query<&mut Schedule>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
     for (event_id, event) in events.iter() {
         match event {
-            PlayerEvent::Use { player_id, item_id } => {
+            PlayerEvent::InfoSchedule {
+                player_id,
+                villager_id,
+            } => {
                 events_to_remove.push(*event_id);
 
-                let Some(item) = items.find_by_id(*item_id) else {
-                    debug!("Failed to find item: {:?}", item_id);
-                    continue;
-                };
-
-                // Validation checks get source entity
-                let Some(owner_entity) = ids.get_entity(item.owner) else {
-                    error!("Cannot find villager entity for {:?}", item.owner);
+                let Some(villager_entity) = ids.get_entity(*villager_id) else {
+                    error!("Cannot find villager entity for {:?}", villager_id);
                     continue;
                 };
 
-                let Ok(owner) = query.get(owner_entity) else {
-                    error!("Query failed to find entity {:?}", owner_entity);
+                let Ok((villager_player_id, _state)) = villager_query.get(villager_entity) else {
+                    error!("Query failed to find entity {:?}", villager_entity);
                     continue;
                 };
 
-                if Obj::is_dead(&owner.state) {
+                if villager_player_id.0 != *player_id {
                     let packet = ResponsePacket::Error {
-                        errmsg: "The dead cannot use items.".to_string(),
+                        errmsg: "Villager not owned by player.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
                 }
 
-                // Check if entity is owned by player
-                if owner.player_id.0 != *player_id {
+                let Ok(schedule) = schedule_query.get(villager_entity) else {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Item not owned by player.".to_string(),
+                        errmsg: "Villager has no schedule.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
-                }
-                // Insert explore event
-                let use_item_event = VisibleEvent::UseItemEvent {
-                    item_id: *item_id,
-                    item_owner_id: owner.id.0,
                 };
 
-                map_events.new(owner.id.0, game_tick.0 + 1, use_item_event);
-            }
-            _ => {}
-        }
-    }
-
-    for event_id in events_to_remove.iter() {
-        events.remove(event_id);
-    }
-}
-
-fn remove_system(
-    mut events: ResMut<PlayerEvents>,
-    game_tick: Res<GameTick>,
-    ids: ResMut<Ids>,
-    clients: Res<Clients>,
-    mut map_events: ResMut<MapEvents>,
-    query: Query<MapObjQuery>,
-) {
-    let mut events_to_remove: Vec<i32> = Vec::new();
+                let packet = ResponsePacket::InfoSchedule {
+                    id: *villager_id,
+                    schedule: schedule_to_packet(&schedule),
+                };
 
-    for (event_id, event) in events.iter() {
-        match event {
-            PlayerEvent::Remove {
+                send_to_client(*player_id, packet, &clients);
+            }
+            PlayerEvent::SetSchedule {
                 player_id,
-                structure_id,
+                villager_id,
+                schedule,
             } => {
                 events_to_remove.push(*event_id);
 
-                let Some(entity) = ids.get_entity(*structure_id) else {
-                    error!("Cannot find entity for {:?}", structure_id);
+                let Some(villager_entity) = ids.get_entity(*villager_id) else {
+                    error!("Cannot find villager entity for {:?}", villager_id);
                     continue;
                 };
 
-                let Ok(obj) = query.get(entity) else {
-                    error!("Cannot find obj for {:?}", entity);
+                let Ok((villager_player_id, state)) = villager_query.get(villager_entity) else {
+                    error!("Query failed to find entity {:?}", villager_entity);
                     continue;
                 };
 
-                // Check if entity is owned by player
-                if obj.player_id.0 != *player_id {
+                if villager_player_id.0 != *player_id {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Obj not owned by player.".to_string(),
+                        errmsg: "Villager not owned by player.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
                 }
 
-                debug!("Removing obj: {:?}", obj.id.0);
+                if Obj::is_dead(state) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "The dead do not keep schedules.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
 
-                map_events.new(
-                    obj.id.0,
-                    game_tick.0 + 1,
-                    VisibleEvent::RemoveObjEvent {
-                        pos: obj.pos.to_owned(),
-                    },
-                );
+                let mut blocks = Vec::new();
+                let mut invalid_block = None;
+
+                for block in schedule.iter() {
+                    let valid_hours = block.start >= 0
+                        && block.start < Schedule::HOURS_PER_DAY
+                        && block.end >= 0
+                        && block.end < Schedule::HOURS_PER_DAY
+                        && block.start != block.end;
+
+                    match ScheduleActivity::from_str(&block.activity) {
+                        Some(activity) if valid_hours => {
+                            blocks.push(ScheduleBlock::new(block.start, block.end, activity));
+                        }
+                        _ => {
+                            invalid_block = Some(block.clone());
+                            break;
+                        }
+                    }
+                }
+
+                if let Some(invalid_block) = invalid_block {
+                    let packet = ResponsePacket::Error {
+                        errmsg: format!("Invalid schedule block {:?}", invalid_block),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                let new_schedule = Schedule::new(blocks);
+
+                if let Ok(mut schedule) = schedule_query.get_mut(villager_entity) {
+                    *schedule = new_schedule.clone();
+                } else {
+                    error!("No schedule component for {:?}", villager_entity);
+                    continue;
+                }
+
+                let packet = ResponsePacket::InfoSchedule {
+                    id: *villager_id,
+                    schedule: schedule_to_packet(&new_schedule),
+                };
+
+                send_to_client(*player_id, packet, &clients);
             }
             _ => {}
         }
@@ -4135,165 +4693,202 @@ fn remove_system(
     }
 }
 
-fn set_experiment_item_system(
+fn schedule_to_packet(schedule: &Schedule) -> Vec<network::ScheduleBlock> {
+    let mut schedule_packet = Vec::new();
+
+    for block in schedule.blocks.iter() {
+        schedule_packet.push(network::ScheduleBlock {
+            start: block.start,
+            end: block.end,
+            activity: block.activity.to_str().to_string(),
+        });
+    }
+
+    return schedule_packet;
+}
+
+fn order_queue_system(
     mut events: ResMut<PlayerEvents>,
-    _game_tick: Res<GameTick>,
     ids: ResMut<Ids>,
     clients: Res<Clients>,
-    _map_events: ResMut<MapEvents>,
-    mut items: ResMut<Items>,
-    mut experiments: ResMut<Experiments>,
-    query: Query<MapObjQuery>,
+    villager_query: Query<(&PlayerId, &State), With<SubclassVillager>>,
+    owner_query: Query<&PlayerId>,
+    structure_query: Query<&PlayerId, With<ClassStructure>>,
+    mut queue_query: Query<&mut OrderQueue>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
     for (event_id, event) in events.iter() {
-        match event {
-            PlayerEvent::SetExperimentItem {
+        let (player_id, villager_id) = match event {
+            PlayerEvent::InfoOrderQueue {
                 player_id,
-                item_id,
-                is_resource,
-            } => {
-                events_to_remove.push(*event_id);
+                villager_id,
+            }
+            | PlayerEvent::QueueOrder {
+                player_id,
+                villager_id,
+                ..
+            }
+            | PlayerEvent::MoveQueuedOrder {
+                player_id,
+                villager_id,
+                ..
+            }
+            | PlayerEvent::CancelQueuedOrder {
+                player_id,
+                villager_id,
+                ..
+            } => (player_id, villager_id),
+            _ => continue,
+        };
+
+        events_to_remove.push(*event_id);
+
+        let Some(villager_entity) = ids.get_entity(*villager_id) else {
+            error!("Cannot find villager entity for {:?}", villager_id);
+            continue;
+        };
+
+        let Ok((villager_player_id, state)) = villager_query.get(villager_entity) else {
+            error!("Query failed to find entity {:?}", villager_entity);
+            continue;
+        };
+
+        if villager_player_id.0 != *player_id {
+            let packet = ResponsePacket::Error {
+                errmsg: "Villager not owned by player.".to_string(),
+            };
+            send_to_client(*player_id, packet, &clients);
+            continue;
+        }
 
-                let Some(item) = items.find_by_id(*item_id) else {
-                    debug!("Failed to find item: {:?}", item_id);
+        let Ok(mut order_queue) = queue_query.get_mut(villager_entity) else {
+            error!("No order queue component for {:?}", villager_entity);
+            continue;
+        };
+
+        match event {
+            PlayerEvent::QueueOrder { order, .. } => {
+                if Obj::is_dead(state) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "The dead cannot take orders.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                let Some(new_order) = Villager::order_from_str(&order.order, order.target.clone())
+                else {
+                    let packet = ResponsePacket::Error {
+                        errmsg: format!("Invalid order {:?}", order.order),
+                    };
+                    send_to_client(*player_id, packet, &clients);
                     continue;
                 };
 
-                if !is_resource {
-                    if Item::is_resource(item.clone()) {
+                let mut structure_id = -1;
+
+                if Villager::order_needs_structure(&new_order) {
+                    let Some(order_structure_id) = order.structureid else {
                         let packet = ResponsePacket::Error {
-                            errmsg: "Cannot set resource item as experiment source.".to_string(),
+                            errmsg: "Order requires a structure.".to_string(),
                         };
                         send_to_client(*player_id, packet, &clients);
                         continue;
-                    }
-                } else {
-                    if !Item::is_resource(item.clone()) {
+                    };
+
+                    let structure_owner = ids
+                        .get_entity(order_structure_id)
+                        .and_then(|structure_entity| structure_query.get(structure_entity).ok());
+
+                    if structure_owner.map(|owner| owner.0) != Some(*player_id) {
                         let packet = ResponsePacket::Error {
-                            errmsg: "Can only set resource items as an experiment reagent."
-                                .to_string(),
+                            errmsg: "Structure not owned by player.".to_string(),
                         };
                         send_to_client(*player_id, packet, &clients);
                         continue;
                     }
+
+                    structure_id = order_structure_id;
                 }
 
-                // Validation checks get source entity
-                let Some(owner_entity) = ids.get_entity(item.owner) else {
-                    error!("Cannot find villager entity for {:?}", item.owner);
-                    continue;
+                let stop = match (order.stop.as_str(), order.quantity) {
+                    (villager::STOP_ONCE, _) => Some(OrderStop::Count(1)),
+                    (villager::STOP_REPEAT, _) => Some(OrderStop::Repeat),
+                    (villager::STOP_COUNT, Some(quantity)) if quantity > 0 => {
+                        Some(OrderStop::Count(quantity))
+                    }
+                    (villager::STOP_HOLDS, Some(quantity)) if quantity > 0 => {
+                        // Defaults to the order's structure, or the villager when gathering
+                        let owner_id = order.ownerid.unwrap_or(if structure_id != -1 {
+                            structure_id
+                        } else {
+                            *villager_id
+                        });
+
+                        let owner = ids
+                            .get_entity(owner_id)
+                            .and_then(|owner_entity| owner_query.get(owner_entity).ok());
+
+                        match (owner, order.item.clone()) {
+                            (Some(owner), Some(item)) if owner.0 == *player_id => {
+                                Some(OrderStop::Holds {
+                                    owner: owner_id,
+                                    item: item,
+                                    quantity: quantity,
+                                })
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
                 };
 
-                let Ok(owner) = query.get(owner_entity) else {
-                    error!("Query failed to find entity {:?}", owner_entity);
+                let Some(stop) = stop else {
+                    let packet = ResponsePacket::Error {
+                        errmsg: format!("Invalid stop condition {:?}", order.stop),
+                    };
+                    send_to_client(*player_id, packet, &clients);
                     continue;
                 };
 
-                // Check if entity is owned by player
-                if owner.player_id.0 != *player_id {
+                if order_queue.push(new_order, structure_id, stop).is_none() {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Item owner not owned by player.".to_string(),
+                        errmsg: "Order queue is full.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
                 }
-
-                if !is_resource {
-                    if let Some(experiment) = experiments.get_mut(&item.owner) {
-                        debug!("Experiment: {:?}", experiment);
-                        if let Some(source_item) = &experiment.source_item {
-                            if source_item.id == *item_id {
-                                // Player is transfering the item source out of experiment
-                                items.remove_experiment_source(*item_id);
-                                Experiment::reset(experiment);
-
-                                send_info_experiment(
-                                    *player_id,
-                                    item.owner,
-                                    experiment.clone(),
-                                    &items,
-                                    &clients,
-                                );
-                            } else {
-                                let packet = ResponsePacket::Error {
-                                    errmsg: "Experiment source item already set.".to_string(),
-                                };
-                                send_to_client(*player_id, packet, &clients);
-                                continue;
-                            }
-                        } else {
-                            let source_item = items.set_experiment_source(*item_id);
-                            experiment.source_item = Some(source_item);
-
-                            send_info_experiment(
-                                *player_id,
-                                item.owner,
-                                experiment.clone(),
-                                &items,
-                                &clients,
-                            );
-                        }
-                    } else {
-                        // Experiment does not exist, set experiment item source and create experiment
-                        let source_item = items.set_experiment_source(*item_id);
-
-                        let experiment = Experiment::create(
-                            item.owner,
-                            None,
-                            ExperimentState::None,
-                            source_item,
-                            Vec::new(),
-                            &mut experiments,
-                        );
-
-                        send_info_experiment(
-                            *player_id,
-                            item.owner,
-                            experiment.clone(),
-                            &items,
-                            &clients,
-                        );
-                    }
-                } else {
-                    if let Some(experiment) = experiments.get(&item.owner) {
-                        if item.experiment.is_none() {
-                            items.set_experiment_reagent(*item_id);
-                        } else {
-                            items.remove_experiment_reagent(*item_id);
-                        }
-
-                        send_info_experiment(
-                            *player_id,
-                            item.owner,
-                            experiment.clone(),
-                            &items,
-                            &clients,
-                        );
-                    }
-                }
             }
-            PlayerEvent::ResetExperiment {
-                player_id,
-                structure_id,
+            PlayerEvent::MoveQueuedOrder {
+                order_id, index, ..
             } => {
-                events_to_remove.push(*event_id);
-
-                if let Some(experiment) = experiments.get_mut(structure_id) {
-                    Experiment::reset(experiment);
-
-                    send_info_experiment(
-                        *player_id,
-                        *structure_id,
-                        experiment.clone(),
-                        &items,
-                        &clients,
-                    );
+                if *index < 0 || !order_queue.move_order(*order_id, *index as usize) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Cannot move queued order.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+            }
+            PlayerEvent::CancelQueuedOrder { order_id, .. } => {
+                if !order_queue.cancel(*order_id) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Cannot find queued order.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
                 }
             }
             _ => {}
         }
+
+        let packet = ResponsePacket::InfoOrderQueue {
+            id: *villager_id,
+            orders: order_queue_to_packet(&order_queue),
+        };
+
+        send_to_client(*player_id, packet, &clients);
     }
 
     for event_id in events_to_remove.iter() {
@@ -4301,219 +4896,128 @@ fn set_experiment_item_system(
     }
 }
 
-fn hire_system(
+fn train_system(
     mut commands: Commands,
     game_tick: Res<GameTick>,
     mut events: ResMut<PlayerEvents>,
     ids: ResMut<Ids>,
     clients: Res<Clients>,
-    items: Res<Items>,
+    skills: Res<Skills>,
     mut map_events: ResMut<MapEvents>,
-    mut pos_query: Query<&mut Position>,
-    merchant_query: Query<&Transport, With<Merchant>>,
-    mut player_query: Query<&mut PlayerId>,
+    villager_query: Query<(&Id, &PlayerId, &Position, &State), With<SubclassVillager>>,
+    structure_query: Query<(&PlayerId, &Position, &State), With<ClassStructure>>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
     for (event_id, event) in events.iter() {
         match event {
-            PlayerEvent::Hire {
+            PlayerEvent::Train {
                 player_id,
-                merchant_id,
-                target_id,
+                teacher_id,
+                student_id,
+                structure_id,
+                skill_name,
             } => {
                 events_to_remove.push(*event_id);
 
-                let Some(hero_id) = ids.get_hero(*player_id) else {
-                    error!("Cannot find hero for player {:?}", *player_id);
-                    break;
+                let Some(teacher_entity) = ids.get_entity(*teacher_id) else {
+                    error!("Cannot find teacher entity for {:?}", teacher_id);
+                    continue;
                 };
 
-                let Some(hero_entity) = ids.get_entity(hero_id) else {
-                    error!("Cannot find entity for {:?}", hero_id);
+                let Some(student_entity) = ids.get_entity(*student_id) else {
+                    error!("Cannot find student entity for {:?}", student_id);
                     continue;
                 };
 
-                let Ok(hero_pos) = pos_query.get(hero_entity).copied() else {
-                    error!("Cannot find obj for {:?}", hero_entity);
+                let Some(structure_entity) = ids.get_entity(*structure_id) else {
+                    error!("Cannot find structure entity for {:?}", structure_id);
                     continue;
                 };
 
-                let Some(merchant_entity) = ids.get_entity(*merchant_id) else {
-                    error!("Cannot find entity for {:?}", merchant_id);
+                let Ok((teacher, teacher_player_id, teacher_pos, teacher_state)) =
+                    villager_query.get(teacher_entity)
+                else {
+                    error!("Query failed to find entity {:?}", teacher_entity);
                     continue;
                 };
 
-                let Ok(merchant_pos) = pos_query.get(merchant_entity).copied() else {
-                    error!("Cannot find obj for {:?}", merchant_entity);
+                let Ok((_student, student_player_id, student_pos, student_state)) =
+                    villager_query.get(student_entity)
+                else {
+                    error!("Query failed to find entity {:?}", student_entity);
                     continue;
                 };
 
-                let Ok(merchant) = merchant_query.get(merchant_entity) else {
-                    error!("Cannot find merchant component for {:?}", merchant_entity);
+                let Ok((structure_player_id, structure_pos, structure_state)) =
+                    structure_query.get(structure_entity)
+                else {
+                    error!("Query failed to find entity {:?}", structure_entity);
                     continue;
                 };
 
-                // Check if merchant is hauling target
-                let mut hauling_target = false;
-
-                for hauling_id in merchant.hauling.iter() {
-                    debug!("hauling id: {:?}", hauling_id);
-                    if hauling_id == target_id {
-                        hauling_target = true;
-                        break;
-                    }
-                }
-
-                if !hauling_target {
+                if teacher_id == student_id
+                    || teacher_player_id.0 != *player_id
+                    || student_player_id.0 != *player_id
+                    || structure_player_id.0 != *player_id
+                {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Hire target is not being hauled".to_string(),
+                        errmsg: "Invalid teacher, student or structure.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
                 }
 
-                debug!("hero gold: {:?}", items.get_total_gold(hero_id));
-
-                if items.get_total_gold(hero_id) < 25 {
+                if Obj::is_dead(teacher_state) || Obj::is_dead(student_state) {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Insufficient gold".to_string(),
+                        errmsg: "The dead cannot train.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
                 }
 
-                if !Map::is_adjacent(hero_pos, merchant_pos) {
+                if *structure_state != State::None {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Merchant is not nearby".to_string(),
+                        errmsg: "Structure is not completed.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
                 }
 
-                let Some(target_entity) = ids.get_entity(*target_id) else {
-                    error!("Cannot find entity for {:?}", target_id);
+                if Map::dist(*teacher_pos, *structure_pos) > skill::TRAIN_RANGE
+                    || Map::dist(*student_pos, *structure_pos) > skill::TRAIN_RANGE
+                {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Teacher and student must both be at the structure.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
                     continue;
-                };
-
-                let mut target_player_id = player_query.get_mut(target_entity).unwrap();
+                }
 
-                *target_player_id = PlayerId(*player_id);
+                let teacher_level = Skill::get_level(*teacher_id, skill_name, &skills);
+                let student_level = Skill::get_level(*student_id, skill_name, &skills);
 
-                let Ok(mut target_pos) = pos_query.get_mut(target_entity) else {
-                    error!("Cannot find pos for {:?}", target_entity);
+                if teacher_level <= student_level {
+                    let packet = ResponsePacket::Error {
+                        errmsg: format!("Teacher is not more skilled in {}.", skill_name),
+                    };
+                    send_to_client(*player_id, packet, &clients);
                     continue;
-                };
-
-                *target_pos = hero_pos;
+                }
 
-                // Add Move Event
-                let new_obj_event = VisibleEvent::NewObjEvent { new_player: false };
+                commands.entity(student_entity).insert(skill::Training {
+                    teacher: *teacher_id,
+                    skill: skill_name.clone(),
+                    structure: *structure_id,
+                    until: game_tick.0 + skill::TRAIN_DURATION,
+                });
 
-                map_events.new(
-                    *target_id,
-                    game_tick.0 + 1, // in the future
-                    new_obj_event,
+                Obj::add_sound_obj_event(
+                    game_tick.0,
+                    format!("Watch closely, this is how {} is done.", skill_name),
+                    teacher,
+                    &mut map_events,
                 );
-
-                commands.entity(target_entity).insert((
-                    Thirst::new(0.0, 0.10), //0.1 before
-                    Hunger::new(0.0, 0.10),
-                    Tired::new(0.0, 0.10),
-                    Morale::new(50.0),
-                    Thinker::build()
-                        .label("My Thinker")
-                        .picker(Highest)
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("EnemyDistanceScorer")
-                                .push(EnemyDistanceScorer),
-                            Flee,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("FindDrinkScorer")
-                                .push(ThirstyScorer)
-                                .push(FindDrinkScorer),
-                            FindDrink,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("DrinkDistanceScorer")
-                                .push(ThirstyScorer)
-                                .push(DrinkDistanceScorer),
-                            MoveToWaterSource,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("TransferDrinkScorer")
-                                .push(ThirstyScorer)
-                                .push(TransferDrinkScorer),
-                            TransferDrink,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("HasDrinkScorer")
-                                .push(ThirstyScorer)
-                                .push(HasDrinkScorer),
-                            Drink { until: 70.0 },
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("FindFoodScorer")
-                                .push(HungryScorer)
-                                .push(FindFoodScorer),
-                            FindFood,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("FoodDistanceScorer")
-                                .push(HungryScorer)
-                                .push(FoodDistanceScorer),
-                            MoveToFoodSource,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("TransferFoodScorer")
-                                .push(HungryScorer)
-                                .push(TransferFoodScorer),
-                            TransferFood,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("HasFoodScorer")
-                                .push(HungryScorer)
-                                .push(HasFoodScorer),
-                            Eat,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("FindShelterScorer")
-                                .push(DrowsyScorer)
-                                .push(FindShelterScorer),
-                            FindShelter,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("ShelterDistanceScorer")
-                                .push(DrowsyScorer)
-                                .push(ShelterDistanceScorer),
-                            MoveToSleepPos,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("NearShelterScorer")
-                                .push(DrowsyScorer)
-                                .push(NearShelterScorer),
-                            Sleep,
-                        )
-                        .when(
-                            ProductOfScorers::build(0.5)
-                                .label("GoodMoraleScorer")
-                                .push(GoodMorale),
-                            ProcessOrder,
-                        ),
-                ));
             }
             _ => {}
         }
@@ -4524,17 +5028,1039 @@ fn hire_system(
     }
 }
 
-fn buy_sell_system(
-    _commands: Commands,
+fn stance_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut events: ResMut<PlayerEvents>,
+    ids: ResMut<Ids>,
+    clients: Res<Clients>,
+    mut map_events: ResMut<MapEvents>,
+    villager_query: Query<(&Id, &PlayerId, &Position, &State), With<SubclassVillager>>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::SetStance {
+                player_id,
+                villager_id,
+                stance,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let Some(villager_entity) = ids.get_entity(*villager_id) else {
+                    error!("Cannot find villager entity for {:?}", villager_id);
+                    continue;
+                };
+
+                let Ok((villager, villager_player_id, villager_pos, villager_state)) =
+                    villager_query.get(villager_entity)
+                else {
+                    error!("Query failed to find entity {:?}", villager_entity);
+                    continue;
+                };
+
+                if villager_player_id.0 != *player_id {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Villager not owned by player.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                if Obj::is_dead(villager_state) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "The dead cannot take up arms.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                let Some(combat_stance) = CombatStance::from_str(stance, *villager_pos) else {
+                    let packet = ResponsePacket::Error {
+                        errmsg: format!("Invalid stance {}.", stance),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                };
+
+                let speech = match combat_stance {
+                    CombatStance::Flee => "I'll keep out of harm's way.",
+                    CombatStance::DefendSelf => "I'll defend myself if I must.",
+                    CombatStance::GuardArea { .. } => "Nothing will get past me here.",
+                    CombatStance::Militia => "I'm ready to fight!",
+                };
+
+                commands.entity(villager_entity).insert(combat_stance);
+
+                Obj::add_sound_obj_event(
+                    game_tick.0,
+                    speech.to_string(),
+                    villager,
+                    &mut map_events,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn order_queue_to_packet(order_queue: &OrderQueue) -> Vec<network::QueuedOrder> {
+    let mut order_queue_packet = Vec::new();
+
+    for queued_order in order_queue.orders.iter() {
+        let (order, target) = Villager::order_to_str(&queued_order.order);
+
+        let (stop, item, quantity, owner_id) = match &queued_order.stop {
+            OrderStop::Count(1) => (villager::STOP_ONCE, None, None, None),
+            OrderStop::Count(count) => (villager::STOP_COUNT, None, Some(*count), None),
+            OrderStop::Holds {
+                owner,
+                item,
+                quantity,
+            } => (
+                villager::STOP_HOLDS,
+                Some(item.clone()),
+                Some(*quantity),
+                Some(*owner),
+            ),
+            OrderStop::Repeat => (villager::STOP_REPEAT, None, None, None),
+        };
+
+        order_queue_packet.push(network::QueuedOrder {
+            id: queued_order.id,
+            order: order,
+            structureid: if queued_order.structure != -1 {
+                Some(queued_order.structure)
+            } else {
+                None
+            },
+            target: target,
+            stop: stop.to_string(),
+            item: item,
+            quantity: quantity,
+            ownerid: owner_id,
+            completed: queued_order.completed,
+        });
+    }
+
+    return order_queue_packet;
+}
+
+fn recipe_list_system(
+    mut events: ResMut<PlayerEvents>,
+    clients: Res<Clients>,
+    ids: ResMut<Ids>,
+    recipes: Res<Recipes>,
+    structure_query: Query<StructureQuery, With<ClassStructure>>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::RecipeList {
+                player_id,
+                structure_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let Some(structure_entity) = ids.get_entity(*structure_id) else {
+                    error!("Cannot find structure entity for {:?}", structure_id);
+                    continue;
+                };
+
+                let Ok(structure) = structure_query.get(structure_entity) else {
+                    error!("Query failed to find entity {:?}", structure_entity);
+                    continue;
+                };
+
+                let structure_recipes =
+                    recipes.get_by_structure_packet(*player_id, structure.template.0.clone());
+
+                let packet = ResponsePacket::RecipeList {
+                    result: structure_recipes,
+                };
+
+                send_to_client(*player_id, packet, &clients);
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn order_refine_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    _ids: ResMut<Ids>,
+    mut events: ResMut<PlayerEvents>,
+    mut map_events: ResMut<MapEvents>,
+    clients: Res<Clients>,
+    villager_query: Query<VillagerQuery, With<SubclassVillager>>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::OrderRefine {
+                player_id,
+                structure_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let mut villager = None;
+
+                //Find villager assigned to structure
+                for villager_item in villager_query.iter() {
+                    if villager_item.attrs.structure == *structure_id
+                        && villager_item.player_id.0 == *player_id
+                    {
+                        villager = Some(villager_item);
+                    }
+                }
+
+                if villager.is_none() {
+                    error!(
+                        "Cannot find a villager assigned to structure {:?}",
+                        *structure_id
+                    );
+                    let packet = ResponsePacket::Error {
+                        errmsg: "No villager assigned to structure to refine.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    break;
+                }
+
+                if let Some(villager) = villager {
+                    info!("Adding Order Refine to {:?}", villager.id);
+
+                    //Add speech
+                    Obj::add_sound_obj_event(
+                        game_tick.0,
+                        Villager::order_to_speech(&Order::Refine),
+                        villager.id,
+                        &mut map_events,
+                    );
+
+                    commands.entity(villager.entity).insert(Order::Refine);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn order_craft_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    _ids: ResMut<Ids>,
+    mut events: ResMut<PlayerEvents>,
+    mut map_events: ResMut<MapEvents>,
+    clients: Res<Clients>,
+    mut items: ResMut<Items>,
+    recipes: Res<Recipes>,
+    villager_query: Query<VillagerQuery, With<SubclassVillager>>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::OrderCraft {
+                player_id,
+                structure_id,
+                recipe_name,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let mut villager = None;
+
+                //Find villager assigned to structure
+                for villager_item in villager_query.iter() {
+                    if villager_item.attrs.structure == *structure_id
+                        && villager_item.player_id.0 == *player_id
+                    {
+                        villager = Some(villager_item);
+                    }
+                }
+
+                if villager.is_none() {
+                    error!(
+                        "Cannot find a villager assigned to structure {:?}",
+                        *structure_id
+                    );
+                    let packet = ResponsePacket::Error {
+                        errmsg: "No villager assigned to structure to refine.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    break;
+                }
+
+                let recipe = recipes.get_by_name(recipe_name.clone());
+
+                if recipe.is_none() {
+                    error!("Invalid recipe name {:?}", *recipe_name);
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Invalid recipe".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    break;
+                }
+
+                if let Some(mut recipe) = recipe {
+                    //TODO consider if checking reqs is required here
+                    if Structure::has_req(*structure_id, &mut recipe.req, &mut items) {
+                        if let Some(villager) = villager {
+                            info!("Adding Order Craft to {:?}", villager.id);
+                            commands.entity(villager.entity).insert(Order::Craft {
+                                recipe_name: recipe_name.clone(),
+                            });
+
+                            //Add speech
+                            Obj::add_sound_obj_event(
+                                game_tick.0,
+                                Villager::order_to_speech(&Order::Craft {
+                                    recipe_name: recipe_name.to_string(),
+                                }),
+                                villager.id,
+                                &mut map_events,
+                            );
+                        }
+                    } else {
+                        error!("Insufficient resources to craft {:?}", *recipe_name);
+                        let packet = ResponsePacket::Error {
+                            errmsg: "Insufficient resources to craft".to_string(),
+                        };
+                        send_to_client(*player_id, packet, &clients);
+                        break;
+                    }
+                } else {
+                    error!("Cannot find recipe: {:?}", *recipe_name);
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Cannot find recipe".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn order_explore_system(
+    mut events: ResMut<PlayerEvents>,
+    game_tick: Res<GameTick>,
+    ids: ResMut<Ids>,
+    mut commands: Commands,
+    mut map_events: ResMut<MapEvents>,
+    clients: Res<Clients>,
+    query: Query<MapObjQuery>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::OrderExplore {
+                player_id,
+                villager_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let Some(entity) = ids.get_entity(*villager_id) else {
+                    error!("Cannot find entity for {:?}", villager_id);
+                    break;
+                };
+
+                let Ok(villager) = query.get(entity) else {
+                    error!("Cannot find villager for {:?}", entity);
+                    break;
+                };
+
+                if villager.player_id.0 != *player_id {
+                    error!("Villager not owned by player {:?}", *player_id);
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Cannot order another player's villager".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    break;
+                }
+
+                // Add OrderFollow component to source and set hero_entity as target
+                for q in &query {
+                    if q.id.0 == *villager_id {
+                        //Add speech
+                        Obj::add_sound_obj_event(
+                            game_tick.0,
+                            Villager::order_to_speech(&Order::Explore),
+                            villager.id,
+                            &mut map_events,
+                        );
+
+                        commands.entity(q.entity).insert(Order::Explore);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn order_experiment_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    _ids: ResMut<Ids>,
+    mut events: ResMut<PlayerEvents>,
+    mut map_events: ResMut<MapEvents>,
+    items: ResMut<Items>,
+    mut experiments: ResMut<Experiments>,
+    _templates: Res<Templates>,
+    active_infos: Res<ActiveInfos>,
+    clients: Res<Clients>,
+    villager_query: Query<VillagerQuery, With<SubclassVillager>>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::OrderExperiment {
+                player_id,
+                structure_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let mut villager = None;
+
+                //Find villager assigned to structure
+                for villager_item in villager_query.iter() {
+                    if villager_item.attrs.structure == *structure_id
+                        && villager_item.player_id.0 == *player_id
+                    {
+                        villager = Some(villager_item);
+                    }
+                }
+
+                if villager.is_none() {
+                    error!(
+                        "Cannot find a villager assigned to structure {:?}",
+                        *structure_id
+                    );
+                    let packet = ResponsePacket::Error {
+                        errmsg: "No villager assigned to structure to refine.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    break;
+                }
+
+                if let Some(villager) = villager {
+                    info!("Adding Order Experiment to {:?}", villager.id);
+
+                    // Update experiment state to progressing
+                    let updated_experiment = Experiment::update_state(
+                        villager.attrs.structure,
+                        experiment::ExperimentState::Waiting,
+                        &mut experiments,
+                    );
+
+                    if let Some(updated_experiment) = updated_experiment {
+                        active_info_experiment(
+                            villager.player_id.0,
+                            villager.attrs.structure,
+                            updated_experiment,
+                            &items,
+                            &active_infos,
+                            &clients,
+                        );
+                    }
+
+                    commands.entity(villager.entity).insert(Order::Experiment);
+
+                    Obj::add_sound_obj_event(
+                        game_tick.0,
+                        Villager::order_to_speech(&Order::Experiment),
+                        villager.id,
+                        &mut map_events,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn order_farm_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    _ids: ResMut<Ids>,
+    mut events: ResMut<PlayerEvents>,
+    mut map_events: ResMut<MapEvents>,
+    items: ResMut<Items>,
+    mut experiments: ResMut<Experiments>,
+    _templates: Res<Templates>,
+    active_infos: Res<ActiveInfos>,
+    clients: Res<Clients>,
+    villager_query: Query<VillagerQuery, With<SubclassVillager>>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::OrderPlant {
+                player_id,
+                structure_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                // Find all villagers assigned to farm and order them to plant
+                for villager_item in villager_query.iter() {
+                    if villager_item.attrs.structure == *structure_id
+                        && villager_item.player_id.0 == *player_id
+                    {
+                        commands.entity(villager_item.entity).insert(Order::Plant);
+
+                        Obj::add_sound_obj_event(
+                            game_tick.0,
+                            Villager::order_to_speech(&Order::Plant),
+                            villager_item.id,
+                            &mut map_events,
+                        );
+                    }
+                }                
+            }
+            PlayerEvent::OrderHarvest {
+                player_id,
+                structure_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                // Find all villagers assigned to farm and order them to plant
+                for villager_item in villager_query.iter() {
+                    if villager_item.attrs.structure == *structure_id
+                        && villager_item.player_id.0 == *player_id
+                    {
+                        commands.entity(villager_item.entity).insert(Order::Harvest);
+
+                        Obj::add_sound_obj_event(
+                            game_tick.0,
+                            Villager::order_to_speech(&Order::Harvest),
+                            villager_item.id,
+                            &mut map_events,
+                        );
+                    }
+                }                
+            }            
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+
+fn use_item_system(
+    mut events: ResMut<PlayerEvents>,
+    game_tick: Res<GameTick>,
+    ids: ResMut<Ids>,
+    _commands: Commands,
+    clients: Res<Clients>,
+    items: ResMut<Items>,
+    mut map_events: ResMut<MapEvents>,
+    query: Query<MapObjQuery>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::Use { player_id, item_id } => {
+                events_to_remove.push(*event_id);
+
+                let Some(item) = items.find_by_id(*item_id) else {
+                    debug!("Failed to find item: {:?}", item_id);
+                    continue;
+                };
+
+                // Validation checks get source entity
+                let Some(owner_entity) = ids.get_entity(item.owner) else {
+                    error!("Cannot find villager entity for {:?}", item.owner);
+                    continue;
+                };
+
+                let Ok(owner) = query.get(owner_entity) else {
+                    error!("Query failed to find entity {:?}", owner_entity);
+                    continue;
+                };
+
+                if Obj::is_dead(&owner.state) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "The dead cannot use items.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                // Check if entity is owned by player
+                if owner.player_id.0 != *player_id {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Item not owned by player.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+                // Insert explore event
+                let use_item_event = VisibleEvent::UseItemEvent {
+                    item_id: *item_id,
+                    item_owner_id: owner.id.0,
+                };
+
+                map_events.new(owner.id.0, game_tick.0 + 1, use_item_event);
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn remove_system(
+    mut events: ResMut<PlayerEvents>,
+    game_tick: Res<GameTick>,
+    ids: ResMut<Ids>,
+    clients: Res<Clients>,
+    mut map_events: ResMut<MapEvents>,
+    query: Query<MapObjQuery>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::Remove {
+                player_id,
+                structure_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let Some(entity) = ids.get_entity(*structure_id) else {
+                    error!("Cannot find entity for {:?}", structure_id);
+                    continue;
+                };
+
+                let Ok(obj) = query.get(entity) else {
+                    error!("Cannot find obj for {:?}", entity);
+                    continue;
+                };
+
+                // Check if entity is owned by player
+                if obj.player_id.0 != *player_id {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Obj not owned by player.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                debug!("Removing obj: {:?}", obj.id.0);
+
+                map_events.new(
+                    obj.id.0,
+                    game_tick.0 + 1,
+                    VisibleEvent::RemoveObjEvent {
+                        pos: obj.pos.to_owned(),
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn set_experiment_item_system(
+    mut events: ResMut<PlayerEvents>,
     _game_tick: Res<GameTick>,
+    ids: ResMut<Ids>,
+    clients: Res<Clients>,
+    _map_events: ResMut<MapEvents>,
+    mut items: ResMut<Items>,
+    mut experiments: ResMut<Experiments>,
+    query: Query<MapObjQuery>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::SetExperimentItem {
+                player_id,
+                item_id,
+                is_resource,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let Some(item) = items.find_by_id(*item_id) else {
+                    debug!("Failed to find item: {:?}", item_id);
+                    continue;
+                };
+
+                if !is_resource {
+                    if Item::is_resource(item.clone()) {
+                        let packet = ResponsePacket::Error {
+                            errmsg: "Cannot set resource item as experiment source.".to_string(),
+                        };
+                        send_to_client(*player_id, packet, &clients);
+                        continue;
+                    }
+                } else {
+                    if !Item::is_resource(item.clone()) {
+                        let packet = ResponsePacket::Error {
+                            errmsg: "Can only set resource items as an experiment reagent."
+                                .to_string(),
+                        };
+                        send_to_client(*player_id, packet, &clients);
+                        continue;
+                    }
+                }
+
+                // Validation checks get source entity
+                let Some(owner_entity) = ids.get_entity(item.owner) else {
+                    error!("Cannot find villager entity for {:?}", item.owner);
+                    continue;
+                };
+
+                let Ok(owner) = query.get(owner_entity) else {
+                    error!("Query failed to find entity {:?}", owner_entity);
+                    continue;
+                };
+
+                // Check if entity is owned by player
+                if owner.player_id.0 != *player_id {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Item owner not owned by player.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                if !is_resource {
+                    if let Some(experiment) = experiments.get_mut(&item.owner) {
+                        debug!("Experiment: {:?}", experiment);
+                        if let Some(source_item) = &experiment.source_item {
+                            if source_item.id == *item_id {
+                                // Player is transfering the item source out of experiment
+                                items.remove_experiment_source(*item_id);
+                                Experiment::reset(experiment);
+
+                                send_info_experiment(
+                                    *player_id,
+                                    item.owner,
+                                    experiment.clone(),
+                                    &items,
+                                    &clients,
+                                );
+                            } else {
+                                let packet = ResponsePacket::Error {
+                                    errmsg: "Experiment source item already set.".to_string(),
+                                };
+                                send_to_client(*player_id, packet, &clients);
+                                continue;
+                            }
+                        } else {
+                            let source_item = items.set_experiment_source(*item_id);
+                            experiment.source_item = Some(source_item);
+
+                            send_info_experiment(
+                                *player_id,
+                                item.owner,
+                                experiment.clone(),
+                                &items,
+                                &clients,
+                            );
+                        }
+                    } else {
+                        // Experiment does not exist, set experiment item source and create experiment
+                        let source_item = items.set_experiment_source(*item_id);
+
+                        let experiment = Experiment::create(
+                            item.owner,
+                            None,
+                            ExperimentState::None,
+                            source_item,
+                            Vec::new(),
+                            &mut experiments,
+                        );
+
+                        send_info_experiment(
+                            *player_id,
+                            item.owner,
+                            experiment.clone(),
+                            &items,
+                            &clients,
+                        );
+                    }
+                } else {
+                    if let Some(experiment) = experiments.get(&item.owner) {
+                        if item.experiment.is_none() {
+                            items.set_experiment_reagent(*item_id);
+                        } else {
+                            items.remove_experiment_reagent(*item_id);
+                        }
+
+                        send_info_experiment(
+                            *player_id,
+                            item.owner,
+                            experiment.clone(),
+                            &items,
+                            &clients,
+                        );
+                    }
+                }
+            }
+            PlayerEvent::ResetExperiment {
+                player_id,
+                structure_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                if let Some(experiment) = experiments.get_mut(structure_id) {
+                    Experiment::reset(experiment);
+
+                    send_info_experiment(
+                        *player_id,
+                        *structure_id,
+                        experiment.clone(),
+                        &items,
+                        &clients,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn hire_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut events: ResMut<PlayerEvents>,
+    mut ids: ResMut<Ids>,
+    clients: Res<Clients>,
+    mut items: ResMut<Items>,
+    skills: Res<Skills>,
+    templates: Res<Templates>,
+    mut map_events: ResMut<MapEvents>,
+    mut pos_query: Query<&mut Position>,
+    mut merchant_query: Query<&mut Transport, With<Merchant>>,
+    mut player_query: Query<&mut PlayerId>,
+    personality_query: Query<&Personality>,
+    contract_query: Query<&Contract>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::Hire {
+                player_id,
+                merchant_id,
+                target_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let Some(hero_id) = ids.get_hero(*player_id) else {
+                    error!("Cannot find hero for player {:?}", *player_id);
+                    break;
+                };
+
+                let Some(hero_entity) = ids.get_entity(hero_id) else {
+                    error!("Cannot find entity for {:?}", hero_id);
+                    continue;
+                };
+
+                let Ok(hero_pos) = pos_query.get(hero_entity).copied() else {
+                    error!("Cannot find obj for {:?}", hero_entity);
+                    continue;
+                };
+
+                let Some(merchant_entity) = ids.get_entity(*merchant_id) else {
+                    error!("Cannot find entity for {:?}", merchant_id);
+                    continue;
+                };
+
+                let Ok(merchant_pos) = pos_query.get(merchant_entity).copied() else {
+                    error!("Cannot find obj for {:?}", merchant_entity);
+                    continue;
+                };
+
+                let Ok(merchant) = merchant_query.get(merchant_entity) else {
+                    error!("Cannot find merchant component for {:?}", merchant_entity);
+                    continue;
+                };
+
+                // Check if merchant is hauling target
+                let mut hauling_target = false;
+
+                for hauling_id in merchant.hauling.iter() {
+                    debug!("hauling id: {:?}", hauling_id);
+                    if hauling_id == target_id {
+                        hauling_target = true;
+                        break;
+                    }
+                }
+
+                if !hauling_target {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Hire target is not being hauled".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                let Some(target_entity) = ids.get_entity(*target_id) else {
+                    error!("Cannot find entity for {:?}", target_id);
+                    continue;
+                };
+
+                let Ok(target_player_id) = player_query.get(target_entity) else {
+                    error!("Cannot find player for {:?}", target_entity);
+                    continue;
+                };
+
+                if let Err(errmsg) =
+                    Contract::can_hire(target_player_id.0, contract_query.get(target_entity).ok())
+                {
+                    let packet = ResponsePacket::Error { errmsg: errmsg };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                debug!("hero gold: {:?}", items.get_total_gold(hero_id));
+
+                let wage = Contract::wage(&Skill::get_levels_by_owner(*target_id, &skills));
+
+                // First day's wage is paid up front
+                if items.get_total_gold(hero_id) < wage {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Insufficient gold".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                if !Map::is_adjacent(hero_pos, merchant_pos) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Merchant is not nearby".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                let mut target_player_id = player_query.get_mut(target_entity).unwrap();
+
+                *target_player_id = PlayerId(*player_id);
+                ids.set_player(*target_id, *player_id);
+
+                // No longer up for hire
+                if let Ok(mut merchant) = merchant_query.get_mut(merchant_entity) {
+                    merchant
+                        .hauling
+                        .retain(|hauling_id| hauling_id != target_id);
+                }
+
+                items.set_reason(ledger::REASON_WAGES);
+                items.remove_gold(hero_id, wage);
+                items.clear_reason();
+
+                let Ok(mut target_pos) = pos_query.get_mut(target_entity) else {
+                    error!("Cannot find pos for {:?}", target_entity);
+                    continue;
+                };
+
+                *target_pos = hero_pos;
+
+                // Add Move Event
+                let new_obj_event = VisibleEvent::NewObjEvent { new_player: false };
+
+                map_events.new(
+                    *target_id,
+                    game_tick.0 + 1, // in the future
+                    new_obj_event,
+                );
+
+                // Villagers without a personality have no trait modifiers
+                let (thirst_rate, hunger_rate, tired_rate, schedule_shift) =
+                    match personality_query.get(target_entity) {
+                        Ok(personality) => (
+                            personality.thirst_rate,
+                            personality.hunger_rate,
+                            personality.tired_rate,
+                            personality.schedule_shift,
+                        ),
+                        Err(_) => (1.0, 1.0, 1.0, 0),
+                    };
+
+                commands.entity(target_entity).insert((
+                    Thirst::new(0.0, 0.10 * thirst_rate), //0.1 before
+                    Hunger::new(0.0, 0.10 * hunger_rate),
+                    Tired::new(0.0, 0.10 * tired_rate),
+                    Morale::new(50.0),
+                    Schedule::default().shifted(schedule_shift),
+                    OrderQueue::default(),
+                    ThreatTable::default(),
+                    Relationships::default(),
+                    CombatStance::Flee,
+                    Contract::new(wage, game_tick.0),
+                    AiThinker::build("Hired Villager", &ThinkerContext::default(), &templates),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn buy_sell_system(
     mut events: ResMut<PlayerEvents>,
     ids: ResMut<Ids>,
     clients: Res<Clients>,
     mut items: ResMut<Items>,
-    _map_events: ResMut<MapEvents>,
+    templates: Res<Templates>,
+    mut reputations: ResMut<Reputations>,
     pos_query: Query<&mut Position>,
-    _merchant_query: Query<&Merchant>,
-    _player_query: Query<&mut PlayerId>,
+    merchant_query: Query<(Option<&Transport>, Option<&Docked>), With<Merchant>>,
+    hero_query: Query<(&Template, Option<&BaseAttrs>)>,
 ) {
     let mut events_to_remove: Vec<i32> = Vec::new();
 
@@ -4577,7 +6103,38 @@ fn buy_sell_system(
                     continue;
                 };
 
-                if items.get_total_gold(hero_id) < 10 {
+                let merchant_id = item.owner;
+
+                // All checks happen before any gold or item moves
+                if let Err(errmsg) = check_trade(
+                    &item,
+                    *quantity,
+                    is_merchant_open(merchant_entity, &merchant_query),
+                ) {
+                    let packet = ResponsePacket::Error { errmsg: errmsg };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                if !Map::is_adjacent(hero_pos, merchant_pos) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Merchant is not nearby".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                let quote = Pricing::quote_with_items(
+                    Trade::Buy,
+                    &item,
+                    *quantity,
+                    merchant_id,
+                    reputations.get(*player_id),
+                    &items,
+                    &templates.item_templates,
+                );
+
+                if items.get_total_gold(hero_id) < quote.total {
                     let packet = ResponsePacket::Error {
                         errmsg: "Insufficient gold".to_string(),
                     };
@@ -4585,24 +6142,159 @@ fn buy_sell_system(
                     continue;
                 }
 
-                if !Map::is_adjacent(hero_pos, merchant_pos) {
+                let Ok((hero_template, hero_attrs)) = hero_query.get(hero_entity) else {
+                    error!("Cannot find hero for {:?}", hero_entity);
+                    continue;
+                };
+
+                let capacity =
+                    Encumbrance::capacity(&hero_template.0, hero_attrs, &templates.obj_templates);
+                let bought_weight = (*quantity as f32 * item.weight) as i32;
+
+                if items.get_total_weight(hero_id) + bought_weight
+                    > Encumbrance::hard_limit(capacity, hero_attrs)
+                {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "You cannot carry that.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                items.set_reason(ledger::REASON_BUY);
+                items.transfer_gold(hero_id, merchant_id, quote.total);
+                items.transfer_quantity(item.id, hero_id, *quantity);
+                items.clear_reason();
+
+                reputations.add_trade(*player_id, quote.total);
+
+                let mut item_filter = Vec::new();
+                item_filter.push(item::GOLD.to_string());
+
+                let source_items = items.get_by_owner_packet(hero_id);
+                let target_items = items.get_by_owner_packet_filter(merchant_id, item_filter);
+
+                let source_inventory = network::Inventory {
+                    id: hero_id,
+                    cap: 0,
+                    tw: 0,
+                    items: source_items.clone(),
+                };
+
+                let target_inventory = network::Inventory {
+                    id: merchant_id,
+                    cap: 0,
+                    tw: 0,
+                    items: target_items.clone(),
+                };
+
+                let item_transfer_packet: ResponsePacket = ResponsePacket::BuyItem {
+                    sourceid: hero_id,
+                    sourceitems: source_inventory,
+                    targetid: merchant_id,
+                    targetitems: target_inventory,
+                };
+
+                send_to_client(*player_id, item_transfer_packet, &clients);
+            }
+            PlayerEvent::SellItem {
+                player_id,
+                item_id,
+                target_id,
+                quantity,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let Some(hero_id) = ids.get_hero(*player_id) else {
+                    error!("Cannot find hero for player {:?}", *player_id);
+                    continue;
+                };
+
+                let Some(item) = items.find_by_id(*item_id) else {
+                    debug!("Failed to find item: {:?}", item_id);
+                    continue;
+                };
+
+                let Some(hero_entity) = ids.get_entity(hero_id) else {
+                    error!("Cannot find entity for {:?}", hero_id);
+                    continue;
+                };
+
+                let Ok(hero_pos) = pos_query.get(hero_entity).copied() else {
+                    error!("Cannot find obj for {:?}", hero_entity);
+                    continue;
+                };
+
+                let Some(merchant_entity) = ids.get_entity(*target_id) else {
+                    error!("Cannot find entity for {:?}", target_id);
+                    continue;
+                };
+
+                let Ok(merchant_pos) = pos_query.get(merchant_entity).copied() else {
+                    error!("Cannot find obj for {:?}", merchant_entity);
+                    continue;
+                };
+
+                if item.owner != hero_id {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Item is not owned by you.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                if let Err(errmsg) = check_trade(
+                    &item,
+                    *quantity,
+                    is_merchant_open(merchant_entity, &merchant_query),
+                ) {
+                    let packet = ResponsePacket::Error { errmsg: errmsg };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                debug!("Hero Pos: {:?} Merchant Pos: {:?}", hero_pos, merchant_pos);
+
+                if !Map::is_adjacent(hero_pos, merchant_pos) {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Merchant is not nearby".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                let quote = Pricing::quote_with_items(
+                    Trade::Sell,
+                    &item,
+                    *quantity,
+                    *target_id,
+                    reputations.get(*player_id),
+                    &items,
+                    &templates.item_templates,
+                );
+
+                if items.get_total_gold(*target_id) < quote.total {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Merchant is not nearby".to_string(),
+                        errmsg: "Merchant cannot afford this".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
                 }
 
-                let merchant_id = item.owner;
+                // TODO check if target has the space to hold the item
 
-                items.transfer_gold(hero_id, merchant_id, 25);
-                items.transfer_quantity(item.id, hero_id, *quantity);
+                items.set_reason(ledger::REASON_SELL);
+                items.transfer_gold(*target_id, hero_id, quote.total);
+                items.transfer_quantity(*item_id, *target_id, *quantity);
+                items.clear_reason();
+
+                reputations.add_trade(*player_id, quote.total);
 
                 let mut item_filter = Vec::new();
                 item_filter.push(item::GOLD.to_string());
 
                 let source_items = items.get_by_owner_packet(hero_id);
-                let target_items = items.get_by_owner_packet_filter(merchant_id, item_filter);
+                let target_items = items.get_by_owner_packet_filter(*target_id, item_filter);
 
                 let source_inventory = network::Inventory {
                     id: hero_id,
@@ -4612,25 +6304,82 @@ fn buy_sell_system(
                 };
 
                 let target_inventory = network::Inventory {
-                    id: merchant_id,
+                    id: *target_id,
                     cap: 0,
                     tw: 0,
                     items: target_items.clone(),
                 };
 
-                let item_transfer_packet: ResponsePacket = ResponsePacket::BuyItem {
+                let item_transfer_packet: ResponsePacket = ResponsePacket::SellItem {
                     sourceid: hero_id,
                     sourceitems: source_inventory,
-                    targetid: merchant_id,
+                    targetid: *target_id,
                     targetitems: target_inventory,
                 };
 
                 send_to_client(*player_id, item_transfer_packet, &clients);
             }
-            PlayerEvent::SellItem {
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn is_merchant_open(
+    merchant_entity: Entity,
+    merchant_query: &Query<(Option<&Transport>, Option<&Docked>), With<Merchant>>,
+) -> bool {
+    let Ok((transport, docked)) = merchant_query.get(merchant_entity) else {
+        return false;
+    };
+
+    return MerchantShips::is_open(transport, docked);
+}
+
+fn check_trade(item: &Item, quantity: i32, is_open: bool) -> Result<(), String> {
+    if !is_open {
+        return Err("Merchant is not open for trade".to_string());
+    }
+
+    if quantity <= 0 {
+        return Err("Invalid quantity".to_string());
+    }
+
+    if item.class == item::GOLD {
+        return Err("Gold cannot be traded".to_string());
+    }
+
+    if item.equipped {
+        return Err("Item is equipped".to_string());
+    }
+
+    if item.quantity < quantity {
+        return Err("Insufficient quantity".to_string());
+    }
+
+    return Ok(());
+}
+
+fn info_price_system(
+    mut events: ResMut<PlayerEvents>,
+    ids: Res<Ids>,
+    clients: Res<Clients>,
+    items: Res<Items>,
+    templates: Res<Templates>,
+    reputations: Res<Reputations>,
+    merchant_query: Query<(Option<&Transport>, Option<&Docked>), With<Merchant>>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::InfoPrice {
                 player_id,
                 item_id,
-                target_id,
+                merchant_id,
                 quantity,
             } => {
                 events_to_remove.push(*event_id);
@@ -4645,82 +6394,663 @@ fn buy_sell_system(
                     continue;
                 };
 
-                let Some(hero_entity) = ids.get_entity(hero_id) else {
-                    error!("Cannot find entity for {:?}", hero_id);
+                let Some(merchant_entity) = ids.get_entity(*merchant_id) else {
+                    error!("Cannot find entity for {:?}", merchant_id);
                     continue;
                 };
 
-                let Ok(hero_pos) = pos_query.get(hero_entity).copied() else {
-                    error!("Cannot find obj for {:?}", hero_entity);
+                // Items held by the merchant are bought, items held by the hero are sold
+                let trade = if item.owner == *merchant_id {
+                    Trade::Buy
+                } else if item.owner == hero_id {
+                    Trade::Sell
+                } else {
+                    let packet = ResponsePacket::Error {
+                        errmsg: "Item is not owned by you or the merchant.".to_string(),
+                    };
+                    send_to_client(*player_id, packet, &clients);
                     continue;
                 };
 
-                let Some(merchant_entity) = ids.get_entity(*target_id) else {
-                    error!("Cannot find entity for {:?}", item.owner);
+                if let Err(errmsg) = check_trade(
+                    &item,
+                    *quantity,
+                    is_merchant_open(merchant_entity, &merchant_query),
+                ) {
+                    let packet = ResponsePacket::Error { errmsg: errmsg };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                }
+
+                let quote = Pricing::quote_with_items(
+                    trade,
+                    &item,
+                    *quantity,
+                    *merchant_id,
+                    reputations.get(*player_id),
+                    &items,
+                    &templates.item_templates,
+                );
+
+                let info_price_packet = ResponsePacket::InfoPrice {
+                    itemid: *item_id,
+                    merchantid: *merchant_id,
+                    action: quote.trade.to_str().to_string(),
+                    quantity: quote.quantity,
+                    base_value: quote.base_value,
+                    quality_mod: quote.quality_mod,
+                    property_mod: quote.property_mod,
+                    supply_mod: quote.supply_mod,
+                    margin: quote.margin,
+                    discount: quote.discount,
+                    unit_price: quote.unit_price,
+                    total: quote.total,
+                };
+
+                send_to_client(*player_id, info_price_packet, &clients);
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn trade_system(
+    mut events: ResMut<PlayerEvents>,
+    game_tick: Res<GameTick>,
+    clients: Res<Clients>,
+    mut ids: ResMut<Ids>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    mut trades: ResMut<Trades>,
+    hero_query: Query<
+        (&PlayerId, &Position, &State, &Template, Option<&BaseAttrs>),
+        With<SubclassHero>,
+    >,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        let (player_id, result) = match event {
+            PlayerEvent::TradeRequest {
+                player_id,
+                target_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let Some(hero_id) = ids.get_hero(*player_id) else {
+                    error!("Cannot find hero for player {:?}", *player_id);
                     continue;
                 };
 
-                let Ok(merchant_pos) = pos_query.get(merchant_entity).copied() else {
-                    error!("Cannot find obj for {:?}", merchant_entity);
+                let Some(hero_entity) = ids.get_entity(hero_id) else {
+                    error!("Cannot find entity for {:?}", hero_id);
                     continue;
                 };
 
-                if item.owner != *player_id {
-                    let packet = ResponsePacket::Error {
-                        errmsg: "Item is not owned by you.".to_string(),
-                    };
-                    send_to_client(*player_id, packet, &clients);
+                let Ok((_hero_player_id, hero_pos, hero_state, _template, _attrs)) =
+                    hero_query.get(hero_entity)
+                else {
+                    error!("Cannot find hero for {:?}", hero_entity);
+                    continue;
+                };
+
+                let target = ids
+                    .get_entity(*target_id)
+                    .and_then(|target_entity| hero_query.get(target_entity).ok());
+
+                let result = match target {
+                    None => Err("You can only trade with another hero.".to_string()),
+                    Some((target_player_id, _pos, _state, _template, _attrs))
+                        if target_player_id.0 == *player_id =>
+                    {
+                        Err("You can only trade with another hero.".to_string())
+                    }
+                    Some((_target_player_id, _pos, target_state, _template, _attrs))
+                        if Obj::is_dead(hero_state) || Obj::is_dead(target_state) =>
+                    {
+                        Err("Cannot trade with the dead.".to_string())
+                    }
+                    Some((_target_player_id, target_pos, _state, _template, _attrs))
+                        if !Map::is_adjacent(*hero_pos, *target_pos) =>
+                    {
+                        Err("Target is not nearby.".to_string())
+                    }
+                    Some((target_player_id, _pos, _state, _template, _attrs)) => {
+                        trades.open(*player_id, hero_id, target_player_id.0, *target_id, &mut ids)
+                    }
+                };
+
+                (player_id, result)
+            }
+            PlayerEvent::TradeOffer {
+                player_id,
+                trade_id,
+                item_id,
+                quantity,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let result = trades
+                    .offer(*trade_id, *player_id, *item_id, *quantity, &mut items)
+                    .map(|_| *trade_id);
+
+                (player_id, result)
+            }
+            PlayerEvent::TradeWithdraw {
+                player_id,
+                trade_id,
+                item_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let result = trades
+                    .withdraw(*trade_id, *player_id, *item_id, &mut items)
+                    .map(|_| *trade_id);
+
+                (player_id, result)
+            }
+            PlayerEvent::TradeAccept {
+                player_id,
+                trade_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let result = trades.accept(*trade_id, *player_id).map(|_| *trade_id);
+
+                (player_id, result)
+            }
+            PlayerEvent::TradeConfirm {
+                player_id,
+                trade_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let session = trades.sessions.get(trade_id).cloned();
+
+                let hard_limit = |hero_id: i32| {
+                    ids.get_entity(hero_id)
+                        .and_then(|hero_entity| hero_query.get(hero_entity).ok())
+                        .map_or(0, |(_player_id, _pos, _state, template, attrs)| {
+                            let capacity =
+                                Encumbrance::capacity(&template.0, attrs, &templates.obj_templates);
+                            Encumbrance::hard_limit(capacity, attrs)
+                        })
+                };
+
+                match trades.confirm(*trade_id, *player_id, hard_limit, &mut items, &game_tick) {
+                    Ok(true) => {
+                        if let Some(session) = session {
+                            close_trade(
+                                &session,
+                                true,
+                                trade::REASON_COMPLETED,
+                                &ids,
+                                &items,
+                                &templates,
+                                &clients,
+                                &hero_query,
+                            );
+                        }
+                        continue;
+                    }
+                    result => (player_id, result.map(|_| *trade_id)),
+                }
+            }
+            PlayerEvent::TradeCancel {
+                player_id,
+                trade_id,
+            } => {
+                events_to_remove.push(*event_id);
+
+                let is_party = trades
+                    .sessions
+                    .get(trade_id)
+                    .is_some_and(|session| session.party_index(*player_id).is_some());
+
+                if !is_party {
+                    (player_id, Err("Trade does not exist.".to_string()))
+                } else {
+                    if let Some(session) = trades.cancel(*trade_id, &mut items) {
+                        close_trade(
+                            &session,
+                            false,
+                            trade::REASON_CANCELLED,
+                            &ids,
+                            &items,
+                            &templates,
+                            &clients,
+                            &hero_query,
+                        );
+                    }
                     continue;
                 }
+            }
+            _ => continue,
+        };
 
-                debug!("Hero Pos: {:?} Merchant Pos: {:?}", hero_pos, merchant_pos);
+        let trade_id = match result {
+            Ok(trade_id) => trade_id,
+            Err(errmsg) => {
+                let packet = ResponsePacket::Error { errmsg: errmsg };
+                send_to_client(*player_id, packet, &clients);
+                continue;
+            }
+        };
+
+        let Some(session) = trades.sessions.get(&trade_id) else {
+            continue;
+        };
+
+        // Both traders see the offers, the acting trader's inventory changes with escrow
+        for party in session.parties.iter() {
+            let update_packet = Trades::update_packet(session, &items);
+            send_to_client(party.player_id, update_packet, &clients);
+        }
+
+        if let Some(index) = session.party_index(*player_id) {
+            send_trade_inventory(
+                &session.parties[index],
+                &ids,
+                &items,
+                &templates,
+                &clients,
+                &hero_query,
+            );
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn close_trade(
+    session: &TradeSession,
+    completed: bool,
+    reason: &str,
+    ids: &ResMut<Ids>,
+    items: &ResMut<Items>,
+    templates: &Res<Templates>,
+    clients: &Res<Clients>,
+    hero_query: &Query<
+        (&PlayerId, &Position, &State, &Template, Option<&BaseAttrs>),
+        With<SubclassHero>,
+    >,
+) {
+    for party in session.parties.iter() {
+        let closed_packet = ResponsePacket::TradeClosed {
+            tradeid: session.id,
+            completed: completed,
+            reason: reason.to_string(),
+        };
+
+        send_to_client(party.player_id, closed_packet, clients);
+
+        send_trade_inventory(party, ids, items, templates, clients, hero_query);
+    }
+}
+
+fn send_trade_inventory(
+    party: &TradeParty,
+    ids: &ResMut<Ids>,
+    items: &ResMut<Items>,
+    templates: &Res<Templates>,
+    clients: &Res<Clients>,
+    hero_query: &Query<
+        (&PlayerId, &Position, &State, &Template, Option<&BaseAttrs>),
+        With<SubclassHero>,
+    >,
+) {
+    let Some(hero_entity) = ids.get_entity(party.hero_id) else {
+        error!("Cannot find entity for {:?}", party.hero_id);
+        return;
+    };
+
+    let Ok((_player_id, _pos, _state, template, attrs)) = hero_query.get(hero_entity) else {
+        error!("Cannot find hero for {:?}", hero_entity);
+        return;
+    };
+
+    let inventory_packet =
+        Trades::inventory_packet(party.hero_id, &template.0, attrs, items, templates);
+
+    send_to_client(party.player_id, inventory_packet, clients);
+}
+
+fn market_system(
+    mut events: ResMut<PlayerEvents>,
+    game_tick: Res<GameTick>,
+    clients: Res<Clients>,
+    mut ids: ResMut<Ids>,
+    mut items: ResMut<Items>,
+    mut markets: ResMut<Markets>,
+    templates: Res<Templates>,
+    hero_query: Query<(&Position, &State, &Template, Option<&BaseAttrs>), With<SubclassHero>>,
+    structure_query: Query<(&PlayerId, &Position, &State, &Subclass), With<ClassStructure>>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        let (player_id, market_id) = match event {
+            PlayerEvent::MarketList { player_id, market_id, .. }
+            | PlayerEvent::MarketSearch { player_id, market_id, .. } => (player_id, *market_id),
+            PlayerEvent::MarketBuy { player_id, listing_id }
+            | PlayerEvent::MarketBid { player_id, listing_id, .. } => {
+                let Some(listing) = markets.listings.iter().find(|l| l.id == *listing_id) else {
+                    events_to_remove.push(*event_id);
 
-                if !Map::is_adjacent(hero_pos, merchant_pos) {
                     let packet = ResponsePacket::Error {
-                        errmsg: "Merchant is not nearby".to_string(),
+                        errmsg: "Listing does not exist.".to_string(),
                     };
                     send_to_client(*player_id, packet, &clients);
                     continue;
+                };
+
+                (player_id, listing.market_id)
+            }
+            _ => continue,
+        };
+
+        events_to_remove.push(*event_id);
+
+        let Some(hero_id) = ids.get_hero(*player_id) else {
+            error!("Cannot find hero for player {:?}", *player_id);
+            continue;
+        };
+
+        let Some(hero_entity) = ids.get_entity(hero_id) else {
+            error!("Cannot find entity for {:?}", hero_id);
+            continue;
+        };
+
+        let Ok((hero_pos, hero_state, hero_template, hero_attrs)) = hero_query.get(hero_entity)
+        else {
+            error!("Cannot find hero for {:?}", hero_entity);
+            continue;
+        };
+
+        let market = ids
+            .get_entity(market_id)
+            .and_then(|market_entity| structure_query.get(market_entity).ok());
+
+        let Some((_market_player_id, market_pos, market_state, market_subclass)) = market else {
+            let packet = ResponsePacket::Error {
+                errmsg: "Market does not exist.".to_string(),
+            };
+            send_to_client(*player_id, packet, &clients);
+            continue;
+        };
+
+        // Searching the listings is allowed from anywhere
+        let is_search = matches!(event, PlayerEvent::MarketSearch { .. });
+
+        let errmsg = if market_subclass.0 != market::SUBCLASS_MARKET {
+            Some("Structure is not a market.")
+        } else if *market_state != State::None {
+            Some("Market is not open.")
+        } else if !is_search && Obj::is_dead(hero_state) {
+            Some("The dead cannot trade.")
+        } else if !is_search && !Map::is_adjacent(*hero_pos, *market_pos) && hero_pos != market_pos
+        {
+            Some("Market is not nearby.")
+        } else {
+            None
+        };
+
+        if let Some(errmsg) = errmsg {
+            let packet = ResponsePacket::Error {
+                errmsg: errmsg.to_string(),
+            };
+            send_to_client(*player_id, packet, &clients);
+            continue;
+        }
+
+        let result = match event {
+            PlayerEvent::MarketList {
+                item_id,
+                quantity,
+                price,
+                duration,
+                return_id,
+                ..
+            } => {
+                // Proceeds and unsold items go back to one of the seller's structures
+                let is_own_structure = ids
+                    .get_entity(*return_id)
+                    .and_then(|return_entity| structure_query.get(return_entity).ok())
+                    .is_some_and(|(return_player_id, _pos, _state, _subclass)| {
+                        return_player_id.0 == *player_id
+                    });
+
+                if !is_own_structure {
+                    Err("Proceeds have to go to one of your structures.".to_string())
+                } else {
+                    markets
+                        .list(
+                            market_id,
+                            *player_id,
+                            hero_id,
+                            *return_id,
+                            *item_id,
+                            *quantity,
+                            *price,
+                            *duration,
+                            &mut ids,
+                            &mut items,
+                            &game_tick,
+                        )
+                        .map(|_| ())
                 }
+            }
+            PlayerEvent::MarketBuy { listing_id, .. } => {
+                let is_standing = |structure_id: i32| {
+                    ids.get_entity(structure_id)
+                        .and_then(|entity| structure_query.get(entity).ok())
+                        .is_some_and(|(_player_id, _pos, state, _subclass)| *state != State::Dead)
+                };
 
-                // TOOD check if owner has room for the gold coins
+                let capacity =
+                    Encumbrance::capacity(&hero_template.0, hero_attrs, &templates.obj_templates);
+                let hard_limit = Encumbrance::hard_limit(capacity, hero_attrs);
 
-                // TODO check if target has the space to hold the item
+                markets
+                    .buy(
+                        *listing_id,
+                        *player_id,
+                        hero_id,
+                        hard_limit,
+                        &ids,
+                        is_standing,
+                        &mut items,
+                    )
+                    .map(|_| ())
+            }
+            PlayerEvent::MarketBid {
+                listing_id, amount, ..
+            } => markets.bid(*listing_id, *player_id, hero_id, *amount, &mut items),
+            _ => Ok(()),
+        };
+
+        if let Err(errmsg) = result {
+            let packet = ResponsePacket::Error { errmsg: errmsg };
+            send_to_client(*player_id, packet, &clients);
+            continue;
+        }
 
-                items.transfer_gold(*target_id, item.owner, 25);
-                items.transfer_quantity(*item_id, *target_id, *quantity);
+        let name = match event {
+            PlayerEvent::MarketSearch { name, .. } => name.clone(),
+            _ => String::new(),
+        };
+
+        let listings_packet = ResponsePacket::MarketListings {
+            marketid: market_id,
+            listings: markets.search(market_id, &name, &items),
+        };
+
+        send_to_client(*player_id, listings_packet, &clients);
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
+
+fn tax_system(
+    mut events: ResMut<PlayerEvents>,
+    game_tick: Res<GameTick>,
+    clients: Res<Clients>,
+    ids: Res<Ids>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    mut vassals: ResMut<Vassals>,
+    mut map_events: ResMut<MapEvents>,
+    hero_query: Query<(&Position, &State), With<SubclassHero>>,
+    mut collector_query: Query<(&Id, &Position, &mut TaxCollector)>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+    let empire_template = &templates.empire_template;
+
+    for (event_id, event) in events.iter() {
+        let (player_id, collector_id) = match event {
+            PlayerEvent::InfoTax { player_id } => {
+                events_to_remove.push(*event_id);
+
+                let vassal = vassals.get_or_new(*player_id, empire_template);
 
-                let mut item_filter = Vec::new();
-                item_filter.push(item::GOLD.to_string());
+                let collector = collector_query
+                    .iter()
+                    .find(|(_id, _pos, collector)| collector.target_player == *player_id);
 
-                let source_items = items.get_by_owner_packet(item.owner);
-                let target_items = items.get_by_owner_packet_filter(*target_id, item_filter);
+                let status_packet = Empire::status_packet(
+                    collector.map(|(collector_id, _pos, _collector)| collector_id.0),
+                    collector.map(|(_collector_id, _pos, collector)| collector),
+                    vassal,
+                    empire_template,
+                );
 
-                let source_inventory = network::Inventory {
-                    id: item.owner,
-                    cap: 0,
-                    tw: 0,
-                    items: source_items.clone(),
-                };
+                send_to_client(*player_id, status_packet, &clients);
+                continue;
+            }
+            PlayerEvent::TaxNegotiate {
+                player_id,
+                collector_id,
+            }
+            | PlayerEvent::TaxBribe {
+                player_id,
+                collector_id,
+                ..
+            } => (player_id, collector_id),
+            _ => continue,
+        };
+
+        events_to_remove.push(*event_id);
+
+        let Some(hero_id) = ids.get_hero(*player_id) else {
+            error!("Cannot find hero for player {:?}", *player_id);
+            continue;
+        };
+
+        let Some(hero_entity) = ids.get_entity(hero_id) else {
+            error!("Cannot find entity for {:?}", hero_id);
+            continue;
+        };
+
+        let Ok((hero_pos, hero_state)) = hero_query.get(hero_entity) else {
+            error!("Cannot find hero for {:?}", hero_entity);
+            continue;
+        };
+
+        let collector = ids
+            .get_entity(*collector_id)
+            .and_then(|collector_entity| collector_query.get_mut(collector_entity).ok());
+
+        let Some((_id, collector_pos, mut collector)) = collector else {
+            let packet = ResponsePacket::Error {
+                errmsg: "Tax collector does not exist.".to_string(),
+            };
+            send_to_client(*player_id, packet, &clients);
+            continue;
+        };
+
+        let errmsg = if collector.target_player != *player_id {
+            Some("The tax collector has no business with you.")
+        } else if Obj::is_dead(hero_state) {
+            Some("The dead owe no taxes.")
+        } else if !Map::is_adjacent(*hero_pos, *collector_pos) {
+            Some("Tax collector is not nearby.")
+        } else {
+            None
+        };
+
+        if let Some(errmsg) = errmsg {
+            let packet = ResponsePacket::Error {
+                errmsg: errmsg.to_string(),
+            };
+            send_to_client(*player_id, packet, &clients);
+            continue;
+        }
 
-                let target_inventory = network::Inventory {
-                    id: *target_id,
-                    cap: 0,
-                    tw: 0,
-                    items: target_items.clone(),
-                };
+        let vassal = vassals.get_or_new(*player_id, empire_template);
+
+        let result = match event {
+            PlayerEvent::TaxBribe { amount, .. } => Empire::bribe(
+                hero_id,
+                *amount,
+                &mut collector,
+                vassal,
+                &mut items,
+                empire_template,
+            )
+            .map(|forgiven| {
+                if forgiven {
+                    "Taxes? I see no taxes owed here.".to_string()
+                } else {
+                    format!(
+                        "Bribing an officer of the {}? Your debt is now {}!",
+                        empire_template.name, collector.debt_amount
+                    )
+                }
+            }),
+            _ => Empire::negotiate(&mut collector, vassal, empire_template).map(|agreed| {
+                if agreed {
+                    format!(
+                        "Fine, fine. {} gold and not a coin less.",
+                        collector.collection_amount
+                    )
+                } else {
+                    "Nonsense! The empire will hear of your insolence.".to_string()
+                }
+            }),
+        };
 
-                let item_transfer_packet: ResponsePacket = ResponsePacket::SellItem {
-                    sourceid: item.owner,
-                    sourceitems: source_inventory,
-                    targetid: *target_id,
-                    targetitems: target_inventory,
+        match result {
+            Ok(speech) => {
+                let sound_event = VisibleEvent::SoundObjEvent {
+                    sound: speech,
+                    intensity: 2,
                 };
 
-                send_to_client(*player_id, item_transfer_packet, &clients);
+                map_events.new(*collector_id, game_tick.0 + 1, sound_event);
+            }
+            Err(errmsg) => {
+                let packet = ResponsePacket::Error { errmsg: errmsg };
+                send_to_client(*player_id, packet, &clients);
+                continue;
             }
-            _ => {}
         }
+
+        let status_packet = Empire::status_packet(
+            Some(*collector_id),
+            Some(&*collector),
+            vassal,
+            empire_template,
+        );
+
+        send_to_client(*player_id, status_packet, &clients);
     }
 
     for event_id in events_to_remove.iter() {
@@ -4905,247 +7235,35 @@ fn new_player(
     debug!("map_events: {:?}", map_events);
 
     // Villager obj
-    let villager_id = ids.new_obj_id();
-
-    let villager_template_name = "Human Villager".to_string();
-    let villager_template = ObjTemplate::get_template(villager_template_name.clone(), templates);
-
-    let villager = Obj {
-        id: Id(villager_id),
-        player_id: PlayerId(player_id),
-        position: Position {
+    spawn_villager(
+        player_id,
+        Position {
             x: start_location.villager_pos[0],
             y: start_location.villager_pos[1],
         },
-        name: Name("Villager 1".into()),
-        template: Template("Human Villager".into()),
-        class: Class("unit".into()),
-        subclass: Subclass("villager".into()),
-        state: State::None,
-        viewshed: Viewshed { range: 2 },
-        misc: Misc {
-            image: "humanvillager1".into(),
-            hsl: Vec::new(),
-            groups: Vec::new(),
-        },
-        stats: Stats {
-            hp: villager_template.base_hp.unwrap(),
-            base_hp: villager_template.base_hp.unwrap(),
-            stamina: villager_template.base_stamina,
-            base_stamina: villager_template.base_stamina,
-            base_def: villager_template.base_def.unwrap(),
-            base_damage: villager_template.base_dmg,
-            damage_range: villager_template.dmg_range,
-            base_speed: villager_template.base_speed,
-            base_vision: villager_template.base_vision,
-        },
-        effects: Effects(HashMap::new()),
-    };
-
-    // Villager generate skills
-    Villager::generate_skills(villager_id, skills, &templates.skill_templates);
-
-    // Villager create attributes components ```
-    let base_attrs = Villager::generate_attributes(1);
-
-    let villager_attrs = VillagerAttrs {
-        shelter: "None".to_string(),
-        structure: -1,
-        structure_template: "None".to_string(),
-        activity: villager::Activity::None,
-    };
-
-    let find_move_to_and_drink = Steps::build()
-        .label("FindMoveToAndDrink")
-        .step(FindDrink)
-        .step(MoveToWaterSource)
-        .step(TransferDrink)
-        .step(Drink { until: 70.0 });
-
-    let find_move_to_and_eat = Steps::build()
-        .label("FindMoveToAndEat")
-        .step(FindFood)
-        .step(MoveToFoodSource)
-        .step(TransferFood)
-        .step(Eat);
-
-    let find_move_to_and_sleep = Steps::build()
-        .label("FindMoveToAndSleep")
-        .step(FindShelter)
-        .step(MoveToSleepPos)
-        .step(Sleep);
-
-    let villager_entity_id = commands
-        .spawn((
-            villager,
-            SubclassVillager,
-            base_attrs,
-            villager_attrs,
-            Thirst::new(0.0, 0.025), //0.1 before
-            Hunger::new(0.0, 0.025),
-            Tired::new(0.0, 0.025),
-            Heat::new(50.0),
-            Morale::new(50.0),
-            Thinker::build()
-                .label("Villager")
-                .picker(Highest)
-                .when(
-                    EnemyDistanceScorer,
-                    Flee,
-                )
-                .when(
-                    ThirstyScorer,
-                    find_move_to_and_drink,
-                )
-                .when(
-                    HungryScorer,
-                    find_move_to_and_eat,
-                )
-                .when(
-                    DrowsyScorer,
-                    find_move_to_and_sleep,
-                )
-                .when(
-                    IdleScorer,
-                    Idle {
-                        start_time: 0,
-                        duration: 100,
-                    },
-                ).when(
-                    GoodMorale,
-                    ProcessOrder,
-                )
-        ))
-        .id();
-
-    ids.new_obj(villager_id, player_id, villager_entity_id);
-
-    map_events.new(
-        villager_id,
-        game_tick.0 + 1,
-        VisibleEvent::NewObjEvent { new_player: false },
+        "humanvillager1",
+        commands,
+        ids,
+        map_events,
+        skills,
+        templates,
+        game_tick,
     );
 
     // Villager obj
-    let villager_id = ids.new_obj_id();
-
-    let villager_template_name = "Human Villager".to_string();
-    let villager_template = ObjTemplate::get_template(villager_template_name.clone(), templates);
-
-    let villager = Obj {
-        id: Id(villager_id),
-        player_id: PlayerId(player_id),
-        position: Position {
+    spawn_villager(
+        player_id,
+        Position {
             x: start_location.villager_pos[0] + 1,
             y: start_location.villager_pos[1] + 1,
         },
-        name: Name("Villager 2".into()),
-        template: Template("Human Villager".into()),
-        class: Class("unit".into()),
-        subclass: Subclass("villager".into()),
-        state: State::None,
-        viewshed: Viewshed { range: 2 },
-        misc: Misc {
-            image: "humanvillager2".into(),
-            hsl: Vec::new(),
-            groups: Vec::new(),
-        },
-        stats: Stats {
-            hp: villager_template.base_hp.unwrap(),
-            base_hp: villager_template.base_hp.unwrap(),
-            stamina: villager_template.base_stamina,
-            base_stamina: villager_template.base_stamina,
-            base_def: villager_template.base_def.unwrap(),
-            base_damage: villager_template.base_dmg,
-            damage_range: villager_template.dmg_range,
-            base_speed: villager_template.base_speed,
-            base_vision: villager_template.base_vision,
-        },
-        effects: Effects(HashMap::new()),
-    };
-
-    // Villager generate skills
-    Villager::generate_skills(villager_id, skills, &templates.skill_templates);
-
-    // Villager create attributes components ```
-    let base_attrs = Villager::generate_attributes(1);
-
-    let villager_attrs = VillagerAttrs {
-        shelter: "None".to_string(),
-        structure: -1,
-        structure_template: "None".to_string(),
-        activity: villager::Activity::None,
-    };
-
-    let find_move_to_and_drink = Steps::build()
-        .label("FindMoveToAndDrink")
-        .step(FindDrink)
-        .step(MoveToWaterSource)
-        .step(TransferDrink)
-        .step(Drink { until: 70.0 });
-
-    let find_move_to_and_eat = Steps::build()
-        .label("FindMoveToAndEat")
-        .step(FindFood)
-        .step(MoveToFoodSource)
-        .step(TransferFood)
-        .step(Eat);
-
-    let find_move_to_and_sleep = Steps::build()
-        .label("FindMoveToAndSleep")
-        .step(FindShelter)
-        .step(MoveToSleepPos)
-        .step(Sleep);
-
-    let villager_entity_id = commands
-        .spawn((
-            villager,
-            SubclassVillager,
-            base_attrs,
-            villager_attrs,
-            Thirst::new(0.0, 0.025), //0.1 before
-            Hunger::new(0.0, 0.025),
-            Tired::new(0.0, 0.025),
-            Heat::new(50.0),
-            Morale::new(50.0),
-            Thinker::build()
-                .label("Villager")
-                .picker(Highest)
-                .when(
-                    EnemyDistanceScorer,
-                    Flee,
-                )
-                .when(
-                    ThirstyScorer,
-                    find_move_to_and_drink,
-                )
-                .when(
-                    HungryScorer,
-                    find_move_to_and_eat,
-                )
-                .when(
-                    DrowsyScorer,
-                    find_move_to_and_sleep,
-                )
-                .when(
-                    IdleScorer,
-                    Idle {
-                        start_time: 0,
-                        duration: 100,
-                    },
-                ).when(
-                    GoodMorale,
-                    ProcessOrder,
-                )
-        ))
-        .id();
-
-    ids.new_obj(villager_id, player_id, villager_entity_id);
-
-    map_events.new(
-        villager_id,
-        game_tick.0 + 1,
-        VisibleEvent::NewObjEvent { new_player: false },
+        "humanvillager2",
+        commands,
+        ids,
+        map_events,
+        skills,
+        templates,
+        game_tick,
     );
 
     // Starting recipes
@@ -5252,7 +7370,7 @@ fn new_player(
     let villager_id2 = ids.new_obj_id();
     let merchant_player_id = 2000;
 
-    let empire_pos = Position { x: 1, y: 37 };
+    let empire_pos = Empire::capital(&templates.empire_template);
     let landing_pos = Position {
         x: start_location.merchant_pos[0],
         y: start_location.merchant_pos[1],
@@ -5442,6 +7560,102 @@ fn new_player(
     );*/
 }
 
+pub fn spawn_villager(
+    player_id: i32,
+    pos: Position,
+    image: &str,
+    commands: &mut Commands,
+    ids: &mut ResMut<Ids>,
+    map_events: &mut ResMut<MapEvents>,
+    skills: &mut ResMut<Skills>,
+    templates: &Res<Templates>,
+    game_tick: &Res<GameTick>,
+) -> (i32, Entity) {
+    let villager_id = ids.new_obj_id();
+
+    let villager_template_name = "Human Villager".to_string();
+    let villager_template = ObjTemplate::get_template(villager_template_name.clone(), templates);
+
+    let (villager_name, personality) = Villager::generate(&templates.villager_templates);
+
+    let villager = Obj {
+        id: Id(villager_id),
+        player_id: PlayerId(player_id),
+        position: pos,
+        name: Name(villager_name),
+        template: Template("Human Villager".into()),
+        class: Class("unit".into()),
+        subclass: Subclass("villager".into()),
+        state: State::None,
+        viewshed: Viewshed { range: 2 },
+        misc: Misc {
+            image: image.to_string(),
+            hsl: Vec::new(),
+            groups: Vec::new(),
+        },
+        stats: Stats {
+            hp: villager_template.base_hp.unwrap(),
+            base_hp: villager_template.base_hp.unwrap(),
+            stamina: villager_template.base_stamina,
+            base_stamina: villager_template.base_stamina,
+            base_def: villager_template.base_def.unwrap(),
+            base_damage: villager_template.base_dmg,
+            damage_range: villager_template.dmg_range,
+            base_speed: villager_template.base_speed,
+            base_vision: villager_template.base_vision,
+        },
+        effects: Effects(HashMap::new()),
+    };
+
+    // Villager generate skills
+    Villager::generate_skills(villager_id, skills, &templates.skill_templates);
+
+    // Villager create attributes components ```
+    let base_attrs = Villager::generate_attributes(1);
+
+    let villager_attrs = VillagerAttrs {
+        shelter: "None".to_string(),
+        structure: -1,
+        structure_template: "None".to_string(),
+        activity: villager::Activity::None,
+    };
+
+    let villager_entity_id = commands
+        .spawn((
+            villager,
+            SubclassVillager,
+            base_attrs,
+            villager_attrs,
+            Thirst::new(0.0, 0.025 * personality.thirst_rate), //0.1 before
+            Hunger::new(0.0, 0.025 * personality.hunger_rate),
+            Tired::new(0.0, 0.025 * personality.tired_rate),
+            Heat::new(50.0),
+            Morale::new(50.0),
+            Schedule::default().shifted(personality.schedule_shift),
+            OrderQueue::default(),
+            Relationships::default(),
+            CombatStance::Flee,
+            personality,
+            AiThinker::build_for_template(
+                &villager_template.template,
+                &ThinkerContext::default(),
+                templates,
+            ),
+        ))
+        .insert(ThreatTable::default())
+        .id();
+
+    ids.new_obj(villager_id, player_id, villager_entity_id);
+
+    map_events.new(
+        villager_id,
+        game_tick.0 + 1,
+        VisibleEvent::NewObjEvent { new_player: false },
+    );
+
+    return (villager_id, villager_entity_id);
+}
+
 fn get_current_req_quantities(
     target: ItemTransferQueryItem,
     items: &ResMut<Items>,
@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+use big_brain::prelude::*;
+use big_brain::thinker::HasThinker;
+
+use std::any::type_name;
+use std::collections::{HashMap, VecDeque};
+
+use crate::game::{Clients, GameTick};
+use crate::ids::Ids;
+use crate::network::{self, send_to_client, ResponsePacket};
+use crate::player::{PlayerEvent, PlayerEvents};
+use crate::plugins::ai::thinker::ThinkerName;
+
+// Number of past decisions kept per actor
+pub const DECISION_HISTORY_LENGTH: usize = 20;
+
+// Clearing, recording and history run in this order after the thinkers have run
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InspectorSet {
+    Clear,
+    Record,
+    History,
+}
+
+// Snapshot of the scorers and actions of every thinking obj, refreshed each frame.
+// Scores are kept per scorer entity, as several choices can use the same scorer type.
+// Actors that lose their thinker or are despawned are dropped along with their history.
+#[derive(Resource, Default, Debug)]
+pub struct AiInspector {
+    pub scores: HashMap<Entity, HashMap<Entity, (String, f32)>>,
+    pub actions: HashMap<Entity, Vec<(String, ActionState)>>,
+    pub last_actions: HashMap<Entity, String>,
+    pub history: HashMap<Entity, VecDeque<network::AiDecision>>,
+}
+
+impl AiInspector {
+    // Scorer and action components are named after their type
+    pub fn short_name<T>() -> String {
+        let name = type_name::<T>();
+        return name.rsplit("::").next().unwrap_or(name).to_string();
+    }
+
+    pub fn scores_to_packet(&self, actor: Entity) -> Vec<network::AiScore> {
+        let mut scores: Vec<network::AiScore> = self
+            .scores
+            .get(&actor)
+            .map(|scores| {
+                scores
+                    .values()
+                    .map(|(name, score)| network::AiScore {
+                        name: name.clone(),
+                        score: *score,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        scores.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.name.cmp(&b.name)));
+
+        return scores;
+    }
+
+    // An actor can briefly have more than one action while the old one is cancelled
+    pub fn action_name(actions: &Vec<(String, ActionState)>) -> String {
+        let mut names: Vec<String> = actions.iter().map(|(name, _state)| name.clone()).collect();
+        names.sort();
+
+        return names.join(", ");
+    }
+}
+
+pub fn clear_system(mut inspector: ResMut<AiInspector>, actors: Query<(), With<HasThinker>>) {
+    inspector.scores.clear();
+    inspector.actions.clear();
+
+    inspector.last_actions.retain(|actor, _| actors.contains(*actor));
+    inspector.history.retain(|actor, _| actors.contains(*actor));
+}
+
+pub fn record_scorer_system<T: Component>(
+    mut inspector: ResMut<AiInspector>,
+    query: Query<(Entity, &Actor, &Score), With<T>>,
+) {
+    for (scorer, Actor(actor), score) in query.iter() {
+        inspector
+            .scores
+            .entry(*actor)
+            .or_default()
+            .insert(scorer, (AiInspector::short_name::<T>(), score.get()));
+    }
+}
+
+pub fn record_action_system<T: Component>(
+    mut inspector: ResMut<AiInspector>,
+    query: Query<(&Actor, &ActionState), With<T>>,
+) {
+    for (Actor(actor), state) in query.iter() {
+        inspector
+            .actions
+            .entry(*actor)
+            .or_default()
+            .push((AiInspector::short_name::<T>(), state.clone()));
+    }
+}
+
+// Adds a decision to the history whenever an actor switches actions
+pub fn decision_history_system(game_tick: Res<GameTick>, mut inspector: ResMut<AiInspector>) {
+    let mut changed = Vec::new();
+
+    for (actor, actions) in inspector.actions.iter() {
+        let action = AiInspector::action_name(actions);
+
+        if inspector.last_actions.get(actor) != Some(&action) {
+            changed.push((*actor, action));
+        }
+    }
+
+    for (actor, action) in changed {
+        inspector.last_actions.insert(actor, action.clone());
+
+        let scores = inspector.scores_to_packet(actor);
+
+        let history = inspector.history.entry(actor).or_default();
+
+        history.push_back(network::AiDecision {
+            tick: game_tick.0,
+            action: action,
+            scores: scores,
+        });
+
+        if history.len() > DECISION_HISTORY_LENGTH {
+            history.pop_front();
+        }
+    }
+}
+
+pub fn inspect_ai_system(
+    mut events: ResMut<PlayerEvents>,
+    ids: Res<Ids>,
+    clients: Res<Clients>,
+    inspector: Res<AiInspector>,
+    thinker_query: Query<(&HasThinker, Option<&ThinkerName>)>,
+) {
+    let mut events_to_remove: Vec<i32> = Vec::new();
+
+    for (event_id, event) in events.iter() {
+        match event {
+            PlayerEvent::InspectAi { player_id, obj_id } => {
+                events_to_remove.push(*event_id);
+
+                let Some(entity) = ids.get_entity(*obj_id) else {
+                    let packet = ResponsePacket::Error {
+                        errmsg: format!("Cannot find obj {}.", obj_id),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                };
+
+                let Ok((has_thinker, thinker_name)) = thinker_query.get(entity) else {
+                    let packet = ResponsePacket::Error {
+                        errmsg: format!("Obj {} has no thinker.", obj_id),
+                    };
+                    send_to_client(*player_id, packet, &clients);
+                    continue;
+                };
+
+                let actions = inspector.actions.get(&entity);
+
+                let packet = ResponsePacket::InspectAi {
+                    id: *obj_id,
+                    thinker: thinker_name
+                        .map(|thinker_name| thinker_name.0.clone())
+                        .unwrap_or(format!("{:?}", has_thinker.entity())),
+                    scores: inspector.scores_to_packet(entity),
+                    action: actions.map(|actions| AiInspector::action_name(actions)),
+                    action_state: actions
+                        .and_then(|actions| actions.last())
+                        .map(|(_name, state)| format!("{:?}", state)),
+                    history: inspector
+                        .history
+                        .get(&entity)
+                        .map(|history| history.iter().cloned().collect())
+                        .unwrap_or_default(),
+                };
+
+                send_to_client(*player_id, packet, &clients);
+            }
+            _ => {}
+        }
+    }
+
+    for event_id in events_to_remove.iter() {
+        events.remove(event_id);
+    }
+}
@@ -4,8 +4,10 @@ use big_brain::{
     prelude::*,
 };
 
+pub mod inspector;
 pub mod npc;
 pub mod tax_collector;
+pub mod thinker;
 pub mod villager;
 
 pub struct AIPlugin;
@@ -16,6 +18,11 @@ impl Plugin for AIPlugin {
             .add_systems(Update, npc::nearby_target_system)
             .add_systems(Update, npc::nearby_corpses_system)
             .add_systems(Update, tax_collector::update_tax_collection_system)
+            .add_systems(Update, villager::advance_order_queue_system)
+            .add_systems(Update, villager::villager_threat_system)
+            .init_resource::<inspector::AiInspector>()
+            .add_systems(Update, inspector::inspect_ai_system)
+            .add_systems(Startup, thinker::validate_thinker_templates_system)
             .add_systems(
                 PreUpdate,
                 (
@@ -37,6 +44,7 @@ impl Plugin for AIPlugin {
                     npc::cast_target_system.in_set(BigBrainSet::Actions),
                     npc::raise_dead_system.in_set(BigBrainSet::Actions),
                     villager::flee_system.in_set(BigBrainSet::Actions),
+                    villager::escort_system.in_set(BigBrainSet::Actions),
                     npc::flee_system.in_set(BigBrainSet::Actions),
                     npc::hide_action_system.in_set(BigBrainSet::Actions),
                 ),
@@ -51,6 +59,10 @@ impl Plugin for AIPlugin {
                     tax_collector::forfeiture_action_system.in_set(BigBrainSet::Actions),
                     tax_collector::set_destination_action_system.in_set(BigBrainSet::Actions),
                     tax_collector::talk_action_system.in_set(BigBrainSet::Actions),
+                    villager::fight_system.in_set(BigBrainSet::Actions),
+                    npc::wander_system.in_set(BigBrainSet::Actions),
+                    npc::patrol_system.in_set(BigBrainSet::Actions),
+                    npc::sail_to_port_system.in_set(BigBrainSet::Actions),
                 ),
             )
             .add_systems(
@@ -62,6 +74,9 @@ impl Plugin for AIPlugin {
                     villager::hungry_scorer_system.in_set(BigBrainSet::Scorers),
                     villager::drowsy_scorer_system.in_set(BigBrainSet::Scorers),
                     villager::morale_scorer_system.in_set(BigBrainSet::Scorers),
+                    villager::escort_scorer_system.in_set(BigBrainSet::Scorers),
+                    villager::fight_scorer_system.in_set(BigBrainSet::Scorers),
+                    villager::schedule_scorer_system.in_set(BigBrainSet::Scorers),
                     npc::target_scorer_system.in_set(BigBrainSet::Scorers),
                     npc::corpses_scorer_system.in_set(BigBrainSet::Scorers),
                     npc::flee_scorer_system.in_set(BigBrainSet::Scorers),
@@ -77,9 +92,30 @@ impl Plugin for AIPlugin {
                     tax_collector::no_taxes_to_collect_scorer_system.in_set(BigBrainSet::Scorers),
                     tax_collector::taxes_to_collect_scorer_system.in_set(BigBrainSet::Scorers),
                     tax_collector::overdue_tax_scorer_system.in_set(BigBrainSet::Scorers),
+                    npc::wander_scorer_system.in_set(BigBrainSet::Scorers),
+                    npc::patrol_scorer_system.in_set(BigBrainSet::Scorers),
                 ),
+            )
+            .configure_sets(
+                PostUpdate,
+                (
+                    inspector::InspectorSet::Clear,
+                    inspector::InspectorSet::Record,
+                    inspector::InspectorSet::History,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                inspector::clear_system.in_set(inspector::InspectorSet::Clear),
+            )
+            .add_systems(
+                PostUpdate,
+                inspector::decision_history_system.in_set(inspector::InspectorSet::History),
             );
 
+        thinker::AiThinker::add_record_systems(app);
+
         let linear = LinearEvaluator::new_inversed();
         debug!("linear: {:?}", linear.evaluate(0.1));
         debug!("linear: {:?}", linear.evaluate(0.25));
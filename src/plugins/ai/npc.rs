@@ -2,21 +2,28 @@ use bevy::prelude::*;
 use big_brain::prelude::*;
 use rand::Rng;
 
+use std::collections::HashMap;
+
 use crate::combat::AttackType;
 use crate::combat::Combat;
 use crate::combat::CombatQuery;
+use crate::combat::Combatant;
 use crate::components::npc::ChaseAndCast;
 use crate::components::npc::FleeScorer;
 use crate::components::npc::FleeToHome;
 use crate::components::npc::Hide;
-use crate::components::npc::MerchantScorer;
+use crate::components::npc::{Docked, MerchantScorer, SailToPort, Transport};
 use crate::components::npc::RaiseDead;
 
 use crate::components::npc::VisibleCorpse;
+use crate::components::npc::{
+    Nocturnal, Patrol, PatrolScorer, Soldier, Territory, Wander, WanderScorer,
+};
 use crate::components::npc::VisibleCorpseScorer;
-use crate::components::npc::{ChaseAndAttack, VisibleTarget, VisibleTargetScorer};
+use crate::components::npc::{ChaseAndAttack, ThreatTable, VisibleTarget, VisibleTargetScorer};
 use crate::components::villager::MoveToInProgress;
 use crate::effect::Effect;
+use crate::empire::EMPIRE_PLAYER_ID;
 use crate::event::Spell;
 use crate::event::{MapEvents, VisibleEvent};
 use crate::game::State;
@@ -25,10 +32,13 @@ use crate::ids::Ids;
 use crate::item::*;
 use crate::map::Map;
 use crate::map::MapPos;
+use crate::merchant::MerchantShips;
 use crate::obj;
 use crate::obj::Obj;
 use crate::obj::ObjStatQuery;
-use crate::templates::Templates;
+use crate::obj::ObjStatQueryItem;
+use crate::population::{Population, WANDER_RANGE};
+use crate::templates::{ObjTemplate, Templates};
 
 pub const INIT_TARGET: i32 = -2;
 pub const NO_TARGET: i32 = -1;
@@ -37,6 +47,24 @@ pub const BASE_SPEED: f32 = 1.0;
 
 pub const NECROMANCER: &str = "Necromancer";
 
+pub const AGGRESSION_HIGH: &str = "high";
+pub const AGGRESSION_MEDIUM: &str = "medium";
+
+pub const THREAT_UPDATE_INTERVAL: i32 = 30;
+pub const THREAT_DECAY: f32 = 0.9;
+pub const THREAT_DISTANCE_DECAY: f32 = 0.5;
+pub const MIN_THREAT: f32 = 1.0;
+pub const PROXIMITY_THREAT: f32 = 5.0;
+pub const DAMAGE_THREAT: f32 = 1.0;
+pub const HEALING_THREAT: f32 = 0.5;
+pub const TAUNT_THREAT: f32 = 50.0;
+pub const GROUP_AGGRO_RANGE: u32 = 4;
+pub const GROUP_THREAT_SHARE: f32 = 0.5;
+
+// Roaming loses out to anything else the npc wants to do
+pub const ROAM_SCORE: f32 = 0.2;
+pub const PATROL_WAYPOINTS: usize = 6;
+
 pub fn target_scorer_system(
     target_query: Query<&VisibleTarget>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<VisibleTargetScorer>>,
@@ -54,33 +82,202 @@ pub fn target_scorer_system(
 
 pub fn nearby_target_system(
     game_tick: Res<GameTick>,
-    mut npc_query: Query<(&Position, &Viewshed, &mut VisibleTarget), With<SubclassNPC>>,
-    target_query: Query<ObjQuery, Or<(With<SubclassHero>, With<SubclassVillager>)>>,
+    templates: Res<Templates>,
+    mut npc_query: Query<
+        (
+            &Id,
+            &PlayerId,
+            &Position,
+            &Viewshed,
+            &Template,
+            &Misc,
+            &mut VisibleTarget,
+            &mut ThreatTable,
+            Option<&Nocturnal>,
+            Option<&Soldier>,
+        ),
+        With<SubclassNPC>,
+    >,
+    target_query: Query<
+        (&Id, &PlayerId, &Position, &State),
+        Or<(With<SubclassHero>, With<SubclassVillager>)>,
+    >,
 ) {
-    if game_tick.0 % 30 == 0 {
-        for (npc_pos, npc_viewshed, mut npc_visible_target) in npc_query.iter_mut() {
-            let mut min_distance = u32::MAX;
-            let mut target_id = NO_TARGET;
+    if game_tick.0 % THREAT_UPDATE_INTERVAL != 0 {
+        return;
+    }
 
-            for target in target_query.iter() {
-                // Skip dead targets
-                if Obj::is_dead(target.state) {
+    // Live targets, their positions and owners
+    let mut targets: HashMap<i32, Position> = HashMap::new();
+    let mut target_players: HashMap<i32, i32> = HashMap::new();
+
+    for (target_id, target_player_id, target_pos, target_state) in target_query.iter() {
+        // Skip dead targets
+        if Obj::is_dead(target_state) {
+            continue;
+        }
+
+        targets.insert(target_id.0, *target_pos);
+        target_players.insert(target_id.0, target_player_id.0);
+    }
+
+    // Decay existing threat and add threat from targets in aggression range
+    for (
+        _npc_id,
+        npc_player_id,
+        npc_pos,
+        npc_viewshed,
+        npc_template,
+        _misc,
+        _visible_target,
+        mut threat_table,
+        nocturnal,
+        soldier,
+    ) in npc_query.iter_mut()
+    {
+        decay_threat(&mut threat_table, *npc_pos, npc_viewshed.range, &targets);
+
+        let template = ObjTemplate::get_template(npc_template.0.clone(), &templates);
+
+        // Resting nocturnal npcs only respond to threat
+        let aggression_range = if Population::is_active(nocturnal.is_some(), &game_tick) {
+            aggression_range(&template.aggression, npc_viewshed.range)
+        } else {
+            0
+        };
+
+        for (target_id, target_pos) in targets.iter() {
+            if !is_enemy(npc_player_id.0, soldier, target_players.get(target_id)) {
+                continue;
+            }
+
+            let distance = Map::dist(*npc_pos, *target_pos);
+
+            if distance <= aggression_range {
+                // Closer targets draw more threat
+                let proximity_threat = PROXIMITY_THREAT * (aggression_range - distance + 1) as f32;
+                threat_table.add(*target_id, proximity_threat);
+            }
+        }
+    }
+
+    // Share threat with nearby members of the same group
+    let mut group_members = Vec::new();
+
+    for (
+        npc_id,
+        _player_id,
+        npc_pos,
+        _viewshed,
+        _template,
+        misc,
+        _visible_target,
+        threat_table,
+        _nocturnal,
+        _soldier,
+    ) in npc_query.iter()
+    {
+        if !misc.groups.is_empty() {
+            group_members.push((
+                npc_id.0,
+                *npc_pos,
+                misc.groups.clone(),
+                threat_table.threat.clone(),
+            ));
+        }
+    }
+
+    for (
+        npc_id,
+        npc_player_id,
+        npc_pos,
+        _viewshed,
+        _template,
+        misc,
+        mut visible_target,
+        mut threat_table,
+        _nocturnal,
+        soldier,
+    ) in npc_query.iter_mut()
+    {
+        for (member_id, member_pos, member_groups, member_threat) in group_members.iter() {
+            if *member_id == npc_id.0 || Map::dist(*npc_pos, *member_pos) > GROUP_AGGRO_RANGE {
+                continue;
+            }
+
+            if !misc.groups.iter().any(|group| member_groups.contains(group)) {
+                continue;
+            }
+
+            for (target_id, threat) in member_threat.iter() {
+                if !is_enemy(npc_player_id.0, soldier, target_players.get(target_id)) {
                     continue;
                 }
 
-                let distance = Map::dist(*npc_pos, *target.pos);
+                let shared_threat = threat * GROUP_THREAT_SHARE;
 
-                if npc_viewshed.range >= distance {
-                    if distance < min_distance {
-                        min_distance = distance;
-                        target_id = target.id.0;
-                    }
+                if threat_table.get(*target_id) < shared_threat {
+                    threat_table.threat.insert(*target_id, shared_threat);
                 }
             }
+        }
 
-            debug!("Distance system target_id: {:?}", target_id);
-            npc_visible_target.target = target_id;
+        // Soldiers drop anyone they were drawn to besides the debtor
+        if soldier.is_some() {
+            threat_table.threat.retain(|target_id, _threat| {
+                is_enemy(npc_player_id.0, soldier, target_players.get(target_id))
+            });
         }
+
+        let target_id = threat_table.highest().unwrap_or(NO_TARGET);
+
+        debug!("Threat table target_id: {:?}", target_id);
+        visible_target.target = target_id;
+    }
+}
+
+// Npcs never start fights with their own side, empire soldiers only go after the debtor's objs
+// and other empire npcs keep the peace
+fn is_enemy(npc_player_id: i32, soldier: Option<&Soldier>, target_player_id: Option<&i32>) -> bool {
+    let Some(target_player_id) = target_player_id else {
+        return false;
+    };
+
+    if let Some(soldier) = soldier {
+        return *target_player_id == soldier.debtor;
+    }
+
+    return *target_player_id != npc_player_id && npc_player_id != EMPIRE_PLAYER_ID;
+}
+
+// Dead or missing targets are dropped and targets out of sight are forgotten faster
+pub fn decay_threat(
+    threat_table: &mut ThreatTable,
+    pos: Position,
+    range: u32,
+    targets: &HashMap<i32, Position>,
+) {
+    threat_table.threat.retain(|target_id, threat| {
+        let Some(target_pos) = targets.get(target_id) else {
+            return false;
+        };
+
+        *threat *= THREAT_DECAY;
+
+        if Map::dist(pos, *target_pos) > range {
+            *threat *= THREAT_DISTANCE_DECAY;
+        }
+
+        return *threat >= MIN_THREAT;
+    });
+}
+
+// Range in which the NPC will start a fight on its own
+fn aggression_range(aggression: &Option<String>, viewshed_range: u32) -> u32 {
+    match aggression.as_deref() {
+        Some(AGGRESSION_HIGH) => viewshed_range,
+        Some(AGGRESSION_MEDIUM) => 1,
+        _ => 0, // Low aggression only responds to threat
     }
 }
 
@@ -95,6 +292,7 @@ pub fn attack_target_system(
     visible_target_query: Query<(&PlayerId, &VisibleTarget), Without<EventInProgress>>,
     mut npc_query: Query<CombatQuery, (With<SubclassNPC>, Without<EventInProgress>)>,
     mut target_query: Query<CombatQuery, Without<SubclassNPC>>,
+    mut threat_query: Query<&mut ThreatTable, Without<SubclassNPC>>,
     mut query: Query<(&Actor, &mut ActionState, &ChaseAndAttack)>,
 ) {
     for (Actor(actor), mut state, _chase_attack) in &mut query {
@@ -112,6 +310,23 @@ pub fn attack_target_system(
 
                 let blockinglist = Obj::blocking_list_combatquery(npc_player_id.0, &npc_query);
 
+                // Other living units used for flanking and surrounded checks
+                let mut combatants = Vec::new();
+
+                for unit in npc_query.iter().chain(target_query.iter()) {
+                    if unit.entity != *actor
+                        && unit.id.0 != target_id
+                        && !Obj::is_dead(&unit.state)
+                    {
+                        combatants.push(Combatant::new(
+                            unit.player_id.0,
+                            *unit.pos,
+                            unit.in_combat,
+                            game_tick.0,
+                        ));
+                    }
+                }
+
                 let Ok(mut npc) = npc_query.get_mut(*actor) else {
                     error!("Query failed to find entity {:?}", *actor);
                     *state = ActionState::Failure;
@@ -218,6 +433,7 @@ pub fn attack_target_system(
                             AttackType::Quick,
                             &mut npc,
                             &mut target,
+                            &combatants,
                             &mut commands,
                             &mut items,
                             &templates,
@@ -238,6 +454,11 @@ pub fn attack_target_system(
                             &mut map_events,
                         );
 
+                        // Villagers remember who attacked them
+                        if let Ok(mut threat_table) = threat_query.get_mut(target_entity) {
+                            threat_table.add(npc.id.0, damage as f32 * DAMAGE_THREAT);
+                        }
+
                         // Add Cooldown Event
                         let cooldown_event = VisibleEvent::CooldownEvent { duration: 30 };
 
@@ -947,12 +1168,443 @@ pub fn hide_action_system(
 }
 
 pub fn merchant_scorer_system(
-    _game_tick: Res<GameTick>,
-    _move_in_progress: Query<&MoveToInProgress>,
-    _pos_query: Query<(&Position, &mut Merchant)>,
+    game_tick: Res<GameTick>,
+    ship_query: Query<(&Transport, Option<&Docked>), With<Merchant>>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<MerchantScorer>>,
 ) {
-    for (Actor(_actor), mut score, _span) in &mut query {
-        score.set(1.0);
+    for (Actor(actor), mut score, _span) in &mut query {
+        let Ok((transport, docked)) = ship_query.get(*actor) else {
+            score.set(0.0);
+            continue;
+        };
+
+        // Ships stay in port until the trading window closes
+        let trading = docked.is_some_and(|docked| docked.until > game_tick.0);
+
+        if transport.route.len() < 2 || trading {
+            score.set(0.0);
+        } else {
+            score.set(1.0);
+        }
     }
 }
+
+pub fn sail_to_port_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    map: Res<Map>,
+    mut items: ResMut<Items>,
+    mut map_events: ResMut<MapEvents>,
+    templates: Res<Templates>,
+    mut ship_query: Query<(&mut Transport, Option<&Docked>), Without<EventInProgress>>,
+    mut obj_query: Query<ObjStatQuery>,
+    mut query: Query<(&Actor, &mut ActionState, &SailToPort)>,
+) {
+    for (Actor(actor), mut state, _sail_to_port) in &mut query {
+        match *state {
+            ActionState::Requested => {
+                let Ok((transport, docked)) = ship_query.get(*actor) else {
+                    continue;
+                };
+
+                let Ok(ship) = obj_query.get(*actor) else {
+                    error!("Query failed to find entity {:?}", *actor);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                if let Some(docked) = docked {
+                    let next_port = transport.route.get(transport.next_stop as usize).and_then(
+                        |next_pos| {
+                            templates
+                                .trade_templates
+                                .get_port_at(next_pos.x, next_pos.y)
+                        },
+                    );
+
+                    MerchantShips::depart(
+                        *actor,
+                        ship.id,
+                        docked,
+                        next_port,
+                        &mut commands,
+                        &game_tick,
+                        &mut map_events,
+                    );
+                }
+
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                // Skip while the ship is still moving
+                let Ok((mut transport, _docked)) = ship_query.get_mut(*actor) else {
+                    continue;
+                };
+
+                let Some(dest) = transport.route.get(transport.next_stop as usize).copied() else {
+                    error!("Ship {:?} has no next stop on its route", *actor);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                let Ok(ship_player_id) = obj_query.get(*actor).map(|ship| ship.player_id.0) else {
+                    error!("Query failed to find entity {:?}", *actor);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                let blocking_list = Obj::blocking_list_objstatquery(ship_player_id, &obj_query);
+
+                let Ok(mut ship) = obj_query.get_mut(*actor) else {
+                    error!("Query failed to find entity {:?}", *actor);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                if *ship.state != State::None {
+                    continue;
+                }
+
+                if *ship.pos == dest {
+                    let Some(port) = templates.trade_templates.get_port_at(dest.x, dest.y) else {
+                        error!("No port at {:?}", dest);
+                        *state = ActionState::Failure;
+                        continue;
+                    };
+
+                    MerchantShips::dock(
+                        *actor,
+                        ship.id,
+                        port,
+                        &mut commands,
+                        &mut items,
+                        &templates,
+                        &game_tick,
+                        &mut map_events,
+                    );
+
+                    transport.next_stop = (transport.next_stop + 1) % transport.route.len() as i32;
+
+                    *state = ActionState::Success;
+                } else if !move_step(
+                    *actor,
+                    &mut ship,
+                    dest,
+                    blocking_list,
+                    false,
+                    true,
+                    &mut commands,
+                    &game_tick,
+                    &map,
+                    &mut map_events,
+                    &templates,
+                ) {
+                    debug!("Cannot find a sea path to {:?}", dest);
+                    *state = ActionState::Failure;
+                }
+            }
+            // All Actions should make sure to handle cancellations!
+            ActionState::Cancelled => {
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn wander_scorer_system(
+    game_tick: Res<GameTick>,
+    roam_query: Query<(Option<&Territory>, Option<&Nocturnal>)>,
+    mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<WanderScorer>>,
+) {
+    for (Actor(actor), mut score, _span) in &mut query {
+        let Ok((territory, nocturnal)) = roam_query.get(*actor) else {
+            continue;
+        };
+
+        // Npcs from a lair patrol around it instead
+        let has_lair = territory.is_some_and(|territory| territory.lair.is_some());
+
+        if !has_lair && Population::is_active(nocturnal.is_some(), &game_tick) {
+            score.set(ROAM_SCORE);
+        } else {
+            score.set(0.0);
+        }
+    }
+}
+
+pub fn patrol_scorer_system(
+    game_tick: Res<GameTick>,
+    roam_query: Query<(&Territory, Option<&Nocturnal>)>,
+    mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<PatrolScorer>>,
+) {
+    for (Actor(actor), mut score, _span) in &mut query {
+        let Ok((territory, nocturnal)) = roam_query.get(*actor) else {
+            score.set(0.0);
+            continue;
+        };
+
+        if territory.lair.is_some() && Population::is_active(nocturnal.is_some(), &game_tick) {
+            score.set(ROAM_SCORE);
+        } else {
+            score.set(0.0);
+        }
+    }
+}
+
+pub fn wander_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    map: Res<Map>,
+    mut map_events: ResMut<MapEvents>,
+    templates: Res<Templates>,
+    roam_query: Query<(&PlayerId, Option<&Territory>), Without<EventInProgress>>,
+    mut obj_query: Query<ObjStatQuery>,
+    mut query: Query<(&Actor, &mut ActionState, &mut Wander)>,
+) {
+    for (Actor(actor), mut state, mut wander) in &mut query {
+        match *state {
+            ActionState::Requested => {
+                wander.dest = None;
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                // Skip while the npc is still moving
+                let Ok((player_id, territory)) = roam_query.get(*actor) else {
+                    continue;
+                };
+
+                let blocking_list = Obj::blocking_list_objstatquery(player_id.0, &obj_query);
+
+                let Ok(mut npc) = obj_query.get_mut(*actor) else {
+                    error!("Query failed to find entity {:?}", *actor);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                if *npc.state != State::None {
+                    continue;
+                }
+
+                // Npcs without a territory wander around where they are
+                let (center, range) = match territory {
+                    Some(territory) => (territory.center, territory.range),
+                    None => (*npc.pos, WANDER_RANGE),
+                };
+
+                let dest = match wander.dest {
+                    Some(dest) => dest,
+                    None => {
+                        let Some(dest) = random_roam_pos(center, range, &map) else {
+                            *state = ActionState::Failure;
+                            continue;
+                        };
+
+                        wander.dest = Some(dest);
+                        dest
+                    }
+                };
+
+                if *npc.pos == dest {
+                    *state = ActionState::Success;
+                } else if !move_step(
+                    *actor,
+                    &mut npc,
+                    dest,
+                    blocking_list,
+                    true,
+                    false,
+                    &mut commands,
+                    &game_tick,
+                    &map,
+                    &mut map_events,
+                    &templates,
+                ) {
+                    debug!("Cannot find path to wander to {:?}", dest);
+                    *state = ActionState::Failure;
+                }
+            }
+            // All Actions should make sure to handle cancellations!
+            ActionState::Cancelled => {
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn patrol_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    map: Res<Map>,
+    mut map_events: ResMut<MapEvents>,
+    templates: Res<Templates>,
+    roam_query: Query<(&PlayerId, &Territory), Without<EventInProgress>>,
+    mut obj_query: Query<ObjStatQuery>,
+    mut query: Query<(&Actor, &mut ActionState, &mut Patrol)>,
+) {
+    for (Actor(actor), mut state, mut patrol) in &mut query {
+        match *state {
+            ActionState::Requested => {
+                patrol.dest = None;
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                // Skip while the npc is still moving
+                let Ok((player_id, territory)) = roam_query.get(*actor) else {
+                    continue;
+                };
+
+                let blocking_list = Obj::blocking_list_objstatquery(player_id.0, &obj_query);
+
+                let Ok(mut npc) = obj_query.get_mut(*actor) else {
+                    error!("Query failed to find entity {:?}", *actor);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                if *npc.state != State::None {
+                    continue;
+                }
+
+                let dest = match patrol.dest {
+                    Some(dest) => dest,
+                    None => {
+                        let waypoints = patrol_waypoints(territory.center, territory.range, &map);
+
+                        // Head for the waypoint after the closest one to keep going round
+                        let Some(closest) = (0..waypoints.len())
+                            .min_by_key(|index| Map::dist(*npc.pos, waypoints[*index]))
+                        else {
+                            *state = ActionState::Failure;
+                            continue;
+                        };
+
+                        let dest = waypoints[(closest + 1) % waypoints.len()];
+
+                        patrol.dest = Some(dest);
+                        dest
+                    }
+                };
+
+                if *npc.pos == dest {
+                    *state = ActionState::Success;
+                } else if !move_step(
+                    *actor,
+                    &mut npc,
+                    dest,
+                    blocking_list,
+                    true,
+                    false,
+                    &mut commands,
+                    &game_tick,
+                    &map,
+                    &mut map_events,
+                    &templates,
+                ) {
+                    debug!("Cannot find path to patrol to {:?}", dest);
+                    *state = ActionState::Failure;
+                }
+            }
+            // All Actions should make sure to handle cancellations!
+            ActionState::Cancelled => {
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn random_roam_pos(center: Position, range: u32, map: &Map) -> Option<Position> {
+    let roam_pos_list: Vec<(i32, i32)> = Map::range((center.x, center.y), range)
+        .into_iter()
+        .filter(|(x, y)| Map::is_valid_pos((*x, *y)) && Map::is_passable(*x, *y, map))
+        .collect();
+
+    if roam_pos_list.is_empty() {
+        return None;
+    }
+
+    let mut rng = rand::thread_rng();
+    let (x, y) = roam_pos_list[rng.gen_range(0..roam_pos_list.len())];
+
+    return Some(Position { x: x, y: y });
+}
+
+// Evenly spaced tiles on the ring around the center, sorted in walking order
+fn patrol_waypoints(center: Position, range: u32, map: &Map) -> Vec<Position> {
+    let mut ring: Vec<Position> = Map::range((center.x, center.y), range)
+        .into_iter()
+        .filter(|(x, y)| Map::is_valid_pos((*x, *y)) && Map::is_passable(*x, *y, map))
+        .map(|(x, y)| Position { x: x, y: y })
+        .filter(|pos| Map::dist(center, *pos) == range)
+        .collect();
+
+    ring.sort_by(|a, b| {
+        let angle_a = ((a.y - center.y) as f32).atan2((a.x - center.x) as f32);
+        let angle_b = ((b.y - center.y) as f32).atan2((b.x - center.x) as f32);
+
+        angle_a.total_cmp(&angle_b)
+    });
+
+    let step = usize::max(ring.len() / PATROL_WAYPOINTS, 1);
+
+    return ring.into_iter().step_by(step).collect();
+}
+
+// Starts a move one tile along the path to dst, false if there is no path
+fn move_step(
+    actor: Entity,
+    npc: &mut ObjStatQueryItem,
+    dst: Position,
+    blocking_list: Vec<MapPos>,
+    landwalk: bool,
+    waterwalk: bool,
+    commands: &mut Commands,
+    game_tick: &Res<GameTick>,
+    map: &Res<Map>,
+    map_events: &mut ResMut<MapEvents>,
+    templates: &Res<Templates>,
+) -> bool {
+    let Some((path, _c)) =
+        Map::find_path(*npc.pos, dst, map, blocking_list, landwalk, waterwalk, false, false)
+    else {
+        return false;
+    };
+
+    let Some(next_pos) = path.get(1) else {
+        return false;
+    };
+
+    let npc_speed = npc.stats.base_speed.unwrap_or(1);
+    let effect_speed_mod = npc.effects.get_speed_effects(templates);
+
+    let move_duration =
+        (BASE_MOVE_TICKS * (BASE_SPEED / npc_speed as f32) * (1.0 / effect_speed_mod)) as i32;
+
+    // Add State Change Event to Moving
+    let state_change_event = VisibleEvent::StateChangeEvent {
+        new_state: "moving".to_string(),
+    };
+
+    *npc.state = State::Moving;
+
+    map_events.new(npc.id.0, game_tick.0 + 4, state_change_event);
+
+    // Add Move Event
+    let move_event = VisibleEvent::MoveEvent {
+        src: *npc.pos,
+        dst: Position {
+            x: next_pos.0,
+            y: next_pos.1,
+        },
+    };
+
+    let move_map_event = map_events.new(npc.id.0, game_tick.0 + move_duration, move_event);
+
+    commands.entity(actor).insert(EventInProgress {
+        event_id: move_map_event.event_id,
+    });
+
+    return true;
+}
@@ -9,28 +9,92 @@ use crate::components::npc::{
     TaxCollectorTransport, TaxesToCollect, Transport,
 };
 use crate::effect::Effect;
+use crate::empire::{Empire, Vassals};
 use crate::event::{MapEvents, VisibleEvent};
 use crate::game::{State};
 use crate::ids::Ids;
 use crate::item::*;
+use crate::ledger;
 use crate::map::Map;
 use crate::obj::Obj;
 use crate::obj::{ObjStatQuery};
 use crate::plugins::ai::npc::{BASE_MOVE_TICKS, BASE_SPEED};
+use crate::structure::Structure;
 use crate::templates::Templates;
-use crate::{game::*, item};
+use crate::game::*;
 
-// General system to start a tax collection event
+// General system to settle payments and start a tax collection event
 pub fn update_tax_collection_system(
-    game_tick: ResMut<GameTick>,
-    mut collector_query: Query<&mut TaxCollector>,
+    game_tick: Res<GameTick>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    mut vassals: ResMut<Vassals>,
+    mut collector_query: Query<(&Id, &mut TaxCollector)>,
+    villager_query: Query<(&PlayerId, &State), With<SubclassVillager>>,
+    structure_query: Query<(&PlayerId, &Name, &State), With<ClassStructure>>,
 ) {
-    for mut collector in collector_query.iter_mut() {
-        let next_tax_collection = collector.last_collection_time + 1000;
+    let empire_template = &templates.empire_template;
+
+    for (collector_id, mut collector) in collector_query.iter_mut() {
+        let target_player = collector.target_player;
+        let vassal = vassals.get_or_new(target_player, empire_template);
+
+        // Gold and goods handed to the collector
+        let owed = Empire::amount_owed(&collector);
+
+        if owed > 0 && Empire::collected_value(collector_id.0, &items, &templates) >= owed {
+            info!("Taxes of {:?} paid by {:?}", owed, target_player);
+
+            items.set_reason(ledger::REASON_TAX);
+            Empire::remit(collector_id.0, &mut items);
+            items.clear_reason();
+
+            collector.collection_amount = 0;
+            collector.debt_amount = 0;
+
+            vassal.settle();
+            vassal.add_loyalty(empire_template.loyalty.paid, empire_template);
+        }
+
+        let next_tax_collection = collector.last_collection_time + empire_template.tax.period;
+
         if next_tax_collection <= game_tick.0 {
-            info!("Tax collection time for {:?}", collector.target_player);
-            collector.collection_amount = 50;
+            let num_villagers = villager_query
+                .iter()
+                .filter(|(player_id, state)| player_id.0 == target_player && !Obj::is_dead(state))
+                .count() as i32;
+
+            let structure_levels: Vec<i32> = structure_query
+                .iter()
+                .filter(|(player_id, _name, state)| {
+                    player_id.0 == target_player && **state == State::None
+                })
+                .map(|(_player_id, name, _state)| {
+                    Structure::get_template_by_name(name.0.clone(), &templates.obj_templates)
+                        .and_then(|structure_template| structure_template.level)
+                        .unwrap_or(0)
+                })
+                .collect();
+
+            let (amount, tier) = Empire::assess(
+                num_villagers,
+                &structure_levels,
+                vassal.loyalty,
+                empire_template,
+            );
+
+            info!(
+                "Tax collection time for {:?}, {:?} owes {:?}",
+                target_player, tier, amount
+            );
+
+            // Taxes never collected are carried over as debt
+            collector.debt_amount += collector.collection_amount;
+            collector.collection_amount = amount;
             collector.last_collection_time = game_tick.0;
+
+            vassal.negotiated = false;
+            vassal.tier = tier;
         }
     }
 }
@@ -51,18 +115,14 @@ pub fn is_aboard_scorer_system(
 }
 
 pub fn is_tax_collected_scorer_system(
-    items: ResMut<Items>,
-    tax_collector_query: Query<(&Id, &TaxCollector)>,
+    tax_collector_query: Query<(&State, &TaxCollector)>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<IsTaxCollected>>,
 ) {
     for (Actor(actor), mut score, _span) in &mut query {
-        if let Ok((id, tax_collector)) = tax_collector_query.get(*actor) {
-            if let Some(gold) = items.get_by_class(id.0, item::GOLD.to_string()) {
-                if gold.quantity >= tax_collector.collection_amount {
-                    score.set(1.0);
-                } else {
-                    score.set(0.0);
-                }
+        if let Ok((state, tax_collector)) = tax_collector_query.get(*actor) {
+            // Nothing left to collect once ashore
+            if Empire::amount_owed(tax_collector) <= 0 && *state != State::Aboard {
+                score.set(1.0);
             } else {
                 score.set(0.0);
             }
@@ -87,7 +147,6 @@ pub fn at_landing_scorer_system(
 
 pub fn no_taxes_to_collect_scorer_system(
     ids: ResMut<Ids>,
-    items: ResMut<Items>,
     transport_query: Query<(&Transport, &TaxCollectorTransport)>,
     collector_query: Query<&TaxCollector>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<NoTaxesToCollect>>,
@@ -104,16 +163,10 @@ pub fn no_taxes_to_collect_scorer_system(
         };
 
         if let Ok(collector) = collector_query.get(collector_entity) {
-            if let Some(gold) =
-                items.get_by_class(tc_transport.tax_collector_id, item::GOLD.to_string())
+            if Empire::amount_owed(collector) <= 0
+                && transport.hauling.contains(&tc_transport.tax_collector_id)
             {
-                if gold.quantity >= collector.collection_amount
-                    && transport.hauling.contains(&tc_transport.tax_collector_id)
-                {
-                    score.set(1.0);
-                } else {
-                    score.set(0.0);
-                }
+                score.set(1.0);
             } else {
                 score.set(0.0);
             }
@@ -123,7 +176,6 @@ pub fn no_taxes_to_collect_scorer_system(
 
 pub fn taxes_to_collect_scorer_system(
     ids: ResMut<Ids>,
-    items: ResMut<Items>,
     transport_query: Query<&TaxCollectorTransport>,
     collector_query: Query<&TaxCollector>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<TaxesToCollect>>,
@@ -140,14 +192,8 @@ pub fn taxes_to_collect_scorer_system(
         };
 
         if let Ok(collector) = collector_query.get(collector_entity) {
-            if let Some(gold) =
-                items.get_by_class(tc_transport.tax_collector_id, item::GOLD.to_string())
-            {
-                if gold.quantity < collector.collection_amount {
-                    score.set(1.0);
-                } else {
-                    score.set(0.0);
-                }
+            if Empire::amount_owed(collector) > 0 {
+                score.set(1.0);
             } else {
                 score.set(0.0);
             }
@@ -159,20 +205,18 @@ pub fn taxes_to_collect_scorer_system(
 
 pub fn overdue_tax_scorer_system(
     game_tick: Res<GameTick>,
-    items: ResMut<Items>,
-    collector_query: Query<(&Id, &TaxCollector)>,
+    templates: Res<Templates>,
+    collector_query: Query<&TaxCollector>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<OverdueTaxScorer>>,
 ) {
     for (Actor(actor), mut score, _span) in &mut query {
-        if let Ok((id, collector)) = collector_query.get(*actor) {
-            if collector.last_collection_time + 750 < game_tick.0 {
-                if let Some(gold) = items.get_by_class(id.0, item::GOLD.to_string()) {
-                    if gold.quantity < collector.collection_amount {
-                        score.set(1.0);
-                    } else {
-                        score.set(0.0);
-                    }
-                }
+        if let Ok(collector) = collector_query.get(*actor) {
+            let due_time = collector.last_collection_time + templates.empire_template.tax.due;
+
+            if due_time < game_tick.0 && Empire::amount_owed(collector) > 0 {
+                score.set(1.0);
+            } else {
+                score.set(0.0);
             }
         }
     }
@@ -278,7 +322,11 @@ pub fn move_to_target_action_system(
                             tax_collector.last_demand_time = game_tick.0;
 
                             let sound_event = VisibleEvent::SoundObjEvent {
-                                sound: "Tax Time! Pay now or face asset forfeiture!".to_string(),
+                                sound: format!(
+                                    "Tax Time! {} gold is owed to the {}. Pay now or face asset forfeiture!",
+                                    Empire::amount_owed(&tax_collector),
+                                    templates.empire_template.name
+                                ),
                                 intensity: 2,
                             };
 
@@ -528,8 +576,7 @@ pub fn move_to_empire_action_system(
                     continue;
                 }
 
-                // TODO replace this
-                let empire_pos = Position { x: 1, y: 37 };
+                let empire_pos = Empire::capital(&templates.empire_template);
 
                 // Get NPC speed
                 let mut npc_speed = 1;
@@ -544,7 +591,7 @@ pub fn move_to_empire_action_system(
                     * (BASE_SPEED / npc_speed as f32)
                     * (1.0 / effect_speed_mod)) as i32;
 
-                if *npc.pos == empire_pos {
+                if Map::in_empire(*npc.pos, &templates.empire_template) {
                     *state = ActionState::Success;
                 } else {
                     if *npc.state == State::None {
@@ -607,10 +654,15 @@ pub fn move_to_empire_action_system(
 }
 
 pub fn forfeiture_action_system(
+    mut commands: Commands,
     game_tick: Res<GameTick>,
+    map: Res<Map>,
     mut items: ResMut<Items>,
-    ids: ResMut<Ids>,
+    mut ids: ResMut<Ids>,
+    templates: Res<Templates>,
+    mut vassals: ResMut<Vassals>,
     mut map_events: ResMut<MapEvents>,
+    hero_query: Query<&Position, With<SubclassHero>>,
     mut collector_query: Query<(&Id, &mut TaxCollector)>,
     mut query: Query<(&Actor, &mut ActionState, &Forfeiture, &ActionSpan)>,
 ) {
@@ -631,44 +683,89 @@ pub fn forfeiture_action_system(
                     continue;
                 };
 
+                let empire_template = &templates.empire_template;
+                let vassal = vassals.get_or_new(collector.target_player, empire_template);
+
                 // Get hero items
+                let owed = Empire::amount_owed(&collector);
                 let total_gold = items.get_total_gold(hero_id);
-                info!(
-                    "Total gold: {:?} collection_amount {:?}",
-                    total_gold, collector.collection_amount
-                );
+                info!("Total gold: {:?} owed {:?}", total_gold, owed);
 
-                let overdue_amount = (collector.collection_amount as f32 * 1.2) as i32;
+                let overdue_penalty = empire_template.tax.overdue_penalty;
+                let overdue_amount = (owed as f32 * (1.0 + overdue_penalty)) as i32;
 
-                if total_gold >= overdue_amount {
-                    items.transfer_gold(hero_id, collector_id.0, overdue_amount);
+                items.set_reason(ledger::REASON_TAX_FORFEITURE);
 
-                    collector.collection_amount = 0;
-                    collector.last_collection_time = game_tick.0;
+                let sound = if total_gold >= owed {
+                    let forfeited = i32::min(total_gold, overdue_amount);
+                    items.transfer_gold(hero_id, collector_id.0, forfeited);
 
-                    let sound_event = VisibleEvent::SoundObjEvent {
-                        sound: "Times up! I will take what you owe and 20% extra.".to_string(),
-                        intensity: 2,
-                    };
+                    collector.collection_amount = 0;
+                    collector.debt_amount = 0;
+                    vassal.settle();
 
-                    map_events.new(collector_id.0, game_tick.0 + 4, sound_event);
+                    format!(
+                        "Times up! I will take what you owe and {}% extra.",
+                        (overdue_penalty * 100.0).round() as i32
+                    )
                 } else {
-                    let remainder_gold = collector.collection_amount - total_gold;
+                    // Whatever gold there is goes towards the debt
+                    items.transfer_gold(hero_id, collector_id.0, total_gold);
+                    items.clear_reason();
+
+                    let remainder_gold = owed - total_gold;
 
-                    collector.debt_amount = (remainder_gold as f32 * 1.5) as i32;
+                    collector.debt_amount =
+                        (remainder_gold as f32 * (1.0 + empire_template.tax.debt_penalty)) as i32;
                     collector.collection_amount = 0;
-                    collector.last_collection_time = game_tick.0;
 
-                    let sound_event = VisibleEvent::SoundObjEvent {
-                        sound: format!(
+                    if vassal.add_strike(empire_template) {
+                        let hero_pos = ids
+                            .get_entity(hero_id)
+                            .and_then(|hero_entity| hero_query.get(hero_entity).ok());
+
+                        if let Some(hero_pos) = hero_pos {
+                            vassal.soldiers = Empire::send_soldiers(
+                                collector.target_player,
+                                hero_id,
+                                *hero_pos,
+                                collector.debt_amount,
+                                &mut commands,
+                                &mut ids,
+                                &mut items,
+                                &map,
+                                &templates,
+                                &game_tick,
+                                &mut map_events,
+                            );
+                        }
+
+                        format!(
+                            "Your debt is now {}. The {} has sent soldiers to collect it!",
+                            collector.debt_amount, empire_template.name
+                        )
+                    } else {
+                        format!(
                             "No gold? Poor rabble, your debt is now {}!",
                             collector.debt_amount
-                        ),
-                        intensity: 2,
-                    };
+                        )
+                    }
+                };
 
-                    map_events.new(collector_id.0, game_tick.0 + 4, sound_event);
-                }
+                // Forfeited gold is sent on to the capital
+                items.set_reason(ledger::REASON_TAX_FORFEITURE);
+                Empire::remit(collector_id.0, &mut items);
+                items.clear_reason();
+
+                vassal.add_loyalty(empire_template.loyalty.forfeiture, empire_template);
+                collector.last_collection_time = game_tick.0;
+
+                let sound_event = VisibleEvent::SoundObjEvent {
+                    sound: sound,
+                    intensity: 2,
+                };
+
+                map_events.new(collector_id.0, game_tick.0 + 4, sound_event);
 
                 *state = ActionState::Success;
             }
@@ -0,0 +1,456 @@
+use bevy::prelude::*;
+use big_brain::evaluators::{LinearEvaluator, PowerEvaluator, SigmoidEvaluator};
+use big_brain::prelude::*;
+use big_brain::scorers::{EvaluatingScorer, ProductOfScorers, SumOfScorers, WinningScorer};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::components::npc::{
+    AtLanding, ChaseAndAttack, ChaseAndCast, FleeScorer, FleeToHome, Forfeiture, Hide, Idle,
+    IsAboard, IsTaxCollected, MerchantScorer, MoveToEmpire, MoveToPos, MoveToTarget,
+    NoTaxesToCollect, OverdueTaxScorer, Patrol, PatrolScorer, RaiseDead, SailToPort,
+    SetDestination, Talk, TaxesToCollect, VisibleCorpseScorer, VisibleTargetScorer, Wander,
+    WanderScorer,
+};
+use crate::components::villager::{
+    Drink, DrinkDistanceScorer, DrowsyScorer, Eat, EnemyDistanceScorer, Escort, EscortScorer,
+    Fight, FightScorer, FindDrink, FindDrinkScorer, FindFood, FindFoodScorer, FindShelter,
+    FindShelterScorer, Flee, FoodDistanceScorer, GoodMorale, HasDrinkScorer, HasFoodScorer,
+    HungryScorer, IdleScorer, MoveToFoodSource, MoveToSleepPos, MoveToWaterSource,
+    NearShelterScorer, ProcessOrder, ScheduleScorer, ShelterDistanceScorer, Sleep, ThirstyScorer,
+    TransferDrink, TransferDrinkScorer, TransferFood, TransferFoodScorer,
+};
+use crate::plugins::ai::inspector::{self, InspectorSet};
+use crate::templates::{
+    EvaluatorTemplate, ThinkerActionTemplate, ThinkerChoiceTemplate, Templates,
+};
+
+// Behaviour used by npcs whose obj template does not name one
+pub const DEFAULT_NPC_THINKER: &str = "NPC Chase";
+
+pub const PICKER_HIGHEST: &str = "highest";
+pub const PICKER_FIRST_TO_SCORE: &str = "first_to_score";
+
+pub const COMBINE_PRODUCT: &str = "product";
+pub const COMBINE_SUM: &str = "sum";
+pub const COMBINE_WINNING: &str = "winning";
+
+pub const DEFAULT_THRESHOLD: f32 = 0.5;
+
+// Scorers and actions looked up by name, wrapped so they can be handed to the big-brain builders
+#[derive(Debug, Clone)]
+pub struct NamedScorer(Arc<dyn ScorerBuilder>);
+
+impl ScorerBuilder for NamedScorer {
+    fn build(&self, cmd: &mut Commands, scorer: Entity, actor: Entity) {
+        self.0.build(cmd, scorer, actor);
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.0.label()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NamedAction(Arc<dyn ActionBuilder>);
+
+impl ActionBuilder for NamedAction {
+    fn build(&self, cmd: &mut Commands, action: Entity, actor: Entity) {
+        self.0.build(cmd, action, actor);
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.0.label()
+    }
+}
+
+// Objs that actions such as MoveToTarget are aimed at, keyed by the name used in the template
+#[derive(Debug, Clone, Default)]
+pub struct ThinkerContext {
+    pub targets: HashMap<String, i32>,
+}
+
+impl ThinkerContext {
+    pub fn with_target(mut self, name: &str, id: i32) -> Self {
+        self.targets.insert(name.to_string(), id);
+        self
+    }
+}
+
+// Name of the thinker template an actor was built from
+#[derive(Debug, Clone, Component)]
+pub struct ThinkerName(pub String);
+
+pub struct AiThinker;
+
+// Registers the scorers thinker templates can name, along with the system
+// recording each of their scores in the ai inspector
+macro_rules! register_scorers {
+    ($($scorer:ident),* $(,)?) => {
+        impl AiThinker {
+            fn scorer_builder(name: &str) -> Option<Arc<dyn ScorerBuilder>> {
+                let scorer: Arc<dyn ScorerBuilder> = match name {
+                    $(stringify!($scorer) => Arc::new($scorer),)*
+                    _ => return None,
+                };
+
+                return Some(scorer);
+            }
+
+            fn add_scorer_record_systems(app: &mut App) {
+                $(app.add_systems(
+                    PostUpdate,
+                    inspector::record_scorer_system::<$scorer>.in_set(InspectorSet::Record),
+                );)*
+            }
+        }
+    };
+}
+
+// Same for actions, built from the action template and the targets of the thinker context
+macro_rules! register_actions {
+    ($template:ident, $context:ident; $($action:ident => $build:expr),* $(,)?) => {
+        impl AiThinker {
+            fn action_builder(
+                $template: &ThinkerActionTemplate,
+                $context: &ThinkerContext,
+            ) -> Option<Arc<dyn ActionBuilder>> {
+                let action: Arc<dyn ActionBuilder> = match $template.name.as_str() {
+                    $(stringify!($action) => Arc::new($build),)*
+                    _ => return None,
+                };
+
+                return Some(action);
+            }
+
+            fn add_action_record_systems(app: &mut App) {
+                $(app.add_systems(
+                    PostUpdate,
+                    inspector::record_action_system::<$action>.in_set(InspectorSet::Record),
+                );)*
+            }
+        }
+    };
+}
+
+register_scorers!(
+    // Villager scorers
+    EnemyDistanceScorer,
+    FightScorer,
+    EscortScorer,
+    IdleScorer,
+    ThirstyScorer,
+    FindDrinkScorer,
+    DrinkDistanceScorer,
+    TransferDrinkScorer,
+    HasDrinkScorer,
+    HungryScorer,
+    FindFoodScorer,
+    FoodDistanceScorer,
+    TransferFoodScorer,
+    HasFoodScorer,
+    DrowsyScorer,
+    FindShelterScorer,
+    ShelterDistanceScorer,
+    NearShelterScorer,
+    GoodMorale,
+    ScheduleScorer,
+    // Npc scorers
+    VisibleTargetScorer,
+    VisibleCorpseScorer,
+    FleeScorer,
+    MerchantScorer,
+    WanderScorer,
+    PatrolScorer,
+    // Tax collector scorers
+    IsAboard,
+    AtLanding,
+    IsTaxCollected,
+    OverdueTaxScorer,
+    NoTaxesToCollect,
+    TaxesToCollect,
+);
+
+register_actions!(
+    action_template, context;
+    // Villager actions
+    Flee => Flee,
+    Fight => Fight,
+    Escort => Escort,
+    FindDrink => FindDrink,
+    MoveToWaterSource => MoveToWaterSource,
+    TransferDrink => TransferDrink,
+    Drink => Drink {
+        until: action_template.until.unwrap_or(70.0),
+    },
+    FindFood => FindFood,
+    MoveToFoodSource => MoveToFoodSource,
+    TransferFood => TransferFood,
+    Eat => Eat,
+    FindShelter => FindShelter,
+    MoveToSleepPos => MoveToSleepPos,
+    Sleep => Sleep,
+    ProcessOrder => ProcessOrder,
+    // Npc actions, ones with a start time wait for the first run to set it
+    ChaseAndAttack => ChaseAndAttack,
+    ChaseAndCast => ChaseAndCast { start_time: i32::MAX },
+    RaiseDead => RaiseDead { start_time: i32::MAX },
+    FleeToHome => FleeToHome,
+    Hide => Hide,
+    SailToPort => SailToPort,
+    Wander => Wander { dest: None },
+    Patrol => Patrol { dest: None },
+    // Without a duration idling never ends
+    Idle => Idle {
+        start_time: 0,
+        duration: action_template.duration.unwrap_or(i32::MAX),
+    },
+    Talk => Talk {
+        speech: action_template.speech.clone().unwrap_or_default(),
+    },
+    // Tax collector actions
+    SetDestination => SetDestination,
+    MoveToPos => MoveToPos,
+    MoveToEmpire => MoveToEmpire,
+    MoveToTarget => MoveToTarget {
+        target: *context.targets.get(action_template.target.as_ref()?)?,
+    },
+    Forfeiture => Forfeiture,
+);
+
+impl AiThinker {
+    pub fn scorer(name: &str) -> Option<NamedScorer> {
+        return Self::scorer_builder(name).map(NamedScorer);
+    }
+
+    pub fn action(
+        action_template: &ThinkerActionTemplate,
+        context: &ThinkerContext,
+    ) -> Option<NamedAction> {
+        return Self::action_builder(action_template, context).map(NamedAction);
+    }
+
+    // Every registered scorer and action shows up in the ai inspector
+    pub fn add_record_systems(app: &mut App) {
+        Self::add_scorer_record_systems(app);
+        Self::add_action_record_systems(app);
+    }
+
+    pub fn evaluate(scorer: NamedScorer, evaluator: &EvaluatorTemplate) -> Option<NamedScorer> {
+        let min = evaluator.min.unwrap_or(0.0);
+        let max = evaluator.max.unwrap_or(1.0);
+
+        let evaluated: Arc<dyn ScorerBuilder> = match evaluator.evaluator_type.as_str() {
+            "linear" => Arc::new(EvaluatingScorer::build(
+                scorer,
+                LinearEvaluator::new(min, 0.0, max, 1.0),
+            )),
+            "inversed_linear" => Arc::new(EvaluatingScorer::build(
+                scorer,
+                LinearEvaluator::new(min, 1.0, max, 0.0),
+            )),
+            "power" => Arc::new(EvaluatingScorer::build(
+                scorer,
+                PowerEvaluator::new(evaluator.power.unwrap_or(2.0)),
+            )),
+            "sigmoid" => Arc::new(EvaluatingScorer::build(
+                scorer,
+                SigmoidEvaluator::new(evaluator.k.unwrap_or(1.0)),
+            )),
+            _ => return None,
+        };
+
+        return Some(NamedScorer(evaluated));
+    }
+
+    pub fn choice_scorer(choice: &ThinkerChoiceTemplate) -> Result<NamedScorer, String> {
+        let mut scorers = Vec::new();
+
+        for scorer_name in choice.scorers.iter() {
+            let Some(scorer) = Self::scorer(scorer_name) else {
+                return Err(format!("Unknown scorer {}", scorer_name));
+            };
+
+            scorers.push(scorer);
+        }
+
+        let threshold = choice.threshold.unwrap_or(DEFAULT_THRESHOLD);
+        let label = choice.label.clone().unwrap_or(choice.scorers.join(" "));
+
+        let scorer = match choice.combine.as_deref() {
+            None if scorers.len() == 1 => scorers.remove(0),
+            None | Some(COMBINE_PRODUCT) => {
+                let mut product = ProductOfScorers::build(threshold).label(label);
+
+                for scorer in scorers {
+                    product = product.push(scorer);
+                }
+
+                NamedScorer(Arc::new(product))
+            }
+            Some(COMBINE_SUM) => {
+                let mut sum = SumOfScorers::build(threshold).label(label);
+
+                for scorer in scorers {
+                    sum = sum.push(scorer);
+                }
+
+                NamedScorer(Arc::new(sum))
+            }
+            Some(COMBINE_WINNING) => {
+                let mut winning = WinningScorer::build(threshold).label(label);
+
+                for scorer in scorers {
+                    winning = winning.push(scorer);
+                }
+
+                NamedScorer(Arc::new(winning))
+            }
+            Some(combine) => return Err(format!("Unknown combine type {}", combine)),
+        };
+
+        let Some(evaluator) = &choice.evaluator else {
+            return Ok(scorer);
+        };
+
+        return Self::evaluate(scorer, evaluator)
+            .ok_or(format!("Unknown evaluator {}", evaluator.evaluator_type));
+    }
+
+    pub fn choice_action(
+        choice: &ThinkerChoiceTemplate,
+        context: &ThinkerContext,
+    ) -> Result<NamedAction, String> {
+        let mut actions = Vec::new();
+
+        for action_template in choice.actions.iter() {
+            let Some(action) = Self::action(action_template, context) else {
+                return Err(format!("Unknown action or target {}", action_template.name));
+            };
+
+            actions.push(action);
+        }
+
+        if actions.len() == 1 {
+            return Ok(actions.remove(0));
+        }
+
+        if actions.is_empty() {
+            return Err("Choice has no actions".to_string());
+        }
+
+        let label = choice.label.clone().unwrap_or(
+            choice
+                .actions
+                .iter()
+                .map(|action| action.name.clone())
+                .collect::<Vec<String>>()
+                .join(" and "),
+        );
+
+        let mut steps = Steps::build().label(label);
+
+        for action in actions {
+            steps = steps.step(action);
+        }
+
+        return Ok(NamedAction(Arc::new(steps)));
+    }
+
+    // Choices naming unknown scorers or actions are left out of the thinker
+    pub fn build(
+        name: &str,
+        context: &ThinkerContext,
+        templates: &Templates,
+    ) -> (ThinkerBuilder, ThinkerName) {
+        let Some(thinker_template) = templates.thinker_templates.get(name) else {
+            // Cannot recover from an invalid thinker template
+            panic!("Cannot find thinker_template: {:?}", name);
+        };
+
+        let thinker = Thinker::build().label(thinker_template.name.clone());
+
+        let mut thinker = match thinker_template.picker.as_str() {
+            PICKER_FIRST_TO_SCORE => thinker.picker(FirstToScore {
+                threshold: thinker_template.threshold.unwrap_or(DEFAULT_THRESHOLD),
+            }),
+            _ => thinker.picker(Highest),
+        };
+
+        for choice in thinker_template.choices.iter() {
+            let scorer = match Self::choice_scorer(choice) {
+                Ok(scorer) => scorer,
+                Err(err) => {
+                    error!("Thinker {}: {}", name, err);
+                    continue;
+                }
+            };
+
+            let action = match Self::choice_action(choice, context) {
+                Ok(action) => action,
+                Err(err) => {
+                    error!("Thinker {}: {}", name, err);
+                    continue;
+                }
+            };
+
+            thinker = thinker.when(scorer, action);
+        }
+
+        return (thinker, ThinkerName(thinker_template.name.clone()));
+    }
+
+    // Thinker for the behaviour named by an obj template, npcs chase by default
+    pub fn build_for_template(
+        template: &str,
+        context: &ThinkerContext,
+        templates: &Templates,
+    ) -> (ThinkerBuilder, ThinkerName) {
+        let behaviour = templates
+            .obj_templates
+            .iter()
+            .find(|obj_template| obj_template.template == template)
+            .and_then(|obj_template| obj_template.behaviour.clone())
+            .unwrap_or(DEFAULT_NPC_THINKER.to_string());
+
+        return Self::build(&behaviour, context, templates);
+    }
+
+    // Targets are only known at spawn, so those choices are not checked here
+    pub fn validate(templates: &Templates) {
+        for (name, thinker_template) in templates.thinker_templates.iter() {
+            if thinker_template.picker != PICKER_HIGHEST
+                && thinker_template.picker != PICKER_FIRST_TO_SCORE
+            {
+                error!("Thinker {}: unknown picker {}", name, thinker_template.picker);
+            }
+
+            for choice in thinker_template.choices.iter() {
+                if let Err(err) = Self::choice_scorer(choice) {
+                    error!("Thinker {}: {}", name, err);
+                }
+
+                for action_template in choice.actions.iter() {
+                    if action_template.target.is_none()
+                        && Self::action(action_template, &ThinkerContext::default()).is_none()
+                    {
+                        error!("Thinker {}: unknown action {}", name, action_template.name);
+                    }
+                }
+            }
+        }
+
+        for obj_template in templates.obj_templates.iter() {
+            if let Some(behaviour) = &obj_template.behaviour {
+                if !templates.thinker_templates.contains_key(behaviour) {
+                    error!("Obj template {} has unknown behaviour {}", obj_template.name, behaviour);
+                }
+            }
+        }
+    }
+}
+
+pub fn validate_thinker_templates_system(templates: Res<Templates>) {
+    AiThinker::validate(&templates);
+}
@@ -2,12 +2,18 @@ use bevy::ecs::query::WorldQuery;
 use bevy::prelude::*;
 
 use big_brain::prelude::*;
+use rand::Rng;
+
+use std::collections::HashMap;
 
 use crate::components::npc::Idle;
 use crate::components::villager::Dehydrated;
 use crate::components::villager::DrinkDistanceScorer;
 use crate::components::villager::DrowsyScorer;
 use crate::components::villager::EnemyDistanceScorer;
+use crate::components::villager::CombatStance;
+use crate::components::villager::Escort;
+use crate::components::villager::EscortScorer;
 use crate::components::villager::Exhausted;
 use crate::components::villager::FindDrink;
 use crate::components::villager::FindDrinkScorer;
@@ -15,6 +21,8 @@ use crate::components::villager::FindFood;
 use crate::components::villager::FindFoodScorer;
 use crate::components::villager::FindShelter;
 use crate::components::villager::FindShelterScorer;
+use crate::components::villager::Fight;
+use crate::components::villager::FightScorer;
 use crate::components::villager::Flee;
 use crate::components::villager::FoodDistanceScorer;
 use crate::components::villager::GoodMorale;
@@ -28,6 +36,10 @@ use crate::components::villager::MoveToShelter;
 use crate::components::villager::NearShelterScorer;
 use crate::components::villager::NoDrinks;
 use crate::components::villager::ProcessOrder;
+use crate::components::villager::OrderQueue;
+use crate::components::villager::RefusedWork;
+use crate::components::villager::Personality;
+use crate::components::villager::{Schedule, ScheduleActivity, ScheduleScorer};
 
 use crate::components::villager::ShelterDistanceScorer;
 
@@ -40,10 +52,18 @@ use crate::constants::DEHYDRATED;
 use crate::constants::EMERGENCY_SCORE;
 use crate::constants::EXHAUSTED;
 use crate::constants::MAX_ROUTINE_SCORE;
+use crate::constants::MORALE_MAX;
+use crate::constants::NPC_PLAYER_ID;
+use crate::constants::REFUSAL_COOLDOWN;
+use crate::constants::SCHEDULED_NEED_MIN;
+use crate::constants::SCHEDULED_SCORE;
 use crate::constants::SLIGHTLY_THIRSTY;
 use crate::constants::STARVING;
 use crate::constants::URGENT_SCORE;
+use crate::encumbrance::{Encumbrance, Load};
 use crate::event::{GameEvent, GameEventType, GameEvents, MapEvents, VisibleEvent};
+use crate::combat::{AttackType, Combat, CombatQuery, Combatant, InCombat};
+use crate::components::npc::ThreatTable;
 use crate::experiment;
 use crate::experiment::*;
 use crate::game::State;
@@ -52,13 +72,17 @@ use crate::ids::Ids;
 use crate::item;
 use crate::item::*;
 use crate::map::Map;
+use crate::social::{self, Child, FleeTogether, Household, Relationships};
 use crate::map::MapPos;
-use crate::network::ResponsePacket;
+use crate::network::{send_to_client, ResponsePacket};
 use crate::obj;
 use crate::obj::Obj;
 use crate::player;
 use crate::player::*;
-use crate::structure;
+use crate::plugins::ai::npc;
+use crate::resource::Resource;
+use crate::skill::{self, Skill, Skills};
+use crate::structure::{self, Structure};
 use crate::templates::Templates;
 use crate::villager;
 use crate::villager::*;
@@ -68,6 +92,14 @@ use crate::components::villager::{
     Sleep, Thirst, ThirstyScorer, Tired,
 };
 
+// Distance at which villagers flee from enemies
+pub const FLEE_RANGE: u32 = 2;
+
+// Ticks for a villager to move one tile when unburdened
+pub const VILLAGER_MOVE_TICKS: i32 = 36;
+// Villagers fleeing or seeing to their needs take longer
+pub const VILLAGER_SLOW_MOVE_TICKS: i32 = 48;
+
 #[derive(WorldQuery)]
 #[world_query(mutable, derive(Debug))]
 pub struct VillagerQuery {
@@ -87,6 +119,7 @@ pub struct VillagerWithOrderQuery {
     pos: &'static Position,
     class: &'static Class,
     order: &'static Order,
+    morale: Option<&'static Morale>,
 }
 
 #[derive(WorldQuery)]
@@ -113,13 +146,36 @@ pub struct VillagerBaseQuery {
 }
 
 pub fn enemy_distance_scorer_system(
+    game_tick: Res<GameTick>,
     ids: ResMut<Ids>,
     hero_query: Query<MapObjQuery, With<SubclassHero>>,
     obj_query: Query<MapObjQuery, Without<SubclassHero>>,
+    target_query: Query<MapObjQuery, Without<SubclassVillager>>,
+    threat_query: Query<&ThreatTable>,
+    personality_query: Query<&Personality>,
+    flee_together_query: Query<&FleeTogether>,
+    items: Res<Items>,
+    stance_query: Query<&CombatStance>,
+    stats_query: Query<&Stats>,
+    morale_query: Query<&Morale>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<EnemyDistanceScorer>>,
 ) {
     for (Actor(actor), mut score, _span) in &mut query {
+        // Friends run when one of them does
+        if let Ok(flee_together) = flee_together_query.get(*actor) {
+            if flee_together.until > game_tick.0 {
+                score.set(1.0);
+                continue;
+            }
+        }
+
         if let Ok(villager) = obj_query.get(*actor) {
+            // Cowards flee from enemies further away
+            let flee_range = match personality_query.get(*actor) {
+                Ok(personality) => (FLEE_RANGE as f32 * personality.flee_weight).round() as u32,
+                Err(_) => FLEE_RANGE,
+            };
+
             let Some(hero_id) = ids.get_hero(villager.player_id.0) else {
                 error!("Cannot find hero for player {:?}", villager.player_id);
                 continue;
@@ -135,6 +191,8 @@ pub fn enemy_distance_scorer_system(
                 continue;
             };
 
+            let threat_table = threat_query.get(*actor).ok();
+
             let mut nearby_enemies = false;
 
             for obj in obj_query.iter() {
@@ -142,17 +200,32 @@ pub fn enemy_distance_scorer_system(
                     continue;
                 }
 
-                if obj.player_id.0 != villager.player_id.0 {
+                if is_hostile(villager.player_id.0, obj.id.0, obj.player_id.0, threat_table) {
                     let distance =
                         Map::distance((villager.pos.x, villager.pos.y), (obj.pos.x, obj.pos.y));
 
-                    if distance <= 2 {
+                    if distance <= flee_range {
                         nearby_enemies = true;
                     }
                 }
             }
 
-            if nearby_enemies {
+            // Villagers willing to stand their ground leave it to the fight scorer
+            let stance = stance_query.get(*actor).unwrap_or(&CombatStance::Flee);
+
+            let will_fight = get_fight_target(
+                *actor,
+                &villager,
+                stance,
+                threat_table,
+                &target_query,
+                &stats_query,
+                &morale_query,
+                &items,
+            )
+            .is_some();
+
+            if nearby_enemies && !will_fight {
                 score.set(1.0);
             } else {
                 score.set(0.0);
@@ -244,9 +317,11 @@ pub fn thirsty_scorer_system(
 }
 
 pub fn hungry_scorer_system(
+    game_tick: Res<GameTick>,
     hungers: Query<&Hunger>,
     starving: Query<&Starving>,
     villager_attrs: Query<&VillagerAttrs>,
+    schedules: Query<&Schedule>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<HungryScorer>>,
 ) {
     for (Actor(actor), mut score, _span) in &mut query {
@@ -272,6 +347,12 @@ pub fn hungry_scorer_system(
                     hunger_score = MAX_ROUTINE_SCORE;
                 }
             }
+            let meal_time = schedules.get(*actor).map_or(false, |schedule| {
+                schedule.current_activity(game_tick.0) == ScheduleActivity::Meal
+            });
+
+            hunger_score = weigh_schedule(hunger_score, hunger.hunger, meal_time);
+
             score.set(hunger_score / 100.0);
             /*debug!(
                 "hunger score: {:?} activity: {:?}",
@@ -314,9 +395,11 @@ pub fn hungry_scorer_system(
 }
 
 pub fn drowsy_scorer_system(
+    game_tick: Res<GameTick>,
     tired_query: Query<&Tired>,
     exhausted: Query<&Exhausted>,
     villager_attrs: Query<&VillagerAttrs>,
+    schedules: Query<&Schedule>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<DrowsyScorer>>,
 ) {
     for (Actor(actor), mut score, _span) in &mut query {
@@ -342,6 +425,12 @@ pub fn drowsy_scorer_system(
                     tired_score = MAX_ROUTINE_SCORE;
                 }
             }
+            let sleep_time = schedules.get(*actor).map_or(false, |schedule| {
+                schedule.current_activity(game_tick.0) == ScheduleActivity::Sleep
+            });
+
+            tired_score = weigh_schedule(tired_score, tired.tired, sleep_time);
+
             score.set(tired_score / 100.0);
             /*debug!(
                 "tired score: {:?} activity: {:?}",
@@ -384,12 +473,29 @@ pub fn drowsy_scorer_system(
 }
 
 pub fn morale_scorer_system(
+    game_tick: Res<GameTick>,
     morale_query: Query<&Morale>,
+    personality_query: Query<&Personality>,
+    refused_query: Query<&RefusedWork>,
     mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<GoodMorale>>,
 ) {
     for (Actor(actor), mut score, _span) in &mut query {
+        // Stays idle for a while after refusing to work
+        if let Ok(refused) = refused_query.get(*actor) {
+            if game_tick.0 < refused.until {
+                score.set(0.0);
+                continue;
+            }
+        }
+
         if let Ok(_morale) = morale_query.get(*actor) {
-            score.set(0.6);
+            // Diligent villagers are keener to get back to work
+            let work_weight = match personality_query.get(*actor) {
+                Ok(personality) => personality.work_weight,
+                Err(_) => 1.0,
+            };
+
+            score.set((0.6 * work_weight).clamp(0.0, 1.0));
             /*if tired.tired >= 80.0 {
                 span.span()
                     .in_scope(|| debug!("Tired above threshold! Score: {}", tired.tired / 100.0));
@@ -398,6 +504,135 @@ pub fn morale_scorer_system(
     }
 }
 
+pub fn schedule_scorer_system(
+    game_tick: Res<GameTick>,
+    order_query: Query<&Order>,
+    schedules: Query<&Schedule>,
+    child_query: Query<&Child>,
+    mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<ScheduleScorer>>,
+) {
+    for (Actor(actor), mut score, _span) in &mut query {
+        // Children do not work until they have grown up
+        if child_query.get(*actor).is_ok() {
+            score.set(0.0);
+            continue;
+        }
+
+        // Following is not shift work, followers stay with their leader
+        if let Ok(Order::Follow { .. }) = order_query.get(*actor) {
+            score.set(1.0);
+            continue;
+        }
+
+        // Villagers without a schedule work whenever they can
+        let Ok(schedule) = schedules.get(*actor) else {
+            score.set(1.0);
+            continue;
+        };
+
+        if schedule.current_activity(game_tick.0) == ScheduleActivity::Work {
+            score.set(1.0);
+        } else {
+            score.set(0.0);
+        }
+    }
+}
+
+// Villagers hold a grudge against units that attacked them until the threat decays
+pub fn villager_threat_system(
+    game_tick: Res<GameTick>,
+    mut villager_query: Query<(&Position, &Viewshed, &mut ThreatTable), With<SubclassVillager>>,
+    unit_query: Query<(&Id, &Position, &State, &Class)>,
+) {
+    if game_tick.0 % npc::THREAT_UPDATE_INTERVAL != 0 {
+        return;
+    }
+
+    let mut units: HashMap<i32, Position> = HashMap::new();
+
+    for (unit_id, unit_pos, unit_state, unit_class) in unit_query.iter() {
+        if unit_class.0 == obj::CLASS_UNIT && !Obj::is_dead(unit_state) {
+            units.insert(unit_id.0, *unit_pos);
+        }
+    }
+
+    for (pos, viewshed, mut threat_table) in villager_query.iter_mut() {
+        npc::decay_threat(&mut threat_table, *pos, viewshed.range, &units);
+    }
+}
+
+pub fn escort_scorer_system(
+    game_tick: Res<GameTick>,
+    ids: Res<Ids>,
+    order_query: Query<&Order>,
+    combat_query: Query<&InCombat>,
+    state_query: Query<&State>,
+    mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<EscortScorer>>,
+) {
+    for (Actor(actor), mut score, _span) in &mut query {
+        score.set(0.0);
+
+        let Ok(Order::Follow { target: leader }) = order_query.get(*actor) else {
+            continue;
+        };
+
+        let Some(enemy_id) = get_escort_target(*leader, game_tick.0, &combat_query) else {
+            continue;
+        };
+
+        let Some(enemy_entity) = ids.get_entity(enemy_id) else {
+            continue;
+        };
+
+        let Ok(enemy_state) = state_query.get(enemy_entity) else {
+            continue;
+        };
+
+        if !Obj::is_dead(enemy_state) {
+            score.set(URGENT_SCORE / 100.0);
+        }
+    }
+}
+
+pub fn fight_scorer_system(
+    items: Res<Items>,
+    obj_query: Query<MapObjQuery, Without<SubclassHero>>,
+    target_query: Query<MapObjQuery, Without<SubclassVillager>>,
+    stance_query: Query<&CombatStance>,
+    threat_query: Query<&ThreatTable>,
+    stats_query: Query<&Stats>,
+    morale_query: Query<&Morale>,
+    mut query: Query<(&Actor, &mut Score, &ScorerSpan), With<FightScorer>>,
+) {
+    for (Actor(actor), mut score, _span) in &mut query {
+        score.set(0.0);
+
+        let Ok(villager) = obj_query.get(*actor) else {
+            continue;
+        };
+
+        // Villagers without a stance always flee
+        let Ok(stance) = stance_query.get(*actor) else {
+            continue;
+        };
+
+        let fight_target = get_fight_target(
+            *actor,
+            &villager,
+            stance,
+            threat_query.get(*actor).ok(),
+            &target_query,
+            &stats_query,
+            &morale_query,
+            &items,
+        );
+
+        if fight_target.is_some() {
+            score.set(1.0);
+        }
+    }
+}
+
 pub fn idle_action_systel(
     mut attrs_query: Query<&mut VillagerAttrs>,
     mut query: Query<(&Actor, &mut ActionState, &Idle, &ActionSpan)>,
@@ -421,10 +656,10 @@ pub fn process_order_system(
     mut experiments: ResMut<Experiments>,
     items: ResMut<Items>,
     active_infos: Res<ActiveInfos>,
-    templates: Res<Templates>,
+    (templates, skills): (Res<Templates>, Res<Skills>),
     villager_query: Query<VillagerWithOrderQuery, (With<Order>, Without<EventInProgress>)>,
     obj_query: Query<(&Id, &PlayerId, &Position)>,
-    template_query: Query<&Template>,
+    (template_query, load_query): (Query<&Template>, Query<&Load>),
     mut attrs_query: Query<&mut VillagerAttrs>,
     mut state_query: Query<&mut State>,
     mut query: Query<(&Actor, &mut ActionState, &ProcessOrder, &ActionSpan)>,
@@ -448,6 +683,25 @@ pub fn process_order_system(
 
                 debug!("Process Order Requested: {:?}", villager.order);
 
+                // Unhappy villagers may refuse to work
+                if let Some(morale) = villager.morale {
+                    if rand::thread_rng().gen::<f32>() < morale.refusal_chance() {
+                        Obj::add_sound_obj_event(
+                            game_tick.0,
+                            "I refuse to work in these conditions!".to_string(),
+                            villager.id,
+                            &mut map_events,
+                        );
+
+                        commands.entity(*actor).insert(RefusedWork {
+                            until: game_tick.0 + REFUSAL_COOLDOWN,
+                        });
+
+                        *state = ActionState::Failure;
+                        continue;
+                    }
+                }
+
                 match villager.order {
                     Order::Follow { target } => {
                         debug!("Process Follow Order");
@@ -685,7 +939,8 @@ pub fn process_order_system(
 
                                         let move_map_event = map_events.new(
                                             villager.id.0,
-                                            game_tick.0 + 36, // in the future
+                                            game_tick.0
+                                                + move_ticks(VILLAGER_MOVE_TICKS, *actor, &load_query),
                                             move_event,
                                         );
 
@@ -710,9 +965,19 @@ pub fn process_order_system(
                                 res_type: res_type.clone(),
                             };
 
+                            let gather_level = Skill::get_level(
+                                villager.id.0,
+                                &Resource::type_to_skill(res_type.clone()),
+                                &skills,
+                            );
+
                             map_events.new(
                                 villager.id.0,
-                                game_tick.0 + 8, // in the future
+                                game_tick.0
+                                    + Morale::work_duration(
+                                        Skill::work_duration(8, gather_level),
+                                        villager.morale,
+                                    ),
                                 gather_event,
                             );
                         }
@@ -776,7 +1041,8 @@ pub fn process_order_system(
 
                                     let move_map_event = map_events.new(
                                         villager.id.0,
-                                        game_tick.0 + 36, // in the future
+                                        game_tick.0
+                                            + move_ticks(VILLAGER_MOVE_TICKS, *actor, &load_query),
                                         move_event,
                                     );
 
@@ -795,9 +1061,19 @@ pub fn process_order_system(
 
                                         *villager_state = State::Refining;
 
+                                        let refine_level = Skill::get_level(
+                                            villager.id.0,
+                                            skill::REFINING,
+                                            &skills,
+                                        );
+
                                         map_event = map_events.new(
                                             villager.id.0,
-                                            game_tick.0 + 120, // in the future
+                                            game_tick.0
+                                                + Morale::work_duration(
+                                                    Skill::work_duration(120, refine_level),
+                                                    villager.morale,
+                                                ),
                                             refine_event,
                                         );
                                     }
@@ -818,9 +1094,21 @@ pub fn process_order_system(
                                         *villager_state =
                                             Villager::get_state_from_structure(template.0.clone());
 
+                                        let operate_level = Skill::get_level(
+                                            villager.id.0,
+                                            &Resource::type_to_skill(Structure::resource_type(
+                                                template.0.clone(),
+                                            )),
+                                            &skills,
+                                        );
+
                                         map_event = map_events.new(
                                             villager.id.0,
-                                            game_tick.0 + 40, // in the future
+                                            game_tick.0
+                                                + Morale::work_duration(
+                                                    Skill::work_duration(40, operate_level),
+                                                    villager.morale,
+                                                ),
                                             operate_event,
                                         );
                                     }
@@ -902,7 +1190,8 @@ pub fn process_order_system(
 
                                     let map_event = map_events.new(
                                         villager.id.0,
-                                        game_tick.0 + 36, // in the future
+                                        game_tick.0
+                                            + move_ticks(VILLAGER_MOVE_TICKS, *actor, &load_query),
                                         move_event,
                                     );
 
@@ -928,9 +1217,22 @@ pub fn process_order_system(
 
                                 let _event_id = ids.new_map_event_id();
 
+                                let craft_level = templates
+                                    .recipe_templates
+                                    .iter()
+                                    .find(|recipe| recipe.name == *recipe_name)
+                                    .and_then(|recipe| Skill::item_class_to_skill(recipe.class.clone()))
+                                    .map_or(0, |skill_name| {
+                                        Skill::get_level(villager.id.0, &skill_name, &skills)
+                                    });
+
                                 let map_event = map_events.new(
                                     villager.id.0,
-                                    game_tick.0 + 200, // in the future
+                                    game_tick.0
+                                        + Morale::work_duration(
+                                            Skill::work_duration(200, craft_level),
+                                            villager.morale,
+                                        ),
                                     craft_event,
                                 );
 
@@ -1004,7 +1306,8 @@ pub fn process_order_system(
 
                                     let map_event = map_events.new(
                                         villager.id.0,
-                                        game_tick.0 + 36, // in the future
+                                        game_tick.0
+                                            + move_ticks(VILLAGER_MOVE_TICKS, *actor, &load_query),
                                         move_event,
                                     );
 
@@ -1027,9 +1330,19 @@ pub fn process_order_system(
 
                                 map_events.new(villager.id.0, game_tick.0 + 4, state_change_event);
 
+                                let experiment_level = Skill::get_level(
+                                    villager.id.0,
+                                    skill::EXPERIMENTING,
+                                    &skills,
+                                );
+
                                 let map_event = map_events.new(
                                     villager.id.0,
-                                    game_tick.0 + 100, // in the future
+                                    game_tick.0
+                                        + Morale::work_duration(
+                                            Skill::work_duration(100, experiment_level),
+                                            villager.morale,
+                                        ),
                                     experiment_event,
                                 );
 
@@ -1121,7 +1434,8 @@ pub fn process_order_system(
 
                                     let map_event = map_events.new(
                                         villager.id.0,
-                                        game_tick.0 + 36, // in the future
+                                        game_tick.0
+                                            + move_ticks(VILLAGER_MOVE_TICKS, *actor, &load_query),
                                         move_event,
                                     );
 
@@ -1221,7 +1535,8 @@ pub fn process_order_system(
 
                                     let map_event = map_events.new(
                                         villager.id.0,
-                                        game_tick.0 + 36, // in the future
+                                        game_tick.0
+                                            + move_ticks(VILLAGER_MOVE_TICKS, *actor, &load_query),
                                         move_event,
                                     );
 
@@ -1295,6 +1610,8 @@ pub fn flee_system(
     hero_query: Query<BaseQuery, (With<SubclassHero>, Without<SubclassVillager>)>,
     villager_query: Query<BaseQuery, With<SubclassVillager>>,
     blocking_query: Query<BaseQuery>,
+    relationships_query: Query<&Relationships>,
+    load_query: Query<&Load>,
     mut attrs_query: Query<&mut VillagerAttrs>,
     mut action_query: Query<(&Actor, &mut ActionState, &Flee, &ActionSpan)>,
 ) {
@@ -1333,6 +1650,7 @@ pub fn flee_system(
                     let response_packet = ResponsePacket::InfoActivityUpdate {
                         id: villager.id.0,
                         activity: villager_attrs.activity.to_string(),
+                        progress: None,
                     };
 
                     info!("Sending info activity update: {:?}", response_packet);
@@ -1346,6 +1664,29 @@ pub fn flee_system(
                     }
                 }
 
+                // Nearby friends flee together with the villager
+                if let Ok(relationships) = relationships_query.get(*actor) {
+                    for friend_id in relationships.friends() {
+                        let Some(friend_entity) = ids.get_entity(friend_id) else {
+                            continue;
+                        };
+
+                        let Ok(friend) = villager_query.get(friend_entity) else {
+                            continue;
+                        };
+
+                        if Obj::is_dead(friend.state)
+                            || Map::dist(*villager.pos, *friend.pos) > social::GROUP_FLEE_RANGE
+                        {
+                            continue;
+                        }
+
+                        commands.entity(friend_entity).insert(FleeTogether {
+                            until: game_tick.0 + social::FLEE_TOGETHER_TIME,
+                        });
+                    }
+                }
+
                 *state = ActionState::Executing;
             }
             ActionState::Executing => {
@@ -1421,7 +1762,8 @@ pub fn flee_system(
 
                             let map_event = map_events.new(
                                 villager.id.0,
-                                game_tick.0 + 48, // in the future
+                                game_tick.0
+                                    + move_ticks(VILLAGER_SLOW_MOVE_TICKS, *actor, &load_query),
                                 move_event,
                             );
 
@@ -1450,6 +1792,514 @@ pub fn flee_system(
     }
 }
 
+pub fn escort_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut ids: ResMut<Ids>,
+    map: Res<Map>,
+    mut map_events: ResMut<MapEvents>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    order_query: Query<&Order>,
+    combat_query: Query<&InCombat>,
+    mut villager_query: Query<CombatQuery, (With<SubclassVillager>, Without<EventInProgress>)>,
+    mut target_query: Query<CombatQuery, Without<SubclassVillager>>,
+    mut threat_query: Query<&mut ThreatTable>,
+    load_query: Query<&Load>,
+    mut attrs_query: Query<&mut VillagerAttrs>,
+    mut query: Query<(&Actor, &mut ActionState, &Escort, &ActionSpan)>,
+) {
+    for (Actor(actor), mut state, _escort, _span) in &mut query {
+        match *state {
+            ActionState::Requested => {
+                let Ok(villager) = villager_query.get(*actor) else {
+                    continue;
+                };
+
+                let Ok(mut villager_attrs) = attrs_query.get_mut(*actor) else {
+                    error!("No villager attrs component for {:?}", *actor);
+                    continue;
+                };
+
+                if villager_attrs.activity != Activity::Escorting {
+                    Obj::add_sound_obj_event(
+                        game_tick.0,
+                        "To arms!".to_string(),
+                        villager.id,
+                        &mut map_events,
+                    );
+                }
+
+                villager_attrs.activity = Activity::Escorting;
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                let Ok(Order::Follow { target: leader }) = order_query.get(*actor) else {
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                // Leader is no longer fighting
+                let Some(enemy_id) = get_escort_target(*leader, game_tick.0, &combat_query) else {
+                    *state = ActionState::Success;
+                    continue;
+                };
+
+                let Some(enemy_entity) = ids.get_entity(enemy_id) else {
+                    error!("Cannot find enemy entity for {:?}", enemy_id);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                // Other living units used for flanking and surrounded checks
+                let mut combatants = Vec::new();
+                let mut blocking_list = Vec::new();
+
+                for unit in villager_query.iter().chain(target_query.iter()) {
+                    if unit.entity == *actor
+                        || unit.entity == enemy_entity
+                        || Obj::is_dead(&unit.state)
+                    {
+                        continue;
+                    }
+
+                    combatants.push(Combatant::new(
+                        unit.player_id.0,
+                        *unit.pos,
+                        unit.in_combat,
+                        game_tick.0,
+                    ));
+
+                    if Obj::is_blocking_state(unit.state.clone()) {
+                        blocking_list.push(MapPos(unit.pos.x, unit.pos.y));
+                    }
+                }
+
+                // Villager is busy with another event
+                let Ok(mut villager) = villager_query.get_mut(*actor) else {
+                    continue;
+                };
+
+                let Ok(mut enemy) = target_query.get_mut(enemy_entity) else {
+                    error!("Query failed to find entity {:?}", enemy_entity);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                if Obj::is_dead(&enemy.state) {
+                    *state = ActionState::Success;
+                    continue;
+                }
+
+                if Map::is_adjacent(*villager.pos, *enemy.pos) {
+                    let (damage, combo, _skill_updated) = Combat::process_attack(
+                        AttackType::Quick,
+                        &mut villager,
+                        &mut enemy,
+                        &combatants,
+                        &mut commands,
+                        &mut items,
+                        &templates,
+                        &map,
+                        &mut ids,
+                        &game_tick,
+                        &mut map_events,
+                    );
+
+                    // Add visible damage event to broadcast to everyone nearby
+                    Combat::add_damage_event(
+                        game_tick.0,
+                        "quick".to_string(),
+                        damage,
+                        combo,
+                        &villager,
+                        &enemy,
+                        &mut map_events,
+                    );
+
+                    if let Ok(mut threat_table) = threat_query.get_mut(enemy_entity) {
+                        threat_table.add(villager.id.0, damage as f32 * npc::DAMAGE_THREAT);
+                    }
+
+                    // Add Cooldown Event
+                    let cooldown_event = VisibleEvent::CooldownEvent { duration: 30 };
+
+                    let cooldown_map_event = map_events.new(
+                        villager.id.0,
+                        game_tick.0 + 30, // in the future
+                        cooldown_event,
+                    );
+
+                    commands.entity(*actor).insert(EventInProgress {
+                        event_id: cooldown_map_event.event_id,
+                    });
+                } else if *villager.state == State::None {
+                    let Some(path_result) = Map::find_path(
+                        *villager.pos,
+                        *enemy.pos,
+                        &map,
+                        blocking_list,
+                        true,
+                        false,
+                        false,
+                        false,
+                    ) else {
+                        debug!("No path found to enemy {:?}", enemy_id);
+                        *state = ActionState::Failure;
+                        continue;
+                    };
+
+                    let (path, _c) = path_result;
+                    let next_pos = &path[1];
+
+                    // Add State Change Event to Moving
+                    let state_change_event = VisibleEvent::StateChangeEvent {
+                        new_state: "moving".to_string(),
+                    };
+
+                    *villager.state = State::Moving;
+
+                    map_events.new(villager.id.0, game_tick.0 + 4, state_change_event);
+
+                    // Add Move Event
+                    let move_event = VisibleEvent::MoveEvent {
+                        src: *villager.pos,
+                        dst: Position {
+                            x: next_pos.0,
+                            y: next_pos.1,
+                        },
+                    };
+
+                    let move_map_event = map_events.new(
+                        villager.id.0,
+                        game_tick.0 + move_ticks(VILLAGER_MOVE_TICKS, *actor, &load_query),
+                        move_event,
+                    );
+
+                    commands.entity(*actor).insert(EventInProgress {
+                        event_id: move_map_event.event_id,
+                    });
+                }
+            }
+            ActionState::Cancelled => {
+                debug!("Escort was cancelled. Considering this a failure.");
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn fight_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut ids: ResMut<Ids>,
+    map: Res<Map>,
+    mut map_events: ResMut<MapEvents>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    stance_query: Query<&CombatStance>,
+    mut villager_query: Query<CombatQuery, (With<SubclassVillager>, Without<EventInProgress>)>,
+    mut target_query: Query<CombatQuery, Without<SubclassVillager>>,
+    mut threat_query: Query<&mut ThreatTable>,
+    load_query: Query<&Load>,
+    mut attrs_query: Query<&mut VillagerAttrs>,
+    mut query: Query<(&Actor, &mut ActionState, &Fight, &ActionSpan)>,
+) {
+    for (Actor(actor), mut state, _fight, _span) in &mut query {
+        match *state {
+            ActionState::Requested => {
+                let Ok(villager) = villager_query.get(*actor) else {
+                    continue;
+                };
+
+                let Ok(mut villager_attrs) = attrs_query.get_mut(*actor) else {
+                    error!("No villager attrs component for {:?}", *actor);
+                    continue;
+                };
+
+                if villager_attrs.activity != Activity::Fighting {
+                    Obj::add_sound_obj_event(
+                        game_tick.0,
+                        "Stand your ground!".to_string(),
+                        villager.id,
+                        &mut map_events,
+                    );
+                }
+
+                villager_attrs.activity = Activity::Fighting;
+                *state = ActionState::Executing;
+            }
+            ActionState::Executing => {
+                // Villager is busy with another event
+                let Ok(villager) = villager_query.get(*actor) else {
+                    continue;
+                };
+
+                let Ok(stance) = stance_query.get(*actor) else {
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                let units = target_query.iter().map(|unit| {
+                    let is_living_unit =
+                        unit.class.0 == obj::CLASS_UNIT && !Obj::is_dead(&unit.state);
+                    (unit.entity, unit.id.0, unit.player_id.0, *unit.pos, is_living_unit)
+                });
+
+                let enemy_entity = nearest_hostile(
+                    villager.player_id.0,
+                    *villager.pos,
+                    stance,
+                    threat_query.get(*actor).ok(),
+                    units,
+                );
+
+                // No enemies left to fight
+                let Some(enemy_entity) = enemy_entity else {
+                    *state = ActionState::Success;
+                    continue;
+                };
+
+                // Other living units used for flanking and surrounded checks
+                let mut combatants = Vec::new();
+                let mut blocking_list = Vec::new();
+
+                for unit in villager_query.iter().chain(target_query.iter()) {
+                    if unit.entity == *actor
+                        || unit.entity == enemy_entity
+                        || Obj::is_dead(&unit.state)
+                    {
+                        continue;
+                    }
+
+                    combatants.push(Combatant::new(
+                        unit.player_id.0,
+                        *unit.pos,
+                        unit.in_combat,
+                        game_tick.0,
+                    ));
+
+                    if Obj::is_blocking_state(unit.state.clone()) {
+                        blocking_list.push(MapPos(unit.pos.x, unit.pos.y));
+                    }
+                }
+
+                let Ok(mut villager) = villager_query.get_mut(*actor) else {
+                    continue;
+                };
+
+                let Ok(mut enemy) = target_query.get_mut(enemy_entity) else {
+                    error!("Query failed to find entity {:?}", enemy_entity);
+                    *state = ActionState::Failure;
+                    continue;
+                };
+
+                if Map::is_adjacent(*villager.pos, *enemy.pos) {
+                    let (damage, combo, _skill_updated) = Combat::process_attack(
+                        AttackType::Quick,
+                        &mut villager,
+                        &mut enemy,
+                        &combatants,
+                        &mut commands,
+                        &mut items,
+                        &templates,
+                        &map,
+                        &mut ids,
+                        &game_tick,
+                        &mut map_events,
+                    );
+
+                    // Add visible damage event to broadcast to everyone nearby
+                    Combat::add_damage_event(
+                        game_tick.0,
+                        "quick".to_string(),
+                        damage,
+                        combo,
+                        &villager,
+                        &enemy,
+                        &mut map_events,
+                    );
+
+                    if let Ok(mut threat_table) = threat_query.get_mut(enemy_entity) {
+                        threat_table.add(villager.id.0, damage as f32 * npc::DAMAGE_THREAT);
+                    }
+
+                    // Add Cooldown Event
+                    let cooldown_event = VisibleEvent::CooldownEvent { duration: 30 };
+
+                    let cooldown_map_event = map_events.new(
+                        villager.id.0,
+                        game_tick.0 + 30, // in the future
+                        cooldown_event,
+                    );
+
+                    commands.entity(*actor).insert(EventInProgress {
+                        event_id: cooldown_map_event.event_id,
+                    });
+                } else if *villager.state == State::None {
+                    let Some(path_result) = Map::find_path(
+                        *villager.pos,
+                        *enemy.pos,
+                        &map,
+                        blocking_list,
+                        true,
+                        false,
+                        false,
+                        false,
+                    ) else {
+                        debug!("No path found to enemy {:?}", enemy.id);
+                        *state = ActionState::Failure;
+                        continue;
+                    };
+
+                    let (path, _c) = path_result;
+                    let next_pos = &path[1];
+
+                    // Add State Change Event to Moving
+                    let state_change_event = VisibleEvent::StateChangeEvent {
+                        new_state: "moving".to_string(),
+                    };
+
+                    *villager.state = State::Moving;
+
+                    map_events.new(villager.id.0, game_tick.0 + 4, state_change_event);
+
+                    // Add Move Event
+                    let move_event = VisibleEvent::MoveEvent {
+                        src: *villager.pos,
+                        dst: Position {
+                            x: next_pos.0,
+                            y: next_pos.1,
+                        },
+                    };
+
+                    let move_map_event = map_events.new(
+                        villager.id.0,
+                        game_tick.0 + move_ticks(VILLAGER_MOVE_TICKS, *actor, &load_query),
+                        move_event,
+                    );
+
+                    commands.entity(*actor).insert(EventInProgress {
+                        event_id: move_map_event.event_id,
+                    });
+                }
+            }
+            ActionState::Cancelled => {
+                debug!("Fight was cancelled. Considering this a failure.");
+                *state = ActionState::Failure;
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn advance_order_queue_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    clients: Res<Clients>,
+    items: Res<Items>,
+    ids: Res<Ids>,
+    active_infos: Res<ActiveInfos>,
+    mut map_events: ResMut<MapEvents>,
+    template_query: Query<&Template>,
+    mut query: Query<
+        (
+            Entity,
+            &Id,
+            &PlayerId,
+            &State,
+            &mut VillagerAttrs,
+            &mut OrderQueue,
+            Option<&Order>,
+        ),
+        Without<EventInProgress>,
+    >,
+) {
+    for (entity, id, player_id, state, mut attrs, mut order_queue, order) in query.iter_mut() {
+        if Obj::is_dead(state) {
+            continue;
+        }
+
+        let Some(current) = order_queue.current().cloned() else {
+            continue;
+        };
+
+        let started = current.completed > 0 || order == Some(&current.order);
+
+        if started {
+            match order {
+                // Order was removed by the game, e.g. an experiment made a discovery
+                None => {
+                    order_queue.orders.pop_front();
+                    continue;
+                }
+                // A direct order from the player replaces the queue
+                Some(order) if *order != current.order => {
+                    order_queue.orders.clear();
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        if current.is_complete(&items) {
+            debug!("Queued order complete: {:?}", current);
+            order_queue.orders.pop_front();
+
+            if order_queue.orders.is_empty() {
+                commands.entity(entity).remove::<Order>();
+                attrs.activity = villager::Activity::None;
+            }
+
+            continue;
+        }
+
+        if !started {
+            if current.structure != -1 {
+                let Some(structure_entity) = ids.get_entity(current.structure) else {
+                    error!("Cannot find structure entity for {:?}", current.structure);
+                    order_queue.orders.pop_front();
+                    continue;
+                };
+
+                let Ok(structure_template) = template_query.get(structure_entity) else {
+                    error!("Query failed to find entity {:?}", structure_entity);
+                    order_queue.orders.pop_front();
+                    continue;
+                };
+
+                attrs.structure = current.structure;
+                attrs.structure_template = structure_template.0.clone();
+            }
+
+            Obj::add_sound_obj_event(
+                game_tick.0,
+                Villager::order_to_speech(&current.order),
+                id,
+                &mut map_events,
+            );
+
+            commands.entity(entity).insert(current.order.clone());
+        }
+
+        // Report progress on the current queued order
+        if order_queue.is_changed() {
+            let active_info_key = (player_id.0, id.0, "obj".to_string());
+
+            if let Some(_active_info) = active_infos.get(&active_info_key) {
+                let response_packet = ResponsePacket::InfoActivityUpdate {
+                    id: id.0,
+                    activity: attrs.activity.to_string(),
+                    progress: Some(current.progress(&items)),
+                };
+
+                send_to_client(player_id.0, response_packet, &clients);
+            }
+        }
+    }
+}
+
 pub fn find_drink_system(
     mut commands: Commands,
     game_tick: Res<GameTick>,
@@ -1496,6 +2346,7 @@ pub fn find_drink_system(
                     let response_packet = ResponsePacket::InfoActivityUpdate {
                         id: villager.id.0,
                         activity: villager.attrs.activity.to_string(),
+                        progress: None,
                     };
 
                     info!("Sending info activity update: {:?}", response_packet);
@@ -1580,6 +2431,7 @@ pub fn move_to_water_source_action_system(
     events_in_progress: Query<&EventInProgress>,
     obj_query: Query<(&Id, &PlayerId, &Position)>,
     mut state_query: Query<&mut State>,
+    load_query: Query<&Load>,
     mut attrs_query: Query<&mut VillagerAttrs>,
     mut action_query: Query<(&Actor, &mut ActionState, &MoveToWaterSource, &ActionSpan)>,
 ) {
@@ -1656,7 +2508,8 @@ pub fn move_to_water_source_action_system(
 
                                 let map_event = map_events.new(
                                     id.0,
-                                    game_tick.0 + 48, // in the future
+                                    game_tick.0
+                                        + move_ticks(VILLAGER_SLOW_MOVE_TICKS, *actor, &load_query),
                                     move_event,
                                 );
 
@@ -1961,6 +2814,7 @@ pub fn find_food_system(
                     let response_packet = ResponsePacket::InfoActivityUpdate {
                         id: villager.id.0,
                         activity: villager.attrs.activity.to_string(),
+                        progress: None,
                     };
 
                     info!("Sending info activity update: {:?}", response_packet);
@@ -2042,6 +2896,7 @@ pub fn move_to_food_action_system(
     mut game_events: ResMut<GameEvents>,
     events_in_progress: Query<&EventInProgress>,
     mut villager_query: Query<VillagerQuery, With<SubclassVillager>>,
+    load_query: Query<&Load>,
     mut action_query: Query<(&Actor, &mut ActionState, &MoveToFoodSource, &ActionSpan)>,
 ) {
     // Loop through all actions, just like you'd loop over all entities in any other query.
@@ -2103,7 +2958,8 @@ pub fn move_to_food_action_system(
 
                                 let map_event = map_events.new(
                                     villager.id.0,
-                                    game_tick.0 + 48, // in the future
+                                    game_tick.0
+                                        + move_ticks(VILLAGER_SLOW_MOVE_TICKS, *actor, &load_query),
                                     move_event,
                                 );
 
@@ -2366,6 +3222,7 @@ pub fn find_shelter_system(
     templates: Res<Templates>,
     mut villager_query: Query<VillagerQuery, With<SubclassVillager>>,
     structure_query: Query<ObjQuery, (With<ClassStructure>, Without<SubclassVillager>)>,
+    household_query: Query<&Household>,
     mut action_query: Query<(&Actor, &mut ActionState, &FindShelter, &ActionSpan)>,
 ) {
     for (Actor(actor), mut state, _find_shelter, span) in &mut action_query {
@@ -2400,6 +3257,7 @@ pub fn find_shelter_system(
                     let response_packet = ResponsePacket::InfoActivityUpdate {
                         id: villager.id.0,
                         activity: villager.attrs.activity.to_string(),
+                        progress: None,
                     };
 
                     info!("Sending info activity update: {:?}", response_packet);
@@ -2422,14 +3280,21 @@ pub fn find_shelter_system(
                     continue;
                 };
 
-                if let (Some(structure_pos), Some(_path)) =
-                    find_shelter(&villager, &structure_query, &map)
+                if let (Some(structure_pos), Some(_path), Some(shelter_name)) =
+                    find_shelter(
+                        &villager,
+                        household_query.get(*actor).ok(),
+                        &structure_query,
+                        &map,
+                    )
                 {
                     commands.entity(*actor).insert(MoveToShelter {
                         dest: structure_pos,
                     });
                     debug!("Found shelter, moving to shelter");
 
+                    villager.attrs.shelter = shelter_name;
+
                     *state = ActionState::Success;
                 } else {
                     debug!(
@@ -2440,6 +3305,9 @@ pub fn find_shelter_system(
                         dest: *villager.pos,
                     });
 
+                    // Sleeping rough
+                    villager.attrs.shelter = "None".to_string();
+
                     *state = ActionState::Success;
                 }
             }
@@ -2469,6 +3337,7 @@ pub fn move_to_shelter_system(
     events_in_progress: Query<&EventInProgress>,
     obj_query: Query<(&Id, &PlayerId, &Position)>,
     mut state_query: Query<&mut State>,
+    load_query: Query<&Load>,
     mut attrs_query: Query<&mut VillagerAttrs>,
     mut action_query: Query<(&Actor, &mut ActionState, &ActionSpan), With<MoveToSleepPos>>,
 ) {
@@ -2545,7 +3414,8 @@ pub fn move_to_shelter_system(
 
                                 let map_event = map_events.new(
                                     id.0,
-                                    game_tick.0 + 48, // in the future
+                                    game_tick.0
+                                        + move_ticks(VILLAGER_SLOW_MOVE_TICKS, *actor, &load_query),
                                     move_event,
                                 );
 
@@ -2786,12 +3656,14 @@ fn find_item_location_by_class(
 
 fn find_shelter(
     villager: &VillagerQueryItem,
+    household: Option<&Household>,
     structure_query: &Query<ObjQuery, (With<ClassStructure>, Without<SubclassVillager>)>,
     map: &Res<Map>,
-) -> (Option<Position>, Option<Vec<MapPos>>) {
+) -> (Option<Position>, Option<Vec<MapPos>>, Option<String>) {
     let mut nearest_shelter_dist = 10000 as u32;
     let mut nearest_structure_pos = None;
     let mut nearest_path = None;
+    let mut nearest_shelter_name = None;
 
     for structure in structure_query.iter() {
         // Skip if player_id of villager and structure are not matching
@@ -2829,14 +3701,156 @@ fn find_shelter(
 
         let (path, c) = path_result;
 
+        // Households always return to their own shelter
+        if let Some(household) = household {
+            if household.shelter == structure.id.0 {
+                return (Some(*structure.pos), Some(path), Some(structure.name.0.clone()));
+            }
+        }
+
         if nearest_shelter_dist > c {
             nearest_shelter_dist = c;
             nearest_structure_pos = Some(*structure.pos);
             nearest_path = Some(path);
+            nearest_shelter_name = Some(structure.name.0.clone());
         }
     }
 
-    return (nearest_structure_pos, nearest_path);
+    return (nearest_structure_pos, nearest_path, nearest_shelter_name);
+}
+
+// Urgent needs always win, otherwise the schedule raises a need during its block
+fn weigh_schedule(need_score: f32, need: f32, scheduled: bool) -> f32 {
+    if !scheduled || need_score >= URGENT_SCORE || need < SCHEDULED_NEED_MIN {
+        return need_score;
+    }
+
+    return f32::max(need_score, SCHEDULED_SCORE);
+}
+
+// Enemy the leader is currently fighting
+fn get_escort_target(
+    leader: Entity,
+    game_tick: i32,
+    combat_query: &Query<&InCombat>,
+) -> Option<i32> {
+    let Ok(in_combat) = combat_query.get(leader) else {
+        return None;
+    };
+
+    if !in_combat.is_active(game_tick) {
+        return None;
+    }
+
+    return Some(in_combat.target_id);
+}
+
+// Villagers slow down under load the same way heroes do
+fn move_ticks(base_ticks: i32, villager: Entity, load_query: &Query<&Load>) -> i32 {
+    let load = load_query.get(villager).map_or(0.0, |load| load.0);
+
+    return Encumbrance::move_ticks(base_ticks, load);
+}
+
+// Monsters are always hostile, other units only once they have attacked the villager
+fn is_hostile(
+    villager_player_id: i32,
+    unit_id: i32,
+    unit_player_id: i32,
+    threat_table: Option<&ThreatTable>,
+) -> bool {
+    if unit_player_id == villager_player_id {
+        return false;
+    }
+
+    return unit_player_id == NPC_PLAYER_ID
+        || threat_table.is_some_and(|threat_table| threat_table.get(unit_id) > 0.0);
+}
+
+// Nearest living hostile unit within the stance's engage range, used by both the
+// fight scorer and the fight action so they always agree on the target.
+// Units are (entity, id, player id, position, is a living unit).
+fn nearest_hostile(
+    villager_player_id: i32,
+    villager_pos: Position,
+    stance: &CombatStance,
+    threat_table: Option<&ThreatTable>,
+    units: impl Iterator<Item = (Entity, i32, i32, Position, bool)>,
+) -> Option<Entity> {
+    let mut nearest_enemy = None;
+    let mut nearest_distance = u32::MAX;
+
+    for (entity, unit_id, unit_player_id, unit_pos, is_living_unit) in units {
+        if !is_living_unit
+            || !is_hostile(villager_player_id, unit_id, unit_player_id, threat_table)
+            || !stance.in_engage_range(villager_pos, unit_pos)
+        {
+            continue;
+        }
+
+        let distance = Map::dist(villager_pos, unit_pos);
+
+        if distance < nearest_distance {
+            nearest_distance = distance;
+            nearest_enemy = Some(entity);
+        }
+    }
+
+    return nearest_enemy;
+}
+
+// Hostile unit the villager is willing to fight
+fn get_fight_target(
+    actor: Entity,
+    villager: &MapObjQueryItem,
+    stance: &CombatStance,
+    threat_table: Option<&ThreatTable>,
+    target_query: &Query<MapObjQuery, Without<SubclassVillager>>,
+    stats_query: &Query<&Stats>,
+    morale_query: &Query<&Morale>,
+    items: &Res<Items>,
+) -> Option<Entity> {
+    if *stance == CombatStance::Flee {
+        return None;
+    }
+
+    let units = target_query.iter().map(|unit| {
+        let is_living_unit = unit.class.0 == obj::CLASS_UNIT && !Obj::is_dead(unit.state);
+        (unit.entity, unit.id.0, unit.player_id.0, *unit.pos, is_living_unit)
+    });
+
+    let enemy_entity =
+        nearest_hostile(villager.player_id.0, *villager.pos, stance, threat_table, units)?;
+
+    let Ok(enemy) = target_query.get(enemy_entity) else {
+        return None;
+    };
+
+    let Ok(villager_stats) = stats_query.get(actor) else {
+        return None;
+    };
+
+    let Ok(enemy_stats) = stats_query.get(enemy.entity) else {
+        return None;
+    };
+
+    let villager_power =
+        Combat::combat_power(villager_stats, items.get_equipped(villager.id.0));
+    let enemy_power = Combat::combat_power(enemy_stats, items.get_equipped(enemy.id.0));
+
+    let threat = enemy_power / villager_power.max(1.0);
+    let hp_ratio = villager_stats.hp as f32 / villager_stats.base_hp.max(1) as f32;
+
+    let morale = match morale_query.get(actor) {
+        Ok(morale) => morale.morale,
+        Err(_) => MORALE_MAX,
+    };
+
+    if stance.should_fight(hp_ratio, morale, threat) {
+        return Some(enemy.entity);
+    }
+
+    return None;
 }
 
 fn remove_components(commands: &mut Commands, entity: &Entity) {
@@ -0,0 +1,672 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use std::collections::HashMap;
+
+use crate::components::npc::Territory;
+use crate::constants::{GAME_HOUR, GAME_TICKS_PER_DAY, NPC_PLAYER_ID};
+use crate::empire::Vassals;
+use crate::encounter::Encounter;
+use crate::event::{MapEvents, VisibleEvent};
+use crate::game::{
+    ClassStructure, GameTick, Id, PlayerId, Position, State, SubclassHero, SubclassNPC,
+};
+use crate::ids::Ids;
+use crate::item::Items;
+use crate::map::{Map, HEIGHT, WIDTH};
+use crate::obj::Obj;
+use crate::player::{get_time_of_day, TimeOfDay};
+use crate::templates::{ObjTemplate, Templates};
+
+pub const SUBCLASS_LAIR: &str = "lair";
+
+// The map is split into square regions which each hold their own monster population
+pub const REGION_SIZE: i32 = 10;
+
+// Regions further than this from every settlement are left empty
+pub const ACTIVE_RANGE: u32 = 20;
+// Monsters never spawn closer than this to a settlement
+pub const MIN_SETTLEMENT_DIST: u32 = 5;
+pub const LAIR_MIN_SETTLEMENT_DIST: u32 = 8;
+
+// Region cap grows by one every REGION_CAP_DIST_STEP tiles away from the nearest settlement
+pub const BASE_REGION_CAP: i32 = 1;
+pub const MAX_REGION_CAP: i32 = 5;
+pub const REGION_CAP_DIST_STEP: u32 = 5;
+
+// Hourly chances for a region under its cap
+pub const SPAWN_CHANCE: f32 = 0.25;
+pub const LAIR_CHANCE: f32 = 0.05;
+pub const SPAWN_ATTEMPTS: i32 = 5;
+
+pub const WANDER_RANGE: u32 = 3;
+pub const PATROL_RANGE: u32 = 2;
+
+pub const LAIR_MAX_MONSTERS: i32 = 3;
+pub const LAIR_UPDATE_INTERVAL: i32 = 10;
+pub const LAIR_RESPAWN_TIME: i32 = GAME_HOUR * 6;
+// Heroes this close to a lair with no monsters left clear it
+pub const LAIR_CLEAR_RANGE: u32 = 1;
+
+// Each cleared lair lowers the region cap and stops new lairs for a while
+pub const CLEARED_LAIR_CAP_PENALTY: i32 = 1;
+pub const CLEARED_LAIR_DURATION: i32 = GAME_TICKS_PER_DAY * 3;
+
+#[derive(Debug, Component, Clone)]
+pub struct Lair {
+    pub spawns: Vec<String>,
+    pub max: i32,
+    pub spawned: i32,
+    pub next_spawn: i32,
+}
+
+impl Lair {
+    pub fn new(spawns: Vec<String>, game_tick: i32) -> Self {
+        Self {
+            spawns: spawns,
+            max: LAIR_MAX_MONSTERS,
+            spawned: 0,
+            next_spawn: game_tick,
+        }
+    }
+
+    // Cleared once every monster it spawned is dead
+    pub fn is_cleared(&self, num_living: i32) -> bool {
+        return num_living == 0 && self.spawned > 0;
+    }
+
+    // Respawn timer only starts once the lair is short of monsters
+    pub fn can_spawn(&mut self, num_living: i32, game_tick: i32) -> bool {
+        if num_living >= self.max {
+            self.next_spawn = game_tick + LAIR_RESPAWN_TIME;
+            return false;
+        }
+
+        return game_tick >= self.next_spawn && !self.spawns.is_empty();
+    }
+
+    // A new lair fills up straight away, later losses take a while to replace
+    pub fn add_spawn(&mut self, game_tick: i32) {
+        if self.spawned >= self.max {
+            self.next_spawn = game_tick + LAIR_RESPAWN_TIME;
+        }
+
+        self.spawned += 1;
+    }
+}
+
+// Ticks at which lairs were cleared in each region
+#[derive(Resource, Debug, Default)]
+pub struct Population {
+    pub cleared_lairs: HashMap<(i32, i32), Vec<i32>>,
+}
+
+impl Population {
+    pub fn region(pos: Position) -> (i32, i32) {
+        return (pos.x / REGION_SIZE, pos.y / REGION_SIZE);
+    }
+
+    pub fn regions() -> Vec<(i32, i32)> {
+        let mut regions = Vec::new();
+
+        for region_y in 0..(HEIGHT + REGION_SIZE - 1) / REGION_SIZE {
+            for region_x in 0..(WIDTH + REGION_SIZE - 1) / REGION_SIZE {
+                regions.push((region_x, region_y));
+            }
+        }
+
+        return regions;
+    }
+
+    pub fn region_center((region_x, region_y): (i32, i32)) -> Position {
+        return Position {
+            x: i32::min(region_x * REGION_SIZE + REGION_SIZE / 2, WIDTH - 1),
+            y: i32::min(region_y * REGION_SIZE + REGION_SIZE / 2, HEIGHT - 1),
+        };
+    }
+
+    pub fn clear_lair(&mut self, pos: Position, game_tick: i32) {
+        self.cleared_lairs
+            .entry(Self::region(pos))
+            .or_default()
+            .push(game_tick);
+    }
+
+    // Cleared lairs are forgotten after a while
+    pub fn forget_cleared(&mut self, game_tick: i32) {
+        self.cleared_lairs.retain(|_region, cleared| {
+            cleared.retain(|cleared_at| game_tick - cleared_at < CLEARED_LAIR_DURATION);
+            return !cleared.is_empty();
+        });
+    }
+
+    pub fn cleared(&self, region: (i32, i32)) -> i32 {
+        return self
+            .cleared_lairs
+            .get(&region)
+            .map(|cleared| cleared.len() as i32)
+            .unwrap_or(0);
+    }
+
+    pub fn region_cap(&self, region: (i32, i32), settlement_dist: u32) -> i32 {
+        if settlement_dist > ACTIVE_RANGE {
+            return 0;
+        }
+
+        let cap = i32::min(
+            BASE_REGION_CAP + (settlement_dist / REGION_CAP_DIST_STEP) as i32,
+            MAX_REGION_CAP,
+        );
+
+        return i32::max(cap - self.cleared(region) * CLEARED_LAIR_CAP_PENALTY, 0);
+    }
+
+    pub fn is_night(game_tick: &GameTick) -> bool {
+        return matches!(get_time_of_day(game_tick.to_hour()), TimeOfDay::Night);
+    }
+
+    // Nocturnal monsters are only active at night, everything else always is
+    pub fn is_active(nocturnal: bool, game_tick: &GameTick) -> bool {
+        return !nocturnal || Self::is_night(game_tick);
+    }
+
+    pub fn settlement_dist(pos: Position, settlements: &Vec<Position>) -> u32 {
+        return settlements
+            .iter()
+            .map(|settlement| Map::dist(pos, *settlement))
+            .min()
+            .unwrap_or(u32::MAX);
+    }
+
+    // Random passable tile in the region at least min_dist from every settlement
+    pub fn random_spawn_pos(
+        region: (i32, i32),
+        min_dist: u32,
+        settlements: &Vec<Position>,
+        map: &Map,
+    ) -> Option<Position> {
+        let mut rng = rand::thread_rng();
+        let (region_x, region_y) = region;
+
+        for _attempt in 0..SPAWN_ATTEMPTS {
+            let x = region_x * REGION_SIZE + rng.gen_range(0..REGION_SIZE);
+            let y = region_y * REGION_SIZE + rng.gen_range(0..REGION_SIZE);
+
+            if !Map::is_valid_pos((x, y)) || !Map::is_passable(x, y, map) {
+                continue;
+            }
+
+            let pos = Position { x: x, y: y };
+
+            if Self::settlement_dist(pos, settlements) >= min_dist {
+                return Some(pos);
+            }
+        }
+
+        return None;
+    }
+
+    // Monster suited to the biome, nocturnal ones only come out at night
+    pub fn pick_monster(
+        pos: Position,
+        night: bool,
+        map: &Map,
+        templates: &Res<Templates>,
+    ) -> Option<String> {
+        let tile_type = Map::tile_type(pos.x, pos.y, map);
+
+        let monsters: Vec<&str> = Encounter::npc_list(tile_type)
+            .into_iter()
+            .filter(|monster| {
+                let template = ObjTemplate::get_template(monster.to_string(), templates);
+                night || !template.nocturnal.unwrap_or(false)
+            })
+            .collect();
+
+        if monsters.is_empty() {
+            return None;
+        }
+
+        let index = rand::thread_rng().gen_range(0..monsters.len());
+
+        return Some(monsters[index].to_string());
+    }
+
+    // Lair whose monsters live in the biome
+    pub fn pick_lair(pos: Position, map: &Map, templates: &Res<Templates>) -> Option<ObjTemplate> {
+        let tile_type = Map::tile_type(pos.x, pos.y, map);
+        let monsters = Encounter::npc_list(tile_type);
+
+        let lairs: Vec<&ObjTemplate> = templates
+            .obj_templates
+            .iter()
+            .filter(|obj_template| obj_template.subclass == SUBCLASS_LAIR)
+            .filter(|obj_template| {
+                obj_template.spawns.as_ref().is_some_and(|spawns| {
+                    spawns
+                        .iter()
+                        .any(|spawn| monsters.contains(&spawn.as_str()))
+                })
+            })
+            .collect();
+
+        if lairs.is_empty() {
+            return None;
+        }
+
+        let index = rand::thread_rng().gen_range(0..lairs.len());
+
+        return Some(lairs[index].clone());
+    }
+
+    pub fn spawn_monster(
+        template: String,
+        pos: Position,
+        territory: Territory,
+        commands: &mut Commands,
+        ids: &mut ResMut<Ids>,
+        items: &mut ResMut<Items>,
+        templates: &Res<Templates>,
+        game_tick: &Res<GameTick>,
+        map_events: &mut ResMut<MapEvents>,
+    ) -> i32 {
+        let (entity, npc_id, _player_id, _pos) = Encounter::spawn_npc(
+            NPC_PLAYER_ID,
+            pos,
+            template,
+            commands,
+            ids,
+            items,
+            templates,
+        );
+
+        commands.entity(entity).insert(territory);
+
+        map_events.new(
+            npc_id.0,
+            game_tick.0 + 1,
+            VisibleEvent::NewObjEvent { new_player: false },
+        );
+
+        return npc_id.0;
+    }
+}
+
+fn spawn_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    map: Res<Map>,
+    mut ids: ResMut<Ids>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    mut map_events: ResMut<MapEvents>,
+    mut population: ResMut<Population>,
+    vassals: Res<Vassals>,
+    settlement_query: Query<
+        (&PlayerId, &Position, &State),
+        Or<(With<SubclassHero>, With<ClassStructure>)>,
+    >,
+    npc_query: Query<(&PlayerId, &Position, &State), With<SubclassNPC>>,
+    lair_query: Query<&Position, With<Lair>>,
+) {
+    // Every game hour
+    if game_tick.0 % GAME_HOUR != 0 {
+        return;
+    }
+
+    population.forget_cleared(game_tick.0);
+
+    let mut settlements = Vec::new();
+
+    // Settlements of loyal vassals are kept clear by the empire
+    let mut protected = Vec::new();
+    let empire_template = &templates.empire_template;
+
+    for (player_id, pos, state) in settlement_query.iter() {
+        if player_id.0 < NPC_PLAYER_ID && !Obj::is_dead(state) {
+            settlements.push(*pos);
+
+            if vassals.is_protected(player_id.0, empire_template) {
+                protected.push(*pos);
+            }
+        }
+    }
+
+    let is_protected = |pos: Position| {
+        Population::settlement_dist(pos, &protected) < empire_template.loyalty.protection_range
+    };
+
+    // Nothing to populate the world for
+    if settlements.is_empty() {
+        return;
+    }
+
+    let mut monsters: HashMap<(i32, i32), i32> = HashMap::new();
+
+    for (player_id, pos, state) in npc_query.iter() {
+        if player_id.0 == NPC_PLAYER_ID && !Obj::is_dead(state) {
+            *monsters.entry(Population::region(*pos)).or_insert(0) += 1;
+        }
+    }
+
+    let lair_regions: Vec<(i32, i32)> = lair_query
+        .iter()
+        .map(|lair_pos| Population::region(*lair_pos))
+        .collect();
+
+    let night = Population::is_night(&game_tick);
+    let mut rng = rand::thread_rng();
+
+    for region in Population::regions() {
+        let settlement_dist =
+            Population::settlement_dist(Population::region_center(region), &settlements);
+
+        let cap = population.region_cap(region, settlement_dist);
+        let num_monsters = *monsters.get(&region).unwrap_or(&0);
+
+        if num_monsters < cap && rng.gen::<f32>() < SPAWN_CHANCE {
+            if let Some(pos) =
+                Population::random_spawn_pos(region, MIN_SETTLEMENT_DIST, &settlements, &map)
+                    .filter(|pos| !is_protected(*pos))
+            {
+                if let Some(monster) = Population::pick_monster(pos, night, &map, &templates) {
+                    debug!("Spawning {:?} at {:?} in region {:?}", monster, pos, region);
+
+                    Population::spawn_monster(
+                        monster,
+                        pos,
+                        Territory {
+                            center: pos,
+                            range: WANDER_RANGE,
+                            lair: None,
+                        },
+                        &mut commands,
+                        &mut ids,
+                        &mut items,
+                        &templates,
+                        &game_tick,
+                        &mut map_events,
+                    );
+                }
+            }
+        }
+
+        // Only one lair per region and none while the region recovers from a clearing
+        if cap == 0 || lair_regions.contains(&region) || population.cleared(region) > 0 {
+            continue;
+        }
+
+        if rng.gen::<f32>() >= LAIR_CHANCE {
+            continue;
+        }
+
+        let Some(pos) =
+            Population::random_spawn_pos(region, LAIR_MIN_SETTLEMENT_DIST, &settlements, &map)
+        else {
+            continue;
+        };
+
+        if is_protected(pos) {
+            continue;
+        }
+
+        let Some(lair_template) = Population::pick_lair(pos, &map, &templates) else {
+            continue;
+        };
+
+        debug!("Creating lair {:?} at {:?}", lair_template.name, pos);
+
+        let (_lair_id, lair_entity) = Obj::create(
+            NPC_PLAYER_ID,
+            lair_template.name.clone(),
+            pos,
+            State::None,
+            &mut commands,
+            &mut ids,
+            &mut map_events,
+            &game_tick,
+            &templates,
+        );
+
+        commands.entity(lair_entity).insert(Lair::new(
+            lair_template.spawns.clone().unwrap_or_default(),
+            game_tick.0,
+        ));
+    }
+}
+
+fn lair_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    map: Res<Map>,
+    mut ids: ResMut<Ids>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    mut map_events: ResMut<MapEvents>,
+    mut population: ResMut<Population>,
+    mut lair_query: Query<(Entity, &Id, &Position, &mut Lair)>,
+    monster_query: Query<(&Territory, &State), With<SubclassNPC>>,
+    hero_query: Query<(&Id, &PlayerId, &Position, &State), With<SubclassHero>>,
+) {
+    if game_tick.0 % LAIR_UPDATE_INTERVAL != 0 {
+        return;
+    }
+
+    let mut living: HashMap<i32, i32> = HashMap::new();
+
+    for (territory, state) in monster_query.iter() {
+        if let Some(lair_id) = territory.lair {
+            if !Obj::is_dead(state) {
+                *living.entry(lair_id).or_insert(0) += 1;
+            }
+        }
+    }
+
+    for (lair_entity, lair_id, lair_pos, mut lair) in lair_query.iter_mut() {
+        let num_living = *living.get(&lair_id.0).unwrap_or(&0);
+
+        if lair.is_cleared(num_living) {
+            let clearing_hero = hero_query.iter().find(|(_id, player_id, pos, state)| {
+                player_id.0 < NPC_PLAYER_ID
+                    && !Obj::is_dead(state)
+                    && Map::dist(*lair_pos, **pos) <= LAIR_CLEAR_RANGE
+            });
+
+            if let Some((hero_id, _player_id, _pos, _state)) = clearing_hero {
+                debug!("Lair {:?} cleared by {:?}", lair_id, hero_id);
+
+                population.clear_lair(*lair_pos, game_tick.0);
+
+                Obj::add_sound_obj_event(
+                    game_tick.0 + 1,
+                    "The lair falls silent.".to_string(),
+                    hero_id,
+                    &mut map_events,
+                );
+
+                map_events.new(
+                    lair_id.0,
+                    game_tick.0 + 1,
+                    VisibleEvent::RemoveObjEvent { pos: *lair_pos },
+                );
+
+                commands.entity(lair_entity).remove::<Lair>();
+                continue;
+            }
+        }
+
+        if !lair.can_spawn(num_living, game_tick.0) {
+            continue;
+        }
+
+        let spawn_positions: Vec<(i32, i32)> = Map::range((lair_pos.x, lair_pos.y), 1)
+            .into_iter()
+            .filter(|(x, y)| Map::is_valid_pos((*x, *y)) && Map::is_passable(*x, *y, &map))
+            .collect();
+
+        if spawn_positions.is_empty() {
+            continue;
+        }
+
+        let mut rng = rand::thread_rng();
+        let (x, y) = spawn_positions[rng.gen_range(0..spawn_positions.len())];
+        let monster = lair.spawns[rng.gen_range(0..lair.spawns.len())].clone();
+
+        debug!("Lair {:?} spawning {:?}", lair_id, monster);
+
+        Population::spawn_monster(
+            monster,
+            Position { x: x, y: y },
+            Territory {
+                center: *lair_pos,
+                range: PATROL_RANGE,
+                lair: Some(lair_id.0),
+            },
+            &mut commands,
+            &mut ids,
+            &mut items,
+            &templates,
+            &game_tick,
+            &mut map_events,
+        );
+
+        lair.add_spawn(game_tick.0);
+    }
+}
+
+pub struct PopulationPlugin;
+
+impl Plugin for PopulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Population>()
+            .add_systems(Update, spawn_system)
+            .add_systems(Update, lair_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_cap() {
+        let mut population = Population::default();
+        let region = (2, 2);
+
+        // Caps grow away from settlements up to the max and stop past the active range
+        assert_eq!(population.region_cap(region, 0), BASE_REGION_CAP);
+        assert_eq!(
+            population.region_cap(region, REGION_CAP_DIST_STEP * 2),
+            BASE_REGION_CAP + 2
+        );
+        assert_eq!(population.region_cap(region, ACTIVE_RANGE), MAX_REGION_CAP);
+        assert_eq!(population.region_cap(region, ACTIVE_RANGE + 1), 0);
+
+        // Every cleared lair lowers the cap of its region only
+        population.clear_lair(Population::region_center(region), 100);
+        population.clear_lair(Population::region_center(region), 200);
+
+        assert_eq!(population.cleared(region), 2);
+        assert_eq!(
+            population.region_cap(region, ACTIVE_RANGE),
+            MAX_REGION_CAP - 2 * CLEARED_LAIR_CAP_PENALTY
+        );
+        assert_eq!(population.region_cap((0, 0), ACTIVE_RANGE), MAX_REGION_CAP);
+
+        // Cap never goes negative
+        for game_tick in 0..MAX_REGION_CAP {
+            population.clear_lair(Population::region_center(region), 300 + game_tick);
+        }
+
+        assert_eq!(population.region_cap(region, 0), 0);
+    }
+
+    #[test]
+    fn test_forget_cleared() {
+        let mut population = Population::default();
+        let pos = Position { x: 25, y: 35 };
+
+        population.clear_lair(pos, 100);
+        population.clear_lair(pos, 500);
+
+        population.forget_cleared(100 + CLEARED_LAIR_DURATION);
+        assert_eq!(population.cleared(Population::region(pos)), 1);
+
+        population.forget_cleared(500 + CLEARED_LAIR_DURATION);
+        assert_eq!(population.cleared(Population::region(pos)), 0);
+        assert!(population.cleared_lairs.is_empty());
+    }
+
+    #[test]
+    fn test_regions() {
+        assert_eq!(Population::region(Position { x: 0, y: 0 }), (0, 0));
+        assert_eq!(Population::region(Position { x: 19, y: 21 }), (1, 2));
+
+        // Every tile falls in one of the regions and centers stay on the map
+        let regions = Population::regions();
+
+        assert!(regions.contains(&Population::region(Position {
+            x: WIDTH - 1,
+            y: HEIGHT - 1,
+        })));
+
+        for region in regions.iter() {
+            assert!(Map::is_valid_pos((
+                Population::region_center(*region).x,
+                Population::region_center(*region).y,
+            )));
+        }
+    }
+
+    #[test]
+    fn test_settlement_dist() {
+        let settlements = vec![Position { x: 10, y: 10 }, Position { x: 30, y: 10 }];
+
+        assert_eq!(
+            Population::settlement_dist(Position { x: 12, y: 10 }, &settlements),
+            2
+        );
+        assert_eq!(
+            Population::settlement_dist(Position { x: 10, y: 10 }, &Vec::new()),
+            u32::MAX
+        );
+    }
+
+    #[test]
+    fn test_lair_spawning() {
+        let mut lair = Lair::new(vec!["Wolf".to_string()], 0);
+
+        // A new lair fills up without waiting
+        for _ in 0..LAIR_MAX_MONSTERS {
+            assert!(lair.can_spawn(lair.spawned, 10));
+            lair.add_spawn(10);
+        }
+
+        assert!(!lair.can_spawn(LAIR_MAX_MONSTERS, 10));
+        assert_eq!(lair.next_spawn, 10 + LAIR_RESPAWN_TIME);
+
+        // Losses are only replaced once the respawn time has passed
+        assert!(!lair.can_spawn(LAIR_MAX_MONSTERS - 1, 20));
+        assert!(lair.can_spawn(LAIR_MAX_MONSTERS - 1, 10 + LAIR_RESPAWN_TIME));
+
+        lair.add_spawn(10 + LAIR_RESPAWN_TIME);
+        assert_eq!(lair.next_spawn, 10 + LAIR_RESPAWN_TIME * 2);
+
+        // Lairs without spawns never spawn
+        let mut empty_lair = Lair::new(Vec::new(), 0);
+        assert!(!empty_lair.can_spawn(0, 10));
+    }
+
+    #[test]
+    fn test_lair_cleared() {
+        let mut lair = Lair::new(vec!["Wolf".to_string()], 0);
+
+        // A lair that never spawned has nothing to clear
+        assert!(!lair.is_cleared(0));
+
+        lair.add_spawn(0);
+
+        assert!(!lair.is_cleared(1));
+        assert!(lair.is_cleared(0));
+    }
+}
@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::item::{AttrKey, AttrVal, Item, Items};
+use crate::quality::Quality;
+use crate::templates::ItemTemplate;
+
+// Value of items whose template does not set one
+pub const DEFAULT_ITEM_VALUE: i32 = 1;
+
+// Merchants sell above and buy below an item's value
+pub const MERCHANT_MARGIN: f32 = 0.25;
+
+// Stock a merchant is content to hold, prices rise below it and fall above it
+pub const TARGET_STOCK: f32 = 10.0;
+pub const SUPPLY_DEMAND_FACTOR: f32 = 0.5;
+pub const MIN_SUPPLY_MOD: f32 = 0.5;
+pub const MAX_SUPPLY_MOD: f32 = 2.0;
+
+// Worn out items keep this much of their value
+pub const MIN_QUALITY_MOD: f32 = 0.5;
+// Each point of resource property adds this much to the value
+pub const PROPERTY_VALUE_MOD: f32 = 0.01;
+
+// Gold traded with merchants for each point of reputation
+pub const GOLD_PER_REPUTATION: i32 = 10;
+pub const DISCOUNT_PER_REPUTATION: f32 = 0.001;
+pub const MAX_REPUTATION_DISCOUNT: f32 = 0.1;
+
+pub const BUY: &str = "buy";
+pub const SELL: &str = "sell";
+
+// Reputation with merchants by player id
+#[derive(Resource, Debug, Default, Deref, DerefMut)]
+pub struct Reputations(HashMap<i32, i32>);
+
+impl Reputations {
+    pub fn get(&self, player_id: i32) -> i32 {
+        return *self.0.get(&player_id).unwrap_or(&0);
+    }
+
+    pub fn add_trade(&mut self, player_id: i32, gold: i32) {
+        *self.0.entry(player_id).or_insert(0) += gold / GOLD_PER_REPUTATION;
+    }
+}
+
+// Trade is from the player's side, buying from or selling to the merchant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trade {
+    Buy,
+    Sell,
+}
+
+impl Trade {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Trade::Buy => BUY,
+            Trade::Sell => SELL,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub trade: Trade,
+    pub quantity: i32,
+    pub base_value: i32,
+    pub quality_mod: f32,
+    pub property_mod: f32,
+    pub supply_mod: f32,
+    pub margin: f32,
+    pub discount: f32,
+    pub unit_price: i32,
+    pub total: i32,
+}
+
+pub struct Pricing;
+
+impl Pricing {
+    pub fn base_value(item_name: &String, item_templates: &Vec<ItemTemplate>) -> i32 {
+        return item_templates
+            .iter()
+            .find(|item_template| item_template.name == *item_name)
+            .and_then(|item_template| item_template.value)
+            .unwrap_or(DEFAULT_ITEM_VALUE);
+    }
+
+    // Better grades are worth more and items lose value as they wear down
+    pub fn quality_mod(item: &Item) -> f32 {
+        let grade_mod = Quality::value_mod(item.quality);
+
+        let Some((durability, max_durability)) = item.get_durability() else {
+            return grade_mod;
+        };
+
+        if max_durability <= 0.0 {
+            return grade_mod;
+        }
+
+        let wear_mod = MIN_QUALITY_MOD + (1.0 - MIN_QUALITY_MOD) * (durability / max_durability);
+
+        return grade_mod * wear_mod;
+    }
+
+    // Resource properties carried over from gathering make an item more valuable
+    pub fn property_mod(item: &Item) -> f32 {
+        let mut total = 0.0;
+
+        for attr_key in AttrKey::property_iter() {
+            if let Some(AttrVal::Num(value)) = item.attrs.get(attr_key) {
+                total += value;
+            }
+        }
+
+        return f32::max(1.0 + total * PROPERTY_VALUE_MOD, 0.0);
+    }
+
+    pub fn supply_mod(stock: f32) -> f32 {
+        let shortage = (TARGET_STOCK - stock) / TARGET_STOCK;
+
+        return (1.0 + shortage * SUPPLY_DEMAND_FACTOR).clamp(MIN_SUPPLY_MOD, MAX_SUPPLY_MOD);
+    }
+
+    pub fn reputation_discount(reputation: i32) -> f32 {
+        return f32::min(
+            reputation as f32 * DISCOUNT_PER_REPUTATION,
+            MAX_REPUTATION_DISCOUNT,
+        );
+    }
+
+    // Stock is what the merchant holds before the trade, the price follows the average stock over it
+    pub fn quote(
+        trade: Trade,
+        item: &Item,
+        quantity: i32,
+        stock: i32,
+        reputation: i32,
+        item_templates: &Vec<ItemTemplate>,
+    ) -> Quote {
+        let base_value = Self::base_value(&item.name, &item_templates);
+        let quality_mod = Self::quality_mod(item);
+        let property_mod = Self::property_mod(item);
+        let discount = Self::reputation_discount(reputation);
+
+        let (average_stock, margin) = match trade {
+            Trade::Buy => (stock as f32 - quantity as f32 / 2.0, MERCHANT_MARGIN),
+            Trade::Sell => (stock as f32 + quantity as f32 / 2.0, -MERCHANT_MARGIN),
+        };
+
+        let supply_mod = Self::supply_mod(average_stock);
+
+        let value = base_value as f32 * quality_mod * property_mod * supply_mod * (1.0 + margin);
+
+        // Merchants round in their own favour
+        let unit_price = match trade {
+            Trade::Buy => (value * (1.0 - discount)).ceil() as i32,
+            Trade::Sell => (value * (1.0 + discount)).floor() as i32,
+        };
+
+        let unit_price = i32::max(unit_price, 1);
+
+        return Quote {
+            trade: trade,
+            quantity: quantity,
+            base_value: base_value,
+            quality_mod: quality_mod,
+            property_mod: property_mod,
+            supply_mod: supply_mod,
+            margin: margin,
+            discount: discount,
+            unit_price: unit_price,
+            total: unit_price * quantity,
+        };
+    }
+
+    pub fn quote_with_items(
+        trade: Trade,
+        item: &Item,
+        quantity: i32,
+        merchant_id: i32,
+        reputation: i32,
+        items: &Items,
+        item_templates: &Vec<ItemTemplate>,
+    ) -> Quote {
+        let stock = items.get_total_quantity(merchant_id, &item.name);
+
+        return Self::quote(trade, item, quantity, stock, reputation, &item_templates);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    fn load_items() -> (Items, Vec<ItemTemplate>) {
+        let item_template_file =
+            fs::File::open("item_template.yaml").expect("Could not open file.");
+        let item_templates: Vec<ItemTemplate> =
+            serde_yaml::from_reader(item_template_file).expect("Could not read values.");
+
+        let mut items = Items::default();
+        items.set_templates(item_templates.clone());
+
+        return (items, &item_templates);
+    }
+
+    #[test]
+    fn test_supply_mod() {
+        assert_eq!(Pricing::supply_mod(TARGET_STOCK), 1.0);
+        assert!(Pricing::supply_mod(TARGET_STOCK - 5.0) > 1.0);
+        assert!(Pricing::supply_mod(TARGET_STOCK + 5.0) < 1.0);
+
+        // Running out or flooding the merchant only moves the price so far
+        assert_eq!(Pricing::supply_mod(-1000.0), MAX_SUPPLY_MOD);
+        assert_eq!(Pricing::supply_mod(1000.0), MIN_SUPPLY_MOD);
+    }
+
+    #[test]
+    fn test_price_follows_stock() {
+        let (mut items, item_templates) = load_items();
+        let item = items.new(1, "Valleyrun Copper Ore".to_string(), 20);
+
+        let scarce = Pricing::quote(Trade::Buy, &item, 1, 0, 0, &item_templates);
+        let stocked = Pricing::quote(Trade::Buy, &item, 1, 10, 0, &item_templates);
+        let flooded = Pricing::quote(Trade::Buy, &item, 1, 100, 0, &item_templates);
+
+        assert!(scarce.unit_price > stocked.unit_price);
+        assert!(stocked.unit_price > flooded.unit_price);
+        assert_eq!(flooded.supply_mod, MIN_SUPPLY_MOD);
+
+        // Selling into a merchant's stock lowers what it pays
+        let first_sale = Pricing::quote(Trade::Sell, &item, 1, 0, 0, &item_templates);
+        let later_sale = Pricing::quote(Trade::Sell, &item, 1, 20, 0, &item_templates);
+
+        assert!(first_sale.unit_price > later_sale.unit_price);
+
+        // A large order is priced over the stock it uses up
+        let single = Pricing::quote(Trade::Buy, &item, 1, 10, 0, &item_templates);
+        let bulk = Pricing::quote(Trade::Buy, &item, 10, 10, 0, &item_templates);
+
+        assert!(bulk.unit_price > single.unit_price);
+        assert_eq!(bulk.total, bulk.unit_price * 10);
+    }
+
+    #[test]
+    fn test_buy_sell_spread() {
+        let (mut items, item_templates) = load_items();
+        let item = items.new(1, "Valleyrun Copper Ore".to_string(), 1);
+
+        let buy = Pricing::quote(Trade::Buy, &item, 1, 10, 0, &item_templates);
+        let sell = Pricing::quote(Trade::Sell, &item, 1, 10, 0, &item_templates);
+
+        assert_eq!(buy.base_value, 4);
+        assert_eq!(buy.unit_price, 6);
+        assert_eq!(sell.unit_price, 2);
+
+        // The best reputation narrows the spread without closing it
+        let max_reputation = 1000000;
+
+        assert_eq!(
+            Pricing::reputation_discount(max_reputation),
+            MAX_REPUTATION_DISCOUNT
+        );
+
+        let buy = Pricing::quote(Trade::Buy, &item, 1, 10, max_reputation, &item_templates);
+        let sell = Pricing::quote(Trade::Sell, &item, 1, 10, max_reputation, &item_templates);
+
+        assert!(buy.unit_price > sell.unit_price);
+    }
+
+    #[test]
+    fn test_min_price() {
+        let (mut items, item_templates) = load_items();
+        let item = items.new(1, "Unknown Trinket".to_string(), 1);
+
+        let sell = Pricing::quote(Trade::Sell, &item, 1, 1000, 0, &item_templates);
+
+        assert_eq!(sell.base_value, DEFAULT_ITEM_VALUE);
+        assert_eq!(sell.unit_price, 1);
+    }
+}
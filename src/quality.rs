@@ -0,0 +1,190 @@
+use rand::Rng;
+
+use crate::item::Item;
+use crate::resource::Property;
+use crate::templates::ResPropertyTemplates;
+
+// Quality is graded in stars
+pub const MIN_QUALITY: i32 = 1;
+pub const MAX_QUALITY: i32 = 5;
+// Items made from templates, such as merchant stock and loot
+pub const DEFAULT_QUALITY: i32 = 2;
+
+pub const QUALITY_NAMES: [&str; 5] = ["Crude", "Common", "Fine", "Superior", "Masterwork"];
+
+// Stars added by resource properties that rolled at the top of their range
+pub const PROPERTY_QUALITY_BONUS: f32 = 1.0;
+// Gatherers earn a star for every this many skill levels
+pub const SKILL_LEVELS_PER_STAR: i32 = 5;
+// Each refining or crafting skill level adds a 5% chance of improving the item by a star
+pub const UPGRADE_CHANCE_PER_LEVEL: f32 = 0.05;
+pub const MAX_UPGRADE_CHANCE: f32 = 0.5;
+
+// Each star above or below the default changes an item's value by a quarter
+pub const VALUE_PER_STAR: f32 = 0.25;
+
+pub struct Quality;
+
+impl Quality {
+    pub fn clamp(quality: i32) -> i32 {
+        return quality.clamp(MIN_QUALITY, MAX_QUALITY);
+    }
+
+    pub fn name(quality: i32) -> String {
+        return QUALITY_NAMES[(Self::clamp(quality) - MIN_QUALITY) as usize].to_string();
+    }
+
+    // How far up their ranges the properties rolled, from 0.0 to 1.0
+    pub fn property_grade(
+        properties: &Vec<Property>,
+        res_level: i32,
+        res_property_templates: &ResPropertyTemplates,
+    ) -> f32 {
+        let mut total_grade = 0.0;
+        let mut num_graded = 0;
+
+        for property in properties.iter() {
+            let Some(property_template) = res_property_templates
+                .get(property.name.clone())
+                .into_iter()
+                .find(|property_template| property_template.name == property.name)
+            else {
+                continue;
+            };
+
+            let Some(range) = property_template.ranges.get((res_level - 1) as usize) else {
+                continue;
+            };
+
+            let (min, max) = (range[0], range[1]);
+
+            let grade = if max > min {
+                (property.value - min) as f32 / (max - min) as f32
+            } else {
+                1.0
+            };
+
+            total_grade += grade.clamp(0.0, 1.0);
+            num_graded += 1;
+        }
+
+        if num_graded == 0 {
+            return 0.0;
+        }
+
+        return total_grade / num_graded as f32;
+    }
+
+    // Rate sample is the tier rolled from the resource template's quality rate
+    pub fn gathered(rate_sample: i32, property_grade: f32, skill_level: i32) -> i32 {
+        let property_bonus = (property_grade * PROPERTY_QUALITY_BONUS).round() as i32;
+        let skill_bonus = skill_level / SKILL_LEVELS_PER_STAR;
+
+        return Self::clamp(MIN_QUALITY + rate_sample + property_bonus + skill_bonus);
+    }
+
+    // Skilled and careful workers sometimes improve on their materials
+    pub fn upgrade(quality: i32, skill_level: i32, care_mod: f32) -> i32 {
+        let chance = f32::min(
+            skill_level as f32 * UPGRADE_CHANCE_PER_LEVEL * care_mod,
+            MAX_UPGRADE_CHANCE,
+        );
+
+        if rand::thread_rng().gen::<f32>() < chance {
+            return Self::clamp(quality + 1);
+        }
+
+        return Self::clamp(quality);
+    }
+
+    // Crafted items start from the average quality of what went into them,
+    // weighted by the quantity used of each input
+    pub fn combine(inputs: &Vec<Item>) -> i32 {
+        let total_quantity: i32 = inputs.iter().map(|input| input.quantity).sum();
+
+        if total_quantity <= 0 {
+            return DEFAULT_QUALITY;
+        }
+
+        let total: i32 = inputs
+            .iter()
+            .map(|input| input.quality * input.quantity)
+            .sum();
+
+        return Self::clamp((total as f32 / total_quantity as f32).round() as i32);
+    }
+
+    pub fn value_mod(quality: i32) -> f32 {
+        return f32::max(1.0 + (quality - DEFAULT_QUALITY) as f32 * VALUE_PER_STAR, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::Items;
+
+    fn input(items: &mut Items, quality: i32, quantity: i32) -> Item {
+        let mut item = items.new(1, "Valleyrun Copper Ore".to_string(), quantity);
+        item.quality = quality;
+
+        return item;
+    }
+
+    #[test]
+    fn test_gathered_skill_levels() {
+        // A star for every SKILL_LEVELS_PER_STAR levels, none below the first
+        assert_eq!(Quality::gathered(0, 0.0, 0), MIN_QUALITY);
+        assert_eq!(
+            Quality::gathered(0, 0.0, SKILL_LEVELS_PER_STAR - 1),
+            MIN_QUALITY
+        );
+        assert_eq!(
+            Quality::gathered(0, 0.0, SKILL_LEVELS_PER_STAR),
+            MIN_QUALITY + 1
+        );
+        assert_eq!(
+            Quality::gathered(0, 0.0, SKILL_LEVELS_PER_STAR * 2 - 1),
+            MIN_QUALITY + 1
+        );
+        assert_eq!(
+            Quality::gathered(0, 0.0, SKILL_LEVELS_PER_STAR * 2),
+            MIN_QUALITY + 2
+        );
+
+        // Clamped to the star range
+        assert_eq!(
+            Quality::gathered(0, 0.0, SKILL_LEVELS_PER_STAR * 100),
+            MAX_QUALITY
+        );
+        assert_eq!(Quality::gathered(-10, 0.0, 0), MIN_QUALITY);
+    }
+
+    #[test]
+    fn test_gathered_property_grade() {
+        assert_eq!(Quality::gathered(0, 0.49, 0), MIN_QUALITY);
+        assert_eq!(Quality::gathered(0, 0.5, 0), MIN_QUALITY + 1);
+        assert_eq!(
+            Quality::gathered(1, 1.0, SKILL_LEVELS_PER_STAR),
+            MIN_QUALITY + 3
+        );
+    }
+
+    #[test]
+    fn test_combine_weighted_by_quantity() {
+        let mut items = Items::default();
+
+        // An unweighted average of the two would be 3
+        let mostly_crude = vec![input(&mut items, 1, 3), input(&mut items, 5, 1)];
+        assert_eq!(Quality::combine(&mostly_crude), 2);
+
+        let mostly_masterwork = vec![input(&mut items, 1, 1), input(&mut items, 5, 3)];
+        assert_eq!(Quality::combine(&mostly_masterwork), 4);
+
+        // Rounded to the nearest star
+        let rounded = vec![input(&mut items, 2, 1), input(&mut items, 3, 1)];
+        assert_eq!(Quality::combine(&rounded), 3);
+
+        assert_eq!(Quality::combine(&Vec::new()), DEFAULT_QUALITY);
+    }
+}
@@ -15,8 +15,9 @@ use crate::map::Map;
 use crate::network;
 
 use crate::skill::{self, Skill, Skills};
+use crate::quality::Quality;
 use crate::templates::{
-    ItemTemplate, ResTemplate, ResTemplates, Templates,
+    ItemTemplate, ResPropertyTemplates, ResTemplate, ResTemplates, Templates,
 };
 
 pub const ORE: &str = "Ore";
@@ -364,6 +365,7 @@ impl Resource {
         item_templates: &Vec<ItemTemplate>,
         resources: &Resources,
         res_templates: &ResTemplates,
+        res_property_templates: &ResPropertyTemplates,
         _ids: &mut Ids,
     ) -> Vec<network::Item> {
         let mut rng = rand::thread_rng();
@@ -411,7 +413,13 @@ impl Resource {
                         // Determine quality
                         let dist = WeightedIndex::new(quality_rate).unwrap();
                         let sample = dist.sample(&mut rng);
-                        let quality_level = sample as i32;
+                        let property_grade = Quality::property_grade(
+                            &resource.properties,
+                            res_template.level,
+                            res_property_templates,
+                        );
+                        let quality_level =
+                            Quality::gathered(sample as i32, property_grade, skill_value);
 
                         debug!("Quality Level: {:?}", quality_level);
 
@@ -426,11 +434,12 @@ impl Resource {
 
                         debug!("item_attrs: {:?}", item_attrs);
 
-                        let (new_item, _merged) = items.new_with_attrs(
+                        let (new_item, _merged) = items.new_with_quality(
                             dest_obj_id,
                             resource.name.clone(),
                             1, //TODO should this be only 1 
-                            item_attrs.clone()
+                            item_attrs.clone(),
+                            quality_level,
                         );
 
                         info!("Gather item created: {:?}", new_item);
@@ -492,14 +501,14 @@ impl Resource {
         }
     }
 
-    fn type_to_skill(restype: String) -> String {
+    pub fn type_to_skill(restype: String) -> String {
         match restype.as_str() {
             ORE => skill::MINING.to_string(),
             WOOD => skill::WOODCUTTING.to_string(),
             STONE => skill::STONECUTTING.to_string(),
-            WATER => skill::GATHERING.to_string(),
+            WATER => skill::FORAGING.to_string(),
             FOOD => skill::FARMING.to_string(),
-            PLANT => skill::GATHERING.to_string(),
+            PLANT => skill::FORAGING.to_string(),
             _ => "Invalid".to_string(),
         }
     }
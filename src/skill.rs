@@ -4,7 +4,14 @@ use bevy::prelude::*;
 use std::collections::HashMap;
 
 
-use crate::templates::{SkillTemplate, SkillTemplates};
+use crate::constants::{GAME_HOUR, GAME_TICKS_PER_DAY, NPC_PLAYER_ID};
+use crate::event::MapEvents;
+use crate::game::{Clients, GameTick, Id, Name, Position, State};
+use crate::ids::Ids;
+use crate::map::Map;
+use crate::network::{send_to_client, ResponsePacket};
+use crate::obj::Obj;
+use crate::templates::{SkillTemplate, SkillTemplates, Templates};
 use crate::{item, network};
 
 pub const CLASS_GATHERING: &str = "Gathering";
@@ -15,10 +22,18 @@ pub const WOODCUTTING: &str = "Woodcutting";
 pub const STONECUTTING: &str = "Stonecutting";
 pub const GATHERING: &str = "Gathering";
 pub const FARMING: &str = "Farming";
+pub const SKINNING: &str = "Skinning";
+pub const BUTCHERING: &str = "Butchering";
+pub const FORAGING: &str = "Foraging";
+pub const EXPLORING: &str = "Exploring";
 
 pub const WEAPONSMITHING: &str = "Weaponsmithing";
 pub const ARMORSMITHING: &str = "Armorsmithing";
 pub const TOOLMAKING: &str = "Toolmaking";
+pub const REPAIRING: &str = "Repairing";
+pub const REFINING: &str = "Refining";
+pub const EXPERIMENTING: &str = "Experimenting";
+pub const BUILDING: &str = "Building";
 
 pub const NOVICE_WARRIOR: &str = "Novice Warrior";
 pub const NOVICE_RANGER: &str = "Novice Ranger";
@@ -34,6 +49,38 @@ pub const LEGENDARY_RANGER: &str = "Legendary Ranger";
 pub const LEGENDARY_MAGE: &str = "Legendary Mage";
 pub const MAX_RANK: &str = "Max Rank";
 
+// Experience awarded per completed activity
+pub const GATHER_XP: i32 = 10;
+pub const OPERATE_XP: i32 = 10;
+pub const REFINE_XP: i32 = 20;
+pub const CRAFT_XP: i32 = 100;
+pub const EXPERIMENT_XP: i32 = 25;
+pub const EXPLORE_XP: i32 = 15;
+pub const FARM_XP: i32 = 10;
+pub const BUILD_XP: i32 = 25;
+
+// Each skill level makes work 5% faster, down to half the base duration
+pub const SPEED_PER_LEVEL: f32 = 0.05;
+pub const MIN_DURATION_MOD: f32 = 0.5;
+// Each skill level adds 5% to the attributes of crafted and refined items
+pub const QUALITY_PER_LEVEL: f32 = 0.05;
+// Each skill level adds a 10% chance of refining an extra item
+pub const YIELD_PER_LEVEL: f32 = 0.1;
+
+// Teacher and student must both be this close to the training structure
+pub const TRAIN_RANGE: u32 = 1;
+pub const TRAIN_DURATION: i32 = GAME_TICKS_PER_DAY / 2;
+// Hourly xp for the student per level the teacher is ahead
+pub const TRAIN_XP_PER_LEVEL: i32 = 10;
+
+// Skill levels announced to the owner when reached
+pub const MILESTONES: [(i32, &str); 4] = [
+    (1, "Apprentice"),
+    (3, "Journeyman"),
+    (5, "Expert"),
+    (7, "Master"),
+];
+
 #[derive(Debug, Clone)]
 pub struct Skill {
     pub name: String,
@@ -48,8 +95,37 @@ pub struct SkillUpdated {
     pub xp: i32,
 }
 
-#[derive(Resource, Deref, DerefMut, Debug)]
-pub struct Skills(HashMap<i32, HashMap<String, Skill>>);
+#[derive(Resource, Deref, DerefMut, Debug, Default)]
+pub struct Skills {
+    #[deref]
+    skills: HashMap<i32, HashMap<String, Skill>>,
+    // Level ups of existing skills not yet announced by the milestone system
+    level_ups: Vec<LevelUp>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelUp {
+    pub obj_id: i32,
+    pub skill_name: String,
+    pub from: i32,
+    pub to: i32,
+}
+
+// Student being taught a skill by another villager at a structure
+#[derive(Debug, Component, Clone)]
+pub struct Training {
+    pub teacher: i32,
+    pub skill: String,
+    pub structure: i32,
+    pub until: i32,
+}
+
+impl Skills {
+    // Level ups since the last call, new skills such as starting skills are not included
+    pub fn take_level_ups(&mut self) -> Vec<LevelUp> {
+        return std::mem::take(&mut self.level_ups);
+    }
+}
 
 impl Skill {
     pub fn update(
@@ -82,9 +158,22 @@ impl Skill {
             panic!("Invalid skill name {:?}, does not exist in templates.", skill_name.clone());
         };
 
+        let mut level_up = None;
+
         if let Some(obj_skills) = skills.get_mut(&obj_id) {
             if let Some(obj_skill) = obj_skills.get_mut(&skill_name) {
+                let previous_level = obj_skill.level;
+
                 Self::update_xp_level(obj_skill, value, skill_template);
+
+                if obj_skill.level > previous_level {
+                    level_up = Some(LevelUp {
+                        obj_id: obj_id,
+                        skill_name: skill_name.clone(),
+                        from: previous_level,
+                        to: obj_skill.level,
+                    });
+                }
             } else {
                 let mut new_skill = Skill {
                     name: skill_name.clone(),
@@ -111,6 +200,63 @@ impl Skill {
 
             skills.insert(obj_id, obj_skills);
         }
+
+        if let Some(level_up) = level_up {
+            skills.level_ups.push(level_up);
+        }
+    }
+
+    // Skips skills without a template instead of panicking, e.g. invalid resource types
+    pub fn award(
+        obj_id: i32,
+        skill_name: &str,
+        value: i32,
+        skills: &mut Skills,
+        skill_templates: &SkillTemplates,
+    ) {
+        if skill_templates.get(skill_name).is_none() {
+            debug!("No skill template for {:?}, no xp awarded", skill_name);
+            return;
+        }
+
+        Self::update(
+            obj_id,
+            skill_name.to_string(),
+            value,
+            skills,
+            skill_templates,
+        );
+    }
+
+    pub fn get_level(obj_id: i32, skill_name: &str, skills: &Skills) -> i32 {
+        if let Some(obj_skills) = skills.get(&obj_id) {
+            if let Some(skill) = obj_skills.get(skill_name) {
+                return skill.level;
+            }
+        }
+
+        return 0;
+    }
+
+    pub fn work_duration(base_duration: i32, level: i32) -> i32 {
+        let duration_mod = f32::max(1.0 - level as f32 * SPEED_PER_LEVEL, MIN_DURATION_MOD);
+
+        return (base_duration as f32 * duration_mod) as i32;
+    }
+
+    pub fn quality_mod(level: i32) -> f32 {
+        return 1.0 + level as f32 * QUALITY_PER_LEVEL;
+    }
+
+    pub fn yield_chance(level: i32) -> f32 {
+        return level as f32 * YIELD_PER_LEVEL;
+    }
+
+    pub fn milestone(level: i32) -> Option<&'static str> {
+        return MILESTONES
+            .iter()
+            .find(|(milestone_level, _title)| *milestone_level == level)
+            .map(|(_level, title)| *title);
     }
 
     pub fn get_total_xp(obj_id: i32, skills: &Skills, skill_templates: &SkillTemplates) -> i32 {
@@ -271,15 +417,131 @@ impl Skill {
         return (next_template.to_string(), required_xp);
     }
 
-    pub fn item_class_to_skill(item_class: String) -> String {
+    pub fn item_class_to_skill(item_class: String) -> Option<String> {
         let skill = match item_class.as_str() {
             item::WEAPON => WEAPONSMITHING,
             item::ARMOR => ARMORSMITHING,
             item::GATHERING => TOOLMAKING,
-            _ => panic!("Invalid item_class"),
+            _ => return None,
+        };
+
+        return Some(skill.to_string());
+    }
+}
+
+fn training_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    ids: Res<Ids>,
+    templates: Res<Templates>,
+    mut skills: ResMut<Skills>,
+    mut map_events: ResMut<MapEvents>,
+    query: Query<(Entity, &Id, &Position, &State, &Training)>,
+    pos_query: Query<(&Position, &State)>,
+) {
+    // Every game hour
+    if game_tick.0 % GAME_HOUR != 0 {
+        return;
+    }
+
+    for (entity, id, pos, state, training) in query.iter() {
+        let teacher = ids
+            .get_entity(training.teacher)
+            .and_then(|teacher_entity| pos_query.get(teacher_entity).ok());
+
+        let structure = ids
+            .get_entity(training.structure)
+            .and_then(|structure_entity| pos_query.get(structure_entity).ok());
+
+        let teacher_level = Skill::get_level(training.teacher, &training.skill, &skills);
+        let student_level = Skill::get_level(id.0, &training.skill, &skills);
+
+        let mut finished = None;
+
+        if game_tick.0 >= training.until {
+            finished = Some("That's enough training for now.");
+        } else if teacher_level <= student_level {
+            finished = Some("There's nothing more you can teach me.");
+        } else if let (Some((teacher_pos, teacher_state)), Some((structure_pos, _))) =
+            (teacher, structure)
+        {
+            if Obj::is_dead(state)
+                || Obj::is_dead(teacher_state)
+                || Map::dist(*pos, *structure_pos) > TRAIN_RANGE
+                || Map::dist(*teacher_pos, *structure_pos) > TRAIN_RANGE
+            {
+                finished = Some("Our lesson has been cut short.");
+            }
+        } else {
+            finished = Some("Our lesson has been cut short.");
+        }
+
+        if let Some(speech) = finished {
+            commands.entity(entity).remove::<Training>();
+
+            if !Obj::is_dead(state) {
+                Obj::add_sound_obj_event(game_tick.0, speech.to_string(), id, &mut map_events);
+            }
+
+            continue;
+        }
+
+        Skill::award(
+            id.0,
+            &training.skill,
+            (teacher_level - student_level) * TRAIN_XP_PER_LEVEL,
+            &mut skills,
+            &templates.skill_templates,
+        );
+    }
+}
+
+fn skill_milestone_system(
+    mut skills: ResMut<Skills>,
+    ids: Res<Ids>,
+    clients: Res<Clients>,
+    name_query: Query<&Name>,
+) {
+    // Checked before draining so skills are not marked changed every frame
+    if skills.level_ups.is_empty() {
+        return;
+    }
+
+    for level_up in skills.take_level_ups() {
+        // Only players are told about their villagers' progress
+        let Some(player_id) = ids.get_player(level_up.obj_id) else {
+            continue;
         };
 
-        return skill.to_string();
+        if player_id >= NPC_PLAYER_ID {
+            continue;
+        }
+
+        for level in (level_up.from + 1)..=level_up.to {
+            let Some(title) = Skill::milestone(level) else {
+                continue;
+            };
+
+            let name = ids
+                .get_entity(level_up.obj_id)
+                .and_then(|entity| name_query.get(entity).ok())
+                .map_or(String::new(), |name| name.0.clone());
+
+            info!(
+                "{:?} reached {:?} level {:?} in {:?}",
+                name, title, level, level_up.skill_name
+            );
+
+            let packet = ResponsePacket::SkillMilestone {
+                id: level_up.obj_id,
+                name: name,
+                skill: level_up.skill_name.clone(),
+                level: level,
+                title: title.to_string(),
+            };
+
+            send_to_client(player_id, packet, &clients);
+        }
     }
 }
 
@@ -287,8 +549,10 @@ pub struct SkillPlugin;
 
 impl Plugin for SkillPlugin {
     fn build(&self, app: &mut App) {
-        let skills = Skills(HashMap::new());
+        let skills = Skills::default();
 
-        app.insert_resource(skills);
+        app.insert_resource(skills)
+            .add_systems(Update, training_system)
+            .add_systems(Update, skill_milestone_system);
     }
 }
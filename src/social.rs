@@ -0,0 +1,725 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::components::villager::{Morale, Personality, MORALE_FRIENDS, MORALE_RIVALS};
+use crate::constants::{GAME_HOUR, GAME_TICKS_PER_DAY};
+use crate::event::MapEvents;
+use crate::game::{
+    ClassStructure, GameTick, Id, PlayerId, Position, State, Subclass, SubclassVillager,
+};
+use crate::ids::Ids;
+use crate::item::{self, Items};
+use crate::map::Map;
+use crate::obj::Obj;
+use crate::player;
+use crate::skill::Skills;
+use crate::structure;
+use crate::templates::Templates;
+
+pub const MAX_AFFINITY: f32 = 100.0;
+pub const FRIEND_AFFINITY: f32 = 50.0;
+pub const RIVAL_AFFINITY: f32 = -50.0;
+// Friends this close will move in together
+pub const HOUSEHOLD_AFFINITY: f32 = 75.0;
+
+// Villagers within this range spend the hour together
+pub const SOCIAL_RANGE: u32 = 2;
+// Hourly affinity gain from time together, plus the same again per shared like
+pub const AFFINITY_GAIN: f32 = 1.0;
+// Hourly affinity loss when one likes what the other dislikes
+pub const AFFINITY_CLASH: f32 = 2.0;
+
+pub const FRIEND_MORALE_BONUS: f32 = 3.0;
+pub const MAX_FRIEND_MORALE_BONUS: f32 = 9.0;
+pub const RIVAL_MORALE_PENALTY: f32 = 5.0;
+
+// Daily chance of a household having a child
+pub const BIRTH_CHANCE: f32 = 0.1;
+// Food the player's structures must hold for a household to have a child
+pub const BIRTH_FOOD_REQ: i32 = 20;
+pub const CHILD_IMAGE: &str = "humanvillager1";
+// Villagers a shelter houses, the lowest level only fits the couple
+pub const SHELTER_OCCUPANTS: usize = 2;
+pub const SHELTER_OCCUPANTS_PER_LEVEL: usize = 1;
+// Ticks before a child grows up and can work
+pub const CHILD_GROWTH_TIME: i32 = GAME_TICKS_PER_DAY * 5;
+pub const PARENT_AFFINITY: f32 = 80.0;
+
+// Friends within this range flee together
+pub const GROUP_FLEE_RANGE: u32 = 3;
+pub const FLEE_TOGETHER_TIME: i32 = GAME_HOUR;
+
+// Affinity towards other villagers by obj id, from -100 to 100
+#[derive(Debug, Component, Clone, Default)]
+pub struct Relationships {
+    pub affinity: HashMap<i32, f32>,
+}
+
+impl Relationships {
+    pub fn get(&self, id: i32) -> f32 {
+        return *self.affinity.get(&id).unwrap_or(&0.0);
+    }
+
+    pub fn change(&mut self, id: i32, amount: f32) {
+        let affinity = self.affinity.entry(id).or_insert(0.0);
+        *affinity = (*affinity + amount).clamp(-MAX_AFFINITY, MAX_AFFINITY);
+    }
+
+    pub fn is_friend(&self, id: i32) -> bool {
+        return self.get(id) >= FRIEND_AFFINITY;
+    }
+
+    pub fn is_rival(&self, id: i32) -> bool {
+        return self.get(id) <= RIVAL_AFFINITY;
+    }
+
+    pub fn friends(&self) -> Vec<i32> {
+        let mut friends: Vec<i32> = self
+            .affinity
+            .iter()
+            .filter(|(_id, affinity)| **affinity >= FRIEND_AFFINITY)
+            .map(|(id, _affinity)| *id)
+            .collect();
+
+        friends.sort();
+        return friends;
+    }
+
+    pub fn rivals(&self) -> Vec<i32> {
+        let mut rivals: Vec<i32> = self
+            .affinity
+            .iter()
+            .filter(|(_id, affinity)| **affinity <= RIVAL_AFFINITY)
+            .map(|(id, _affinity)| *id)
+            .collect();
+
+        rivals.sort();
+        return rivals;
+    }
+}
+
+// Pair of villagers sharing a shelter
+#[derive(Debug, Component, Clone)]
+pub struct Household {
+    pub partner: i32,
+    pub shelter: i32,
+}
+
+#[derive(Debug, Component, Clone)]
+pub struct Child {
+    pub born_at: i32,
+    pub parents: Vec<i32>,
+}
+
+// Set on friends of a fleeing villager so they run with them
+#[derive(Debug, Component, Clone)]
+pub struct FleeTogether {
+    pub until: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Social;
+
+impl Social {
+    // Shared likes bring villagers closer, one liking what the other dislikes drives them apart
+    pub fn affinity_change(
+        likes: &Vec<String>,
+        dislikes: &Vec<String>,
+        other_likes: &Vec<String>,
+        other_dislikes: &Vec<String>,
+    ) -> f32 {
+        let shared = likes.iter().filter(|like| other_likes.contains(*like)).count();
+
+        let clashes = likes
+            .iter()
+            .filter(|like| other_dislikes.contains(*like))
+            .count()
+            + other_likes
+                .iter()
+                .filter(|like| dislikes.contains(*like))
+                .count();
+
+        return AFFINITY_GAIN * (1 + shared) as f32 - AFFINITY_CLASH * clashes as f32;
+    }
+
+    pub fn friends_morale(num_friends: usize) -> f32 {
+        return f32::min(
+            FRIEND_MORALE_BONUS * num_friends as f32,
+            MAX_FRIEND_MORALE_BONUS,
+        );
+    }
+
+    pub fn shelter_occupants(level: i32) -> usize {
+        return SHELTER_OCCUPANTS + level.max(0) as usize * SHELTER_OCCUPANTS_PER_LEVEL;
+    }
+
+    // Both partners and their children have to fit in the shelter
+    pub fn has_room(shelter_level: i32, num_children: usize) -> bool {
+        return 2 + num_children < Social::shelter_occupants(shelter_level);
+    }
+
+    pub fn stored_food(player_id: i32, structures: &Vec<(i32, i32)>, items: &Items) -> i32 {
+        let mut total = 0;
+
+        for (structure_id, structure_player_id) in structures.iter() {
+            if *structure_player_id != player_id {
+                continue;
+            }
+
+            for structure_item in items.get_by_owner(*structure_id).iter() {
+                if structure_item.class == item::FOOD {
+                    total += structure_item.quantity;
+                }
+            }
+        }
+
+        return total;
+    }
+}
+
+fn relationship_system(
+    game_tick: Res<GameTick>,
+    mut query: Query<
+        (
+            &Id,
+            &PlayerId,
+            &Position,
+            &State,
+            Option<&Personality>,
+            &mut Relationships,
+            &mut Morale,
+        ),
+        With<SubclassVillager>,
+    >,
+) {
+    // Every game hour
+    if game_tick.0 % GAME_HOUR != 0 {
+        return;
+    }
+
+    let mut villagers = Vec::new();
+
+    for (id, player_id, pos, state, personality, _relationships, _morale) in query.iter() {
+        if Obj::is_dead(state) {
+            continue;
+        }
+
+        let (likes, dislikes) = match personality {
+            Some(personality) => (personality.likes.clone(), personality.dislikes.clone()),
+            None => (Vec::new(), Vec::new()),
+        };
+
+        villagers.push((id.0, player_id.0, *pos, likes, dislikes));
+    }
+
+    let mut changes: HashMap<i32, Vec<(i32, f32)>> = HashMap::new();
+
+    for (i, (id, player_id, pos, likes, dislikes)) in villagers.iter().enumerate() {
+        for (other_id, other_player_id, other_pos, other_likes, other_dislikes) in
+            villagers.iter().skip(i + 1)
+        {
+            if player_id != other_player_id || Map::dist(*pos, *other_pos) > SOCIAL_RANGE {
+                continue;
+            }
+
+            let change = Social::affinity_change(likes, dislikes, other_likes, other_dislikes);
+
+            changes.entry(*id).or_default().push((*other_id, change));
+            changes.entry(*other_id).or_default().push((*id, change));
+        }
+    }
+
+    let living: HashSet<i32> = villagers.iter().map(|villager| villager.0).collect();
+
+    for (id, _player_id, _pos, state, _personality, mut relationships, mut morale) in
+        query.iter_mut()
+    {
+        if Obj::is_dead(state) {
+            continue;
+        }
+
+        let nearby = changes.remove(&id.0).unwrap_or_default();
+
+        for (other_id, change) in nearby.iter() {
+            relationships.change(*other_id, *change);
+        }
+
+        let num_friends = relationships
+            .friends()
+            .iter()
+            .filter(|friend_id| living.contains(*friend_id))
+            .count();
+
+        if num_friends > 0 {
+            morale.add_modifier(
+                MORALE_FRIENDS,
+                Social::friends_morale(num_friends),
+                game_tick.0 + GAME_HOUR * 2,
+            );
+        }
+
+        // Rivals only grate when they are around
+        if nearby
+            .iter()
+            .any(|(other_id, _change)| relationships.is_rival(*other_id))
+        {
+            morale.add_modifier(
+                MORALE_RIVALS,
+                -RIVAL_MORALE_PENALTY,
+                game_tick.0 + GAME_HOUR * 2,
+            );
+        }
+    }
+}
+
+fn household_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    ids: Res<Ids>,
+    mut map_events: ResMut<MapEvents>,
+    villager_query: Query<
+        (
+            Entity,
+            &Id,
+            &PlayerId,
+            &State,
+            &Relationships,
+            Option<&Household>,
+            Option<&Child>,
+        ),
+        With<SubclassVillager>,
+    >,
+    structure_query: Query<(&Id, &PlayerId, &Subclass, &State), With<ClassStructure>>,
+) {
+    // Every game day
+    if game_tick.0 % GAME_TICKS_PER_DAY != 0 {
+        return;
+    }
+
+    let mut shelters = HashMap::new();
+
+    for (structure_id, structure_player_id, subclass, structure_state) in structure_query.iter() {
+        if subclass.0 == structure::SHELTER && *structure_state == State::None {
+            shelters.insert(structure_id.0, structure_player_id.0);
+        }
+    }
+
+    let is_living = |villager_id: i32| -> bool {
+        let Some(entity) = ids.get_entity(villager_id) else {
+            return false;
+        };
+
+        let Ok((_, _, _, state, _, _, _)) = villager_query.get(entity) else {
+            return false;
+        };
+
+        return !Obj::is_dead(state);
+    };
+
+    let mut paired = HashSet::new();
+    let mut claimed_shelters = HashSet::new();
+
+    // Break up households that lost their partner or shelter
+    for (entity, id, _player_id, state, _relationships, household, _child) in villager_query.iter()
+    {
+        let Some(household) = household else {
+            continue;
+        };
+
+        if Obj::is_dead(state) {
+            continue;
+        }
+
+        if is_living(household.partner) && shelters.contains_key(&household.shelter) {
+            paired.insert(id.0);
+            claimed_shelters.insert(household.shelter);
+        } else {
+            debug!("Household of {:?} has broken up", id.0);
+            commands.entity(entity).remove::<Household>();
+        }
+    }
+
+    // Close friends without a household move in together
+    for (entity, id, player_id, state, relationships, _household, child) in villager_query.iter() {
+        if Obj::is_dead(state) || child.is_some() || paired.contains(&id.0) {
+            continue;
+        }
+
+        for friend_id in relationships.friends() {
+            if relationships.get(friend_id) < HOUSEHOLD_AFFINITY || paired.contains(&friend_id) {
+                continue;
+            }
+
+            let Some(friend_entity) = ids.get_entity(friend_id) else {
+                continue;
+            };
+
+            let Ok((_, _, friend_player_id, friend_state, friend_relationships, _, friend_child)) =
+                villager_query.get(friend_entity)
+            else {
+                continue;
+            };
+
+            if friend_player_id.0 != player_id.0
+                || Obj::is_dead(friend_state)
+                || friend_child.is_some()
+                || friend_relationships.get(id.0) < HOUSEHOLD_AFFINITY
+            {
+                continue;
+            }
+
+            let Some(shelter_id) = shelters
+                .iter()
+                .filter(|(shelter_id, shelter_player_id)| {
+                    **shelter_player_id == player_id.0 && !claimed_shelters.contains(*shelter_id)
+                })
+                .map(|(shelter_id, _shelter_player_id)| *shelter_id)
+                .min()
+            else {
+                debug!("No free shelter for a household for player {:?}", player_id.0);
+                break;
+            };
+
+            commands.entity(entity).insert(Household {
+                partner: friend_id,
+                shelter: shelter_id,
+            });
+
+            commands.entity(friend_entity).insert(Household {
+                partner: id.0,
+                shelter: shelter_id,
+            });
+
+            paired.insert(id.0);
+            paired.insert(friend_id);
+            claimed_shelters.insert(shelter_id);
+
+            Obj::add_sound_obj_event(
+                game_tick.0,
+                "We're moving in together!".to_string(),
+                id,
+                &mut map_events,
+            );
+
+            break;
+        }
+    }
+}
+
+fn birth_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut ids: ResMut<Ids>,
+    mut map_events: ResMut<MapEvents>,
+    mut skills: ResMut<Skills>,
+    items: Res<Items>,
+    templates: Res<Templates>,
+    household_query: Query<(&Id, &PlayerId, &State, &Household), With<SubclassVillager>>,
+    structure_query: Query<(&Id, &PlayerId, &Name, &Position, &State), With<ClassStructure>>,
+    child_query: Query<(&State, &Child)>,
+) {
+    // Every game day
+    if game_tick.0 % GAME_TICKS_PER_DAY != 0 {
+        return;
+    }
+
+    let structures: Vec<(i32, i32)> = structure_query
+        .iter()
+        .filter(|(_id, _player_id, _name, _pos, state)| **state == State::None)
+        .map(|(id, player_id, _name, _pos, _state)| (id.0, player_id.0))
+        .collect();
+
+    for (id, player_id, state, household) in household_query.iter() {
+        // Each household is only rolled once
+        if id.0 > household.partner || Obj::is_dead(state) {
+            continue;
+        }
+
+        let num_children = child_query
+            .iter()
+            .filter(|(child_state, child)| {
+                !Obj::is_dead(child_state) && child.parents.contains(&id.0)
+            })
+            .count();
+
+        // One child growing up per household at a time
+        if num_children > 0 {
+            continue;
+        }
+
+        let Some(shelter_entity) = ids.get_entity(household.shelter) else {
+            continue;
+        };
+
+        let Ok((_, _, shelter_name, shelter_pos, shelter_state)) =
+            structure_query.get(shelter_entity)
+        else {
+            continue;
+        };
+
+        if *shelter_state != State::None {
+            continue;
+        }
+
+        let shelter_level = templates
+            .obj_templates
+            .iter()
+            .find(|obj_template| obj_template.name == shelter_name.0)
+            .and_then(|obj_template| obj_template.level)
+            .unwrap_or(0);
+
+        if !Social::has_room(shelter_level, num_children) {
+            debug!("No room for a child in shelter {:?}", household.shelter);
+            continue;
+        }
+
+        if Social::stored_food(player_id.0, &structures, &items) < BIRTH_FOOD_REQ {
+            debug!("Not enough food for a child for player {:?}", player_id.0);
+            continue;
+        }
+
+        if rand::thread_rng().gen::<f32>() >= BIRTH_CHANCE {
+            continue;
+        }
+
+        let (child_id, child_entity) = player::spawn_villager(
+            player_id.0,
+            *shelter_pos,
+            CHILD_IMAGE,
+            &mut commands,
+            &mut ids,
+            &mut map_events,
+            &mut skills,
+            &templates,
+            &game_tick,
+        );
+
+        let mut child_relationships = Relationships::default();
+        child_relationships.change(id.0, PARENT_AFFINITY);
+        child_relationships.change(household.partner, PARENT_AFFINITY);
+
+        commands.entity(child_entity).insert((
+            Child {
+                born_at: game_tick.0,
+                parents: vec![id.0, household.partner],
+            },
+            child_relationships,
+        ));
+
+        info!(
+            "Child {:?} born to {:?} and {:?}",
+            child_id, id.0, household.partner
+        );
+
+        Obj::add_sound_obj_event(
+            game_tick.0,
+            "We've been blessed with a child!".to_string(),
+            id,
+            &mut map_events,
+        );
+    }
+}
+
+fn growth_system(
+    mut commands: Commands,
+    game_tick: Res<GameTick>,
+    mut map_events: ResMut<MapEvents>,
+    query: Query<(Entity, &Id, &State, &Child)>,
+) {
+    // Every game day
+    if game_tick.0 % GAME_TICKS_PER_DAY != 0 {
+        return;
+    }
+
+    for (entity, id, state, child) in query.iter() {
+        if Obj::is_dead(state) || game_tick.0 - child.born_at < CHILD_GROWTH_TIME {
+            continue;
+        }
+
+        commands.entity(entity).remove::<Child>();
+
+        Obj::add_sound_obj_event(
+            game_tick.0,
+            "I'm all grown up and ready to work!".to_string(),
+            id,
+            &mut map_events,
+        );
+    }
+}
+
+pub struct SocialPlugin;
+
+impl Plugin for SocialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, relationship_system)
+            .add_systems(Update, household_system)
+            .add_systems(Update, birth_system)
+            .add_systems(Update, growth_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::templates::ItemTemplate;
+
+    fn new_ids() -> Ids {
+        return Ids {
+            map_event: 0,
+            player_event: 0,
+            obj: 0,
+            item: 0,
+            player_hero_map: HashMap::new(),
+            obj_entity_map: HashMap::new(),
+            obj_player_map: HashMap::new(),
+        };
+    }
+
+    #[test]
+    fn test_relationships() {
+        let mut relationships = Relationships::default();
+
+        relationships.change(3, 60.0);
+        relationships.change(2, 60.0);
+        relationships.change(4, -60.0);
+        relationships.change(5, 10.0);
+
+        assert!(relationships.is_friend(2));
+        assert!(relationships.is_rival(4));
+        assert!(!relationships.is_friend(5) && !relationships.is_rival(5));
+        assert_eq!(relationships.friends(), vec![2, 3]);
+        assert_eq!(relationships.rivals(), vec![4]);
+
+        // Affinity stays within its bounds
+        relationships.change(2, 500.0);
+        relationships.change(4, -500.0);
+
+        assert_eq!(relationships.get(2), MAX_AFFINITY);
+        assert_eq!(relationships.get(4), -MAX_AFFINITY);
+        assert_eq!(relationships.get(6), 0.0);
+    }
+
+    #[test]
+    fn test_affinity_change() {
+        let likes = vec!["Farming".to_string(), "Fishing".to_string()];
+        let dislikes = vec!["Mining".to_string()];
+
+        let friendly = Social::affinity_change(&likes, &dislikes, &likes, &Vec::new());
+        let neutral = Social::affinity_change(&likes, &dislikes, &Vec::new(), &Vec::new());
+        let clashing = Social::affinity_change(
+            &likes,
+            &dislikes,
+            &vec!["Mining".to_string()],
+            &vec!["Fishing".to_string()],
+        );
+
+        assert_eq!(friendly, AFFINITY_GAIN * 3.0);
+        assert_eq!(neutral, AFFINITY_GAIN);
+        assert_eq!(clashing, AFFINITY_GAIN - AFFINITY_CLASH * 2.0);
+
+        assert_eq!(Social::friends_morale(1), FRIEND_MORALE_BONUS);
+        assert_eq!(Social::friends_morale(10), MAX_FRIEND_MORALE_BONUS);
+    }
+
+    #[test]
+    fn test_shelter_room() {
+        // The couple alone fills the lowest level of shelter
+        assert!(!Social::has_room(0, 0));
+        assert!(Social::has_room(1, 0));
+        assert!(!Social::has_room(1, 1));
+        assert!(Social::has_room(2, 1));
+    }
+
+    #[test]
+    fn test_stored_food() {
+        let item_template_file =
+            fs::File::open("item_template.yaml").expect("Could not open file.");
+        let item_templates: Vec<ItemTemplate> =
+            serde_yaml::from_reader(item_template_file).expect("Could not read values.");
+
+        let mut items = Items::default();
+        items.set_templates(item_templates.clone());
+
+        let food = item_templates
+            .iter()
+            .find(|item_template| item_template.class == item::FOOD)
+            .unwrap();
+
+        items.new(10, food.name.clone(), 15);
+        items.new(11, food.name.clone(), 10);
+        items.new(11, "Valleyrun Copper Ore".to_string(), 50);
+        items.new(20, food.name.clone(), 100);
+
+        let structures = vec![(10, 1), (11, 1), (20, 2)];
+
+        assert_eq!(Social::stored_food(1, &structures, &items), 25);
+        assert_eq!(Social::stored_food(2, &structures, &items), 100);
+        assert_eq!(Social::stored_food(3, &structures, &items), 0);
+    }
+
+    #[test]
+    fn test_households() {
+        let mut app = App::new();
+
+        app.insert_resource(GameTick(0))
+            .init_resource::<MapEvents>()
+            .add_systems(Update, household_system);
+
+        let villager = |id: i32, friend_id: i32, affinity: f32| {
+            let mut relationships = Relationships::default();
+            relationships.change(friend_id, affinity);
+
+            return (
+                Id(id),
+                PlayerId(1),
+                State::None,
+                relationships,
+                SubclassVillager,
+            );
+        };
+
+        let first = app.world.spawn(villager(1, 2, 80.0)).id();
+        let second = app.world.spawn(villager(2, 1, 80.0)).id();
+        // Friends, but not close enough to move in together
+        let third = app.world.spawn(villager(3, 1, 60.0)).id();
+
+        let shelter = app
+            .world
+            .spawn((
+                Id(10),
+                PlayerId(1),
+                Subclass(structure::SHELTER.to_string()),
+                State::None,
+                ClassStructure,
+            ))
+            .id();
+
+        let mut ids = new_ids();
+        ids.obj_entity_map.insert(1, first);
+        ids.obj_entity_map.insert(2, second);
+        ids.obj_entity_map.insert(3, third);
+        ids.obj_entity_map.insert(10, shelter);
+
+        app.insert_resource(ids);
+        app.update();
+
+        let household = app.world.get::<Household>(first).unwrap();
+
+        assert_eq!(household.partner, 2);
+        assert_eq!(household.shelter, 10);
+        assert_eq!(app.world.get::<Household>(second).unwrap().partner, 1);
+        assert!(app.world.get::<Household>(third).is_none());
+
+        // Losing the shelter breaks the household up
+        *app.world.get_mut::<State>(shelter).unwrap() = State::Dead;
+        app.update();
+
+        assert!(app.world.get::<Household>(first).is_none());
+        assert!(app.world.get::<Household>(second).is_none());
+    }
+}
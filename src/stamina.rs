@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+
+use crate::combat::AttackType;
+use crate::encumbrance::{self, Overloaded};
+use crate::game::{BaseAttrs, Clients, GameTick, Id, PlayerId, State, Stats};
+use crate::item::Item;
+use crate::network::{send_to_client, ResponsePacket, StatsData};
+
+// Stamina costs per attack type
+pub const QUICK_ATTACK_COST: i32 = 100;
+pub const PRECISE_ATTACK_COST: i32 = 200;
+pub const FIERCE_ATTACK_COST: i32 = 300;
+
+// Additional stamina cost per unit of weapon weight
+pub const WEAPON_WEIGHT_COST: f32 = 10.0;
+
+// Stamina costs per action
+pub const GATHER_COST: i32 = 200;
+pub const BUILD_COST: i32 = 300;
+pub const REFINE_COST: i32 = 200;
+pub const CRAFT_COST_PER_REQ: i32 = 25;
+
+// Stamina regen per second by state
+pub const RESTING_REGEN: i32 = 100;
+pub const SLEEPING_REGEN: i32 = 250;
+pub const MOVING_REGEN: i32 = 25;
+
+// Damage penalty when the attacker has no stamina left
+pub const EXHAUSTED_DAMAGE_MOD: f32 = 0.5;
+
+pub const REGEN_INTERVAL: i32 = 10;
+
+#[derive(Debug, Clone)]
+pub struct Stamina;
+
+impl Stamina {
+    pub fn attack_cost(attack_type: AttackType, attacker_weapons: &Vec<Item>) -> i32 {
+        let attack_type_cost = match attack_type {
+            AttackType::Quick => QUICK_ATTACK_COST,
+            AttackType::Precise => PRECISE_ATTACK_COST,
+            AttackType::Fierce => FIERCE_ATTACK_COST,
+        };
+
+        let mut weapon_weight = 0.0;
+
+        for weapon in attacker_weapons.iter() {
+            weapon_weight += weapon.weight;
+        }
+
+        return attack_type_cost + (weapon_weight * WEAPON_WEIGHT_COST) as i32;
+    }
+
+    pub fn craft_cost(stamina_req: Option<i32>) -> i32 {
+        return stamina_req.unwrap_or(0) * CRAFT_COST_PER_REQ;
+    }
+
+    pub fn has_stamina(stats: &Stats, cost: i32) -> bool {
+        // Objects without stamina are not limited by it
+        let Some(stamina) = stats.stamina else {
+            return true;
+        };
+
+        return stamina >= cost;
+    }
+
+    pub fn is_exhausted(stats: &Stats) -> bool {
+        if let Some(stamina) = stats.stamina {
+            return stamina <= 0;
+        }
+
+        return false;
+    }
+
+    pub fn consume(stats: &mut Stats, cost: i32) {
+        if let Some(stamina) = stats.stamina {
+            stats.stamina = Some(i32::max(stamina - cost, 0));
+        }
+    }
+
+    pub fn regen_rate(state: &State, endurance: i32) -> i32 {
+        let base_rate = match state {
+            State::None | State::Hiding | State::Aboard => RESTING_REGEN,
+            State::Sleeping => SLEEPING_REGEN,
+            State::Moving => MOVING_REGEN,
+            // Working states do not regenerate stamina
+            _ => 0,
+        };
+
+        // Each point of endurance adds 5% to the regen rate
+        return base_rate * (100 + endurance * 5) / 100;
+    }
+
+    pub fn to_stats_data(id: i32, stats: &Stats) -> StatsData {
+        StatsData {
+            id: id,
+            hp: stats.hp,
+            base_hp: stats.base_hp,
+            stamina: stats.stamina.unwrap_or(0),
+            base_stamina: stats.base_stamina.unwrap_or(0),
+            effects: Vec::new(),
+        }
+    }
+}
+
+fn stamina_regen_system(
+    game_tick: Res<GameTick>,
+    clients: Res<Clients>,
+    mut query: Query<(
+        &Id,
+        &PlayerId,
+        &State,
+        &mut Stats,
+        Option<&BaseAttrs>,
+        Option<&Overloaded>,
+    )>,
+) {
+    if game_tick.0 % REGEN_INTERVAL != 0 {
+        return;
+    }
+
+    for (id, player_id, state, mut stats, base_attrs, overloaded) in query.iter_mut() {
+        let (Some(stamina), Some(base_stamina)) = (stats.stamina, stats.base_stamina) else {
+            continue;
+        };
+
+        let endurance = base_attrs.map_or(0, |attrs| attrs.endurance);
+        let mut regen = Stamina::regen_rate(state, endurance);
+
+        // Carrying too much wears down stamina whatever the state
+        if overloaded.is_some() {
+            regen -= encumbrance::OVERLOAD_STAMINA_DRAIN;
+        }
+
+        let at_limit = match regen {
+            regen if regen > 0 => stamina >= base_stamina,
+            regen if regen < 0 => stamina <= 0,
+            _ => true,
+        };
+
+        if at_limit {
+            continue;
+        }
+
+        stats.stamina = Some((stamina + regen).clamp(0, base_stamina));
+
+        // Only player objects need stamina updates sent
+        if player_id.0 < 1000 {
+            let packet = ResponsePacket::Stats {
+                data: Stamina::to_stats_data(id.0, &stats),
+            };
+
+            send_to_client(player_id.0, packet, &clients);
+        }
+    }
+}
+
+pub struct StaminaPlugin;
+
+impl Plugin for StaminaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, stamina_regen_system);
+    }
+}
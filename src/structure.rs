@@ -104,7 +104,7 @@ impl Structure {
         return None;
     }
 
-    pub fn has_req(structure_id: i32, source_req_items: &Vec<ResReq>, items: &ResMut<Items>) -> bool {
+    pub fn has_req(structure_id: i32, source_req_items: &Vec<ResReq>, items: &Items) -> bool {
         let structure_items = items.get_by_owner(structure_id);
 
         let mut req_items = source_req_items.clone();
@@ -142,18 +142,31 @@ impl Structure {
         return true;
     }
 
-    pub fn consume_reqs(structure_id: i32, req_items: Vec<ResReq>, items: &mut ResMut<Items>) -> Vec<Item> {
-        let structure_items = items.get_by_owner(structure_id).clone();
+    // Takes from matching stacks until each requirement is met, stacks of different
+    // quality do not merge. Returns the items used up with the quantity taken from each.
+    pub fn consume_reqs(structure_id: i32, req_items: Vec<ResReq>, items: &mut Items) -> Vec<Item> {
         let mut consumed_items = Vec::new();
 
         for req_item in req_items.iter() {
-            for structure_item in structure_items.iter() {
+            let mut req_quantity = req_item.quantity;
+
+            for structure_item in items.get_by_owner(structure_id).iter() {
+                if req_quantity <= 0 {
+                    break;
+                }
+
                 if req_item.req_type == structure_item.name
                     || req_item.req_type == structure_item.class
                     || req_item.req_type == structure_item.subclass
                 {
-                    items.remove_quantity(structure_item.id, req_item.quantity);
-                    consumed_items.push(structure_item.clone());
+                    let quantity = i32::min(req_quantity, structure_item.quantity);
+
+                    items.remove_quantity(structure_item.id, quantity);
+                    req_quantity -= quantity;
+
+                    let mut consumed_item = structure_item.clone();
+                    consumed_item.quantity = quantity;
+                    consumed_items.push(consumed_item);
                 }
             }
         }
@@ -21,6 +21,10 @@ pub struct Templates {
     pub res_property_templates: ResPropertyTemplates,
     pub terrain_feature_templates: TerrainFeatureTemplates,
     pub dialogue_templates: DialogueTemplates,
+    pub villager_templates: VillagerTemplates,
+    pub thinker_templates: ThinkerTemplates,
+    pub trade_templates: TradeTemplates,
+    pub empire_template: EmpireTemplate,
 }
 
 impl Templates {
@@ -45,6 +49,14 @@ pub struct ResReq {
     pub cquantity: Option<i32>, // current quantity
 }
 
+#[derive(Debug, Clone, Resource, PartialEq, Serialize, Deserialize)]
+pub struct LootTemplate {
+    pub item: String,
+    pub drop_rate: f32,
+    pub min: i32,
+    pub max: i32,
+}
+
 #[derive(Debug, Clone, Resource, PartialEq, Serialize, Deserialize)]
 // Another way to build the struct...
 /*pub struct ObjTemplate {
@@ -85,6 +97,12 @@ pub struct ObjTemplate {
     pub upgrade_to: Option<Vec<String>>,
     pub profession: Option<String>,
     pub upkeep: Option<Vec<ResReq>>,
+    pub loot: Option<Vec<LootTemplate>>,
+    pub skin: Option<Vec<LootTemplate>>,
+    pub butcher: Option<Vec<LootTemplate>>,
+    pub behaviour: Option<String>,
+    pub nocturnal: Option<bool>,
+    pub spawns: Option<Vec<String>>,
 }
 
 impl ObjTemplate {
@@ -127,6 +145,7 @@ pub struct ItemTemplate {
     pub weight: f32,
     pub produces: Option<Vec<String>>,
     pub slot: Option<String>,
+    pub value: Option<i32>,
 }
 
 #[derive(Debug, Resource, Deref, DerefMut)]
@@ -342,6 +361,194 @@ impl DialogueTemplates {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CultureTemplate {
+    pub name: String,
+    pub first_names: Vec<String>,
+    pub last_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraitTemplate {
+    pub name: String,
+    pub description: String,
+    pub thirst_rate: Option<f32>,
+    pub hunger_rate: Option<f32>,
+    pub tired_rate: Option<f32>,
+    pub work_weight: Option<f32>,
+    pub flee_weight: Option<f32>,
+    pub schedule_shift: Option<i32>,
+}
+
+#[derive(Debug, Clone, Resource, PartialEq, Serialize, Deserialize)]
+pub struct VillagerTemplates {
+    pub cultures: Vec<CultureTemplate>,
+    pub traits: Vec<TraitTemplate>,
+    pub work_types: Vec<String>,
+    pub backstories: Vec<String>,
+}
+
+impl VillagerTemplates {
+    pub fn get_trait(&self, name: &String) -> Option<&TraitTemplate> {
+        return self.traits.iter().find(|trait_template| trait_template.name == *name);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluatorTemplate {
+    #[serde(rename = "type")]
+    pub evaluator_type: String,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    pub power: Option<f32>,
+    pub k: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThinkerActionTemplate {
+    pub name: String,
+    pub duration: Option<i32>,
+    pub until: Option<f32>,
+    pub speech: Option<String>,
+    pub target: Option<String>,
+}
+
+// Scorers are combined when there is more than one or a combine type is given,
+// and several actions run one after another as steps
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThinkerChoiceTemplate {
+    pub label: Option<String>,
+    pub scorers: Vec<String>,
+    pub combine: Option<String>,
+    pub threshold: Option<f32>,
+    pub evaluator: Option<EvaluatorTemplate>,
+    pub actions: Vec<ThinkerActionTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThinkerTemplate {
+    pub name: String,
+    pub picker: String,
+    pub threshold: Option<f32>,
+    pub choices: Vec<ThinkerChoiceTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortSupplyTemplate {
+    pub item: String,
+    pub quantity: i32,
+}
+
+// pos is the water tile merchant ships dock on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortTemplate {
+    pub name: String,
+    pub pos: Vec<i32>,
+    pub supplies: Vec<PortSupplyTemplate>,
+    pub demands: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteTemplate {
+    pub name: String,
+    pub ship: String,
+    pub ports: Vec<String>,
+}
+
+#[derive(Debug, Clone, Resource, PartialEq, Serialize, Deserialize)]
+pub struct TradeTemplates {
+    pub ports: Vec<PortTemplate>,
+    pub routes: Vec<RouteTemplate>,
+}
+
+impl TradeTemplates {
+    pub fn get_port(&self, name: &String) -> Option<&PortTemplate> {
+        return self.ports.iter().find(|port| port.name == *name);
+    }
+
+    pub fn get_port_at(&self, x: i32, y: i32) -> Option<&PortTemplate> {
+        return self.ports.iter().find(|port| port.pos == vec![x, y]);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettlementTierTemplate {
+    pub name: String,
+    pub structures: i32,
+    pub rate: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaxTemplate {
+    pub period: i32,
+    pub due: i32,
+    pub base: i32,
+    pub per_villager: i32,
+    pub per_structure: i32,
+    pub tiers: Vec<SettlementTierTemplate>,
+    pub goods_rate: f32,
+    pub overdue_penalty: f32,
+    pub debt_penalty: f32,
+    pub negotiate_chance: f32,
+    pub negotiate_discount: f32,
+    pub bribe_share: f32,
+    pub bribe_caught_chance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoyaltyTemplate {
+    pub start: i32,
+    pub max: i32,
+    pub paid: i32,
+    pub negotiate_failed: i32,
+    pub forfeiture: i32,
+    pub bribe_caught: i32,
+    pub rate_mod: f32,
+    pub protection: i32,
+    pub protection_range: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoldierTemplate {
+    pub template: String,
+    pub strikes: i32,
+    pub debt_per_soldier: i32,
+    pub max: i32,
+    pub recall: i32,
+}
+
+#[derive(Debug, Clone, Resource, PartialEq, Serialize, Deserialize)]
+pub struct EmpireTemplate {
+    pub name: String,
+    pub capital: Vec<i32>,
+    pub territory: u32,
+    pub tax: TaxTemplate,
+    pub loyalty: LoyaltyTemplate,
+    pub soldiers: SoldierTemplate,
+}
+
+impl TaxTemplate {
+    // Highest tier the settlement's structure count reaches
+    pub fn get_tier(&self, num_structures: i32) -> Option<&SettlementTierTemplate> {
+        return self
+            .tiers
+            .iter()
+            .filter(|tier| num_structures >= tier.structures)
+            .max_by_key(|tier| tier.structures);
+    }
+}
+
+#[derive(Debug, Resource, Deref, DerefMut)]
+pub struct ThinkerTemplates(HashMap<String, ThinkerTemplate>);
+
+impl ThinkerTemplates {
+    pub fn load(&mut self, thinker_templates: Vec<ThinkerTemplate>) {
+        for thinker_template in thinker_templates.iter() {
+            self.insert(thinker_template.name.clone(), thinker_template.clone());
+        }
+    }
+}
+
 /// The systems that make structures tick.
 pub struct TemplatesPlugin;
 
@@ -431,7 +638,28 @@ impl Plugin for TemplatesPlugin {
             serde_yaml::from_reader(dialogue_template_file).expect("Could not read values.");
         let mut dialogue_templates = DialogueTemplates(HashMap::new());
         dialogue_templates.load(dialogue_template_list);
-    
+
+        let villager_template_file =
+            fs::File::open("villager_template.yaml").expect("Could not open file.");
+        let villager_templates: VillagerTemplates =
+            serde_yaml::from_reader(villager_template_file).expect("Could not read values.");
+
+        let thinker_template_file =
+            fs::File::open("thinker_template.yaml").expect("Could not open file.");
+        let thinker_template_list: Vec<ThinkerTemplate> =
+            serde_yaml::from_reader(thinker_template_file).expect("Could not read values.");
+        let mut thinker_templates = ThinkerTemplates(HashMap::new());
+        thinker_templates.load(thinker_template_list);
+
+        let trade_template_file =
+            fs::File::open("trade_template.yaml").expect("Could not open file.");
+        let trade_templates: TradeTemplates =
+            serde_yaml::from_reader(trade_template_file).expect("Could not read values.");
+
+        let empire_template_file =
+            fs::File::open("empire_template.yaml").expect("Could not open file.");
+        let empire_template: EmpireTemplate =
+            serde_yaml::from_reader(empire_template_file).expect("Could not read values.");
 
         let templates = Templates {
             item_templates: item_templates,
@@ -444,6 +672,10 @@ impl Plugin for TemplatesPlugin {
             res_property_templates: res_property_templates,
             terrain_feature_templates: terrain_feature_templates,
             dialogue_templates: dialogue_templates,
+            villager_templates: villager_templates,
+            thinker_templates: thinker_templates,
+            trade_templates: trade_templates,
+            empire_template: empire_template,
         };
 
         app.insert_resource(templates);
@@ -0,0 +1,780 @@
+use bevy::prelude::*;
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use crate::encumbrance::Encumbrance;
+use crate::game::{BaseAttrs, Clients, GameTick, Id, Position, State, SubclassHero, Template};
+use crate::ids::Ids;
+use crate::item::{self, Items};
+use crate::ledger;
+use crate::map::Map;
+use crate::network::{self, send_to_client, ResponsePacket};
+use crate::obj::Obj;
+use crate::templates::Templates;
+
+pub const TRADE_UPDATE_INTERVAL: i32 = 5;
+
+// Records kept in memory, older ones are appended to the trade log file
+pub const MAX_TRADE_RECORDS: usize = 1000;
+pub const TRADE_LOG_FILE: &str = "trades.log";
+
+pub const PHASE_OFFER: &str = "offer";
+pub const PHASE_CONFIRM: &str = "confirm";
+
+pub const REASON_COMPLETED: &str = "Trade completed.";
+pub const REASON_CANCELLED: &str = "Trade cancelled.";
+pub const REASON_MOVED_AWAY: &str = "Trade cancelled, the traders are no longer adjacent.";
+pub const REASON_DISCONNECTED: &str = "Trade cancelled, the other trader has left.";
+
+// Both sides accept the offers, then both confirm the accepted offers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TradePhase {
+    Offer,
+    Confirm,
+}
+
+impl TradePhase {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            TradePhase::Offer => PHASE_OFFER,
+            TradePhase::Confirm => PHASE_CONFIRM,
+        }
+    }
+}
+
+// Offered items are held by the escrow id until the trade completes or is cancelled
+#[derive(Debug, Clone)]
+pub struct TradeParty {
+    pub player_id: i32,
+    pub hero_id: i32,
+    pub escrow_id: i32,
+    pub accepted: bool,
+    pub confirmed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TradeSession {
+    pub id: i32,
+    pub parties: [TradeParty; 2],
+    pub phase: TradePhase,
+}
+
+impl TradeSession {
+    pub fn party_index(&self, player_id: i32) -> Option<usize> {
+        return self
+            .parties
+            .iter()
+            .position(|party| party.player_id == player_id);
+    }
+
+    // Any change to the offers has to be accepted again
+    fn reset(&mut self) {
+        self.phase = TradePhase::Offer;
+
+        for party in self.parties.iter_mut() {
+            party.accepted = false;
+            party.confirmed = false;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeRecord {
+    pub trade_id: i32,
+    pub tick: i32,
+    pub players: [i32; 2],
+    // Name and quantity of everything each player gave
+    pub given: [Vec<(String, i32)>; 2],
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Trades {
+    pub sessions: HashMap<i32, TradeSession>,
+    pub log: Vec<TradeRecord>,
+    next_id: i32,
+}
+
+impl Trades {
+    pub fn find_by_player(&self, player_id: i32) -> Option<i32> {
+        return self
+            .sessions
+            .values()
+            .find(|session| session.party_index(player_id).is_some())
+            .map(|session| session.id);
+    }
+
+    pub fn open(
+        &mut self,
+        player_id: i32,
+        hero_id: i32,
+        target_player_id: i32,
+        target_hero_id: i32,
+        ids: &mut Ids,
+    ) -> Result<i32, String> {
+        if self.find_by_player(player_id).is_some() {
+            return Err("You are already trading.".to_string());
+        }
+
+        if self.find_by_player(target_player_id).is_some() {
+            return Err("The other player is already trading.".to_string());
+        }
+
+        self.next_id += 1;
+
+        let party = TradeParty {
+            player_id: player_id,
+            hero_id: hero_id,
+            escrow_id: ids.new_obj_id(),
+            accepted: false,
+            confirmed: false,
+        };
+
+        let target_party = TradeParty {
+            player_id: target_player_id,
+            hero_id: target_hero_id,
+            escrow_id: ids.new_obj_id(),
+            accepted: false,
+            confirmed: false,
+        };
+
+        let session = TradeSession {
+            id: self.next_id,
+            parties: [party, target_party],
+            phase: TradePhase::Offer,
+        };
+
+        self.sessions.insert(session.id, session);
+
+        return Ok(self.next_id);
+    }
+
+    // Moves the offered quantity from the hero into escrow
+    pub fn offer(
+        &mut self,
+        trade_id: i32,
+        player_id: i32,
+        item_id: i32,
+        quantity: i32,
+        items: &mut Items,
+    ) -> Result<(), String> {
+        let session = self.get_session_mut(trade_id, player_id)?;
+        let party = session.parties[session.party_index(player_id).unwrap()].clone();
+
+        let Some(item) = items.find_by_id(item_id) else {
+            return Err("Item does not exist.".to_string());
+        };
+
+        if item.owner != party.hero_id {
+            return Err("Item is not owned by you.".to_string());
+        }
+
+        if item.equipped {
+            return Err("Item is equipped.".to_string());
+        }
+
+        if quantity <= 0 {
+            return Err("Invalid quantity.".to_string());
+        }
+
+        if item.class == item::GOLD {
+            if items.get_total_gold(party.hero_id) < quantity {
+                return Err("Insufficient gold.".to_string());
+            }
+
+            items.set_reason(ledger::REASON_TRADE);
+            items.transfer_gold(party.hero_id, party.escrow_id, quantity);
+        } else {
+            if item.quantity < quantity {
+                return Err("Insufficient quantity.".to_string());
+            }
+
+            items.set_reason(ledger::REASON_TRADE);
+            items.transfer_quantity(item_id, party.escrow_id, quantity);
+        }
+
+        items.clear_reason();
+
+        session.reset();
+
+        return Ok(());
+    }
+
+    // Returns an offered item from escrow to the hero
+    pub fn withdraw(
+        &mut self,
+        trade_id: i32,
+        player_id: i32,
+        item_id: i32,
+        items: &mut Items,
+    ) -> Result<(), String> {
+        let session = self.get_session_mut(trade_id, player_id)?;
+        let party = session.parties[session.party_index(player_id).unwrap()].clone();
+
+        let Some(item) = items.find_by_id(item_id) else {
+            return Err("Item does not exist.".to_string());
+        };
+
+        if item.owner != party.escrow_id {
+            return Err("Item is not part of your offer.".to_string());
+        }
+
+        items.set_reason(ledger::REASON_TRADE);
+        items.transfer(item_id, party.hero_id);
+        items.clear_reason();
+
+        session.reset();
+
+        return Ok(());
+    }
+
+    pub fn accept(&mut self, trade_id: i32, player_id: i32) -> Result<(), String> {
+        let session = self.get_session_mut(trade_id, player_id)?;
+
+        if session.phase != TradePhase::Offer {
+            return Err("Offers are already accepted.".to_string());
+        }
+
+        let index = session.party_index(player_id).unwrap();
+        session.parties[index].accepted = true;
+
+        if session.parties.iter().all(|party| party.accepted) {
+            session.phase = TradePhase::Confirm;
+        }
+
+        return Ok(());
+    }
+
+    // Returns true once both sides have confirmed and the trade has completed,
+    // neither hero can be left carrying more than their hard limit
+    pub fn confirm(
+        &mut self,
+        trade_id: i32,
+        player_id: i32,
+        hard_limit: impl Fn(i32) -> i32,
+        items: &mut Items,
+        game_tick: &GameTick,
+    ) -> Result<bool, String> {
+        let session = self.get_session_mut(trade_id, player_id)?;
+
+        if session.phase != TradePhase::Confirm {
+            return Err("Both players have to accept the offers first.".to_string());
+        }
+
+        let index = session.party_index(player_id).unwrap();
+        session.parties[index].confirmed = true;
+
+        if !session.parties.iter().all(|party| party.confirmed) {
+            return Ok(false);
+        }
+
+        let overloaded = session.parties.iter().enumerate().find(|(party_index, party)| {
+            let other_party = &session.parties[1 - party_index];
+            let total_weight = items.get_total_weight(party.hero_id)
+                + items.get_total_weight(other_party.escrow_id);
+
+            return total_weight > hard_limit(party.hero_id);
+        });
+
+        if let Some((_party_index, party)) = overloaded {
+            let errmsg = if party.player_id == player_id {
+                "You cannot carry everything offered."
+            } else {
+                "The other hero cannot carry everything offered."
+            };
+
+            session.parties[index].confirmed = false;
+            return Err(errmsg.to_string());
+        }
+
+        let Some(session) = self.sessions.remove(&trade_id) else {
+            return Err("Trade does not exist.".to_string());
+        };
+
+        let [party, other_party] = &session.parties;
+
+        let given = [
+            Trades::escrowed(party.escrow_id, items),
+            Trades::escrowed(other_party.escrow_id, items),
+        ];
+
+        items.set_reason(ledger::REASON_TRADE);
+        items.transfer_all_items(party.escrow_id, other_party.hero_id);
+        items.transfer_all_items(other_party.escrow_id, party.hero_id);
+        items.clear_reason();
+
+        let record = TradeRecord {
+            trade_id: session.id,
+            tick: game_tick.0,
+            players: [party.player_id, other_party.player_id],
+            given: given,
+        };
+
+        info!("Trade completed: {:?}", record);
+
+        self.log.push(record);
+
+        return Ok(true);
+    }
+
+    // Returns all escrowed items to their heroes
+    pub fn cancel(&mut self, trade_id: i32, items: &mut Items) -> Option<TradeSession> {
+        let Some(session) = self.sessions.remove(&trade_id) else {
+            return None;
+        };
+
+        items.set_reason(ledger::REASON_TRADE);
+
+        for party in session.parties.iter() {
+            items.transfer_all_items(party.escrow_id, party.hero_id);
+        }
+
+        items.clear_reason();
+
+        info!("Trade {:?} cancelled", trade_id);
+
+        return Some(session);
+    }
+
+    pub fn update_packet(session: &TradeSession, items: &Items) -> ResponsePacket {
+        let sides = session
+            .parties
+            .iter()
+            .map(|party| network::TradeSide {
+                heroid: party.hero_id,
+                accepted: party.accepted,
+                confirmed: party.confirmed,
+                items: items.get_by_owner_packet(party.escrow_id),
+            })
+            .collect();
+
+        return ResponsePacket::TradeUpdate {
+            tradeid: session.id,
+            phase: session.phase.to_str().to_string(),
+            sides: sides,
+        };
+    }
+
+    pub fn inventory_packet(
+        hero_id: i32,
+        template: &String,
+        attrs: Option<&BaseAttrs>,
+        items: &Items,
+        templates: &Templates,
+    ) -> ResponsePacket {
+        return Encumbrance::inventory_packet(hero_id, template, attrs, items, templates);
+    }
+
+    fn get_session_mut(
+        &mut self,
+        trade_id: i32,
+        player_id: i32,
+    ) -> Result<&mut TradeSession, String> {
+        match self.sessions.get_mut(&trade_id) {
+            Some(session) if session.party_index(player_id).is_some() => Ok(session),
+            _ => Err("Trade does not exist.".to_string()),
+        }
+    }
+
+    // Removes the oldest records once over the limit
+    pub fn flush(&mut self) -> Vec<TradeRecord> {
+        if self.log.len() <= MAX_TRADE_RECORDS {
+            return Vec::new();
+        }
+
+        let num_flushed = self.log.len() - MAX_TRADE_RECORDS / 2;
+
+        return self.log.drain(..num_flushed).collect();
+    }
+
+    // Trades go on while both players are connected and their heroes are alive and adjacent
+    pub fn cancel_reason(
+        session: &TradeSession,
+        connected: &Vec<i32>,
+        heroes: &Vec<(Position, State)>,
+    ) -> Option<&'static str> {
+        if session
+            .parties
+            .iter()
+            .any(|party| !connected.contains(&party.player_id))
+        {
+            return Some(REASON_DISCONNECTED);
+        }
+
+        if heroes.len() != 2
+            || heroes.iter().any(|(_pos, state)| Obj::is_dead(state))
+            || !Map::is_adjacent(heroes[0].0, heroes[1].0)
+        {
+            return Some(REASON_MOVED_AWAY);
+        }
+
+        return None;
+    }
+
+    fn escrowed(escrow_id: i32, items: &Items) -> Vec<(String, i32)> {
+        return items
+            .get_by_owner(escrow_id)
+            .into_iter()
+            .map(|item| (item.name, item.quantity))
+            .collect();
+    }
+}
+
+// Cancels trades whose heroes have moved apart, died or whose players have left
+fn trade_watch_system(
+    game_tick: Res<GameTick>,
+    clients: Res<Clients>,
+    ids: Res<Ids>,
+    mut items: ResMut<Items>,
+    templates: Res<Templates>,
+    mut trades: ResMut<Trades>,
+    hero_query: Query<
+        (&Id, &Position, &State, &Template, Option<&BaseAttrs>),
+        With<SubclassHero>,
+    >,
+) {
+    if game_tick.0 % TRADE_UPDATE_INTERVAL != 0 {
+        return;
+    }
+
+    let connected: Vec<i32> = clients
+        .lock()
+        .unwrap()
+        .values()
+        .map(|client| client.player_id)
+        .collect();
+
+    let mut cancelled = Vec::new();
+
+    for session in trades.sessions.values() {
+        let heroes: Vec<(Position, State)> = session
+            .parties
+            .iter()
+            .filter_map(|party| ids.get_entity(party.hero_id))
+            .filter_map(|hero_entity| hero_query.get(hero_entity).ok())
+            .map(|(_id, pos, state, _template, _attrs)| (*pos, state.clone()))
+            .collect();
+
+        if let Some(reason) = Trades::cancel_reason(session, &connected, &heroes) {
+            cancelled.push((session.id, reason));
+        }
+    }
+
+    for (trade_id, reason) in cancelled.into_iter() {
+        let Some(session) = trades.cancel(trade_id, &mut items) else {
+            continue;
+        };
+
+        for party in session.parties.iter() {
+            let closed_packet = ResponsePacket::TradeClosed {
+                tradeid: trade_id,
+                completed: false,
+                reason: reason.to_string(),
+            };
+
+            send_to_client(party.player_id, closed_packet, &clients);
+
+            let Some(hero_entity) = ids.get_entity(party.hero_id) else {
+                continue;
+            };
+
+            if let Ok((_id, _pos, _state, template, attrs)) = hero_query.get(hero_entity) {
+                let inventory_packet = Trades::inventory_packet(
+                    party.hero_id,
+                    &template.0,
+                    attrs,
+                    &items,
+                    &templates,
+                );
+                send_to_client(party.player_id, inventory_packet, &clients);
+            }
+        }
+    }
+}
+
+fn trade_log_flush_system(mut trades: ResMut<Trades>) {
+    let flushed = trades.flush();
+
+    if flushed.is_empty() {
+        return;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(TRADE_LOG_FILE);
+
+    let Ok(mut file) = file else {
+        error!(
+            "Cannot open {}, dropping {} trade records",
+            TRADE_LOG_FILE,
+            flushed.len()
+        );
+        return;
+    };
+
+    for record in flushed.iter() {
+        let Ok(line) = serde_json::to_string(record) else {
+            error!("Cannot serialize trade record {:?}", record);
+            continue;
+        };
+
+        if let Err(err) = writeln!(file, "{}", line) {
+            error!("Cannot write {}: {:?}", TRADE_LOG_FILE, err);
+            return;
+        }
+    }
+}
+
+pub struct TradePlugin;
+
+impl Plugin for TradePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Trades>()
+            .add_systems(Update, trade_watch_system)
+            .add_systems(Last, trade_log_flush_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::templates::ItemTemplate;
+
+    fn load_items() -> Items {
+        let item_template_file =
+            fs::File::open("item_template.yaml").expect("Could not open file.");
+        let item_templates: Vec<ItemTemplate> =
+            serde_yaml::from_reader(item_template_file).expect("Could not read values.");
+
+        let mut items = Items::default();
+        items.set_templates(item_templates);
+
+        return items;
+    }
+
+    fn new_ids() -> Ids {
+        return Ids {
+            map_event: 0,
+            player_event: 0,
+            obj: 100,
+            item: 0,
+            player_hero_map: HashMap::new(),
+            obj_entity_map: HashMap::new(),
+            obj_player_map: HashMap::new(),
+        };
+    }
+
+    // Player 1 with hero 11 offers ore to player 2 with hero 12 for gold
+    fn open_trade(trades: &mut Trades, ids: &mut Ids, items: &mut Items) -> i32 {
+        let ore = items.new(11, "Valleyrun Copper Ore".to_string(), 10);
+        let gold = items.new(12, item::GOLD.to_string(), 50);
+
+        let trade_id = trades.open(1, 11, 2, 12, ids).unwrap();
+
+        trades.offer(trade_id, 1, ore.id, 4, items).unwrap();
+        trades.offer(trade_id, 2, gold.id, 20, items).unwrap();
+
+        return trade_id;
+    }
+
+    #[test]
+    fn test_offer_escrow() {
+        let mut trades = Trades::default();
+        let mut ids = new_ids();
+        let mut items = load_items();
+
+        let trade_id = open_trade(&mut trades, &mut ids, &mut items);
+        let session = trades.sessions.get(&trade_id).unwrap().clone();
+        let [party, other_party] = &session.parties;
+
+        assert_eq!(
+            items.get_total_quantity(11, &"Valleyrun Copper Ore".to_string()),
+            6
+        );
+        assert_eq!(
+            items.get_total_quantity(party.escrow_id, &"Valleyrun Copper Ore".to_string()),
+            4
+        );
+        assert_eq!(items.get_total_gold(12), 30);
+        assert_eq!(items.get_total_gold(other_party.escrow_id), 20);
+
+        assert!(trades.open(1, 11, 3, 13, &mut ids).is_err());
+        assert!(trades.offer(trade_id, 2, 999, 1, &mut items).is_err());
+        assert!(trades.offer(trade_id, 3, 1, 1, &mut items).is_err());
+    }
+
+    #[test]
+    fn test_accept_and_confirm() {
+        let mut trades = Trades::default();
+        let mut ids = new_ids();
+        let mut items = load_items();
+
+        let trade_id = open_trade(&mut trades, &mut ids, &mut items);
+
+        assert!(trades
+            .confirm(trade_id, 1, |_| 1000, &mut items, &GameTick(1))
+            .is_err());
+
+        trades.accept(trade_id, 1).unwrap();
+        assert_eq!(trades.sessions[&trade_id].phase, TradePhase::Offer);
+
+        // A changed offer has to be accepted again
+        let ore = items.get_by_owner(11).into_iter().next().unwrap();
+        trades.offer(trade_id, 1, ore.id, 1, &mut items).unwrap();
+        assert!(!trades.sessions[&trade_id].parties[0].accepted);
+
+        trades.accept(trade_id, 1).unwrap();
+        trades.accept(trade_id, 2).unwrap();
+        assert_eq!(trades.sessions[&trade_id].phase, TradePhase::Confirm);
+
+        assert_eq!(
+            trades.confirm(trade_id, 1, |_| 1000, &mut items, &GameTick(1)),
+            Ok(false)
+        );
+        assert_eq!(
+            trades.confirm(trade_id, 2, |_| 1000, &mut items, &GameTick(1)),
+            Ok(true)
+        );
+
+        assert!(trades.sessions.is_empty());
+        assert_eq!(
+            items.get_total_quantity(12, &"Valleyrun Copper Ore".to_string()),
+            5
+        );
+        assert_eq!(items.get_total_gold(11), 20);
+        assert_eq!(items.get_total_gold(12), 30);
+
+        assert_eq!(trades.log.len(), 1);
+        assert_eq!(trades.log[0].players, [1, 2]);
+        assert!(items.ledger().check(&items.recount()).is_empty());
+    }
+
+    #[test]
+    fn test_confirm_over_hard_limit() {
+        let mut trades = Trades::default();
+        let mut ids = new_ids();
+        let mut items = load_items();
+
+        let trade_id = open_trade(&mut trades, &mut ids, &mut items);
+
+        trades.accept(trade_id, 1).unwrap();
+        trades.accept(trade_id, 2).unwrap();
+        trades
+            .confirm(trade_id, 1, |_| 0, &mut items, &GameTick(1))
+            .unwrap();
+
+        // Hero 12 cannot take on the ore
+        let hard_limit = |hero_id: i32| if hero_id == 12 { 0 } else { 1000 };
+
+        assert!(trades
+            .confirm(trade_id, 2, hard_limit, &mut items, &GameTick(1))
+            .is_err());
+        assert!(!trades.sessions[&trade_id].parties[1].confirmed);
+        assert_eq!(items.get_total_gold(11), 0);
+        assert!(trades.log.is_empty());
+    }
+
+    #[test]
+    fn test_cancel_returns_items() {
+        let mut trades = Trades::default();
+        let mut ids = new_ids();
+        let mut items = load_items();
+
+        let trade_id = open_trade(&mut trades, &mut ids, &mut items);
+        let session = trades.cancel(trade_id, &mut items).unwrap();
+
+        for party in session.parties.iter() {
+            assert!(items.get_by_owner(party.escrow_id).is_empty());
+        }
+
+        assert_eq!(
+            items.get_total_quantity(11, &"Valleyrun Copper Ore".to_string()),
+            10
+        );
+        assert_eq!(items.get_total_gold(12), 50);
+        assert!(trades.sessions.is_empty());
+        assert!(trades.cancel(trade_id, &mut items).is_none());
+        assert!(items.ledger().check(&items.recount()).is_empty());
+    }
+
+    #[test]
+    fn test_cancel_reason() {
+        let mut trades = Trades::default();
+        let mut ids = new_ids();
+        let mut items = load_items();
+
+        let trade_id = open_trade(&mut trades, &mut ids, &mut items);
+        let session = &trades.sessions[&trade_id];
+
+        let adjacent = vec![
+            (Position { x: 1, y: 1 }, State::None),
+            (Position { x: 2, y: 1 }, State::None),
+        ];
+
+        assert_eq!(Trades::cancel_reason(session, &vec![1, 2], &adjacent), None);
+        assert_eq!(
+            Trades::cancel_reason(session, &vec![1], &adjacent),
+            Some(REASON_DISCONNECTED)
+        );
+
+        let apart = vec![
+            (Position { x: 1, y: 1 }, State::None),
+            (Position { x: 5, y: 1 }, State::None),
+        ];
+
+        assert_eq!(
+            Trades::cancel_reason(session, &vec![1, 2], &apart),
+            Some(REASON_MOVED_AWAY)
+        );
+
+        let dead = vec![
+            (Position { x: 1, y: 1 }, State::None),
+            (Position { x: 2, y: 1 }, State::Dead),
+        ];
+
+        assert_eq!(
+            Trades::cancel_reason(session, &vec![1, 2], &dead),
+            Some(REASON_MOVED_AWAY)
+        );
+        assert_eq!(
+            Trades::cancel_reason(session, &vec![1, 2], &adjacent[..1].to_vec()),
+            Some(REASON_MOVED_AWAY)
+        );
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut trades = Trades::default();
+
+        for trade_id in 0..MAX_TRADE_RECORDS as i32 {
+            trades.log.push(TradeRecord {
+                trade_id: trade_id,
+                tick: trade_id,
+                players: [1, 2],
+                given: [Vec::new(), Vec::new()],
+            });
+        }
+
+        assert!(trades.flush().is_empty());
+
+        trades.log.push(TradeRecord {
+            trade_id: MAX_TRADE_RECORDS as i32,
+            tick: MAX_TRADE_RECORDS as i32,
+            players: [1, 2],
+            given: [Vec::new(), Vec::new()],
+        });
+
+        let flushed = trades.flush();
+
+        assert_eq!(flushed.len(), MAX_TRADE_RECORDS / 2 + 1);
+        assert_eq!(flushed[0].trade_id, 0);
+        assert_eq!(trades.log.len(), MAX_TRADE_RECORDS / 2);
+        assert_eq!(trades.log[0].trade_id, flushed.len() as i32);
+    }
+}
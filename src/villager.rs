@@ -2,14 +2,35 @@ use bevy::prelude::*;
 
 
 
+use rand::seq::SliceRandom;
 use rand::Rng;
 
+use crate::components::villager::Personality;
+
 use crate::game::{BaseAttrs, EventInProgress, Order, SubclassNPC, VillagerQuery, State};
 
 use crate::map::MapPos;
 use crate::obj::Obj;
 use crate::skill::{self, Skill, Skills};
-use crate::templates::{SkillTemplates};
+use crate::templates::{CultureTemplate, SkillTemplates, TraitTemplate, VillagerTemplates};
+
+pub const ORDER_FOLLOW: &str = "follow";
+pub const ORDER_GATHER: &str = "gather";
+pub const ORDER_OPERATE: &str = "operate";
+pub const ORDER_REFINE: &str = "refine";
+pub const ORDER_CRAFT: &str = "craft";
+pub const ORDER_EXPERIMENT: &str = "experiment";
+pub const ORDER_EXPLORE: &str = "explore";
+pub const ORDER_PLANT: &str = "plant";
+pub const ORDER_TEND: &str = "tend";
+pub const ORDER_HARVEST: &str = "harvest";
+
+pub const STOP_ONCE: &str = "once";
+pub const STOP_COUNT: &str = "count";
+pub const STOP_HOLDS: &str = "holds";
+pub const STOP_REPEAT: &str = "repeat";
+
+pub const MAX_TRAITS: usize = 2;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Activity {
@@ -20,7 +41,9 @@ pub enum Activity {
     FindingShelter,
     Eating,
     Fleeing,
+    Fighting,
     Following,
+    Escorting,
     Gathering,
     Operating,
     Refining,
@@ -37,6 +60,7 @@ impl Activity {
         let str = match self {
             Activity::None => "None",
             Activity::Following => "Following",
+            Activity::Escorting => "Escorting",
             Activity::GettingDrink => "Getting a drink",
             Activity::GettingFood => "Getting some food",
             Activity::FindingShelter => "Finding shelter",
@@ -46,6 +70,7 @@ impl Activity {
             Activity::Refining => "Refining",
             Activity::Crafting => "Crafting",
             Activity::Experimenting => "Experimenting",
+            Activity::Fighting => "Fighting",
             _ => "Unknown",
         };
 
@@ -57,20 +82,74 @@ impl Activity {
 pub struct Villager;
 
 impl Villager {
-    pub fn generate() {}
+    // Name and personality, traits and likes are drawn without repeats
+    pub fn generate(villager_templates: &VillagerTemplates) -> (String, Personality) {
+        let mut rng = rand::thread_rng();
+
+        let culture = villager_templates
+            .cultures
+            .choose(&mut rng)
+            .expect("No cultures in villager templates");
+
+        let name = Villager::generate_name(culture);
+
+        let num_traits = rng.gen_range(1..=MAX_TRAITS);
+        let trait_templates: Vec<&TraitTemplate> = villager_templates
+            .traits
+            .choose_multiple(&mut rng, num_traits)
+            .collect();
+
+        let mut work_types = villager_templates.work_types.clone();
+        work_types.shuffle(&mut rng);
+
+        let likes: Vec<String> = work_types.pop().into_iter().collect();
+        let dislikes: Vec<String> = work_types.pop().into_iter().collect();
+
+        let mut personality = Personality {
+            culture: culture.name.clone(),
+            traits: Vec::new(),
+            likes: likes,
+            dislikes: dislikes,
+            backstory: String::new(),
+            thirst_rate: 1.0,
+            hunger_rate: 1.0,
+            tired_rate: 1.0,
+            work_weight: 1.0,
+            flee_weight: 1.0,
+            schedule_shift: 0,
+        };
+
+        for trait_template in trait_templates.iter() {
+            personality.traits.push(trait_template.name.clone());
+            personality.thirst_rate *= trait_template.thirst_rate.unwrap_or(1.0);
+            personality.hunger_rate *= trait_template.hunger_rate.unwrap_or(1.0);
+            personality.tired_rate *= trait_template.tired_rate.unwrap_or(1.0);
+            personality.work_weight *= trait_template.work_weight.unwrap_or(1.0);
+            personality.flee_weight *= trait_template.flee_weight.unwrap_or(1.0);
+            personality.schedule_shift += trait_template.schedule_shift.unwrap_or(0);
+        }
+
+        if let Some(backstory) = villager_templates.backstories.choose(&mut rng) {
+            let first = |list: &Vec<String>| list.first().cloned().unwrap_or_default();
 
-    pub fn generate_name() -> String {
-        let names = vec![
-            "Geoffry Holte",
-            "Roderich Denholm",
-            "Warder Folcey",
-            "Andes Bardaye",
-        ];
+            personality.backstory = backstory
+                .replace("{name}", &name)
+                .replace("{culture}", &culture.name)
+                .replace("{trait}", &first(&personality.traits).to_lowercase())
+                .replace("{like}", &first(&personality.likes))
+                .replace("{dislike}", &first(&personality.dislikes));
+        }
+
+        return (name, personality);
+    }
 
+    pub fn generate_name(culture: &CultureTemplate) -> String {
         let mut rng = rand::thread_rng();
-        let index = rng.gen_range(0..names.len());
 
-        return names[index].to_string();
+        let first_name = culture.first_names.choose(&mut rng).expect("No first names");
+        let last_name = culture.last_names.choose(&mut rng).expect("No last names");
+
+        return format!("{} {}", first_name, last_name);
     }
 
     pub fn generate_attributes(level: i32) -> BaseAttrs {
@@ -148,6 +227,50 @@ impl Villager {
         }
     }
 
+    pub fn order_to_str(order: &Order) -> (String, Option<String>) {
+        match order {
+            Order::Follow { .. } => (ORDER_FOLLOW.to_string(), None),
+            Order::Gather { res_type } => (ORDER_GATHER.to_string(), Some(res_type.clone())),
+            Order::Operate => (ORDER_OPERATE.to_string(), None),
+            Order::Refine => (ORDER_REFINE.to_string(), None),
+            Order::Craft { recipe_name } => (ORDER_CRAFT.to_string(), Some(recipe_name.clone())),
+            Order::Experiment => (ORDER_EXPERIMENT.to_string(), None),
+            Order::Explore => (ORDER_EXPLORE.to_string(), None),
+            Order::Plant => (ORDER_PLANT.to_string(), None),
+            Order::Tend => (ORDER_TEND.to_string(), None),
+            Order::Harvest => (ORDER_HARVEST.to_string(), None),
+        }
+    }
+
+    // Follow is excluded as it needs a live target entity
+    pub fn order_from_str(order: &str, target: Option<String>) -> Option<Order> {
+        match (order, target) {
+            (ORDER_GATHER, Some(res_type)) => Some(Order::Gather { res_type }),
+            (ORDER_OPERATE, _) => Some(Order::Operate),
+            (ORDER_REFINE, _) => Some(Order::Refine),
+            (ORDER_CRAFT, Some(recipe_name)) => Some(Order::Craft { recipe_name }),
+            (ORDER_EXPERIMENT, _) => Some(Order::Experiment),
+            (ORDER_EXPLORE, _) => Some(Order::Explore),
+            (ORDER_PLANT, _) => Some(Order::Plant),
+            (ORDER_TEND, _) => Some(Order::Tend),
+            (ORDER_HARVEST, _) => Some(Order::Harvest),
+            _ => None,
+        }
+    }
+
+    pub fn order_needs_structure(order: &Order) -> bool {
+        match order {
+            Order::Operate
+            | Order::Refine
+            | Order::Craft { .. }
+            | Order::Experiment
+            | Order::Plant
+            | Order::Tend
+            | Order::Harvest => true,
+            _ => false,
+        }
+    }
+
     pub fn blocking_list(
         player_id: i32,
         query: &Query<VillagerQuery, (With<SubclassNPC>, Without<EventInProgress>)>,